@@ -0,0 +1,88 @@
+//! Measures the memory and time used by a record with a million tiny commands.
+//!
+//! Run it with and without the `compact` feature to compare the storage:
+//!
+//! ```text
+//! cargo bench --bench storage
+//! cargo bench --bench storage --features compact
+//! ```
+
+use redo::{Command, Record};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+/// Counts the bytes that are currently allocated.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const COMMANDS: usize = 1_000_000;
+
+struct Insert(char);
+
+impl Command<String> for Insert {
+    type Error = ();
+
+    fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+        s.push(self.0);
+        Ok(())
+    }
+
+    fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+        self.0 = s.pop().ok_or(())?;
+        Ok(())
+    }
+}
+
+fn main() {
+    let storage = if cfg!(feature = "compact") {
+        "compact"
+    } else {
+        "default"
+    };
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let mut record = Record::builder().capacity(COMMANDS).build(String::new());
+    let start = Instant::now();
+    for _ in 0..COMMANDS {
+        record.apply(Insert('a')).unwrap();
+    }
+    let apply = start.elapsed();
+    let bytes = ALLOCATED.load(Ordering::Relaxed) - before;
+    let start = Instant::now();
+    record.go_to(0).unwrap().unwrap();
+    record.go_to(COMMANDS).unwrap().unwrap();
+    let travel = start.elapsed();
+    println!("storage: {}", storage);
+    println!("commands: {}", record.len());
+    println!(
+        "memory: {} bytes ({} bytes per command)",
+        bytes,
+        bytes / COMMANDS
+    );
+    println!("apply: {:?}", apply);
+    println!("undo and redo all: {:?}", travel);
+}
@@ -0,0 +1,124 @@
+use crate::{Command, Merge};
+use alloc::sync::Arc;
+
+/// Shares the command between clones of a record.
+///
+/// Cloning the command only clones the `Arc`, and the command itself is cloned the first time
+/// it is changed while it is shared, e.g. when one of the records undoes it.
+impl<R, C: Command<R> + Clone> Command<R> for Arc<C> {
+    type Error = C::Error;
+
+    #[inline]
+    fn apply(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        Arc::make_mut(self).apply(receiver)
+    }
+
+    #[inline]
+    fn undo(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        Arc::make_mut(self).undo(receiver)
+    }
+
+    #[inline]
+    fn redo(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        Arc::make_mut(self).redo(receiver)
+    }
+
+    #[inline]
+    fn merge(&mut self, command: Self) -> Merge<Self> {
+        let command = Arc::try_unwrap(command).unwrap_or_else(|command| C::clone(&command));
+        match Arc::make_mut(self).merge(command) {
+            Merge::Yes => Merge::Yes,
+            Merge::No(command) => Merge::No(Arc::new(command)),
+            Merge::Annul => Merge::Annul,
+        }
+    }
+
+    #[inline]
+    fn is_dead(&self) -> bool {
+        (**self).is_dead()
+    }
+
+    #[inline]
+    fn undoable(&self, receiver: &R) -> bool {
+        (**self).undoable(receiver)
+    }
+
+    #[inline]
+    fn is_noop(&self, receiver: &R) -> bool {
+        (**self).is_noop(receiver)
+    }
+
+    #[inline]
+    fn validate(&self, receiver: &R) -> Result<(), C::Error> {
+        (**self).validate(receiver)
+    }
+
+    #[inline]
+    fn region(&self) -> Option<usize> {
+        (**self).region()
+    }
+
+    #[inline]
+    fn cost(&self) -> usize {
+        (**self).cost()
+    }
+
+    #[inline]
+    fn prefetch(&mut self) {
+        Arc::make_mut(self).prefetch();
+    }
+
+    #[inline]
+    fn transpose(&self, command: &Self) -> bool {
+        (**self).transpose(command)
+    }
+
+    #[inline]
+    fn restorable(&self) -> bool {
+        (**self).restorable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Record};
+    use std::sync::Arc;
+
+    #[derive(Clone)]
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            self.0 = s.pop().ok_or("`s` is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn try_clone() {
+        let mut record = Record::default();
+        record.apply(Arc::new(Add('a'))).unwrap();
+        record.apply(Arc::new(Add('b'))).unwrap();
+        let mut clone = record.try_clone();
+        assert!(record
+            .commands()
+            .zip(clone.commands())
+            .all(|(a, b)| Arc::ptr_eq(a, b)));
+        clone.undo().unwrap().unwrap();
+        assert_eq!(clone.as_receiver(), "a");
+        assert_eq!(record.as_receiver(), "ab");
+        let shared: Vec<_> = record
+            .commands()
+            .zip(clone.commands())
+            .map(|(a, b)| Arc::ptr_eq(a, b))
+            .collect();
+        assert_eq!(shared, vec![true, false]);
+    }
+}
@@ -0,0 +1,522 @@
+use crate::{
+    slot::{Slot, State},
+    ConfigError, Merge, Signal,
+};
+use alloc::collections::VecDeque;
+use core::{future::Future, num::NonZeroUsize};
+
+/// Base functionality for commands that are applied asynchronously, e.g. over the network.
+///
+/// It is the asynchronous version of [`Command`], and is used by [`AsyncRecord`].
+///
+/// [`Command`]: trait.Command.html
+/// [`AsyncRecord`]: struct.AsyncRecord.html
+pub trait AsyncCommand<R> {
+    /// The error type.
+    type Error;
+
+    /// Applies the command on the receiver and returns `Ok` if everything went fine,
+    /// and `Err` if something went wrong.
+    fn apply(&mut self, receiver: &mut R) -> impl Future<Output = Result<(), Self::Error>>;
+
+    /// Restores the state of the receiver as it was before the command was applied
+    /// and returns `Ok` if everything went fine, and `Err` if something went wrong.
+    fn undo(&mut self, receiver: &mut R) -> impl Future<Output = Result<(), Self::Error>>;
+
+    /// Reapplies the command on the receiver and return `Ok` if everything went fine,
+    /// and `Err` if something went wrong.
+    ///
+    /// The default implementation uses the [`apply`] implementation.
+    ///
+    /// [`apply`]: trait.AsyncCommand.html#tymethod.apply
+    #[inline]
+    fn redo(&mut self, receiver: &mut R) -> impl Future<Output = Result<(), Self::Error>> {
+        self.apply(receiver)
+    }
+
+    /// Used for manual merging of two commands, see [`Command::merge`].
+    ///
+    /// [`Command::merge`]: trait.Command.html#method.merge
+    #[inline]
+    fn merge(&mut self, command: Self) -> Merge<Self>
+    where
+        Self: Sized,
+    {
+        Merge::No(command)
+    }
+}
+
+/// A record of asynchronous commands.
+///
+/// It works like a [`Record`], but the commands are awaited when they are applied, undone,
+/// and redone. The position, limit, and saved state are only changed, and the signals are only
+/// emitted, once the command has completed successfully. If the future is dropped before the
+/// command completes, the record is left unchanged, but the receiver might have been partially
+/// changed by the command. [`go_to`] completes one command at a time, so if its future is
+/// dropped the commands that completed are kept.
+///
+/// # Examples
+/// ```
+/// # use redo::{AsyncCommand, AsyncRecord};
+/// struct Add(char);
+///
+/// impl AsyncCommand<String> for Add {
+///     type Error = &'static str;
+///
+///     async fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+///         s.push(self.0);
+///         Ok(())
+///     }
+///
+///     async fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+///         self.0 = s.pop().ok_or("`s` is empty")?;
+///         Ok(())
+///     }
+/// }
+///
+/// async fn edit() -> Result<(), &'static str> {
+///     let mut record = AsyncRecord::new(String::new());
+///     record.apply(Add('a')).await?;
+///     record.apply(Add('b')).await?;
+///     record.undo().await.unwrap()?;
+///     assert_eq!(record.as_receiver(), "a");
+///     Ok(())
+/// }
+/// ```
+///
+/// [`Record`]: struct.Record.html
+/// [`go_to`]: struct.AsyncRecord.html#method.go_to
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct AsyncRecord<R, C, F = fn(Signal)> {
+    commands: VecDeque<C>,
+    receiver: R,
+    current: usize,
+    limit: NonZeroUsize,
+    saved: Option<usize>,
+    slot: Slot<F>,
+}
+
+impl<R, C> AsyncRecord<R, C> {
+    /// Returns a new record.
+    #[inline]
+    pub fn new(receiver: impl Into<R>) -> AsyncRecord<R, C> {
+        AsyncRecord {
+            commands: VecDeque::new(),
+            receiver: receiver.into(),
+            current: 0,
+            limit: NonZeroUsize::new(usize::MAX).unwrap(),
+            saved: Some(0),
+            slot: Slot::default(),
+        }
+    }
+}
+
+impl<R, C, F> AsyncRecord<R, C, F> {
+    /// Returns the number of commands in the record.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns `true` if the record is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Returns the limit of the record.
+    #[inline]
+    pub fn limit(&self) -> usize {
+        self.limit.get()
+    }
+
+    /// Sets the limit of the record, which only affects the commands applied afterwards.
+    ///
+    /// # Panics
+    /// Panics if `limit` is `0`, see [`try_set_limit`].
+    ///
+    /// [`try_set_limit`]: struct.AsyncRecord.html#method.try_set_limit
+    #[inline]
+    pub fn set_limit(&mut self, limit: usize) {
+        if let Err(error) = self.try_set_limit(limit) {
+            panic!("{}", error);
+        }
+    }
+
+    /// Sets the limit of the record, which only affects the commands applied afterwards.
+    ///
+    /// # Errors
+    /// Returns [`ConfigError::ZeroLimit`] if `limit` is `0`.
+    ///
+    /// [`ConfigError::ZeroLimit`]: enum.ConfigError.html#variant.ZeroLimit
+    #[inline]
+    pub fn try_set_limit(&mut self, limit: usize) -> Result<(), ConfigError> {
+        self.limit = NonZeroUsize::new(limit).ok_or(ConfigError::ZeroLimit)?;
+        Ok(())
+    }
+
+    /// Returns the position of the current command.
+    #[inline]
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Returns `true` if the record can undo.
+    #[inline]
+    pub fn can_undo(&self) -> bool {
+        self.current > 0
+    }
+
+    /// Returns `true` if the record can redo.
+    #[inline]
+    pub fn can_redo(&self) -> bool {
+        self.current < self.len()
+    }
+
+    /// Returns `true` if the receiver is in a saved state, `false` otherwise.
+    #[inline]
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.current)
+    }
+
+    /// Sets how the signal should be handled when the state changes.
+    ///
+    /// The previous slot is returned if it exists.
+    #[inline]
+    pub fn connect(&mut self, slot: F) -> Option<F> {
+        self.slot.f.replace(slot)
+    }
+
+    /// Creates a new record that uses the provided slot.
+    #[inline]
+    pub fn connect_with<G>(self, slot: G) -> AsyncRecord<R, C, G> {
+        AsyncRecord {
+            commands: self.commands,
+            receiver: self.receiver,
+            current: self.current,
+            limit: self.limit,
+            saved: self.saved,
+            slot: Slot::from(slot),
+        }
+    }
+
+    /// Removes and returns the slot.
+    #[inline]
+    pub fn disconnect(&mut self) -> Option<F> {
+        self.slot.f.take()
+    }
+
+    /// Returns an iterator over the commands in the record.
+    #[inline]
+    pub fn commands(&self) -> impl Iterator<Item = &C> {
+        self.commands.iter()
+    }
+
+    /// Returns a reference to the `receiver`.
+    #[inline]
+    pub fn as_receiver(&self) -> &R {
+        &self.receiver
+    }
+
+    /// Returns a mutable reference to the `receiver`.
+    ///
+    /// This method should **only** be used when doing changes that should not be able to be undone.
+    #[inline]
+    pub fn as_mut_receiver(&mut self) -> &mut R {
+        &mut self.receiver
+    }
+
+    /// Consumes the record, returning the `receiver`.
+    #[inline]
+    pub fn into_receiver(self) -> R {
+        self.receiver
+    }
+
+    #[inline]
+    fn state(&self) -> State {
+        State {
+            current: self.current,
+            branch: 0,
+            can_undo: self.can_undo(),
+            can_redo: self.can_redo(),
+            is_saved: self.is_saved(),
+        }
+    }
+}
+
+impl<R, C: AsyncCommand<R>, F: FnMut(Signal)> AsyncRecord<R, C, F> {
+    /// Marks the receiver as currently being in a saved or unsaved state.
+    #[inline]
+    pub fn set_saved(&mut self, saved: bool) {
+        let start = self.state();
+        self.saved = if saved { Some(self.current) } else { None };
+        self.emit(start);
+    }
+
+    /// Pushes the command on top of the record and awaits its [`apply`] method.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned.
+    ///
+    /// [`apply`]: trait.AsyncCommand.html#tymethod.apply
+    #[inline]
+    pub async fn apply(&mut self, mut command: C) -> Result<(), C::Error> {
+        command.apply(&mut self.receiver).await?;
+        let start = self.state();
+        let current = self.current;
+        self.commands.truncate(current);
+        self.saved = self.saved.filter(|&saved| saved <= current);
+        // Try to merge commands unless the receiver is in a saved state.
+        let merged = match self.commands.back_mut() {
+            Some(last) if !start.is_saved => last.merge(command),
+            _ => Merge::No(command),
+        };
+        match merged {
+            Merge::Yes => (),
+            Merge::Annul => {
+                self.commands.pop_back();
+                self.current -= 1;
+            }
+            Merge::No(command) => {
+                // If limit is reached, pop off the first command.
+                if self.limit() == self.current {
+                    self.commands.pop_front();
+                    self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+                } else {
+                    self.current += 1;
+                }
+                self.commands.push_back(command);
+            }
+        }
+        self.emit(start);
+        Ok(())
+    }
+
+    /// Awaits the [`undo`] method for the active command and sets
+    /// the previous one as the new active one.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned.
+    ///
+    /// [`undo`]: trait.AsyncCommand.html#tymethod.undo
+    #[inline]
+    pub async fn undo(&mut self) -> Option<Result<(), C::Error>> {
+        let start = self.state();
+        let result = self.__undo().await?;
+        self.emit(start);
+        Some(result)
+    }
+
+    /// Awaits the [`redo`] method for the active command and sets
+    /// the next one as the new active one.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned.
+    ///
+    /// [`redo`]: trait.AsyncCommand.html#method.redo
+    #[inline]
+    pub async fn redo(&mut self) -> Option<Result<(), C::Error>> {
+        let start = self.state();
+        let result = self.__redo().await?;
+        self.emit(start);
+        Some(result)
+    }
+
+    /// Repeatedly awaits [`undo`] or [`redo`] until the command at `current` is reached.
+    ///
+    /// The signals are emitted after each command, so they match the record even if
+    /// the future is dropped before the command at `current` is reached.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`undo`]: trait.AsyncCommand.html#tymethod.undo
+    /// [`redo`]: trait.AsyncCommand.html#method.redo
+    #[inline]
+    pub async fn go_to(&mut self, current: usize) -> Option<Result<(), C::Error>> {
+        if current > self.len() {
+            return None;
+        }
+        let mut result = Ok(());
+        while self.current != current && result.is_ok() {
+            let start = self.state();
+            result = if current > self.current {
+                self.__redo().await.unwrap()
+            } else {
+                self.__undo().await.unwrap()
+            };
+            self.emit(start);
+        }
+        Some(result)
+    }
+
+    #[inline]
+    async fn __undo(&mut self) -> Option<Result<(), C::Error>> {
+        if !self.can_undo() {
+            return None;
+        }
+        let result = self.commands[self.current - 1]
+            .undo(&mut self.receiver)
+            .await;
+        if result.is_ok() {
+            self.current -= 1;
+        }
+        Some(result)
+    }
+
+    #[inline]
+    async fn __redo(&mut self) -> Option<Result<(), C::Error>> {
+        if !self.can_redo() {
+            return None;
+        }
+        let result = self.commands[self.current].redo(&mut self.receiver).await;
+        if result.is_ok() {
+            self.current += 1;
+        }
+        Some(result)
+    }
+
+    /// Emits the net signals of the changes since the `start` state.
+    #[inline]
+    fn emit(&mut self, start: State) {
+        self.slot.begin(start);
+        self.slot.end(self.state());
+    }
+}
+
+impl<R: Default, C> Default for AsyncRecord<R, C> {
+    #[inline]
+    fn default() -> Self {
+        AsyncRecord::new(R::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AsyncCommand, AsyncRecord, Signal};
+    use std::{
+        cell::RefCell,
+        future::{poll_fn, Future},
+        pin::pin,
+        rc::Rc,
+        sync::Arc,
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    /// A waker that does nothing, since the futures are polled in a loop.
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// Polls the future to completion on the current thread.
+    fn block_on<T>(future: impl Future<Output = T>) -> T {
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(t) = future.as_mut().poll(&mut cx) {
+                return t;
+            }
+        }
+    }
+
+    /// Returns `Pending` once before it completes, like a network request.
+    async fn request() {
+        let mut pending = true;
+        poll_fn(|_| {
+            if pending {
+                pending = false;
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+        .await
+    }
+
+    struct Add(char);
+
+    impl AsyncCommand<String> for Add {
+        type Error = &'static str;
+
+        async fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            request().await;
+            if self.0 == '!' {
+                return Err("invalid");
+            }
+            s.push(self.0);
+            Ok(())
+        }
+
+        async fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            request().await;
+            self.0 = s.pop().ok_or("`s` is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn apply_undo_redo() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record =
+            AsyncRecord::default().connect_with(move |signal| slot.borrow_mut().push(signal));
+        block_on(async {
+            record.apply(Add('a')).await.unwrap();
+            record.apply(Add('b')).await.unwrap();
+            record.apply(Add('c')).await.unwrap();
+            assert_eq!(record.apply(Add('!')).await, Err("invalid"));
+            assert_eq!(record.len(), 3);
+            record.undo().await.unwrap().unwrap();
+            record.set_saved(true);
+            record.go_to(0).await.unwrap().unwrap();
+            assert_eq!(record.as_receiver(), "");
+            record.redo().await.unwrap().unwrap();
+            record.go_to(2).await.unwrap().unwrap();
+            assert!(record.is_saved());
+        });
+        assert_eq!(record.as_receiver(), "ab");
+        signals.borrow_mut().clear();
+        block_on(record.apply(Add('d'))).unwrap();
+        assert_eq!(record.len(), 3);
+        assert_eq!(
+            *signals.borrow(),
+            [
+                Signal::Current { old: 2, new: 3 },
+                Signal::Redo(false),
+                Signal::Saved(false)
+            ]
+        );
+    }
+
+    #[test]
+    fn go_to_dropped() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record =
+            AsyncRecord::default().connect_with(move |signal| slot.borrow_mut().push(signal));
+        block_on(async {
+            record.apply(Add('a')).await.unwrap();
+            record.apply(Add('b')).await.unwrap();
+            record.apply(Add('c')).await.unwrap();
+        });
+        signals.borrow_mut().clear();
+        {
+            // Drops the future while the second undo is pending.
+            let mut future = pin!(record.go_to(0));
+            let waker = Waker::from(Arc::new(Noop));
+            let mut cx = Context::from_waker(&waker);
+            for _ in 0..2 {
+                assert!(future.as_mut().poll(&mut cx).is_pending());
+            }
+        }
+        assert_eq!(record.current(), 2);
+        assert_eq!(record.as_receiver(), "ab");
+        assert_eq!(
+            *signals.borrow(),
+            [Signal::Current { old: 3, new: 2 }, Signal::Redo(true)]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use crate::ignored::Ignored;
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::fmt;
+
+type SaveFn<R> = dyn Fn(&R) -> Arc<RestoreFn<R>> + Send + Sync;
+type RestoreFn<R> = dyn Fn(&mut R) + Send + Sync;
+
+/// The snapshots of the receiver that are used to shorten long jumps in the record.
+///
+/// A snapshot is taken every time the record reaches a position that is a multiple of `every`,
+/// and it is keyed by the id of the command before it. The snapshots are shared when the
+/// cache is cloned, and they are ignored when comparing and hashing records.
+#[derive(Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub(crate) struct SnapshotCache<R> {
+    every: usize,
+    save: Ignored<Option<Arc<SaveFn<R>>>>,
+    snapshots: Ignored<BTreeMap<usize, Arc<RestoreFn<R>>>>,
+}
+
+impl<R> SnapshotCache<R> {
+    /// Returns a cache that takes a snapshot every `every` commands.
+    #[inline]
+    pub(crate) fn new<S: Send + Sync + 'static>(
+        every: usize,
+        save: impl Fn(&R) -> S + Send + Sync + 'static,
+        restore: impl Fn(&S, &mut R) + Send + Sync + 'static,
+    ) -> SnapshotCache<R> {
+        let restore = Arc::new(restore);
+        SnapshotCache {
+            every,
+            save: Ignored(Some(Arc::new(move |receiver: &R| {
+                let snapshot = save(receiver);
+                let restore = Arc::clone(&restore);
+                Arc::new(move |receiver: &mut R| restore(&snapshot, receiver))
+            }))),
+            snapshots: Ignored(BTreeMap::new()),
+        }
+    }
+
+    /// Takes a snapshot of the receiver at `current` if it is a multiple of `every`.
+    #[inline]
+    pub(crate) fn take(&mut self, current: usize, id: usize, receiver: &R) {
+        match &*self.save {
+            Some(save) if current > 0 && current.checked_rem(self.every) == Some(0) => {
+                self.snapshots.entry(id).or_insert_with(|| save(receiver));
+            }
+            _ => (),
+        }
+    }
+
+    /// Returns the function that restores the snapshot taken after the command with `id`.
+    #[inline]
+    pub(crate) fn get(&self, id: usize) -> Option<Arc<RestoreFn<R>>> {
+        self.snapshots.get(&id).cloned()
+    }
+
+    /// Removes the snapshot taken after the command with `id`.
+    #[inline]
+    pub(crate) fn remove(&mut self, id: usize) {
+        self.snapshots.remove(&id);
+    }
+
+    /// Removes all the snapshots.
+    #[inline]
+    pub(crate) fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+impl<R> Default for SnapshotCache<R> {
+    #[inline]
+    fn default() -> Self {
+        SnapshotCache {
+            every: 0,
+            save: Ignored(None),
+            snapshots: Ignored(BTreeMap::new()),
+        }
+    }
+}
+
+impl<R> Clone for SnapshotCache<R> {
+    #[inline]
+    fn clone(&self) -> Self {
+        SnapshotCache {
+            every: self.every,
+            save: self.save.clone(),
+            snapshots: self.snapshots.clone(),
+        }
+    }
+}
+
+impl<R> fmt::Debug for SnapshotCache<R> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SnapshotCache")
+            .field("every", &self.every)
+            .field("snapshots", &self.snapshots.len())
+            .finish()
+    }
+}
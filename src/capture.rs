@@ -0,0 +1,497 @@
+use crate::{Command, Merge};
+use core::fmt;
+
+/// A command wrapper that captures the state it changes.
+///
+/// Before the command is applied, the part of the receiver given by the `lens` closure is cloned,
+/// and when the command is undone it is restored from the clone. This means the command does not
+/// need to store the old value itself, and its [`undo`] method is only called if the wrapper has
+/// no clone to restore, e.g. when the record was built with commands that were applied elsewhere.
+///
+/// If two wrapped commands are merged, the snapshot from the first command is kept.
+///
+/// # Examples
+/// ```
+/// # use redo::{Capturing, Command, Record};
+/// struct Set(i32);
+///
+/// impl Command<Vec<i32>> for Set {
+///     type Error = ();
+///
+///     fn apply(&mut self, v: &mut Vec<i32>) -> Result<(), ()> {
+///         v[0] = self.0;
+///         Ok(())
+///     }
+///
+///     fn undo(&mut self, _: &mut Vec<i32>) -> Result<(), ()> {
+///         unreachable!()
+///     }
+/// }
+///
+/// fn first(v: &mut Vec<i32>) -> &mut i32 {
+///     &mut v[0]
+/// }
+///
+/// fn main() -> Result<(), ()> {
+///     let mut record = Record::new(vec![1, 2]);
+///     record.apply(Capturing::new(Set(3), first))?;
+///     assert_eq!(record.as_receiver(), &[3, 2]);
+///     record.undo().unwrap()?;
+///     assert_eq!(record.as_receiver(), &[1, 2]);
+///     Ok(())
+/// }
+/// ```
+///
+/// [`undo`]: trait.Command.html#tymethod.undo
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Capturing<C, S, F> {
+    command: C,
+    snapshot: Option<S>,
+    lens: F,
+}
+
+impl<C, S, F> Capturing<C, S, F> {
+    /// Wraps the command and captures the part of the receiver returned by `lens`.
+    #[inline]
+    pub fn new(command: C, lens: F) -> Capturing<C, S, F> {
+        Capturing {
+            command,
+            snapshot: None,
+            lens,
+        }
+    }
+
+    /// Returns a reference to the wrapped command.
+    #[inline]
+    pub fn as_command(&self) -> &C {
+        &self.command
+    }
+
+    /// Returns the captured snapshot if the command has been applied.
+    #[inline]
+    pub fn snapshot(&self) -> Option<&S> {
+        self.snapshot.as_ref()
+    }
+
+    /// Consumes the wrapper, returning the wrapped command.
+    #[inline]
+    pub fn into_command(self) -> C {
+        self.command
+    }
+}
+
+impl<R, C, S, F> Command<R> for Capturing<C, S, F>
+where
+    C: Command<R>,
+    S: Clone,
+    F: FnMut(&mut R) -> &mut S,
+{
+    type Error = C::Error;
+
+    #[inline]
+    fn apply(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        let snapshot = (self.lens)(receiver).clone();
+        self.command.apply(receiver)?;
+        self.snapshot = Some(snapshot);
+        Ok(())
+    }
+
+    #[inline]
+    fn undo(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        match self.snapshot.take() {
+            Some(snapshot) => {
+                *(self.lens)(receiver) = snapshot;
+                Ok(())
+            }
+            None => self.command.undo(receiver),
+        }
+    }
+
+    #[inline]
+    fn redo(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        let snapshot = (self.lens)(receiver).clone();
+        self.command.redo(receiver)?;
+        self.snapshot = Some(snapshot);
+        Ok(())
+    }
+
+    #[inline]
+    fn merge(&mut self, command: Self) -> Merge<Self> {
+        let Capturing {
+            command,
+            snapshot,
+            lens,
+        } = command;
+        self.command.merge(command).map(|command| Capturing {
+            command,
+            snapshot,
+            lens,
+        })
+    }
+
+    #[inline]
+    fn is_dead(&self) -> bool {
+        self.command.is_dead()
+    }
+
+    #[inline]
+    fn undoable(&self, receiver: &R) -> bool {
+        self.command.undoable(receiver)
+    }
+
+    #[inline]
+    fn is_noop(&self, receiver: &R) -> bool {
+        self.command.is_noop(receiver)
+    }
+
+    #[inline]
+    fn validate(&self, receiver: &R) -> Result<(), C::Error> {
+        self.command.validate(receiver)
+    }
+
+    #[inline]
+    fn region(&self) -> Option<usize> {
+        self.command.region()
+    }
+
+    #[inline]
+    fn cost(&self) -> usize {
+        self.command.cost()
+    }
+
+    #[inline]
+    fn prefetch(&mut self) {
+        self.command.prefetch();
+    }
+
+    #[inline]
+    fn transpose(&self, command: &Self) -> bool {
+        self.command.transpose(&command.command)
+    }
+
+    #[inline]
+    fn restorable(&self) -> bool {
+        self.command.restorable()
+    }
+}
+
+impl<C: fmt::Display, S, F> fmt::Display for Capturing<C, S, F> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (&self.command as &dyn fmt::Display).fmt(f)
+    }
+}
+
+/// A command wrapper that snapshots the receiver before expensive commands are applied.
+///
+/// If the [`cost`] of the command is above the threshold, the whole receiver is cloned before the
+/// command is applied, and undoing the command restores the receiver from the clone instead of calling
+/// [`undo`]. Cheaper commands are undone as usual.
+///
+/// If two wrapped commands are merged, the snapshot from the first command is kept.
+///
+/// [`cost`]: trait.Command.html#method.cost
+/// [`undo`]: trait.Command.html#tymethod.undo
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Snapshotting<C, R> {
+    command: C,
+    threshold: usize,
+    snapshot: Option<R>,
+}
+
+impl<C, R> Snapshotting<C, R> {
+    /// Wraps the command and snapshots the receiver if its cost is above `threshold`.
+    #[inline]
+    pub fn new(command: C, threshold: usize) -> Snapshotting<C, R> {
+        Snapshotting {
+            command,
+            threshold,
+            snapshot: None,
+        }
+    }
+
+    /// Returns a reference to the wrapped command.
+    #[inline]
+    pub fn as_command(&self) -> &C {
+        &self.command
+    }
+
+    /// Returns `true` if a snapshot of the receiver is held.
+    #[inline]
+    pub fn has_snapshot(&self) -> bool {
+        self.snapshot.is_some()
+    }
+
+    /// Consumes the wrapper, returning the wrapped command.
+    #[inline]
+    pub fn into_command(self) -> C {
+        self.command
+    }
+}
+
+impl<R: Clone, C: Command<R>> Command<R> for Snapshotting<C, R> {
+    type Error = C::Error;
+
+    #[inline]
+    fn apply(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        let snapshot = self.snapshot(receiver);
+        self.command.apply(receiver)?;
+        self.snapshot = snapshot;
+        Ok(())
+    }
+
+    #[inline]
+    fn undo(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        match self.snapshot.take() {
+            Some(snapshot) => {
+                *receiver = snapshot;
+                Ok(())
+            }
+            None => self.command.undo(receiver),
+        }
+    }
+
+    #[inline]
+    fn redo(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        let snapshot = self.snapshot(receiver);
+        self.command.redo(receiver)?;
+        self.snapshot = snapshot;
+        Ok(())
+    }
+
+    #[inline]
+    fn merge(&mut self, command: Self) -> Merge<Self> {
+        let Snapshotting {
+            command,
+            threshold,
+            snapshot,
+        } = command;
+        self.command.merge(command).map(|command| Snapshotting {
+            command,
+            threshold,
+            snapshot,
+        })
+    }
+
+    #[inline]
+    fn is_dead(&self) -> bool {
+        self.command.is_dead()
+    }
+
+    #[inline]
+    fn undoable(&self, receiver: &R) -> bool {
+        self.command.undoable(receiver)
+    }
+
+    #[inline]
+    fn is_noop(&self, receiver: &R) -> bool {
+        self.command.is_noop(receiver)
+    }
+
+    #[inline]
+    fn validate(&self, receiver: &R) -> Result<(), C::Error> {
+        self.command.validate(receiver)
+    }
+
+    #[inline]
+    fn region(&self) -> Option<usize> {
+        self.command.region()
+    }
+
+    #[inline]
+    fn cost(&self) -> usize {
+        self.command.cost()
+    }
+
+    #[inline]
+    fn prefetch(&mut self) {
+        self.command.prefetch();
+    }
+}
+
+impl<R: Clone, C: Command<R>> Snapshotting<C, R> {
+    #[inline]
+    fn snapshot(&self, receiver: &R) -> Option<R> {
+        if self.command.cost() > self.threshold {
+            Some(receiver.clone())
+        } else {
+            None
+        }
+    }
+}
+
+impl<C: fmt::Display, R> fmt::Display for Snapshotting<C, R> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (&self.command as &dyn fmt::Display).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Capturing, Command, Record, Snapshotting};
+
+    struct Push(char);
+
+    impl Command<(String, usize)> for Push {
+        type Error = ();
+
+        fn apply(&mut self, (s, len): &mut (String, usize)) -> Result<(), ()> {
+            s.push(self.0);
+            *len += 1;
+            Ok(())
+        }
+
+        fn undo(&mut self, _: &mut (String, usize)) -> Result<(), ()> {
+            unreachable!()
+        }
+    }
+
+    fn string((s, _): &mut (String, usize)) -> &mut String {
+        s
+    }
+
+    #[test]
+    fn capture() {
+        let mut record = Record::new((String::new(), 0));
+        record.apply(Capturing::new(Push('a'), string)).unwrap();
+        record.apply(Capturing::new(Push('b'), string)).unwrap();
+        assert_eq!(record.as_receiver(), &("ab".to_string(), 2));
+        record.undo().unwrap().unwrap();
+        // Only the captured part is restored.
+        assert_eq!(record.as_receiver(), &("a".to_string(), 2));
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), &(String::new(), 2));
+        record.redo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), &("ab".to_string(), 4));
+    }
+
+    #[test]
+    fn capture_without_snapshot() {
+        struct Type(char);
+
+        impl Command<(String, usize)> for Type {
+            type Error = ();
+
+            fn apply(&mut self, (s, _): &mut (String, usize)) -> Result<(), ()> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, (s, _): &mut (String, usize)) -> Result<(), ()> {
+                s.pop();
+                Ok(())
+            }
+        }
+
+        // The commands were applied before the record was built, so nothing has been captured.
+        let mut record = Record::builder()
+            .entries(vec![Capturing::new(Type('a'), string)], 1)
+            .build(("a".to_string(), 0));
+        assert!(record.get(0).unwrap().snapshot().is_none());
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), &(String::new(), 0));
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), &("a".to_string(), 0));
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), &(String::new(), 0));
+    }
+
+    #[test]
+    fn capture_with_snapshots() {
+        let mut record = Record::builder()
+            .snapshot_every(2, <(String, usize)>::clone, |snapshot, receiver| {
+                receiver.clone_from(snapshot)
+            })
+            .build((String::new(), 0));
+        for c in "abcd".chars() {
+            record.apply(Capturing::new(Push(c), string)).unwrap();
+        }
+        // The captured states are updated by redo, so the snapshots are not used.
+        record.go_to(0).unwrap().unwrap();
+        record.go_to(4).unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), &("abc".to_string(), 8));
+    }
+
+    #[test]
+    fn capture_forwards() {
+        struct Add(bool, i32);
+
+        impl Command<(i32, i32)> for Add {
+            type Error = ();
+
+            fn apply(&mut self, (a, b): &mut (i32, i32)) -> Result<(), ()> {
+                *if self.0 { a } else { b } += self.1;
+                Ok(())
+            }
+
+            fn undo(&mut self, _: &mut (i32, i32)) -> Result<(), ()> {
+                unreachable!()
+            }
+
+            fn transpose(&self, command: &Self) -> bool {
+                self.0 != command.0
+            }
+
+            fn restorable(&self) -> bool {
+                true
+            }
+        }
+
+        type Lens = fn(&mut (i32, i32)) -> &mut i32;
+        let first: Lens = |(a, _)| a;
+        let second: Lens = |(_, b)| b;
+        let mut record = Record::new((0, 0));
+        record.apply(Capturing::new(Add(true, 1), first)).unwrap();
+        record.apply(Capturing::new(Add(false, 2), second)).unwrap();
+        assert!(record.get(0).unwrap().transpose(record.get(1).unwrap()));
+        assert!(record.get(0).unwrap().restorable());
+        // The commands change different fields, so the first can be undone directly.
+        record.undo_at(0).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), &(0, 2));
+        assert_eq!(record.len(), 1);
+    }
+
+    struct Filter(bool);
+
+    impl Command<Vec<u8>> for Filter {
+        type Error = ();
+
+        fn apply(&mut self, v: &mut Vec<u8>) -> Result<(), ()> {
+            v.iter_mut().for_each(|x| *x = x.wrapping_add(1));
+            Ok(())
+        }
+
+        fn undo(&mut self, v: &mut Vec<u8>) -> Result<(), ()> {
+            // Pretend that this is slow when the cost is high.
+            v.iter_mut().for_each(|x| *x = x.wrapping_sub(1));
+            Ok(())
+        }
+
+        fn cost(&self) -> usize {
+            if self.0 {
+                100
+            } else {
+                0
+            }
+        }
+    }
+
+    #[test]
+    fn snapshotting() {
+        let mut record = Record::new(vec![1, 2]);
+        record.apply(Snapshotting::new(Filter(true), 10)).unwrap();
+        record.apply(Snapshotting::new(Filter(false), 10)).unwrap();
+        assert_eq!(record.as_receiver(), &[3, 4]);
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), &[2, 3]);
+        // The receiver is changed behind the records back, and the snapshot is restored.
+        record.as_mut_receiver().push(0);
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), &[1, 2]);
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), &[2, 3]);
+    }
+}
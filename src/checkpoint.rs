@@ -1,5 +1,10 @@
-use crate::{Command, Entry, History, Queue, Record, Signal};
-use std::collections::VecDeque;
+#[cfg(feature = "history")]
+use crate::History;
+#[cfg(feature = "queue")]
+use crate::Queue;
+use crate::{Command, Entry, Position, Record, Signal};
+use alloc::{collections::VecDeque, vec::Vec};
+use core::{error, fmt};
 
 /// A checkpoint wrapper.
 ///
@@ -103,7 +108,12 @@ impl<R, C: Command<R>, F: FnMut(Signal)> Checkpoint<'_, Record<R, C, F>, C> {
     /// [`apply`]: struct.Record.html#method.apply
     #[inline]
     pub fn apply(&mut self, command: C) -> Result<(), C::Error> {
-        let (_, v) = self.inner.__apply(Entry::from(command))?;
+        // Skipped no-ops leave nothing to cancel.
+        if !self.inner.keep_noops && command.is_noop(self.inner.as_receiver()) {
+            return Ok(());
+        }
+        let entry = self.inner.entry(command);
+        let (_, v) = self.inner.__apply(entry)?;
         self.stack.push(Action::Apply(v));
         Ok(())
     }
@@ -144,13 +154,40 @@ impl<R, C: Command<R>, F: FnMut(Signal)> Checkpoint<'_, Record<R, C, F>, C> {
         let old = self.inner.current();
         match self.inner.go_to(current) {
             Some(Ok(_)) => {
-                self.stack.push(Action::GoTo(0, old));
+                self.stack.push(Action::GoTo(Position::new(0, old)));
                 Some(Ok(()))
             }
             go_to => go_to,
         }
     }
 
+    /// Calls the [`set_saved`] method.
+    ///
+    /// The previous saved state is restored when the checkpoint is canceled.
+    ///
+    /// [`set_saved`]: struct.Record.html#method.set_saved
+    #[inline]
+    pub fn set_saved(&mut self, saved: bool) {
+        let old = self.inner.saved();
+        self.inner.set_saved(saved);
+        self.stack
+            .push(Action::Saved(old.map(|current| Position::new(0, current))));
+    }
+
+    /// Calls the [`set_limit`] method and returns the new limit.
+    ///
+    /// The previous limit is restored when the checkpoint is canceled,
+    /// but the commands that were removed because of the new limit are not.
+    ///
+    /// [`set_limit`]: struct.Record.html#method.set_limit
+    #[inline]
+    pub fn set_limit(&mut self, limit: usize) -> usize {
+        let old = self.inner.limit();
+        let limit = self.inner.set_limit(limit);
+        self.stack.push(Action::Limit(old));
+        limit
+    }
+
     /// Calls the [`extend`] method.
     ///
     /// [`extend`]: struct.Record.html#method.extend
@@ -164,50 +201,69 @@ impl<R, C: Command<R>, F: FnMut(Signal)> Checkpoint<'_, Record<R, C, F>, C> {
 
     /// Cancels the changes and consumes the checkpoint.
     ///
+    /// If the signals are deferred, they are emitted once all the changes have been canceled.
+    ///
     /// # Errors
     /// If an error occur when canceling the changes, the error is returned
     /// and the remaining commands are not canceled.
     #[inline]
     pub fn cancel(self) -> Result<(), C::Error> {
-        for action in self.stack.into_iter().rev() {
-            match action {
-                Action::Apply(mut v) => {
-                    if let Some(Err(error)) = self.inner.undo() {
-                        return Err(error);
+        let Checkpoint { inner, stack } = self;
+        inner.transaction(|inner| {
+            for action in stack.into_iter().rev() {
+                match action {
+                    Action::Apply(mut v) => {
+                        if let Some(Err(error)) = inner.__undo() {
+                            return Err(error);
+                        }
+                        let current = inner.current();
+                        inner.commands.truncate(current);
+                        inner.commands.append(&mut v);
                     }
-                    let current = self.inner.current();
-                    self.inner.commands.truncate(current);
-                    self.inner.commands.append(&mut v);
-                }
-                Action::Undo => {
-                    if let Some(Err(error)) = self.inner.redo() {
-                        return Err(error);
+                    Action::Undo => {
+                        if let Some(Err(error)) = inner.redo() {
+                            return Err(error);
+                        }
                     }
-                }
-                Action::Redo => {
-                    if let Some(Err(error)) = self.inner.undo() {
-                        return Err(error);
+                    Action::Redo => {
+                        if let Some(Err(error)) = inner.undo() {
+                            return Err(error);
+                        }
                     }
-                }
-                Action::GoTo(_, current) => {
-                    if let Some(Err(error)) = self.inner.go_to(current) {
-                        return Err(error);
+                    Action::GoTo(position) => {
+                        if let Some(Err(error)) = inner.go_to(position.current()) {
+                            return Err(error);
+                        }
+                    }
+                    Action::Saved(saved) => inner.saved = saved.map(|saved| saved.current()),
+                    Action::Limit(limit) => {
+                        inner.set_limit(limit);
                     }
                 }
             }
+            Ok(())
+        })
+    }
+
+    /// Cancels the changes because of the `error`.
+    #[inline]
+    pub(crate) fn rollback(self, error: C::Error) -> RollbackError<C::Error> {
+        match self.cancel() {
+            Ok(()) => RollbackError::Restored(error),
+            Err(rollback) => RollbackError::Unrestored { error, rollback },
         }
-        Ok(())
     }
 
     /// Returns a checkpoint.
     #[inline]
-    pub fn checkpoint(&mut self) -> Checkpoint<Record<R, C, F>, C> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, Record<R, C, F>, C> {
         self.inner.checkpoint()
     }
 
     /// Returns a queue.
     #[inline]
-    pub fn queue(&mut self) -> Queue<Record<R, C, F>, C> {
+    #[cfg(feature = "queue")]
+    pub fn queue(&mut self) -> Queue<'_, Record<R, C, F>, C> {
         self.inner.queue()
     }
 
@@ -240,16 +296,16 @@ impl<R, C, F> AsMut<R> for Checkpoint<'_, Record<R, C, F>, C> {
     }
 }
 
+#[cfg(feature = "history")]
 impl<R, C: Command<R>, F: FnMut(Signal)> Checkpoint<'_, History<R, C, F>, C> {
     /// Calls the [`apply`] method.
     ///
     /// [`apply`]: struct.History.html#method.apply
     #[inline]
     pub fn apply(&mut self, command: C) -> Result<(), C::Error> {
-        let root = self.inner.branch();
-        let old = self.inner.current();
+        let position = self.inner.position();
         self.inner.apply(command)?;
-        self.stack.push(Action::GoTo(root, old));
+        self.stack.push(Action::GoTo(position));
         Ok(())
     }
 
@@ -286,17 +342,42 @@ impl<R, C: Command<R>, F: FnMut(Signal)> Checkpoint<'_, History<R, C, F>, C> {
     /// [`go_to`]: struct.History.html#method.go_to
     #[inline]
     pub fn go_to(&mut self, branch: usize, current: usize) -> Option<Result<(), C::Error>> {
-        let root = self.inner.branch();
-        let old = self.inner.current();
+        let position = self.inner.position();
         match self.inner.go_to(branch, current) {
             Some(Ok(_)) => {
-                self.stack.push(Action::GoTo(root, old));
+                self.stack.push(Action::GoTo(position));
                 Some(Ok(()))
             }
             go_to => go_to,
         }
     }
 
+    /// Calls the [`set_saved`] method.
+    ///
+    /// The previous saved state is restored when the checkpoint is canceled.
+    ///
+    /// [`set_saved`]: struct.History.html#method.set_saved
+    #[inline]
+    pub fn set_saved(&mut self, saved: bool) {
+        let old = self.inner.saved().map(Position::from);
+        self.inner.set_saved(saved);
+        self.stack.push(Action::Saved(old));
+    }
+
+    /// Calls the [`set_limit`] method and returns the new limit.
+    ///
+    /// The previous limit is restored when the checkpoint is canceled,
+    /// but the commands that were removed because of the new limit are not.
+    ///
+    /// [`set_limit`]: struct.History.html#method.set_limit
+    #[inline]
+    pub fn set_limit(&mut self, limit: usize) -> usize {
+        let old = self.inner.limit();
+        let limit = self.inner.set_limit(limit);
+        self.stack.push(Action::Limit(old));
+        limit
+    }
+
     /// Calls the [`extend`] method.
     ///
     /// [`extend`]: struct.History.html#method.extend
@@ -310,43 +391,62 @@ impl<R, C: Command<R>, F: FnMut(Signal)> Checkpoint<'_, History<R, C, F>, C> {
 
     /// Cancels the changes and consumes the checkpoint.
     ///
+    /// If the signals are deferred, they are emitted once all the changes have been canceled.
+    ///
     /// # Errors
     /// If an error occur when canceling the changes, the error is returned
     /// and the remaining commands are not canceled.
     #[inline]
     pub fn cancel(self) -> Result<(), C::Error> {
-        for action in self.stack.into_iter().rev() {
-            match action {
-                Action::Apply(_) => unreachable!(),
-                Action::Undo => {
-                    if let Some(Err(error)) = self.inner.redo() {
-                        return Err(error);
+        let Checkpoint { inner, stack } = self;
+        inner.transaction(|inner| {
+            for action in stack.into_iter().rev() {
+                match action {
+                    Action::Apply(_) => unreachable!(),
+                    Action::Undo => {
+                        if let Some(Err(error)) = inner.redo() {
+                            return Err(error);
+                        }
                     }
-                }
-                Action::Redo => {
-                    if let Some(Err(error)) = self.inner.undo() {
-                        return Err(error);
+                    Action::Redo => {
+                        if let Some(Err(error)) = inner.undo() {
+                            return Err(error);
+                        }
                     }
-                }
-                Action::GoTo(branch, current) => {
-                    if let Some(Err(error)) = self.inner.go_to(branch, current) {
-                        return Err(error);
+                    Action::GoTo(position) => {
+                        if let Some(Err(error)) = inner.go_to_position(position) {
+                            return Err(error);
+                        }
+                    }
+                    Action::Saved(saved) => inner.restore_saved(saved),
+                    Action::Limit(limit) => {
+                        inner.set_limit(limit);
                     }
                 }
             }
+            Ok(())
+        })
+    }
+
+    /// Cancels the changes because of the `error`.
+    #[inline]
+    pub(crate) fn rollback(self, error: C::Error) -> RollbackError<C::Error> {
+        match self.cancel() {
+            Ok(()) => RollbackError::Restored(error),
+            Err(rollback) => RollbackError::Unrestored { error, rollback },
         }
-        Ok(())
     }
 
     /// Returns a checkpoint.
     #[inline]
-    pub fn checkpoint(&mut self) -> Checkpoint<History<R, C, F>, C> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, History<R, C, F>, C> {
         self.inner.checkpoint()
     }
 
     /// Returns a queue.
     #[inline]
-    pub fn queue(&mut self) -> Queue<History<R, C, F>, C> {
+    #[cfg(feature = "queue")]
+    pub fn queue(&mut self) -> Queue<'_, History<R, C, F>, C> {
         self.inner.queue()
     }
 
@@ -365,6 +465,7 @@ impl<R, C: Command<R>, F: FnMut(Signal)> Checkpoint<'_, History<R, C, F>, C> {
     }
 }
 
+#[cfg(feature = "history")]
 impl<R, C, F> AsRef<R> for Checkpoint<'_, History<R, C, F>, C> {
     #[inline]
     fn as_ref(&self) -> &R {
@@ -372,6 +473,7 @@ impl<R, C, F> AsRef<R> for Checkpoint<'_, History<R, C, F>, C> {
     }
 }
 
+#[cfg(feature = "history")]
 impl<R, C, F> AsMut<R> for Checkpoint<'_, History<R, C, F>, C> {
     #[inline]
     fn as_mut(&mut self) -> &mut R {
@@ -379,17 +481,270 @@ impl<R, C, F> AsMut<R> for Checkpoint<'_, History<R, C, F>, C> {
     }
 }
 
+/// A checkpoint over several records or histories at once.
+///
+/// The commands are applied to one of the records by its index, and the changes to all of
+/// them are committed or canceled together. This gives an all-or-nothing operation over several
+/// documents, where [`atomic`] cancels all the changes on the first error.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, MultiCheckpoint, Record};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = &'static str;
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+/// #         if self.0 == '!' {
+/// #             return Err("invalid");
+/// #         }
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+/// #         self.0 = s.pop().ok_or("`s` is empty")?;
+/// #         Ok(())
+/// #     }
+/// # }
+/// let mut a = Record::default();
+/// let mut b = Record::default();
+/// let result = MultiCheckpoint::new(vec![&mut a, &mut b]).atomic(|cp| {
+///     cp.apply(0, Add('a'))?;
+///     cp.apply(1, Add('b'))?;
+///     cp.apply(1, Add('!'))
+/// });
+/// assert!(result.unwrap_err().is_restored());
+/// assert_eq!(a.as_receiver(), "");
+/// assert_eq!(b.as_receiver(), "");
+/// ```
+///
+/// [`atomic`]: struct.MultiCheckpoint.html#method.atomic
+#[derive(Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct MultiCheckpoint<'a, T, C> {
+    checkpoints: Vec<Checkpoint<'a, T, C>>,
+}
+
+impl<'a, T, C> MultiCheckpoint<'a, T, C> {
+    /// Returns a checkpoint over the records or histories, in the order they are given.
+    #[inline]
+    pub fn new(inners: impl IntoIterator<Item = &'a mut T>) -> MultiCheckpoint<'a, T, C> {
+        MultiCheckpoint {
+            checkpoints: inners.into_iter().map(Checkpoint::new).collect(),
+        }
+    }
+
+    /// Returns the number of records or histories in the checkpoint.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    /// Returns `true` if the checkpoint does not contain any records or histories.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.checkpoints.is_empty()
+    }
+
+    /// Returns a reference to the checkpoint of the record or history at `index`.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&Checkpoint<'a, T, C>> {
+        self.checkpoints.get(index)
+    }
+
+    /// Returns a mutable reference to the checkpoint of the record or history at `index`.
+    ///
+    /// The changes made through it are committed and canceled together with the others.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Checkpoint<'a, T, C>> {
+        self.checkpoints.get_mut(index)
+    }
+
+    /// Commits the changes to all the records or histories and consumes the checkpoint.
+    #[inline]
+    pub fn commit(self) {}
+}
+
+impl<R, C: Command<R>, F: FnMut(Signal)> MultiCheckpoint<'_, Record<R, C, F>, C> {
+    /// Calls the [`apply`] method on the record at `index`.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    ///
+    /// [`apply`]: struct.Record.html#method.apply
+    #[inline]
+    pub fn apply(&mut self, index: usize, command: C) -> Result<(), C::Error> {
+        self.checkpoints[index].apply(command)
+    }
+
+    /// Cancels the changes to all the records and consumes the checkpoint.
+    ///
+    /// The records are canceled in the reverse order, and a record that fails to be
+    /// canceled does not stop the others from being canceled.
+    ///
+    /// # Errors
+    /// If an error occur when canceling the changes, the first error is returned.
+    #[inline]
+    pub fn cancel(self) -> Result<(), C::Error> {
+        let mut result = Ok(());
+        for checkpoint in self.checkpoints.into_iter().rev() {
+            if let Err(error) = checkpoint.cancel() {
+                result = result.and(Err(error));
+            }
+        }
+        result
+    }
+
+    /// Calls `f` with the checkpoint, commits the changes if it succeeds,
+    /// and cancels the changes to all the records if it fails.
+    ///
+    /// # Errors
+    /// If `f` returns an error the changes are canceled and the error is returned
+    /// together with the result of canceling them.
+    #[inline]
+    pub fn atomic<T>(
+        mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, C::Error>,
+    ) -> Result<T, RollbackError<C::Error>> {
+        match f(&mut self) {
+            Ok(t) => {
+                self.commit();
+                Ok(t)
+            }
+            Err(error) => match self.cancel() {
+                Ok(()) => Err(RollbackError::Restored(error)),
+                Err(rollback) => Err(RollbackError::Unrestored { error, rollback }),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "history")]
+impl<R, C: Command<R>, F: FnMut(Signal)> MultiCheckpoint<'_, History<R, C, F>, C> {
+    /// Calls the [`apply`] method on the history at `index`.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    ///
+    /// [`apply`]: struct.History.html#method.apply
+    #[inline]
+    pub fn apply(&mut self, index: usize, command: C) -> Result<(), C::Error> {
+        self.checkpoints[index].apply(command)
+    }
+
+    /// Cancels the changes to all the histories and consumes the checkpoint.
+    ///
+    /// The histories are canceled in the reverse order, and a history that fails to be
+    /// canceled does not stop the others from being canceled.
+    ///
+    /// # Errors
+    /// If an error occur when canceling the changes, the first error is returned.
+    #[inline]
+    pub fn cancel(self) -> Result<(), C::Error> {
+        let mut result = Ok(());
+        for checkpoint in self.checkpoints.into_iter().rev() {
+            if let Err(error) = checkpoint.cancel() {
+                result = result.and(Err(error));
+            }
+        }
+        result
+    }
+
+    /// Calls `f` with the checkpoint, commits the changes if it succeeds,
+    /// and cancels the changes to all the histories if it fails.
+    ///
+    /// # Errors
+    /// If `f` returns an error the changes are canceled and the error is returned
+    /// together with the result of canceling them.
+    #[inline]
+    pub fn atomic<T>(
+        mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, C::Error>,
+    ) -> Result<T, RollbackError<C::Error>> {
+        match f(&mut self) {
+            Ok(t) => {
+                self.commit();
+                Ok(t)
+            }
+            Err(error) => match self.cancel() {
+                Ok(()) => Err(RollbackError::Restored(error)),
+                Err(rollback) => Err(RollbackError::Unrestored { error, rollback }),
+            },
+        }
+    }
+}
+
 /// An action that can be applied to a Record or History.
 #[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
 enum Action<C> {
     Apply(VecDeque<Entry<C>>),
     Undo,
     Redo,
-    GoTo(usize, usize),
+    GoTo(Position),
+    Saved(Option<Position>),
+    Limit(usize),
+}
+
+/// The error returned when an atomic operation fails.
+///
+/// The changes made by the operation are canceled before the error is returned.
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum RollbackError<E> {
+    /// The operation failed and the changes were canceled.
+    Restored(E),
+    /// The operation failed and the changes could not be canceled.
+    Unrestored {
+        /// The error that caused the rollback.
+        error: E,
+        /// The error returned when canceling the changes.
+        rollback: E,
+    },
+    /// The changes were rejected and could not be canceled.
+    Rejected(E),
+}
+
+impl<E> RollbackError<E> {
+    /// Returns `true` if the changes were canceled.
+    #[inline]
+    pub fn is_restored(&self) -> bool {
+        matches!(self, RollbackError::Restored(_))
+    }
+
+    /// Returns the error that caused the rollback,
+    /// or the error returned when canceling the rejected changes.
+    #[inline]
+    pub fn into_error(self) -> E {
+        match self {
+            RollbackError::Restored(error)
+            | RollbackError::Unrestored { error, .. }
+            | RollbackError::Rejected(error) => error,
+        }
+    }
 }
 
+impl<E: fmt::Display> fmt::Display for RollbackError<E> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RollbackError::Restored(error) => write!(f, "{} (changes canceled)", error),
+            RollbackError::Unrestored { error, rollback } => {
+                write!(f, "{} (changes could not be canceled: {})", error, rollback)
+            }
+            RollbackError::Rejected(error) => {
+                write!(
+                    f,
+                    "changes were rejected but could not be canceled: {}",
+                    error
+                )
+            }
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> error::Error for RollbackError<E> {}
+
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "queue")]
+    use crate::RollbackError;
     use crate::{Command, Record};
     use std::error;
 
@@ -456,4 +811,122 @@ mod tests {
         cp1.cancel().unwrap();
         assert_eq!(record.as_receiver(), "");
     }
+
+    #[test]
+    fn cancel_saved_and_limit() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        let mut cp = record.checkpoint();
+        cp.apply(Add('b')).unwrap();
+        cp.set_saved(true);
+        cp.apply(Add('c')).unwrap();
+        assert_eq!(cp.set_limit(10), 10);
+        cp.cancel().unwrap();
+        assert_eq!(record.as_receiver(), "a");
+        assert_eq!(record.saved(), Some(0));
+        assert_eq!(record.limit(), usize::MAX);
+    }
+
+    #[test]
+    #[cfg(feature = "history")]
+    fn cancel_history_saved() {
+        use crate::History;
+
+        let mut history = History::default();
+        history.extend(vec![Add('a'), Add('b')]).unwrap();
+        history.undo().unwrap().unwrap();
+        history.set_saved(true);
+        let saved = history.saved();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('c')).unwrap();
+        let mut cp = history.checkpoint();
+        cp.set_saved(true);
+        cp.apply(Add('d')).unwrap();
+        cp.set_saved(false);
+        cp.cancel().unwrap();
+        assert_eq!(history.as_receiver(), "c");
+        assert_eq!(history.saved(), saved);
+        history.go_to_saved().unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "a");
+    }
+
+    #[test]
+    fn multi_checkpoint() {
+        use crate::MultiCheckpoint;
+
+        let mut a = Record::default();
+        let mut b = Record::default();
+        a.apply(Add('a')).unwrap();
+        let mut cp = MultiCheckpoint::new(vec![&mut a, &mut b]);
+        assert_eq!(cp.len(), 2);
+        cp.apply(0, Add('b')).unwrap();
+        cp.apply(1, Add('c')).unwrap();
+        cp.get_mut(0).unwrap().undo().unwrap().unwrap();
+        cp.get_mut(0).unwrap().undo().unwrap().unwrap();
+        assert_eq!(cp.get(0).unwrap().as_receiver(), "");
+        cp.cancel().unwrap();
+        assert_eq!(a.as_receiver(), "a");
+        assert_eq!(b.as_receiver(), "");
+        let result = MultiCheckpoint::new(vec![&mut a, &mut b]).atomic(|cp| {
+            cp.apply(0, Add('b'))?;
+            cp.apply(1, Add('c'))
+        });
+        assert!(result.is_ok());
+        assert_eq!(a.as_receiver(), "ab");
+        assert_eq!(b.as_receiver(), "c");
+        let result = MultiCheckpoint::new(vec![&mut a, &mut b]).atomic(|cp| {
+            cp.apply(1, Add('d'))?;
+            cp.get_mut(0).unwrap().go_to(0).unwrap()?;
+            cp.get_mut(1).unwrap().go_to(0).unwrap()?;
+            cp.get_mut(1).unwrap().undo().ok_or("nothing to undo")?
+        });
+        assert!(result.unwrap_err().is_restored());
+        assert_eq!(a.as_receiver(), "ab");
+        assert_eq!(b.as_receiver(), "c");
+    }
+
+    #[test]
+    #[cfg(feature = "queue")]
+    fn extend_atomic() {
+        struct Push(char);
+
+        impl Command<String> for Push {
+            type Error = &'static str;
+
+            fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+                if self.0 == '!' {
+                    return Err("invalid");
+                }
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+                self.0 = s.pop().ok_or("`s` is empty")?;
+                Ok(())
+            }
+        }
+
+        let mut record = Record::default();
+        record.apply(Push('a')).unwrap();
+        record.apply(Push('b')).unwrap();
+        record.undo().unwrap().unwrap();
+        let commands = vec![Push('c'), Push('d'), Push('!')];
+        assert_eq!(
+            record.extend_atomic(commands),
+            Err(RollbackError::Restored("invalid"))
+        );
+        assert_eq!(record.as_receiver(), "a");
+        assert_eq!(record.len(), 2);
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        let mut queue = record.queue();
+        queue.apply(Push('c'));
+        queue.undo();
+        queue.undo();
+        queue.apply(Push('!'));
+        assert!(queue.commit_atomic().unwrap_err().is_restored());
+        assert_eq!(record.as_receiver(), "ab");
+        assert_eq!(record.len(), 2);
+    }
 }
@@ -0,0 +1,183 @@
+use crate::{timestamp, Time, Timestamp};
+use alloc::sync::Arc;
+use core::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+/// A source of the time that is stored with the commands when they are applied.
+///
+/// By default the system time is used, but without the `chrono` or `time` feature it is only
+/// recorded when [`merge_within`] is set. A clock can be set with [`RecordBuilder::clock`] to use
+/// another source of the time, e.g. a mock clock in tests, or a time that is synchronized
+/// between the users of a shared workspace. The time is used for merging commands with
+/// [`merge_within`], and for finding the commands with `time_travel`.
+///
+/// The trait is implemented for functions that return the time.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, Record};
+/// # use std::time::{Duration, SystemTime, UNIX_EPOCH};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+/// let start = UNIX_EPOCH + Duration::from_secs(60);
+/// let mut record = Record::builder().clock(move || start.into()).default();
+/// record.apply(Add('a')).unwrap();
+/// let time = record.entries().next().unwrap().timestamp();
+/// assert_eq!(SystemTime::from(time), start);
+/// ```
+///
+/// [`RecordBuilder::clock`]: struct.RecordBuilder.html#method.clock
+/// [`merge_within`]: struct.RecordBuilder.html#method.merge_within
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Time;
+}
+
+impl<F: Fn() -> Time + Send + Sync> Clock for F {
+    #[inline]
+    fn now(&self) -> Time {
+        self()
+    }
+}
+
+/// The clock used by a record, which is the system time if no clock has been set.
+///
+/// The clock is shared when the record is cloned, and it is ignored when comparing and
+/// hashing records.
+#[derive(Clone, Default)]
+pub(crate) struct SharedClock(Option<Arc<dyn Clock>>);
+
+impl SharedClock {
+    /// Returns a shared clock that uses `clock`.
+    #[inline]
+    pub(crate) fn new(clock: impl Clock + 'static) -> SharedClock {
+        SharedClock(Some(Arc::new(clock)))
+    }
+
+    /// Returns the current time of the clock, or `None` if the system time should be used.
+    #[inline]
+    pub(crate) fn now(&self) -> Option<Timestamp> {
+        self.0.as_ref().map(|clock| timestamp(clock.now()))
+    }
+}
+
+impl PartialEq for SharedClock {
+    #[inline]
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for SharedClock {}
+
+impl PartialOrd for SharedClock {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SharedClock {
+    #[inline]
+    fn cmp(&self, _: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl Hash for SharedClock {
+    #[inline]
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+impl fmt::Debug for SharedClock {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("SharedClock")
+            .field(&self.0.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Merge, Record};
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    struct Add(String);
+
+    impl Command<String> for Add {
+        type Error = ();
+
+        fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+            s.push_str(&self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+            s.truncate(s.len() - self.0.len());
+            Ok(())
+        }
+
+        fn merge(&mut self, Add(s): Self) -> Merge<Self> {
+            self.0.push_str(&s);
+            Merge::Yes
+        }
+    }
+
+    // The time is a `SystemTime` without chrono or time, which makes the conversions redundant.
+    #[test]
+    #[allow(clippy::useless_conversion)]
+    fn mock_clock() {
+        let secs = Arc::new(AtomicU64::new(0));
+        let clock = secs.clone();
+        let mut record = Record::builder()
+            .merge_within(Duration::from_secs(1))
+            .clock(move || (UNIX_EPOCH + Duration::from_secs(clock.load(Ordering::SeqCst))).into())
+            .default();
+        record.apply(Add("a".into())).unwrap();
+        record.apply(Add("b".into())).unwrap();
+        assert_eq!(record.len(), 1);
+        secs.store(10, Ordering::SeqCst);
+        record.apply(Add("c".into())).unwrap();
+        assert_eq!(record.len(), 2);
+        let timestamps: Vec<_> = record
+            .entries()
+            .map(|entry| std::time::SystemTime::from(entry.timestamp()))
+            .collect();
+        assert_eq!(
+            timestamps,
+            [UNIX_EPOCH, UNIX_EPOCH + Duration::from_secs(10)]
+        );
+        #[cfg(feature = "chrono")]
+        {
+            let time = chrono::DateTime::<chrono::Utc>::from(UNIX_EPOCH + Duration::from_secs(5));
+            record.time_travel(&time).unwrap().unwrap();
+            assert_eq!(record.as_receiver(), "ab");
+        }
+        #[cfg(all(feature = "time", not(feature = "chrono")))]
+        {
+            let time = time::OffsetDateTime::from(UNIX_EPOCH + Duration::from_secs(5));
+            record.time_travel(&time).unwrap().unwrap();
+            assert_eq!(record.as_receiver(), "ab");
+        }
+    }
+}
@@ -0,0 +1,63 @@
+#[cfg(feature = "std")]
+use crate::Time;
+use alloc::{string::String, vec::Vec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The time when a command was applied, stored as microseconds since the Unix epoch.
+///
+/// This is half the size of a `SystemTime`, and is converted to the public time type when
+/// it is returned to the user.
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub(crate) struct Timestamp(u64);
+
+#[cfg(feature = "std")]
+impl Timestamp {
+    /// Returns the current time.
+    #[inline]
+    pub(crate) fn now() -> Timestamp {
+        let since = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Timestamp(since.as_micros() as u64)
+    }
+
+    /// Returns the time from `from` to `self`, or `None` if `self` is before `from`.
+    #[inline]
+    pub(crate) fn since(self, from: Timestamp) -> Option<Duration> {
+        self.0.checked_sub(from.0).map(Duration::from_micros)
+    }
+
+    /// Converts the public time type to a timestamp.
+    #[inline]
+    pub(crate) fn from_time(time: Time) -> Timestamp {
+        // The public time type is a `SystemTime` unless it comes from `chrono` or `time`.
+        #[cfg(any(feature = "chrono", feature = "time"))]
+        let time = SystemTime::from(time);
+        let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Timestamp(since.as_micros() as u64)
+    }
+
+    /// Converts the timestamp to the public time type.
+    #[inline]
+    pub(crate) fn to_time(self) -> Time {
+        let time = UNIX_EPOCH + Duration::from_micros(self.0);
+        #[cfg(any(feature = "chrono", feature = "time"))]
+        let time = Time::from(time);
+        time
+    }
+}
+
+/// The rarely used parts of an entry, which are boxed to keep the entries small.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub(crate) struct Extra {
+    /// The ids of the commands that has been merged into the entry.
+    pub(crate) merged: Vec<usize>,
+    /// The label the command was applied with.
+    pub(crate) label: Option<String>,
+}
@@ -0,0 +1,303 @@
+use crate::Command;
+use alloc::vec::Vec;
+use core::{fmt, iter::FromIterator};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A command made up of several commands.
+///
+/// The commands are applied and redone in order, and undone in reverse order. If one of the
+/// commands fails to be applied, the commands before it are undone before the error is returned,
+/// so the receiver is left as it was. If one of them also fails to be undone the rollback stops
+/// there, and the error of the failed apply is still the one that is returned.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, Composite, Record};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+/// let mut record = Record::default();
+/// record.apply(Composite::from(vec![Add('a'), Add('b'), Add('c')])).unwrap();
+/// assert_eq!(record.as_receiver(), "abc");
+/// record.undo().unwrap().unwrap();
+/// assert_eq!(record.as_receiver(), "");
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Composite<C> {
+    commands: Vec<C>,
+}
+
+impl<C> Composite<C> {
+    /// Returns an empty composite command.
+    #[inline]
+    pub fn new() -> Composite<C> {
+        Composite {
+            commands: Vec::new(),
+        }
+    }
+
+    /// Adds the command to the end of the composite command.
+    #[inline]
+    pub fn push(&mut self, command: C) {
+        self.commands.push(command);
+    }
+
+    /// Returns the number of commands.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns `true` if there are no commands.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Returns the commands.
+    #[inline]
+    pub fn commands(&self) -> &[C] {
+        &self.commands
+    }
+
+    /// Consumes the composite command, returning the commands.
+    #[inline]
+    pub fn into_commands(self) -> Vec<C> {
+        self.commands
+    }
+}
+
+impl<R, C: Command<R>> Command<R> for Composite<C> {
+    type Error = C::Error;
+
+    #[inline]
+    fn apply(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        for i in 0..self.commands.len() {
+            if let Err(error) = self.commands[i].apply(receiver) {
+                for command in self.commands[..i].iter_mut().rev() {
+                    // The commands before a failed undo would be undone on the wrong state.
+                    if command.undo(receiver).is_err() {
+                        break;
+                    }
+                }
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn undo(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        self.commands
+            .iter_mut()
+            .rev()
+            .try_for_each(|command| command.undo(receiver))
+    }
+
+    #[inline]
+    fn redo(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        self.commands
+            .iter_mut()
+            .try_for_each(|command| command.redo(receiver))
+    }
+
+    #[inline]
+    fn undoable(&self, receiver: &R) -> bool {
+        self.commands
+            .iter()
+            .all(|command| command.undoable(receiver))
+    }
+
+    #[inline]
+    fn is_noop(&self, receiver: &R) -> bool {
+        // If every command is a no-op the receiver never changes, so they can all be checked against it.
+        self.commands
+            .iter()
+            .all(|command| command.is_noop(receiver))
+    }
+
+    #[inline]
+    fn validate(&self, receiver: &R) -> Result<(), C::Error> {
+        // Only the first command can be checked, since the others depend on the ones before them.
+        self.commands
+            .first()
+            .map_or(Ok(()), |command| command.validate(receiver))
+    }
+
+    #[inline]
+    fn cost(&self) -> usize {
+        self.commands.iter().map(C::cost).sum()
+    }
+
+    #[inline]
+    fn prefetch(&mut self) {
+        self.commands.iter_mut().for_each(C::prefetch);
+    }
+
+    #[inline]
+    fn transpose(&self, command: &Self) -> bool {
+        self.commands
+            .iter()
+            .all(|a| command.commands.iter().all(|b| a.transpose(b)))
+    }
+
+    #[inline]
+    fn restorable(&self) -> bool {
+        self.commands.iter().all(C::restorable)
+    }
+
+    #[inline]
+    fn is_dead(&self) -> bool {
+        self.commands.iter().any(C::is_dead)
+    }
+
+    #[inline]
+    fn region(&self) -> Option<usize> {
+        // The commands only have a region if they all change the same one.
+        let mut regions = self.commands.iter().map(C::region);
+        let first = regions.next()?;
+        if regions.all(|region| region == first) {
+            first
+        } else {
+            None
+        }
+    }
+}
+
+impl<C> Default for Composite<C> {
+    #[inline]
+    fn default() -> Self {
+        Composite::new()
+    }
+}
+
+impl<C> From<Vec<C>> for Composite<C> {
+    #[inline]
+    fn from(commands: Vec<C>) -> Self {
+        Composite { commands }
+    }
+}
+
+impl<C> FromIterator<C> for Composite<C> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = C>>(commands: I) -> Self {
+        Composite {
+            commands: commands.into_iter().collect(),
+        }
+    }
+}
+
+impl<C> Extend<C> for Composite<C> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = C>>(&mut self, commands: I) {
+        self.commands.extend(commands);
+    }
+}
+
+impl<C: fmt::Display> fmt::Display for Composite<C> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, command) in self.commands.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            (command as &dyn fmt::Display).fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Composite, Record};
+
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            if self.0 == '!' {
+                return Err("invalid");
+            }
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            self.0 = s.pop().ok_or("`s` is empty")?;
+            Ok(())
+        }
+
+        fn region(&self) -> Option<usize> {
+            self.0.to_digit(10).map(|digit| digit as usize)
+        }
+    }
+
+    #[test]
+    fn rollback() {
+        let mut record = Record::new("x");
+        let composite: Composite<_> = vec![Add('a'), Add('b'), Add('!')].into_iter().collect();
+        assert_eq!(record.apply(composite), Err("invalid"));
+        assert_eq!(record.as_receiver(), "x");
+        assert!(record.is_empty());
+        record
+            .apply(Composite::from(vec![Add('a'), Add('b')]))
+            .unwrap();
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "x");
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "xab");
+    }
+
+    #[test]
+    fn rollback_error() {
+        struct Push(char);
+
+        impl Command<String> for Push {
+            type Error = &'static str;
+
+            fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+                s.push(self.0);
+                if self.0 == '!' {
+                    return Err("invalid");
+                }
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+                match s.pop() {
+                    Some(c) if c == self.0 => Ok(()),
+                    _ => Err("wrong state"),
+                }
+            }
+        }
+
+        let mut composite = Composite::from(vec![Push('a'), Push('b'), Push('!')]);
+        let mut receiver = String::new();
+        // The failed apply leaves '!' behind, so undoing 'b' fails and the rollback stops.
+        assert_eq!(composite.apply(&mut receiver), Err("invalid"));
+        assert_eq!(receiver, "ab");
+    }
+
+    #[test]
+    fn region() {
+        let composite = Composite::from(vec![Add('1'), Add('1')]);
+        assert_eq!(Command::<String>::region(&composite), Some(1));
+        let composite = Composite::from(vec![Add('1'), Add('2')]);
+        assert_eq!(Command::<String>::region(&composite), None);
+        let composite = Composite::<Add>::new();
+        assert_eq!(Command::<String>::region(&composite), None);
+    }
+}
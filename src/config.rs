@@ -0,0 +1,154 @@
+use core::{error, fmt};
+
+/// The error returned when a record or history is configured with invalid values.
+///
+/// It is returned by the fallible alternatives to the methods that panic on invalid values,
+/// like [`Record::try_set_limit`] and [`RecordBuilder::try_build`], and by [`Record::validate`]
+/// and [`History::validate`] when a deserialized record or history is in an invalid state.
+///
+/// [`Record::try_set_limit`]: struct.Record.html#method.try_set_limit
+/// [`RecordBuilder::try_build`]: struct.RecordBuilder.html#method.try_build
+/// [`Record::validate`]: struct.Record.html#method.validate
+/// [`History::validate`]: struct.History.html#method.validate
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum ConfigError {
+    /// The limit is `0`.
+    ZeroLimit,
+    /// The position of the current command is greater than the number of commands.
+    CurrentOutOfBounds {
+        /// The position of the current command.
+        current: usize,
+        /// The number of commands.
+        len: usize,
+    },
+    /// The number of commands is greater than the limit.
+    LimitExceeded {
+        /// The number of commands.
+        len: usize,
+        /// The limit.
+        limit: usize,
+    },
+    /// The position of the saved state is greater than the number of commands.
+    SavedOutOfBounds {
+        /// The position of the saved state.
+        saved: usize,
+        /// The number of commands.
+        len: usize,
+    },
+    /// A branch or the saved state refers to a branch that is not in the history.
+    UnknownBranch(usize),
+    /// The branch is one of its own ancestors.
+    BranchCycle(usize),
+    /// The branch diverges after the end of its parent branch.
+    ParentOutOfBounds {
+        /// The id of the branch.
+        branch: usize,
+        /// The position in the parent branch where the branch diverges.
+        current: usize,
+        /// The number of commands in the parent branch.
+        len: usize,
+    },
+    /// The id that is given to the next branch is already used by the branch.
+    BranchIdInUse(usize),
+    /// The saved state is both in the current branch and in another branch.
+    DuplicateSaved,
+    /// The saved state that is kept for the other branches is in the current branch.
+    SavedInRoot,
+}
+
+impl fmt::Display for ConfigError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::ZeroLimit => f.write_str("limit can not be `0`"),
+            ConfigError::CurrentOutOfBounds { current, len } => write!(
+                f,
+                "current can not be greater than the number of commands ({} > {})",
+                current, len
+            ),
+            ConfigError::LimitExceeded { len, limit } => write!(
+                f,
+                "the number of commands can not be greater than the limit ({} > {})",
+                len, limit
+            ),
+            ConfigError::SavedOutOfBounds { saved, len } => write!(
+                f,
+                "saved can not be greater than the number of commands ({} > {})",
+                saved, len
+            ),
+            ConfigError::UnknownBranch(branch) => write!(f, "branch {} does not exist", branch),
+            ConfigError::BranchCycle(branch) => {
+                write!(f, "branch {} is one of its own ancestors", branch)
+            }
+            ConfigError::ParentOutOfBounds {
+                branch,
+                current,
+                len,
+            } => write!(
+                f,
+                "branch {} can not diverge after the end of its parent ({} > {})",
+                branch, current, len
+            ),
+            ConfigError::BranchIdInUse(branch) => {
+                write!(
+                    f,
+                    "the id of the next branch is already used by branch {}",
+                    branch
+                )
+            }
+            ConfigError::DuplicateSaved => {
+                f.write_str("saved can not be in both the current branch and another branch")
+            }
+            ConfigError::SavedInRoot => {
+                f.write_str("saved can only be in the current branch if it is kept by the record")
+            }
+        }
+    }
+}
+
+impl error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, ConfigError, Record};
+
+    #[derive(Debug)]
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = ();
+
+        fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+            self.0 = s.pop().ok_or(())?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn config_error() {
+        let mut record: Record<String, Add> = Record::default();
+        assert_eq!(record.try_set_limit(0), Err(ConfigError::ZeroLimit));
+        assert_eq!(record.limit(), usize::MAX);
+        assert_eq!(record.try_set_limit(2), Ok(2));
+        assert!(Record::<String, Add>::builder().try_limit(0).is_err());
+        let error = Record::<String, Add>::builder()
+            .entries(vec![Add('a')], 2)
+            .try_build("a")
+            .unwrap_err();
+        assert_eq!(
+            error,
+            ConfigError::CurrentOutOfBounds { current: 2, len: 1 }
+        );
+        let error = Record::<String, Add>::builder()
+            .limit(1)
+            .entries(vec![Add('a'), Add('b')], 2)
+            .try_build("ab")
+            .unwrap_err();
+        assert_eq!(error, ConfigError::LimitExceeded { len: 2, limit: 1 });
+    }
+}
@@ -0,0 +1,251 @@
+#[cfg(feature = "std")]
+use crate::time;
+use crate::{ignored::Ignored, Timestamp};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+use core::{error, fmt};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(all(feature = "std", not(any(feature = "chrono", feature = "time"))))]
+use std::time::SystemTime;
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+use time::OffsetDateTime;
+
+/// The operation that was performed on a command.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum Op {
+    /// The command was applied.
+    Apply,
+    /// The command was undone.
+    Undo,
+    /// The command was redone.
+    Redo,
+    /// The command was validated before it was applied.
+    Validate,
+}
+
+impl fmt::Display for Op {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Op::Apply => f.write_str("apply"),
+            Op::Undo => f.write_str("undo"),
+            Op::Redo => f.write_str("redo"),
+            Op::Validate => f.write_str("validate"),
+        }
+    }
+}
+
+/// Where and when an operation on a command failed.
+///
+/// The record keeps the context of the last command that failed, see [`Record::error_context`].
+///
+/// [`Record::error_context`]: struct.Record.html#method.error_context
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct OpContext {
+    op: Op,
+    index: usize,
+    branch: usize,
+    timestamp: Ignored<Timestamp>,
+}
+
+impl OpContext {
+    #[inline]
+    pub(crate) fn new(op: Op, index: usize, branch: usize, timestamp: Timestamp) -> OpContext {
+        OpContext {
+            op,
+            index,
+            branch,
+            timestamp: Ignored(timestamp),
+        }
+    }
+
+    /// Returns the operation that failed.
+    #[inline]
+    pub fn op(&self) -> Op {
+        self.op
+    }
+
+    /// Returns the index of the command in its branch.
+    ///
+    /// For a failed apply this is the index the command would have gotten.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the branch of the command, which is always `0` for a record.
+    #[inline]
+    pub fn branch(&self) -> usize {
+        self.branch
+    }
+
+    /// Returns the time the command was applied, or the time it failed to be applied.
+    #[inline]
+    #[cfg(feature = "chrono")]
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        time(*self.timestamp)
+    }
+
+    /// Returns the time the command was applied, or the time it failed to be applied.
+    #[inline]
+    #[cfg(all(feature = "time", not(feature = "chrono")))]
+    pub fn timestamp(&self) -> OffsetDateTime {
+        time(*self.timestamp)
+    }
+
+    /// Returns the time the command was applied, or the time it failed to be applied.
+    #[inline]
+    #[cfg(all(feature = "std", not(any(feature = "chrono", feature = "time"))))]
+    pub fn timestamp(&self) -> SystemTime {
+        time(*self.timestamp)
+    }
+}
+
+impl fmt::Display for OpContext {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} of entry {} on branch {}",
+            self.op, self.index, self.branch
+        )
+    }
+}
+
+/// An error from a command together with the context of the operation that failed.
+///
+/// It is returned by [`Record::with_context`].
+///
+/// [`Record::with_context`]: struct.Record.html#method.with_context
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Error<E> {
+    context: Option<OpContext>,
+    error: E,
+}
+
+impl<E> Error<E> {
+    #[inline]
+    pub(crate) fn new(context: Option<OpContext>, error: E) -> Error<E> {
+        Error { context, error }
+    }
+
+    /// Returns the context of the operation that failed.
+    ///
+    /// Returns `None` if the error was not caused by a command the record applied, undid, or redid.
+    #[inline]
+    pub fn context(&self) -> Option<&OpContext> {
+        self.context.as_ref()
+    }
+
+    /// Returns a reference to the error of the command.
+    #[inline]
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Returns the error of the command.
+    #[inline]
+    pub fn into_error(self) -> E {
+        self.error
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.context {
+            Some(ref context) => write!(f, "{} failed: {}", context, self.error),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> error::Error for Error<E> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Op, Record};
+
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            if self.0 == '!' {
+                return Err("invalid");
+            }
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.pop().ok_or("`s` is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn context() {
+        let mut record = Record::default();
+        record.extend(vec![Add('a'), Add('b')]).unwrap();
+        assert!(record.error_context().is_none());
+        let error = record.with_context(|r| r.apply(Add('!'))).unwrap_err();
+        let context = error.context().unwrap();
+        assert_eq!(context.op(), Op::Apply);
+        assert_eq!(context.index(), 2);
+        assert_eq!(
+            error.to_string(),
+            "apply of entry 2 on branch 0 failed: invalid"
+        );
+        record.as_mut_receiver().clear();
+        let error = record.with_context(|r| r.undo().unwrap()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "undo of entry 1 on branch 0 failed: `s` is empty"
+        );
+        assert_eq!(record.error_context(), error.context());
+    }
+
+    struct Pop(char);
+
+    impl Command<String> for Pop {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.pop();
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn validate(&self, s: &String) -> Result<(), Self::Error> {
+            if s.ends_with(self.0) {
+                Ok(())
+            } else {
+                Err("stale")
+            }
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let mut record = Record::new("ab");
+        record.apply(Pop('b')).unwrap();
+        let error = record.with_context(|r| r.apply(Pop('b'))).unwrap_err();
+        assert_eq!(error.context().unwrap().op(), Op::Validate);
+        assert_eq!(
+            error.to_string(),
+            "validate of entry 1 on branch 0 failed: stale"
+        );
+        assert_eq!(record.as_receiver(), "a");
+        assert_eq!(record.len(), 1);
+    }
+}
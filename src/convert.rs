@@ -0,0 +1,294 @@
+//! Conversion of the error types of commands.
+//!
+//! A record stores commands with a single error type, so commands with different error types
+//! can not be stored in the same record without converting their errors first. [MapErr] wraps
+//! a command and converts its error with a function, which makes it possible to use commands
+//! from different libraries in the same [BoxedCommand] or enum without writing the conversions
+//! by hand. Everything but the error is forwarded to the wrapped command.
+//!
+//! # Examples
+//! ```
+//! # use redo::{Command, Record, convert::MapErr, dynamic::BoxedCommand};
+//! # use std::num::ParseIntError;
+//! struct Push(char);
+//!
+//! impl Command<String> for Push {
+//!     type Error = &'static str;
+//!
+//!     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+//!         s.push(self.0);
+//!         Ok(())
+//!     }
+//!
+//!     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+//!         self.0 = s.pop().ok_or("`s` is empty")?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! struct Parse(String);
+//!
+//! impl Command<String> for Parse {
+//!     type Error = ParseIntError;
+//!
+//!     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+//!         let n: i32 = self.0.parse()?;
+//!         s.push_str(&n.to_string());
+//!         Ok(())
+//!     }
+//!
+//!     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+//!         let n: i32 = self.0.parse()?;
+//!         s.truncate(s.len() - n.to_string().len());
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let message = |e: ParseIntError| e.to_string();
+//! let mut record: Record<String, BoxedCommand<String, String>> = Record::default();
+//! record.apply(Box::new(MapErr::new(Push('a'), String::from))).unwrap();
+//! record.apply(Box::new(MapErr::new(Parse("12".into()), message))).unwrap();
+//! assert_eq!(record.as_receiver(), "a12");
+//! let error = record.apply(Box::new(MapErr::new(Parse("x".into()), message)));
+//! assert_eq!(error.unwrap_err(), "invalid digit found in string");
+//! ```
+//!
+//! [MapErr]: struct.MapErr.html
+//! [BoxedCommand]: ../dynamic/type.BoxedCommand.html
+
+use crate::{Command, Merge};
+use core::fmt;
+
+/// A command that converts the error of the wrapped command with a function.
+///
+/// Two wrapped commands are merged if the wrapped commands are merged,
+/// and the function of the command that was applied first is kept.
+#[derive(Clone, Copy)]
+pub struct MapErr<C, F> {
+    command: C,
+    f: F,
+}
+
+impl<C, F> MapErr<C, F> {
+    /// Returns a command that converts the errors of `command` with `f`.
+    #[inline]
+    pub fn new(command: C, f: F) -> MapErr<C, F> {
+        MapErr { command, f }
+    }
+
+    /// Returns a reference to the wrapped command.
+    #[inline]
+    pub fn get_ref(&self) -> &C {
+        &self.command
+    }
+
+    /// Returns a mutable reference to the wrapped command.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.command
+    }
+
+    /// Consumes the wrapper, returning the wrapped command.
+    #[inline]
+    pub fn into_inner(self) -> C {
+        self.command
+    }
+}
+
+impl<R, C: Command<R>, E, F: Fn(C::Error) -> E> Command<R> for MapErr<C, F> {
+    type Error = E;
+
+    #[inline]
+    fn apply(&mut self, receiver: &mut R) -> Result<(), E> {
+        self.command.apply(receiver).map_err(&self.f)
+    }
+
+    #[inline]
+    fn undo(&mut self, receiver: &mut R) -> Result<(), E> {
+        self.command.undo(receiver).map_err(&self.f)
+    }
+
+    #[inline]
+    fn redo(&mut self, receiver: &mut R) -> Result<(), E> {
+        self.command.redo(receiver).map_err(&self.f)
+    }
+
+    #[inline]
+    fn merge(&mut self, MapErr { command, f }: Self) -> Merge<Self> {
+        match self.command.merge(command) {
+            Merge::Yes => Merge::Yes,
+            Merge::No(command) => Merge::No(MapErr { command, f }),
+            Merge::Annul => Merge::Annul,
+        }
+    }
+
+    #[inline]
+    fn is_dead(&self) -> bool {
+        self.command.is_dead()
+    }
+
+    #[inline]
+    fn undoable(&self, receiver: &R) -> bool {
+        self.command.undoable(receiver)
+    }
+
+    #[inline]
+    fn is_noop(&self, receiver: &R) -> bool {
+        self.command.is_noop(receiver)
+    }
+
+    #[inline]
+    fn validate(&self, receiver: &R) -> Result<(), E> {
+        self.command.validate(receiver).map_err(&self.f)
+    }
+
+    #[inline]
+    fn region(&self) -> Option<usize> {
+        self.command.region()
+    }
+
+    #[inline]
+    fn cost(&self) -> usize {
+        self.command.cost()
+    }
+
+    #[inline]
+    fn prefetch(&mut self) {
+        self.command.prefetch();
+    }
+
+    #[inline]
+    fn transpose(&self, command: &Self) -> bool {
+        self.command.transpose(&command.command)
+    }
+
+    #[inline]
+    fn restorable(&self) -> bool {
+        self.command.restorable()
+    }
+}
+
+impl<C: fmt::Debug, F> fmt::Debug for MapErr<C, F> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("MapErr").field(&self.command).finish()
+    }
+}
+
+impl<C: fmt::Display, F> fmt::Display for MapErr<C, F> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.command.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MapErr;
+    use crate::{Command, Merge, Record};
+
+    #[derive(Debug, PartialEq)]
+    enum AppError {
+        Empty,
+        Full(usize),
+    }
+
+    struct Add(String);
+
+    impl Command<String> for Add {
+        type Error = usize;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), usize> {
+            if s.len() + self.0.len() > 3 {
+                return Err(s.len());
+            }
+            s.push_str(&self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), usize> {
+            s.truncate(s.len() - self.0.len());
+            Ok(())
+        }
+
+        fn merge(&mut self, Add(s): Self) -> Merge<Self> {
+            self.0.push_str(&s);
+            Merge::Yes
+        }
+    }
+
+    struct Pop(Option<char>);
+
+    impl Command<String> for Pop {
+        type Error = ();
+
+        fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+            self.0 = Some(s.pop().ok_or(())?);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+            s.push(self.0.take().ok_or(())?);
+            Ok(())
+        }
+    }
+
+    enum Edit {
+        Add(MapErr<Add, fn(usize) -> AppError>),
+        Pop(MapErr<Pop, fn(()) -> AppError>),
+    }
+
+    impl Command<String> for Edit {
+        type Error = AppError;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), AppError> {
+            match self {
+                Edit::Add(add) => add.apply(s),
+                Edit::Pop(pop) => pop.apply(s),
+            }
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), AppError> {
+            match self {
+                Edit::Add(add) => add.undo(s),
+                Edit::Pop(pop) => pop.undo(s),
+            }
+        }
+
+        fn merge(&mut self, command: Self) -> Merge<Self> {
+            match (self, command) {
+                (Edit::Add(a), Edit::Add(b)) => match a.merge(b) {
+                    Merge::Yes => Merge::Yes,
+                    Merge::No(b) => Merge::No(Edit::Add(b)),
+                    Merge::Annul => Merge::Annul,
+                },
+                (_, command) => Merge::No(command),
+            }
+        }
+    }
+
+    fn add(s: &str) -> Edit {
+        Edit::Add(MapErr::new(Add(s.into()), AppError::Full))
+    }
+
+    fn pop() -> Edit {
+        Edit::Pop(MapErr::new(Pop(None), |()| AppError::Empty))
+    }
+
+    #[test]
+    fn map_err() {
+        let mut record = Record::default();
+        assert_eq!(record.apply(pop()), Err(AppError::Empty));
+        record.apply(add("a")).unwrap();
+        record.apply(add("b")).unwrap();
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.apply(add("cd")), Err(AppError::Full(2)));
+        record.apply(pop()).unwrap();
+        assert_eq!(record.as_receiver(), "a");
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "");
+        let add = MapErr::new(Add("a".into()), AppError::Full);
+        assert_eq!(add.into_inner().0, "a");
+    }
+}
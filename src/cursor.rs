@@ -0,0 +1,58 @@
+/// A position in a record of `len` commands.
+///
+/// The arithmetic on the position is checked, so moving it outside of `0..=len`
+/// returns `None` instead of overflowing.
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub(crate) struct Cursor {
+    position: usize,
+    len: usize,
+}
+
+impl Cursor {
+    /// Returns a cursor at `position`, or `None` if it is after `len`.
+    #[inline]
+    pub(crate) fn new(position: usize, len: usize) -> Option<Cursor> {
+        if position <= len {
+            Some(Cursor { position, len })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the position.
+    #[inline]
+    pub(crate) fn get(self) -> usize {
+        self.position
+    }
+
+    /// Moves the cursor `offset` commands forward, or backward if `offset` is negative.
+    #[inline]
+    pub(crate) fn offset(self, offset: isize) -> Option<Cursor> {
+        Cursor::new(self.position.checked_add_signed(offset)?, self.len)
+    }
+
+    /// Moves the cursor as if `n` commands were removed from the start of the record.
+    ///
+    /// Returns `None` if the commands before the position are removed.
+    #[inline]
+    pub(crate) fn evict(self, n: usize) -> Option<Cursor> {
+        Cursor::new(self.position.checked_sub(n)?, self.len.checked_sub(n)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cursor;
+
+    #[test]
+    fn checked() {
+        let cursor = Cursor::new(2, 3).unwrap();
+        assert!(Cursor::new(4, 3).is_none());
+        assert_eq!(cursor.offset(1).map(Cursor::get), Some(3));
+        assert!(cursor.offset(2).is_none());
+        assert!(cursor.offset(-3).is_none());
+        assert_eq!(cursor.evict(2).map(Cursor::get), Some(0));
+        assert!(cursor.evict(3).is_none());
+        assert!(Cursor::new(3, 3).unwrap().evict(4).is_none());
+    }
+}
@@ -1,15 +1,21 @@
-use crate::{At, Entry, History, Record};
-#[cfg(feature = "chrono")]
-use chrono::{DateTime, Utc};
+#[cfg(feature = "history")]
+use crate::History;
+use crate::{time, Entry, EntryView, Position, Record, Time};
 use colored::{Color, Colorize};
-use std::fmt::{self, Write};
+use std::{
+    fmt::{self, Write},
+    time::SystemTime,
+};
+
+/// Renders the message of an entry.
+type Message<'m, C> = dyn Fn(EntryView<'_, C>) -> String + 'm;
 
 /// Configurable display formatting of structures.
 ///
 /// # Examples
 /// ```
 /// # use std::fmt::{self, Display, Formatter};
-/// # use redo::{Command, History};
+/// # use redo::{Command, Record};
 /// # struct Add(char);
 /// # impl Command<String> for Add {
 /// #     type Error = ();
@@ -19,13 +25,10 @@ use std::fmt::{self, Write};
 /// # impl Display for Add {
 /// #     fn fmt(&self, f: &mut Formatter) -> fmt::Result { Ok(()) }
 /// # }
-/// # fn foo() -> History<String, Add> {
-/// let history = History::default();
-/// println!(
-///     "{}",
-///     history.display().graph(true).colored(true).ligatures(true)
-/// );
-/// # history
+/// # fn foo() -> Record<String, Add> {
+/// let record = Record::default();
+/// println!("{}", record.display().colored(true).ligatures(true));
+/// # record
 /// # }
 /// ```
 #[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
@@ -34,7 +37,7 @@ pub struct Display<'a, T> {
     view: View,
 }
 
-impl<T> Display<'_, T> {
+impl<'a, T> Display<'a, T> {
     /// Show colored output (off by default).
     #[inline]
     pub fn colored(&mut self, on: bool) -> &mut Self {
@@ -78,94 +81,241 @@ impl<T> Display<'_, T> {
         self.view.saved = on;
         self
     }
+
+    /// Only show the commands on `page`, where each page has `page_size` commands.
+    ///
+    /// The commands are shown starting with the newest, so page `0` contains the newest commands.
+    /// In a history, only the commands in the current branch are paged.
+    ///
+    /// # Panics
+    /// Panics if `page_size` is `0`.
+    #[inline]
+    pub fn page(&mut self, page: usize, page_size: usize) -> &mut Self {
+        assert_ne!(page_size, 0, "page size can not be `0`");
+        self.view.page = Some((page, page_size));
+        self
+    }
+
+    /// Use `formatter` to format the time stamps of the commands in detailed output.
+    ///
+    /// By default the time stamps are formatted using RFC 2822 when the `chrono` or `time` feature
+    /// is enabled, and not shown otherwise. Without these features the time is only recorded when
+    /// a clock or `merge_within` is set on the record.
+    #[inline]
+    pub fn with_timestamp_formatter(&mut self, formatter: fn(SystemTime) -> String) -> &mut Self {
+        self.view.timestamp = Some(formatter);
+        self
+    }
+
+    #[inline]
+    fn formatted<G>(&self, formatter: G) -> Formatted<'a, T, G> {
+        Formatted {
+            display: Display {
+                data: self.data,
+                view: self.view,
+            },
+            formatter,
+        }
+    }
 }
 
-impl<R, C, F> Display<'_, History<R, C, F>> {
+#[cfg(feature = "history")]
+impl<'a, R, C, F> Display<'a, History<R, C, F>> {
     /// Show the history as a graph (off by default).
     #[inline]
     pub fn graph(&mut self, on: bool) -> &mut Self {
         self.view.graph = on;
         self
     }
+
+    /// Returns the number of pages of the current branch, see [`page`].
+    ///
+    /// Returns `1` if no page has been set.
+    ///
+    /// [`page`]: struct.Display.html#method.page
+    #[inline]
+    pub fn total_pages(&self) -> usize {
+        self.view.total_pages(self.data.len())
+    }
+
+    /// Use `formatter` to format the commands instead of their `Display` implementation,
+    /// see [`Display::with_formatter`] for records.
+    ///
+    /// [`Display::with_formatter`]: struct.Display.html#method.with_formatter-1
+    #[inline]
+    pub fn with_formatter<G>(&self, formatter: G) -> Formatted<'a, History<R, C, F>, G>
+    where
+        G: Fn(EntryView<'_, C>, &mut fmt::Formatter) -> fmt::Result,
+    {
+        self.formatted(formatter)
+    }
 }
 
-impl<R, C: fmt::Display, F> Display<'_, Record<R, C, F>> {
+impl<'a, R, C, F> Display<'a, Record<R, C, F>> {
+    /// Returns the number of pages, see [`page`].
+    ///
+    /// Returns `1` if no page has been set.
+    ///
+    /// [`page`]: struct.Display.html#method.page
     #[inline]
-    fn fmt_list(&self, f: &mut fmt::Formatter, at: At, entry: &Entry<C>) -> fmt::Result {
+    pub fn total_pages(&self) -> usize {
+        self.view.total_pages(self.data.len())
+    }
+
+    /// Use `formatter` to format the commands instead of their `Display` implementation.
+    ///
+    /// The formatter is given a view of the entry, which gives access to the command, its time
+    /// stamp, and whether it is the current or saved command. The position, current, and saved
+    /// decorations are still shown as configured.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = ();
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> { s.push(self.0); Ok(()) }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> { s.pop(); Ok(()) }
+    /// # }
+    /// let mut record = Record::default();
+    /// record.apply(Add('a')).unwrap();
+    /// let output = record
+    ///     .display()
+    ///     .detailed(false)
+    ///     .with_formatter(|entry, f| write!(f, "+ {}", entry.command().0))
+    ///     .to_string();
+    /// assert_eq!(output, "* [1] (current) + a\n");
+    /// ```
+    #[inline]
+    pub fn with_formatter<G>(&self, formatter: G) -> Formatted<'a, Record<R, C, F>, G>
+    where
+        G: Fn(EntryView<'_, C>, &mut fmt::Formatter) -> fmt::Result,
+    {
+        self.formatted(formatter)
+    }
+}
+
+impl<R, C, F> Display<'_, Record<R, C, F>> {
+    #[inline]
+    fn fmt_commands(&self, f: &mut fmt::Formatter, message: &Message<C>) -> fmt::Result {
+        let (skip, take) = self.view.bounds();
+        for (i, cmd) in self
+            .data
+            .commands
+            .iter()
+            .enumerate()
+            .rev()
+            .skip(skip)
+            .take(take)
+        {
+            let at = Position {
+                branch: 0,
+                current: i + 1,
+            };
+            self.fmt_list(f, at, cmd, message)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn fmt_list(
+        &self,
+        f: &mut fmt::Formatter,
+        at: Position,
+        entry: &Entry<C>,
+        message: &Message<C>,
+    ) -> fmt::Result {
+        let current = Position {
+            branch: 0,
+            current: self.data.current(),
+        };
+        let saved = self.data.saved.map(|saved| Position {
+            branch: 0,
+            current: saved,
+        });
         self.view.mark(f, 0)?;
         self.view.position(f, at, false)?;
         if self.view.detailed {
-            #[cfg(feature = "chrono")]
-            self.view.timestamp(f, &entry.timestamp)?;
+            self.view.timestamp(f, time(*entry.timestamp))?;
         }
-        self.view.current(
-            f,
-            at,
-            At {
-                branch: 0,
-                current: self.data.current(),
-            },
-        )?;
-        self.view.saved(
-            f,
-            at,
-            self.data.saved.map(|saved| At {
-                branch: 0,
-                current: saved,
-            }),
-        )?;
+        self.view.current(f, at, current)?;
+        self.view.saved(f, at, saved)?;
+        let msg = message(EntryView::new(entry, at == current, saved == Some(at)));
         if self.view.detailed {
             writeln!(f)?;
-            self.view.message(f, entry, 0)
+            self.view.message(f, &msg, 0)
         } else {
             f.write_char(' ')?;
-            self.view.message(f, entry, 0)?;
+            self.view.message(f, &msg, 0)?;
             writeln!(f)
         }
     }
 }
 
-impl<R, C: fmt::Display, F> Display<'_, History<R, C, F>> {
+#[cfg(feature = "history")]
+impl<R, C, F> Display<'_, History<R, C, F>> {
+    #[inline]
+    fn fmt_commands(&self, f: &mut fmt::Formatter, message: &Message<C>) -> fmt::Result {
+        let (skip, take) = self.view.bounds();
+        for (i, cmd) in self
+            .data
+            .record
+            .commands
+            .iter()
+            .enumerate()
+            .rev()
+            .skip(skip)
+            .take(take)
+        {
+            let at = Position {
+                branch: self.data.branch(),
+                current: i + 1,
+            };
+            if self.view.graph {
+                self.fmt_graph(f, at, cmd, 0, message)?;
+            } else {
+                self.fmt_list(f, at, cmd, 0, message)?;
+            }
+        }
+        Ok(())
+    }
+
     #[inline]
     fn fmt_list(
         &self,
         f: &mut fmt::Formatter,
-        at: At,
+        at: Position,
         entry: &Entry<C>,
         level: usize,
+        message: &Message<C>,
     ) -> fmt::Result {
+        let current = Position {
+            branch: self.data.branch(),
+            current: self.data.current(),
+        };
+        let saved = self
+            .data
+            .record
+            .saved
+            .map(|saved| Position {
+                branch: self.data.branch(),
+                current: saved,
+            })
+            .or(self.data.saved);
         self.view.mark(f, level)?;
         self.view.position(f, at, true)?;
         if self.view.detailed {
-            #[cfg(feature = "chrono")]
-            self.view.timestamp(f, &entry.timestamp)?;
+            self.view.timestamp(f, time(*entry.timestamp))?;
         }
-        self.view.current(
-            f,
-            at,
-            At {
-                branch: self.data.branch(),
-                current: self.data.current(),
-            },
-        )?;
-        self.view.saved(
-            f,
-            at,
-            self.data
-                .record
-                .saved
-                .map(|saved| At {
-                    branch: self.data.branch(),
-                    current: saved,
-                })
-                .or(self.data.saved),
-        )?;
+        self.view.current(f, at, current)?;
+        self.view.saved(f, at, saved)?;
+        let msg = message(EntryView::new(entry, at == current, saved == Some(at)));
         if self.view.detailed {
             writeln!(f)?;
-            self.view.message(f, entry, level)
+            self.view.message(f, &msg, level)
         } else {
             f.write_char(' ')?;
-            self.view.message(f, entry, level)?;
+            self.view.message(f, &msg, level)?;
             writeln!(f)
         }
     }
@@ -174,9 +324,10 @@ impl<R, C: fmt::Display, F> Display<'_, History<R, C, F>> {
     fn fmt_graph(
         &self,
         f: &mut fmt::Formatter,
-        at: At,
+        at: Position,
         entry: &Entry<C>,
         level: usize,
+        message: &Message<C>,
     ) -> fmt::Result {
         for (&i, branch) in self
             .data
@@ -185,11 +336,11 @@ impl<R, C: fmt::Display, F> Display<'_, History<R, C, F>> {
             .filter(|(_, branch)| branch.parent == at)
         {
             for (j, cmd) in branch.commands.iter().enumerate().rev() {
-                let at = At {
+                let at = Position {
                     branch: i,
                     current: j + branch.parent.current + 1,
                 };
-                self.fmt_graph(f, at, cmd, level + 1)?;
+                self.fmt_graph(f, at, cmd, level + 1, message)?;
             }
             for j in 0..level {
                 self.view.edge(f, j)?;
@@ -202,7 +353,7 @@ impl<R, C: fmt::Display, F> Display<'_, History<R, C, F>> {
             self.view.edge(f, i)?;
             f.write_char(' ')?;
         }
-        self.fmt_list(f, at, entry, level)
+        self.fmt_list(f, at, entry, level, message)
     }
 }
 
@@ -219,35 +370,85 @@ impl<'a, T> From<&'a T> for Display<'a, T> {
 impl<R, C: fmt::Display, F> fmt::Display for Display<'_, Record<R, C, F>> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for (i, cmd) in self.data.commands.iter().enumerate().rev() {
-            let at = At {
-                branch: 0,
-                current: i + 1,
-            };
-            self.fmt_list(f, at, cmd)?;
-        }
-        Ok(())
+        self.fmt_commands(f, &label_or_command)
     }
 }
 
+#[cfg(feature = "history")]
 impl<R, C: fmt::Display, F> fmt::Display for Display<'_, History<R, C, F>> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for (i, cmd) in self.data.record.commands.iter().enumerate().rev() {
-            let at = At {
-                branch: self.data.branch(),
-                current: i + 1,
-            };
-            if self.view.graph {
-                self.fmt_graph(f, at, cmd, 0)?;
-            } else {
-                self.fmt_list(f, at, cmd, 0)?;
-            }
-        }
-        Ok(())
+        self.fmt_commands(f, &label_or_command)
+    }
+}
+
+/// Returns the label of the entry if it has one, and the string of the command otherwise.
+#[inline]
+fn label_or_command<C: fmt::Display>(entry: EntryView<'_, C>) -> String {
+    match entry.label() {
+        Some(label) => label.to_string(),
+        None => entry.command().to_string(),
+    }
+}
+
+/// Display formatting of structures with a custom formatter for the commands.
+///
+/// Returned by [`Display::with_formatter`].
+///
+/// [`Display::with_formatter`]: struct.Display.html#method.with_formatter
+#[derive(Copy, Clone)]
+pub struct Formatted<'a, T, G> {
+    display: Display<'a, T>,
+    formatter: G,
+}
+
+impl<R, C, F, G> fmt::Display for Formatted<'_, Record<R, C, F>, G>
+where
+    G: Fn(EntryView<'_, C>, &mut fmt::Formatter) -> fmt::Result,
+{
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.display
+            .fmt_commands(f, &|entry| Adapter(&self.formatter, entry).to_string())
+    }
+}
+
+#[cfg(feature = "history")]
+impl<R, C, F, G> fmt::Display for Formatted<'_, History<R, C, F>, G>
+where
+    G: Fn(EntryView<'_, C>, &mut fmt::Formatter) -> fmt::Result,
+{
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.display
+            .fmt_commands(f, &|entry| Adapter(&self.formatter, entry).to_string())
+    }
+}
+
+impl<T, G> fmt::Debug for Formatted<'_, T, G> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Formatted")
+            .field("view", &self.display.view)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Formats an entry with a custom formatter.
+struct Adapter<'a, 'b, C, G>(&'a G, EntryView<'b, C>);
+
+impl<C, G> fmt::Display for Adapter<'_, '_, C, G>
+where
+    G: Fn(EntryView<'_, C>, &mut fmt::Formatter) -> fmt::Result,
+{
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (self.0)(self.1, f)
     }
 }
 
+// The formatter is compared by address, which is fine since it is only used for formatting.
+#[allow(unpredictable_function_pointer_comparisons)]
 #[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
 struct View {
     colored: bool,
@@ -257,6 +458,8 @@ struct View {
     ligatures: bool,
     position: bool,
     saved: bool,
+    timestamp: Option<fn(SystemTime) -> String>,
+    page: Option<(usize, usize)>,
 }
 
 impl Default for View {
@@ -270,11 +473,27 @@ impl Default for View {
             ligatures: false,
             position: true,
             saved: true,
+            timestamp: None,
+            page: None,
         }
     }
 }
 
 impl View {
+    /// Returns the number of commands to skip and to show.
+    #[inline]
+    fn bounds(self) -> (usize, usize) {
+        self.page.map_or((0, usize::MAX), |(page, page_size)| {
+            (page.saturating_mul(page_size), page_size)
+        })
+    }
+
+    #[inline]
+    fn total_pages(self, len: usize) -> usize {
+        self.page
+            .map_or(1, |(_, page_size)| len.div_ceil(page_size).max(1))
+    }
+
     #[inline]
     fn message(self, f: &mut fmt::Formatter, msg: &impl ToString, level: usize) -> fmt::Result {
         let msg = msg.to_string();
@@ -288,7 +507,7 @@ impl View {
                 writeln!(f, "{}", line.trim())?;
             }
         } else if let Some(line) = lines.map(str::trim).find(|s| !s.is_empty()) {
-            f.write_str(&line)?;
+            f.write_str(line)?;
         }
         Ok(())
     }
@@ -314,6 +533,7 @@ impl View {
     }
 
     #[inline]
+    #[cfg(feature = "history")]
     fn split(self, f: &mut fmt::Formatter, level: usize) -> fmt::Result {
         match (self.colored, self.ligatures) {
             (true, true) => write!(
@@ -335,7 +555,7 @@ impl View {
     }
 
     #[inline]
-    fn position(self, f: &mut fmt::Formatter, at: At, use_branch: bool) -> fmt::Result {
+    fn position(self, f: &mut fmt::Formatter, at: Position, use_branch: bool) -> fmt::Result {
         if self.position {
             if self.colored {
                 let position = if use_branch {
@@ -355,7 +575,7 @@ impl View {
     }
 
     #[inline]
-    fn current(self, f: &mut fmt::Formatter, at: At, current: At) -> fmt::Result {
+    fn current(self, f: &mut fmt::Formatter, at: Position, current: Position) -> fmt::Result {
         if self.current && at == current {
             if self.colored {
                 write!(f, " {}{}{}", "(".yellow(), "current".cyan(), ")".yellow())
@@ -368,8 +588,8 @@ impl View {
     }
 
     #[inline]
-    fn saved(self, f: &mut fmt::Formatter, at: At, saved: Option<At>) -> fmt::Result {
-        if self.saved && saved.map_or(false, |saved| saved == at) {
+    fn saved(self, f: &mut fmt::Formatter, at: Position, saved: Option<Position>) -> fmt::Result {
+        if self.saved && saved == Some(at) {
             if self.colored {
                 write!(
                     f,
@@ -386,19 +606,25 @@ impl View {
         }
     }
 
+    // The conversion is only needed when the time stamps come from `chrono` or `time`.
+    #[allow(clippy::useless_conversion)]
     #[inline]
-    #[cfg(feature = "chrono")]
-    fn timestamp(self, f: &mut fmt::Formatter, timestamp: &DateTime<Utc>) -> fmt::Result {
+    fn timestamp(self, f: &mut fmt::Formatter, timestamp: Time) -> fmt::Result {
+        let timestamp = match self.timestamp {
+            Some(formatter) => formatter(SystemTime::from(timestamp)),
+            #[cfg(feature = "chrono")]
+            None => timestamp.to_rfc2822(),
+            #[cfg(all(feature = "time", not(feature = "chrono")))]
+            None => timestamp
+                .format(&time::format_description::well_known::Rfc2822)
+                .unwrap_or_default(),
+            #[cfg(not(any(feature = "chrono", feature = "time")))]
+            None => return Ok(()),
+        };
         if self.colored {
-            write!(
-                f,
-                " {}{}{}",
-                "[".yellow(),
-                timestamp.to_rfc2822().yellow(),
-                "]".yellow()
-            )
+            write!(f, " {}{}{}", "[".yellow(), timestamp.yellow(), "]".yellow())
         } else {
-            write!(f, " [{}]", timestamp.to_rfc2822())
+            write!(f, " [{}]", timestamp)
         }
     }
 }
@@ -415,3 +641,80 @@ fn color(i: usize) -> Color {
         _ => unreachable!(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Record};
+    use std::{fmt, time::SystemTime};
+
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = ();
+
+        fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+            s.pop();
+            Ok(())
+        }
+    }
+
+    impl fmt::Display for Add {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Add {}", self.0)
+        }
+    }
+
+    #[test]
+    fn timestamp_formatter() {
+        fn formatter(_: SystemTime) -> String {
+            "now".into()
+        }
+
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        let output = record
+            .display()
+            .with_timestamp_formatter(formatter)
+            .to_string();
+        assert_eq!(output, "* [1] [now] (current)\n| Add a\n");
+    }
+
+    #[test]
+    fn page() {
+        let mut record = Record::default();
+        for c in "abcde".chars() {
+            record.apply(Add(c)).unwrap();
+        }
+        let mut display = record.display();
+        display.detailed(false).current(false).page(0, 2);
+        assert_eq!(display.total_pages(), 3);
+        assert_eq!(display.to_string(), "* [5] Add e\n* [4] Add d\n");
+        display.page(2, 2);
+        assert_eq!(display.to_string(), "* [1] Add a\n");
+        display.page(3, 2);
+        assert_eq!(display.to_string(), "");
+    }
+
+    #[test]
+    fn with_formatter() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.set_saved(true);
+        record.undo().unwrap().unwrap();
+        let output = record
+            .display()
+            .detailed(false)
+            .with_formatter(|entry, f| {
+                let icon = if entry.is_saved() { '💾' } else { '+' };
+                write!(f, "{} {}", icon, entry.command().0)
+            })
+            .to_string();
+        assert_eq!(output, "* [2] (saved) 💾 b\n* [1] (current) + a\n");
+    }
+}
@@ -0,0 +1,357 @@
+//! Dynamic dispatch of commands.
+//!
+//! A record stores a single command type, which usually means that every edit operation
+//! needs to be a variant of a large enum. [AnyCommand] is an object safe version of [Command]
+//! that is implemented for all commands, so commands of different types can be boxed
+//! and stored in the same record as a [BoxedCommand]. Two boxed commands are only merged
+//! if they have the same concrete type.
+//!
+//! # Examples
+//! ```
+//! # use redo::{Command, Record, dynamic::BoxedCommand};
+//! struct Push(char);
+//!
+//! impl Command<String> for Push {
+//!     type Error = &'static str;
+//!
+//!     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+//!         s.push(self.0);
+//!         Ok(())
+//!     }
+//!
+//!     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+//!         self.0 = s.pop().ok_or("`s` is empty")?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! struct Clear(String);
+//!
+//! impl Command<String> for Clear {
+//!     type Error = &'static str;
+//!
+//!     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+//!         self.0 = core::mem::take(s);
+//!         Ok(())
+//!     }
+//!
+//!     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+//!         *s = core::mem::take(&mut self.0);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # fn main() -> Result<(), &'static str> {
+//! let mut record: Record<String, BoxedCommand<String, &'static str>> = Record::default();
+//! record.apply(Box::new(Push('a')))?;
+//! record.apply(Box::new(Push('b')))?;
+//! record.apply(Box::new(Clear(String::new())))?;
+//! assert_eq!(record.as_receiver(), "");
+//! record.undo().unwrap()?;
+//! assert_eq!(record.as_receiver(), "ab");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [AnyCommand]: trait.AnyCommand.html
+//! [BoxedCommand]: type.BoxedCommand.html
+//! [Command]: ../trait.Command.html
+
+use crate::{Command, Merge};
+use alloc::boxed::Box;
+use core::any::Any;
+
+/// A boxed command that can hold any command with the error type `E`.
+pub type BoxedCommand<R, E> = Box<dyn AnyCommand<R, Error = E>>;
+
+/// An object safe version of [`Command`].
+///
+/// It is implemented for all commands and should not need to be implemented manually.
+///
+/// [`Command`]: ../trait.Command.html
+pub trait AnyCommand<R>: Any {
+    /// The error type.
+    type Error;
+
+    /// Calls [`Command::apply`](../trait.Command.html#tymethod.apply).
+    fn apply(&mut self, receiver: &mut R) -> Result<(), Self::Error>;
+
+    /// Calls [`Command::undo`](../trait.Command.html#tymethod.undo).
+    fn undo(&mut self, receiver: &mut R) -> Result<(), Self::Error>;
+
+    /// Calls [`Command::redo`](../trait.Command.html#method.redo).
+    fn redo(&mut self, receiver: &mut R) -> Result<(), Self::Error>;
+
+    /// Calls [`Command::merge`](../trait.Command.html#method.merge) if `command`
+    /// has the same concrete type as `self`, otherwise `command` is returned unmerged.
+    fn merge(
+        &mut self,
+        command: BoxedCommand<R, Self::Error>,
+    ) -> Merge<BoxedCommand<R, Self::Error>>;
+
+    /// Calls [`Command::is_dead`](../trait.Command.html#method.is_dead).
+    fn is_dead(&self) -> bool;
+
+    /// Calls [`Command::undoable`](../trait.Command.html#method.undoable).
+    fn undoable(&self, receiver: &R) -> bool;
+
+    /// Calls [`Command::is_noop`](../trait.Command.html#method.is_noop).
+    fn is_noop(&self, receiver: &R) -> bool;
+
+    /// Calls [`Command::validate`](../trait.Command.html#method.validate).
+    fn validate(&self, receiver: &R) -> Result<(), Self::Error>;
+
+    /// Calls [`Command::region`](../trait.Command.html#method.region).
+    fn region(&self) -> Option<usize>;
+
+    /// Calls [`Command::cost`](../trait.Command.html#method.cost).
+    fn cost(&self) -> usize;
+
+    /// Calls [`Command::prefetch`](../trait.Command.html#method.prefetch).
+    fn prefetch(&mut self);
+
+    /// Calls [`Command::transpose`](../trait.Command.html#method.transpose) if `command`
+    /// has the same concrete type as `self`, otherwise `false` is returned.
+    fn transpose(&self, command: &dyn AnyCommand<R, Error = Self::Error>) -> bool;
+
+    /// Calls [`Command::restorable`](../trait.Command.html#method.restorable).
+    fn restorable(&self) -> bool;
+
+    /// Returns the command as [`Any`](https://doc.rust-lang.org/std/any/trait.Any.html).
+    fn as_any(&self) -> &dyn Any;
+
+    /// Converts the boxed command into a boxed [`Any`](https://doc.rust-lang.org/std/any/trait.Any.html).
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<R: 'static, C: Command<R> + 'static> AnyCommand<R> for C
+where
+    C::Error: 'static,
+{
+    type Error = C::Error;
+
+    #[inline]
+    fn apply(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        Command::apply(self, receiver)
+    }
+
+    #[inline]
+    fn undo(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        Command::undo(self, receiver)
+    }
+
+    #[inline]
+    fn redo(&mut self, receiver: &mut R) -> Result<(), C::Error> {
+        Command::redo(self, receiver)
+    }
+
+    #[inline]
+    fn merge(&mut self, command: BoxedCommand<R, C::Error>) -> Merge<BoxedCommand<R, C::Error>> {
+        if !(*command).as_any().is::<C>() {
+            return Merge::No(command);
+        }
+        let command = *command.into_any().downcast::<C>().unwrap();
+        match Command::merge(self, command) {
+            Merge::Yes => Merge::Yes,
+            Merge::No(command) => Merge::No(Box::new(command)),
+            Merge::Annul => Merge::Annul,
+        }
+    }
+
+    #[inline]
+    fn is_dead(&self) -> bool {
+        Command::is_dead(self)
+    }
+
+    #[inline]
+    fn undoable(&self, receiver: &R) -> bool {
+        Command::undoable(self, receiver)
+    }
+
+    #[inline]
+    fn is_noop(&self, receiver: &R) -> bool {
+        Command::is_noop(self, receiver)
+    }
+
+    #[inline]
+    fn validate(&self, receiver: &R) -> Result<(), Self::Error> {
+        Command::validate(self, receiver)
+    }
+
+    #[inline]
+    fn region(&self) -> Option<usize> {
+        Command::region(self)
+    }
+
+    #[inline]
+    fn cost(&self) -> usize {
+        Command::cost(self)
+    }
+
+    #[inline]
+    fn prefetch(&mut self) {
+        Command::prefetch(self);
+    }
+
+    #[inline]
+    fn transpose(&self, command: &dyn AnyCommand<R, Error = C::Error>) -> bool {
+        command
+            .as_any()
+            .downcast_ref::<C>()
+            .is_some_and(|command| Command::transpose(self, command))
+    }
+
+    #[inline]
+    fn restorable(&self) -> bool {
+        Command::restorable(self)
+    }
+
+    #[inline]
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    #[inline]
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl<R: 'static, E: 'static> Command<R> for BoxedCommand<R, E> {
+    type Error = E;
+
+    #[inline]
+    fn apply(&mut self, receiver: &mut R) -> Result<(), E> {
+        (**self).apply(receiver)
+    }
+
+    #[inline]
+    fn undo(&mut self, receiver: &mut R) -> Result<(), E> {
+        (**self).undo(receiver)
+    }
+
+    #[inline]
+    fn redo(&mut self, receiver: &mut R) -> Result<(), E> {
+        (**self).redo(receiver)
+    }
+
+    #[inline]
+    fn merge(&mut self, command: Self) -> Merge<Self> {
+        (**self).merge(command)
+    }
+
+    #[inline]
+    fn is_dead(&self) -> bool {
+        (**self).is_dead()
+    }
+
+    #[inline]
+    fn undoable(&self, receiver: &R) -> bool {
+        (**self).undoable(receiver)
+    }
+
+    #[inline]
+    fn is_noop(&self, receiver: &R) -> bool {
+        (**self).is_noop(receiver)
+    }
+
+    #[inline]
+    fn validate(&self, receiver: &R) -> Result<(), Self::Error> {
+        (**self).validate(receiver)
+    }
+
+    #[inline]
+    fn region(&self) -> Option<usize> {
+        (**self).region()
+    }
+
+    #[inline]
+    fn cost(&self) -> usize {
+        (**self).cost()
+    }
+
+    #[inline]
+    fn prefetch(&mut self) {
+        (**self).prefetch();
+    }
+
+    #[inline]
+    fn transpose(&self, command: &Self) -> bool {
+        (**self).transpose(&**command)
+    }
+
+    #[inline]
+    fn restorable(&self) -> bool {
+        (**self).restorable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BoxedCommand;
+    use crate::{Command, Merge, Record};
+
+    struct Push(String);
+
+    impl Command<String> for Push {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.push_str(&self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            let len = s.len() - self.0.len();
+            s.truncate(len);
+            Ok(())
+        }
+
+        fn merge(&mut self, Push(s): Self) -> Merge<Self> {
+            self.0.push_str(&s);
+            Merge::Yes
+        }
+    }
+
+    struct Upper(String);
+
+    impl Command<String> for Upper {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            self.0 = s.to_uppercase();
+            core::mem::swap(s, &mut self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            core::mem::swap(s, &mut self.0);
+            Ok(())
+        }
+
+        fn is_noop(&self, s: &String) -> bool {
+            s.chars().all(|c| !c.is_lowercase())
+        }
+    }
+
+    #[test]
+    fn heterogeneous() {
+        let mut record: Record<String, BoxedCommand<String, &'static str>> = Record::default();
+        record.apply(Box::new(Push("a".into()))).unwrap();
+        record.apply(Box::new(Push("b".into()))).unwrap();
+        assert_eq!(record.len(), 1);
+        record.apply(Box::new(Upper(String::new()))).unwrap();
+        record.apply(Box::new(Upper(String::new()))).unwrap();
+        assert_eq!(record.len(), 2);
+        record.apply(Box::new(Push("c".into()))).unwrap();
+        assert_eq!(record.len(), 3);
+        assert_eq!(record.as_receiver(), "ABc");
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "");
+        record.go_to(3).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ABc");
+    }
+}
@@ -0,0 +1,89 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// An event in the event stream of a record.
+///
+/// Each command in the record is turned into one event, in the order they were applied. The
+/// sequence number is the id of the command, which is unique and increasing, and the inverse
+/// marker is set for the commands that have been undone. This makes it possible to store the
+/// record in an existing event store, and to load it back into a record, without keeping track
+/// of the undo state a second time.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, Record};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+/// let mut record = Record::default();
+/// record.apply(Add('a')).unwrap();
+/// record.apply(Add('b')).unwrap();
+/// record.undo().unwrap().unwrap();
+/// let events: Vec<_> = record.events().map(|event| event.map(|add| Add(add.0))).collect();
+/// assert!(events[1].is_inverse());
+/// let record: Record<String, Add> = Record::from_events("", events).unwrap();
+/// assert_eq!(record.as_receiver(), "a");
+/// assert_eq!(record.len(), 2);
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Event<C> {
+    sequence: usize,
+    command: C,
+    inverse: bool,
+}
+
+impl<C> Event<C> {
+    /// Returns a new event.
+    #[inline]
+    pub fn new(sequence: usize, command: C, inverse: bool) -> Event<C> {
+        Event {
+            sequence,
+            command,
+            inverse,
+        }
+    }
+
+    /// Returns the sequence number of the event.
+    #[inline]
+    pub fn sequence(&self) -> usize {
+        self.sequence
+    }
+
+    /// Returns a reference to the command.
+    #[inline]
+    pub fn command(&self) -> &C {
+        &self.command
+    }
+
+    /// Returns `true` if the command has been undone.
+    #[inline]
+    pub fn is_inverse(&self) -> bool {
+        self.inverse
+    }
+
+    /// Consumes the event, returning the command.
+    #[inline]
+    pub fn into_command(self) -> C {
+        self.command
+    }
+
+    /// Maps the command of the event with `f`.
+    #[inline]
+    pub fn map<T>(self, f: impl FnOnce(C) -> T) -> Event<T> {
+        Event {
+            sequence: self.sequence,
+            command: f(self.command),
+            inverse: self.inverse,
+        }
+    }
+}
@@ -0,0 +1,731 @@
+use crate::{
+    slot::{Slot, State},
+    stack::{self, Pushed, Stack},
+    Command, Merge, Signal,
+};
+
+/// A record of commands with a fixed capacity of `N` commands.
+///
+/// The commands are stored in a ring buffer that is allocated inline when the record is created,
+/// which means that no allocations are made when commands are applied. When the record is full,
+/// the oldest command is overwritten, and the current and saved positions are moved accordingly.
+/// Otherwise the commands are applied, merged, undone, and redone like they are by [`Record`].
+/// This makes it useful in places where allocations are not allowed, like the frame loop of an
+/// audio or game tool.
+///
+/// # Panics
+/// Panics when created if `N` is `0`.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, FixedRecord};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = &'static str;
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+/// #         self.0 = s.pop().ok_or("`s` is empty")?;
+/// #         Ok(())
+/// #     }
+/// # }
+/// # fn main() -> Result<(), &'static str> {
+/// let mut record: FixedRecord<String, Add, 2> = FixedRecord::default();
+/// record.apply(Add('a'))?;
+/// record.apply(Add('b'))?;
+/// record.apply(Add('c'))?;
+/// assert_eq!(record.len(), 2);
+/// record.undo().unwrap()?;
+/// record.undo().unwrap()?;
+/// assert_eq!(record.as_receiver(), "a");
+/// assert!(record.undo().is_none());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Record`]: struct.Record.html
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct FixedRecord<R, C, const N: usize, F = fn(Signal)> {
+    /// The commands with the ids they are reported by in the signals.
+    commands: Ring<(usize, C), N>,
+    current: usize,
+    saved: Option<usize>,
+    next_id: usize,
+    receiver: R,
+    slot: Slot<F>,
+}
+
+/// A record of commands with a fixed capacity of `N` commands, backed by an array.
+///
+/// This is the same type as [`FixedRecord`], under the name it is often looked for by.
+///
+/// [`FixedRecord`]: struct.FixedRecord.html
+pub type ArrayRecord<R, C, const N: usize, F = fn(Signal)> = FixedRecord<R, C, N, F>;
+
+impl<R, C, const N: usize> FixedRecord<R, C, N> {
+    /// Returns a new record.
+    #[inline]
+    pub fn new(receiver: impl Into<R>) -> FixedRecord<R, C, N> {
+        assert_ne!(N, 0, "capacity can not be `0`");
+        FixedRecord {
+            commands: Ring::new(),
+            current: 0,
+            saved: Some(0),
+            next_id: 0,
+            receiver: receiver.into(),
+            slot: Slot::default(),
+        }
+    }
+}
+
+impl<R, C, const N: usize, F> FixedRecord<R, C, N, F> {
+    /// Returns the capacity of the record.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of commands in the record.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.commands.len
+    }
+
+    /// Returns `true` if the record is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.commands.len == 0
+    }
+
+    /// Returns the position of the current command.
+    #[inline]
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Sets how the signal should be handled when the state changes.
+    ///
+    /// The previous slot is returned if it exists.
+    #[inline]
+    pub fn connect(&mut self, slot: F) -> Option<F> {
+        self.slot.f.replace(slot)
+    }
+
+    /// Creates a new record that uses the provided slot.
+    #[inline]
+    pub fn connect_with<G>(self, slot: G) -> FixedRecord<R, C, N, G> {
+        FixedRecord {
+            commands: self.commands,
+            current: self.current,
+            saved: self.saved,
+            next_id: self.next_id,
+            receiver: self.receiver,
+            slot: Slot::from(slot),
+        }
+    }
+
+    /// Removes and returns the slot.
+    #[inline]
+    pub fn disconnect(&mut self) -> Option<F> {
+        self.slot.f.take()
+    }
+
+    /// Returns `true` if the receiver is in a saved state, `false` otherwise.
+    #[inline]
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.current)
+    }
+
+    /// Returns `true` if the record can undo.
+    #[inline]
+    pub fn can_undo(&self) -> bool {
+        self.current > 0
+    }
+
+    /// Returns `true` if the record can redo.
+    #[inline]
+    pub fn can_redo(&self) -> bool {
+        self.current < self.commands.len
+    }
+
+    /// Returns a reference to the `receiver`.
+    #[inline]
+    pub fn as_receiver(&self) -> &R {
+        &self.receiver
+    }
+
+    /// Returns a mutable reference to the `receiver`.
+    ///
+    /// This method should **only** be used when doing changes that should not be able to be undone.
+    #[inline]
+    pub fn as_mut_receiver(&mut self) -> &mut R {
+        &mut self.receiver
+    }
+
+    /// Consumes the record, returning the `receiver`.
+    #[inline]
+    pub fn into_receiver(self) -> R {
+        self.receiver
+    }
+
+    /// Returns an iterator over the commands in the record, from the oldest to the newest.
+    #[inline]
+    pub fn commands(&self) -> impl Iterator<Item = &C> {
+        (0..self.commands.len).map(move |i| &self.commands.get(i).1)
+    }
+
+    #[inline]
+    fn state(&self) -> State {
+        State {
+            current: self.current,
+            branch: 0,
+            can_undo: self.can_undo(),
+            can_redo: self.can_redo(),
+            is_saved: self.is_saved(),
+        }
+    }
+}
+
+impl<R, C: Command<R>, const N: usize, F: FnMut(Signal)> FixedRecord<R, C, N, F> {
+    /// Marks the receiver as currently being in a saved or unsaved state.
+    #[inline]
+    pub fn set_saved(&mut self, saved: bool) {
+        self.transaction(|record| {
+            record.saved = if saved { Some(record.current) } else { None };
+        });
+    }
+
+    /// Removes all commands from the record without undoing them.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.transaction(|record| {
+            record.saved = if record.is_saved() { Some(0) } else { None };
+            record.commands = Ring::new();
+            record.current = 0;
+        });
+    }
+
+    /// Pushes the command on top of the record and executes its [`apply`] method.
+    ///
+    /// If the record is full, the oldest command is overwritten. Commands that are dead or
+    /// leave the receiver unchanged are skipped, see [`is_dead`] and [`is_noop`].
+    ///
+    /// # Errors
+    /// If an error occur when executing [`validate`] or [`apply`] the error is returned.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    /// [`is_dead`]: trait.Command.html#method.is_dead
+    /// [`is_noop`]: trait.Command.html#method.is_noop
+    /// [`validate`]: trait.Command.html#method.validate
+    #[inline]
+    pub fn apply(&mut self, command: C) -> Result<(), C::Error> {
+        self.transaction(|record| record.push(command))
+    }
+
+    /// Pushes the command like `apply`, in the transaction that emits its signals.
+    #[inline]
+    fn push(&mut self, mut command: C) -> Result<(), C::Error> {
+        if command.is_dead() {
+            return Ok(());
+        }
+        command.validate(&self.receiver)?;
+        if command.is_noop(&self.receiver) {
+            return Ok(());
+        }
+        command.apply(&mut self.receiver)?;
+        let id = self.next_id;
+        self.next_id += 1;
+        let current = self.current;
+        let was_saved = self.is_saved();
+        // Remove the commands that can no longer be redone.
+        self.commands.truncate(current);
+        self.saved = self.saved.filter(|&saved| saved <= current);
+        // Try to merge the commands unless the receiver is in a saved state.
+        let into = self.commands.back_mut().map(|&mut (id, _)| id);
+        let pushed = stack::push(
+            &mut self.commands,
+            &mut self.current,
+            &mut self.saved,
+            N,
+            (id, command),
+            |(_, last), (id, command)| {
+                if was_saved {
+                    Merge::No((id, command))
+                } else {
+                    match last.merge(command) {
+                        Merge::Yes => Merge::Yes,
+                        Merge::Annul => Merge::Annul,
+                        Merge::No(command) => Merge::No((id, command)),
+                    }
+                }
+            },
+        );
+        let signal = match pushed {
+            Pushed::Merged => into.map(|into| Signal::Merged { into }),
+            Pushed::Annulled(_) => into.map(|with| Signal::Annulled { with }),
+            Pushed::Added(_) => None,
+        };
+        self.slot.emit(Signal::Current {
+            old: current,
+            new: self.current,
+        });
+        if let Some(signal) = signal {
+            self.slot.emit(signal);
+        }
+        Ok(())
+    }
+
+    /// Calls the [`undo`] method for the active command and sets
+    /// the previous one as the new active one.
+    ///
+    /// Commands that are dead or can no longer be undone are removed without being undone,
+    /// see [`undoable`].
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`undoable`]: trait.Command.html#method.undoable
+    #[inline]
+    pub fn undo(&mut self) -> Option<Result<(), C::Error>> {
+        self.transaction(|record| {
+            loop {
+                if !record.can_undo() {
+                    return None;
+                }
+                let (_, command) = record.commands.get(record.current - 1);
+                if !command.is_dead() && command.undoable(&record.receiver) {
+                    break;
+                }
+                stack::expire(&mut record.commands, &mut record.current, &mut record.saved);
+            }
+            let current = record.current - 1;
+            let (_, command) = record.commands.get_mut(current);
+            if let Err(error) = command.undo(&mut record.receiver) {
+                return Some(Err(error));
+            }
+            record.current = current;
+            Some(Ok(()))
+        })
+    }
+
+    /// Calls the [`redo`] method for the active command and sets
+    /// the next one as the new active one.
+    ///
+    /// Dead commands are removed without being redone, see [`is_dead`].
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned.
+    ///
+    /// [`redo`]: trait.Command.html#method.redo
+    /// [`is_dead`]: trait.Command.html#method.is_dead
+    #[inline]
+    pub fn redo(&mut self) -> Option<Result<(), C::Error>> {
+        self.transaction(|record| {
+            loop {
+                if !record.can_redo() {
+                    return None;
+                }
+                if !record.commands.get(record.current).1.is_dead() {
+                    break;
+                }
+                stack::bury(&mut record.commands, record.current, &mut record.saved);
+            }
+            let current = record.current;
+            let (_, command) = record.commands.get_mut(current);
+            if let Err(error) = command.redo(&mut record.receiver) {
+                return Some(Err(error));
+            }
+            record.current = current + 1;
+            Some(Ok(()))
+        })
+    }
+
+    /// Repeatedly calls [`undo`] or [`redo`] until the command at `current` is reached.
+    /// Returns `None` if `current` is out of bounds, or if dead commands were removed while
+    /// redoing so that `current` can no longer be reached.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn go_to(&mut self, current: usize) -> Option<Result<(), C::Error>> {
+        if current > self.commands.len {
+            return None;
+        }
+        self.transaction(|record| {
+            while record.current != current {
+                let f = if current > record.current {
+                    FixedRecord::redo
+                } else {
+                    FixedRecord::undo
+                };
+                // The record is shorter than `current` if redo removed dead commands.
+                if let Err(err) = f(record)? {
+                    return Some(Err(err));
+                }
+            }
+            Some(Ok(()))
+        })
+    }
+
+    #[inline]
+    fn transaction<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.slot.begin(self.state());
+        let t = f(self);
+        self.slot.end(self.state());
+        t
+    }
+}
+
+/// A ring buffer that holds at most `N` entries.
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+struct Ring<E, const N: usize> {
+    entries: [Option<E>; N],
+    head: usize,
+    len: usize,
+}
+
+impl<E, const N: usize> Ring<E, N> {
+    #[inline]
+    fn new() -> Ring<E, N> {
+        Ring {
+            entries: core::array::from_fn(|_| None),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    #[inline]
+    fn index(&self, i: usize) -> usize {
+        (self.head + i) % N
+    }
+
+    #[inline]
+    fn get(&self, i: usize) -> &E {
+        self.entries[self.index(i)].as_ref().unwrap()
+    }
+
+    #[inline]
+    fn get_mut(&mut self, i: usize) -> &mut E {
+        let index = self.index(i);
+        self.entries[index].as_mut().unwrap()
+    }
+
+    /// Removes the entries after the first `len` entries.
+    #[inline]
+    fn truncate(&mut self, len: usize) {
+        while self.len > len {
+            self.pop_back();
+        }
+    }
+}
+
+impl<E, const N: usize> Stack for Ring<E, N> {
+    type Entry = E;
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn back_mut(&mut self) -> Option<&mut E> {
+        let i = self.len.checked_sub(1)?;
+        Some(self.get_mut(i))
+    }
+
+    #[inline]
+    fn push_back(&mut self, entry: E) {
+        debug_assert!(self.len < N);
+        let index = self.index(self.len);
+        self.entries[index] = Some(entry);
+        self.len += 1;
+    }
+
+    #[inline]
+    fn pop_back(&mut self) -> Option<E> {
+        self.len = self.len.checked_sub(1)?;
+        let index = self.index(self.len);
+        self.entries[index].take()
+    }
+
+    #[inline]
+    fn pop_front(&mut self) -> Option<E> {
+        let entry = self.entries[self.head].take()?;
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(entry)
+    }
+
+    #[inline]
+    fn remove(&mut self, i: usize) -> Option<E> {
+        if i >= self.len {
+            return None;
+        }
+        let index = self.index(i);
+        let entry = self.entries[index].take();
+        for j in i + 1..self.len {
+            let (from, to) = (self.index(j), self.index(j - 1));
+            self.entries[to] = self.entries[from].take();
+        }
+        self.len -= 1;
+        entry
+    }
+}
+
+impl<R: Default, C, const N: usize> Default for FixedRecord<R, C, N> {
+    #[inline]
+    fn default() -> FixedRecord<R, C, N> {
+        FixedRecord::new(R::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, FixedRecord, Merge, Signal};
+    use std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+    };
+
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            self.0 = s.pop().ok_or("`s` is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn overwrite() {
+        let mut record: FixedRecord<String, Add, 3> = FixedRecord::default();
+        record.apply(Add('a')).unwrap();
+        record.set_saved(true);
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        record.apply(Add('d')).unwrap();
+        assert_eq!(record.len(), 3);
+        assert_eq!(record.current(), 3);
+        assert!(record.go_to(0).unwrap().is_ok());
+        assert_eq!(record.as_receiver(), "a");
+        assert!(record.is_saved());
+        record.apply(Add('e')).unwrap();
+        record.apply(Add('f')).unwrap();
+        record.apply(Add('g')).unwrap();
+        record.apply(Add('h')).unwrap();
+        assert!(!record.is_saved());
+        assert_eq!(
+            record.commands().map(|add| add.0).collect::<String>(),
+            "fgh"
+        );
+        assert!(record.go_to(0).unwrap().is_ok());
+        assert_eq!(record.as_receiver(), "ae");
+        assert!(!record.is_saved());
+    }
+
+    /// Appends to the last `Push` and annuls a `Push` that is followed by a `Pop`.
+    enum Edit {
+        Push(String),
+        Pop(char),
+    }
+
+    impl Command<String> for Edit {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            match self {
+                Edit::Push(text) => s.push_str(text),
+                Edit::Pop(c) => *c = s.pop().ok_or("`s` is empty")?,
+            }
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            match self {
+                Edit::Push(text) => s.truncate(s.len() - text.len()),
+                Edit::Pop(c) => s.push(*c),
+            }
+            Ok(())
+        }
+
+        fn merge(&mut self, command: Self) -> Merge<Self> {
+            match (self, command) {
+                (Edit::Push(text), Edit::Push(other)) => {
+                    text.push_str(&other);
+                    Merge::Yes
+                }
+                (Edit::Push(text), Edit::Pop(_)) if text.len() == 1 => Merge::Annul,
+                (_, command) => Merge::No(command),
+            }
+        }
+
+        fn is_noop(&self, _: &String) -> bool {
+            matches!(self, Edit::Push(text) if text.is_empty())
+        }
+
+        fn validate(&self, s: &String) -> Result<(), Self::Error> {
+            match self {
+                Edit::Pop(_) if s.is_empty() => Err("`s` is empty"),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn merge() {
+        let mut record: FixedRecord<String, Edit, 3> = FixedRecord::default();
+        record.apply(Edit::Push("a".into())).unwrap();
+        record.apply(Edit::Push("b".into())).unwrap();
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.as_receiver(), "ab");
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "");
+        assert!(record.is_saved());
+    }
+
+    #[test]
+    fn saved_after_merge() {
+        let mut record: FixedRecord<String, Edit, 3> = FixedRecord::default();
+        record.apply(Edit::Push("a".into())).unwrap();
+        record.set_saved(true);
+        record.apply(Edit::Push("b".into())).unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        assert_eq!(record.len(), 2);
+        assert!(!record.is_saved());
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "a");
+        assert!(record.is_saved());
+    }
+
+    #[test]
+    fn annul() {
+        let mut record: FixedRecord<String, Edit, 3> = FixedRecord::default();
+        record.apply(Edit::Push("a".into())).unwrap();
+        record.apply(Edit::Pop(' ')).unwrap();
+        assert_eq!(record.as_receiver(), "");
+        assert!(record.is_empty());
+        assert!(record.is_saved());
+        // The saved command is not annulled.
+        record.apply(Edit::Push("b".into())).unwrap();
+        record.set_saved(true);
+        record.apply(Edit::Pop(' ')).unwrap();
+        assert_eq!(record.as_receiver(), "");
+        assert_eq!(record.len(), 2);
+        assert!(!record.is_saved());
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "b");
+        assert!(record.is_saved());
+    }
+
+    #[test]
+    fn skip() {
+        let mut record: FixedRecord<String, Edit, 3> = FixedRecord::default();
+        assert!(record.apply(Edit::Pop(' ')).is_err());
+        record.apply(Edit::Push(String::new())).unwrap();
+        assert!(record.is_empty());
+        assert!(record.is_saved());
+    }
+
+    #[test]
+    fn apply_signals() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let clone = Rc::clone(&signals);
+        let record: FixedRecord<String, Edit, 3> = FixedRecord::default();
+        let mut record = record.connect_with(move |signal| clone.borrow_mut().push(signal));
+        record.apply(Edit::Push("a".into())).unwrap();
+        assert_eq!(
+            signals.borrow_mut().split_off(0),
+            [
+                Signal::Current { old: 0, new: 1 },
+                Signal::Undo(true),
+                Signal::Saved(false),
+            ]
+        );
+        record.apply(Edit::Push("b".into())).unwrap();
+        assert_eq!(
+            signals.borrow_mut().split_off(0),
+            [
+                Signal::Current { old: 1, new: 1 },
+                Signal::Merged { into: 0 },
+            ]
+        );
+        record.set_saved(true);
+        signals.borrow_mut().clear();
+        record.apply(Edit::Push("c".into())).unwrap();
+        assert_eq!(
+            signals.borrow_mut().split_off(0),
+            [Signal::Current { old: 1, new: 2 }, Signal::Saved(false)]
+        );
+        record.apply(Edit::Pop(' ')).unwrap();
+        assert_eq!(
+            signals.borrow_mut().split_off(0),
+            [
+                Signal::Current { old: 2, new: 1 },
+                Signal::Annulled { with: 2 },
+                Signal::Saved(true),
+            ]
+        );
+    }
+
+    /// Removes its own char when undone, and becomes dead when the flag is set.
+    struct Push(char, Rc<Cell<bool>>);
+
+    impl Command<String> for Push {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            let index = s.rfind(self.0).ok_or("`s` does not contain the char")?;
+            s.remove(index);
+            Ok(())
+        }
+
+        fn is_dead(&self) -> bool {
+            self.1.get()
+        }
+    }
+
+    #[test]
+    fn dead() {
+        let dead = Rc::new(Cell::new(false));
+        let mut record: FixedRecord<String, Push, 3> = FixedRecord::default();
+        record.apply(Push('a', Rc::new(Cell::new(false)))).unwrap();
+        record.apply(Push('b', Rc::clone(&dead))).unwrap();
+        record.apply(Push('c', Rc::new(Cell::new(false)))).unwrap();
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        record.set_saved(true);
+        dead.set(true);
+        // The dead command is removed without being redone.
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ac");
+        assert_eq!(record.len(), 2);
+        assert!(!record.is_saved());
+        assert!(record.redo().is_none());
+        dead.set(false);
+        record.apply(Push('d', Rc::clone(&dead))).unwrap();
+        dead.set(true);
+        // The dead command is removed without being undone, and its changes are kept.
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ad");
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.current(), 1);
+    }
+}
@@ -0,0 +1,525 @@
+#[cfg(feature = "checkpoint")]
+use crate::Checkpoint;
+#[cfg(feature = "history")]
+use crate::History;
+#[cfg(feature = "queue")]
+use crate::Queue;
+use crate::{Command, Record, Signal};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::Keys, HashMap},
+    hash::Hash,
+};
+
+/// The signal sent when the group changes.
+///
+/// The signals of the items are sent to the slots connected to the items themselves.
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum GroupSignal<K> {
+    /// Says which item is active.
+    ///
+    /// This signal will be emitted when the active item changes.
+    Active(Option<K>),
+    /// Says if all the items are in a saved state.
+    ///
+    /// This signal will be emitted when the group enters or leaves the state where every item is saved.
+    Saved(bool),
+}
+
+/// A group of records or histories, where one of them is active.
+///
+/// This is useful for applications with several documents open at the same time, where the
+/// undo-redo commands are applied to the active document. The operations are forwarded to the
+/// active item, and the group tells its slot when the active item changes and when the group
+/// enters or leaves the state where every item is saved.
+///
+/// Changes made through the references returned by [`get_mut`] and [`active_mut`], or through
+/// a checkpoint or queue, are not reported to the slot of the group.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, Group, Record};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = &'static str;
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+/// #         self.0 = s.pop().ok_or("`s` is empty")?;
+/// #         Ok(())
+/// #     }
+/// # }
+/// # fn main() -> Result<(), &'static str> {
+/// let mut group: Group<_, Record<_, _>> = Group::new();
+/// group.insert("a", Record::default());
+/// group.insert("b", Record::default());
+/// group.set_active("a");
+/// group.apply(Add('a')).unwrap()?;
+/// group.set_active("b");
+/// group.apply(Add('b')).unwrap()?;
+/// group.undo().unwrap()?;
+/// assert_eq!(group.get("a").unwrap().as_receiver(), "a");
+/// assert_eq!(group.get("b").unwrap().as_receiver(), "");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`get_mut`]: struct.Group.html#method.get_mut
+/// [`active_mut`]: struct.Group.html#method.active_mut
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "K: Serialize, T: Serialize",
+        deserialize = "K: Deserialize<'de> + Hash + Eq, T: Deserialize<'de>"
+    ))
+)]
+#[derive(Clone, Debug)]
+pub struct Group<K, T, S = fn(GroupSignal<&K>)> {
+    items: HashMap<K, T>,
+    active: Option<K>,
+    saved: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    slot: Option<S>,
+}
+
+impl<K, T> Group<K, T> {
+    /// Returns a new group.
+    #[inline]
+    pub fn new() -> Group<K, T> {
+        Group {
+            items: HashMap::new(),
+            active: None,
+            saved: true,
+            slot: None,
+        }
+    }
+}
+
+impl<K, T, S> Group<K, T, S> {
+    /// Sets how the signal should be handled when the group changes.
+    ///
+    /// The previous slot is returned if it exists.
+    #[inline]
+    pub fn connect(&mut self, slot: S) -> Option<S> {
+        self.slot.replace(slot)
+    }
+
+    /// Creates a new group that uses the provided slot.
+    #[inline]
+    pub fn connect_with<G>(self, slot: G) -> Group<K, T, G> {
+        Group {
+            items: self.items,
+            active: self.active,
+            saved: self.saved,
+            slot: Some(slot),
+        }
+    }
+
+    /// Removes and returns the slot.
+    #[inline]
+    pub fn disconnect(&mut self) -> Option<S> {
+        self.slot.take()
+    }
+
+    /// Returns the number of items in the group.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if the group is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns an iterator over the keys of the items, in arbitrary order.
+    #[inline]
+    pub fn keys(&self) -> Keys<'_, K, T> {
+        self.items.keys()
+    }
+
+    /// Returns the key of the active item.
+    #[inline]
+    pub fn active_key(&self) -> Option<&K> {
+        self.active.as_ref()
+    }
+}
+
+impl<K: Hash + Eq, T, S> Group<K, T, S> {
+    /// Returns a reference to the item with the `key`.
+    #[inline]
+    pub fn get<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> Option<&T>
+    where
+        K: Borrow<Q>,
+    {
+        self.items.get(key)
+    }
+
+    /// Returns a mutable reference to the item with the `key`.
+    #[inline]
+    pub fn get_mut<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<&mut T>
+    where
+        K: Borrow<Q>,
+    {
+        self.items.get_mut(key)
+    }
+
+    /// Returns a reference to the active item.
+    #[inline]
+    pub fn active(&self) -> Option<&T> {
+        self.items.get(self.active.as_ref()?)
+    }
+
+    /// Returns a mutable reference to the active item.
+    #[inline]
+    pub fn active_mut(&mut self) -> Option<&mut T> {
+        self.items.get_mut(self.active.as_ref()?)
+    }
+}
+
+impl<K: Hash + Eq, T, S: FnMut(GroupSignal<&K>)> Group<K, T, S> {
+    /// Sets the item with the `key` as the active item.
+    ///
+    /// Returns `false` if there is no item with the `key`.
+    #[inline]
+    pub fn set_active(&mut self, key: K) -> bool {
+        if !self.items.contains_key(&key) {
+            return false;
+        }
+        if self.active.as_ref() != Some(&key) {
+            self.active = Some(key);
+            emit(&mut self.slot, GroupSignal::Active(self.active.as_ref()));
+        }
+        true
+    }
+
+    /// Sets that no item is active.
+    #[inline]
+    pub fn unset_active(&mut self) {
+        if self.active.take().is_some() {
+            emit(&mut self.slot, GroupSignal::Active(None));
+        }
+    }
+
+    /// Removes the item with the `key`, and removes the active item if it was the one removed.
+    #[inline]
+    fn take<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<T>
+    where
+        K: Borrow<Q>,
+    {
+        let item = self.items.remove(key)?;
+        if self
+            .active
+            .as_ref()
+            .is_some_and(|active| active.borrow() == key)
+        {
+            self.active = None;
+            emit(&mut self.slot, GroupSignal::Active(None));
+        }
+        Some(item)
+    }
+
+    /// Updates if all the items are saved, and emits the signal if it changed.
+    #[inline]
+    fn update_saved(&mut self, is_saved: impl Fn(&T) -> bool) {
+        let saved = self.items.values().all(is_saved);
+        if self.saved != saved {
+            self.saved = saved;
+            emit(&mut self.slot, GroupSignal::Saved(saved));
+        }
+    }
+}
+
+impl<K: Hash + Eq, R, C: Command<R>, F: FnMut(Signal), S: FnMut(GroupSignal<&K>)>
+    Group<K, Record<R, C, F>, S>
+{
+    /// Inserts the record with the `key`, and returns the record it replaced, if any.
+    #[inline]
+    pub fn insert(&mut self, key: K, record: Record<R, C, F>) -> Option<Record<R, C, F>> {
+        let old = self.items.insert(key, record);
+        self.update_saved(Record::is_saved);
+        old
+    }
+
+    /// Removes and returns the record with the `key`.
+    ///
+    /// If the record is the active record, no record is active afterwards.
+    #[inline]
+    pub fn remove<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<Record<R, C, F>>
+    where
+        K: Borrow<Q>,
+    {
+        let record = self.take(key)?;
+        self.update_saved(Record::is_saved);
+        Some(record)
+    }
+
+    /// Returns `true` if all the records are in a saved state.
+    #[inline]
+    pub fn is_saved(&self) -> bool {
+        self.items.values().all(Record::is_saved)
+    }
+
+    /// Calls the [`apply`] method on the active record.
+    ///
+    /// Returns `None` if there is no active record.
+    ///
+    /// [`apply`]: struct.Record.html#method.apply
+    #[inline]
+    pub fn apply(&mut self, command: C) -> Option<Result<(), C::Error>> {
+        let result = self.active_mut()?.apply(command);
+        self.update_saved(Record::is_saved);
+        Some(result)
+    }
+
+    /// Calls the [`undo`] method on the active record.
+    ///
+    /// Returns `None` if there is no active record or nothing to undo.
+    ///
+    /// [`undo`]: struct.Record.html#method.undo
+    #[inline]
+    pub fn undo(&mut self) -> Option<Result<(), C::Error>> {
+        let result = self.active_mut()?.undo()?;
+        self.update_saved(Record::is_saved);
+        Some(result)
+    }
+
+    /// Calls the [`redo`] method on the active record.
+    ///
+    /// Returns `None` if there is no active record or nothing to redo.
+    ///
+    /// [`redo`]: struct.Record.html#method.redo
+    #[inline]
+    pub fn redo(&mut self) -> Option<Result<(), C::Error>> {
+        let result = self.active_mut()?.redo()?;
+        self.update_saved(Record::is_saved);
+        Some(result)
+    }
+
+    /// Calls the [`set_saved`] method on the active record.
+    ///
+    /// Returns `false` if there is no active record.
+    ///
+    /// [`set_saved`]: struct.Record.html#method.set_saved
+    #[inline]
+    pub fn set_saved(&mut self, saved: bool) -> bool {
+        match self.active_mut() {
+            Some(record) => record.set_saved(saved),
+            None => return false,
+        }
+        self.update_saved(Record::is_saved);
+        true
+    }
+
+    /// Returns a checkpoint of the active record.
+    #[inline]
+    #[cfg(feature = "checkpoint")]
+    pub fn checkpoint(&mut self) -> Option<Checkpoint<'_, Record<R, C, F>, C>> {
+        self.active_mut().map(Record::checkpoint)
+    }
+
+    /// Returns a queue of the active record.
+    #[inline]
+    #[cfg(feature = "queue")]
+    pub fn queue(&mut self) -> Option<Queue<'_, Record<R, C, F>, C>> {
+        self.active_mut().map(Record::queue)
+    }
+}
+
+#[cfg(feature = "history")]
+impl<K: Hash + Eq, R, C: Command<R>, F: FnMut(Signal), S: FnMut(GroupSignal<&K>)>
+    Group<K, History<R, C, F>, S>
+{
+    /// Inserts the history with the `key`, and returns the history it replaced, if any.
+    #[inline]
+    pub fn insert(&mut self, key: K, history: History<R, C, F>) -> Option<History<R, C, F>> {
+        let old = self.items.insert(key, history);
+        self.update_saved(History::is_saved);
+        old
+    }
+
+    /// Removes and returns the history with the `key`.
+    ///
+    /// If the history is the active history, no history is active afterwards.
+    #[inline]
+    pub fn remove<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<History<R, C, F>>
+    where
+        K: Borrow<Q>,
+    {
+        let history = self.take(key)?;
+        self.update_saved(History::is_saved);
+        Some(history)
+    }
+
+    /// Returns `true` if all the histories are in a saved state.
+    #[inline]
+    pub fn is_saved(&self) -> bool {
+        self.items.values().all(History::is_saved)
+    }
+
+    /// Calls the [`apply`] method on the active history.
+    ///
+    /// Returns `None` if there is no active history.
+    ///
+    /// [`apply`]: struct.History.html#method.apply
+    #[inline]
+    pub fn apply(&mut self, command: C) -> Option<Result<(), C::Error>> {
+        let result = self.active_mut()?.apply(command);
+        self.update_saved(History::is_saved);
+        Some(result)
+    }
+
+    /// Calls the [`undo`] method on the active history.
+    ///
+    /// Returns `None` if there is no active history or nothing to undo.
+    ///
+    /// [`undo`]: struct.History.html#method.undo
+    #[inline]
+    pub fn undo(&mut self) -> Option<Result<(), C::Error>> {
+        let result = self.active_mut()?.undo()?;
+        self.update_saved(History::is_saved);
+        Some(result)
+    }
+
+    /// Calls the [`redo`] method on the active history.
+    ///
+    /// Returns `None` if there is no active history or nothing to redo.
+    ///
+    /// [`redo`]: struct.History.html#method.redo
+    #[inline]
+    pub fn redo(&mut self) -> Option<Result<(), C::Error>> {
+        let result = self.active_mut()?.redo()?;
+        self.update_saved(History::is_saved);
+        Some(result)
+    }
+
+    /// Calls the [`set_saved`] method on the active history.
+    ///
+    /// Returns `false` if there is no active history.
+    ///
+    /// [`set_saved`]: struct.History.html#method.set_saved
+    #[inline]
+    pub fn set_saved(&mut self, saved: bool) -> bool {
+        match self.active_mut() {
+            Some(history) => history.set_saved(saved),
+            None => return false,
+        }
+        self.update_saved(History::is_saved);
+        true
+    }
+
+    /// Returns a checkpoint of the active history.
+    #[inline]
+    #[cfg(feature = "checkpoint")]
+    pub fn checkpoint(&mut self) -> Option<Checkpoint<'_, History<R, C, F>, C>> {
+        self.active_mut().map(History::checkpoint)
+    }
+
+    /// Returns a queue of the active history.
+    #[inline]
+    #[cfg(feature = "queue")]
+    pub fn queue(&mut self) -> Option<Queue<'_, History<R, C, F>, C>> {
+        self.active_mut().map(History::queue)
+    }
+}
+
+/// Emits the signal to the slot, if any.
+#[inline]
+fn emit<K>(slot: &mut Option<impl FnMut(GroupSignal<K>)>, signal: GroupSignal<K>) {
+    if let Some(slot) = slot {
+        slot(signal);
+    }
+}
+
+impl<K, T> Default for Group<K, T> {
+    #[inline]
+    fn default() -> Self {
+        Group::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Group, GroupSignal, Record};
+    use std::{cell::RefCell, rc::Rc};
+
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            self.0 = s.pop().ok_or("`s` is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn active() {
+        let mut group: Group<_, Record<_, _>> = Group::new();
+        assert!(group.apply(Add('a')).is_none());
+        group.insert(1, Record::default());
+        group.insert(2, Record::default());
+        assert!(!group.set_active(3));
+        assert!(group.set_active(1));
+        group.apply(Add('a')).unwrap().unwrap();
+        group.apply(Add('b')).unwrap().unwrap();
+        group.set_active(2);
+        group.apply(Add('c')).unwrap().unwrap();
+        group.undo().unwrap().unwrap();
+        assert!(group.undo().is_none());
+        group.redo().unwrap().unwrap();
+        assert_eq!(group.get(&1).unwrap().as_receiver(), "ab");
+        assert_eq!(group.active().unwrap().as_receiver(), "c");
+        let record = group.remove(&2).unwrap();
+        assert_eq!(record.as_receiver(), "c");
+        assert_eq!(group.active_key(), None);
+        assert_eq!(group.len(), 1);
+    }
+
+    #[test]
+    fn signals() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let group: Group<_, Record<_, _>> = Group::new();
+        let mut group = group.connect_with(move |signal: GroupSignal<&&'static str>| {
+            slot.borrow_mut().push(match signal {
+                GroupSignal::Active(key) => GroupSignal::Active(key.copied()),
+                GroupSignal::Saved(saved) => GroupSignal::Saved(saved),
+            })
+        });
+        group.insert("a", Record::default());
+        group.insert("b", Record::default());
+        group.set_active("a");
+        group.set_active("a");
+        group.apply(Add('a')).unwrap().unwrap();
+        group.set_active("b");
+        group.apply(Add('b')).unwrap().unwrap();
+        group.undo().unwrap().unwrap();
+        group.set_active("a");
+        group.set_saved(true);
+        group.remove("a");
+        assert_eq!(
+            *signals.borrow(),
+            [
+                GroupSignal::Active(Some("a")),
+                GroupSignal::Saved(false),
+                GroupSignal::Active(Some("b")),
+                GroupSignal::Active(Some("a")),
+                GroupSignal::Saved(true),
+                GroupSignal::Active(None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use crate::{time, Entry, Time};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(not(any(feature = "chrono", feature = "time")))]
+use std::time::UNIX_EPOCH;
+use std::{collections::BTreeMap, time::Duration};
+
+/// A summary of where in the receiver the commands in a record are concentrated over time.
+///
+/// The commands are counted per time bucket and per [`region`] of the receiver, and split into
+/// the commands that are active and the commands that have been undone. This makes it possible
+/// to find out which parts of a document generate the most undone work.
+///
+/// # Examples
+/// ```
+/// # use std::time::Duration;
+/// # use redo::{Command, Record};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+/// let mut record = Record::default();
+/// record.apply(Add('a')).unwrap();
+/// record.apply(Add('b')).unwrap();
+/// record.undo().unwrap().unwrap();
+/// let heatmap = record.heatmap(Duration::from_secs(60));
+/// assert_eq!(heatmap.active(), 1);
+/// assert_eq!(heatmap.undone(), 1);
+/// ```
+///
+/// [`region`]: trait.Command.html#method.region
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Heatmap {
+    bucket: Duration,
+    cells: Vec<HeatmapCell>,
+}
+
+impl Heatmap {
+    #[inline]
+    pub(crate) fn new<'a, C: 'a>(
+        bucket: Duration,
+        entries: impl IntoIterator<Item = (&'a Entry<C>, Option<usize>, bool)>,
+    ) -> Heatmap {
+        assert_ne!(bucket, Duration::from_secs(0), "bucket can not be `0`");
+        let mut cells = BTreeMap::new();
+        for (entry, region, undone) in entries {
+            let key = (index(time(*entry.timestamp), bucket), region);
+            let cell = cells.entry(key).or_insert((0, 0));
+            if undone {
+                cell.1 += 1;
+            } else {
+                cell.0 += 1;
+            }
+        }
+        Heatmap {
+            bucket,
+            cells: cells
+                .into_iter()
+                .map(|((bucket, region), (active, undone))| HeatmapCell {
+                    bucket,
+                    region,
+                    active,
+                    undone,
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the length of the time buckets.
+    #[inline]
+    pub fn bucket(&self) -> Duration {
+        self.bucket
+    }
+
+    /// Returns the cells, ordered by bucket and then region.
+    #[inline]
+    pub fn cells(&self) -> &[HeatmapCell] {
+        &self.cells
+    }
+
+    /// Returns the total number of active commands.
+    #[inline]
+    pub fn active(&self) -> usize {
+        self.cells.iter().map(|cell| cell.active).sum()
+    }
+
+    /// Returns the total number of undone commands.
+    #[inline]
+    pub fn undone(&self) -> usize {
+        self.cells.iter().map(|cell| cell.undone).sum()
+    }
+}
+
+/// The number of commands in one region of the receiver during one time bucket.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct HeatmapCell {
+    bucket: u64,
+    region: Option<usize>,
+    active: usize,
+    undone: usize,
+}
+
+impl HeatmapCell {
+    /// Returns the index of the time bucket, counted from the Unix epoch.
+    #[inline]
+    pub fn bucket(&self) -> u64 {
+        self.bucket
+    }
+
+    /// Returns the region of the receiver, or `None` for commands without a region.
+    #[inline]
+    pub fn region(&self) -> Option<usize> {
+        self.region
+    }
+
+    /// Returns the number of commands that are active.
+    #[inline]
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// Returns the number of commands that have been undone.
+    #[inline]
+    pub fn undone(&self) -> usize {
+        self.undone
+    }
+}
+
+/// Returns the index of the bucket the time stamp falls into.
+#[inline]
+fn index(timestamp: Time, bucket: Duration) -> u64 {
+    #[cfg(feature = "chrono")]
+    let nanos = timestamp
+        .timestamp_nanos_opt()
+        .map_or(0, |nanos| nanos.max(0) as u128);
+    #[cfg(all(feature = "time", not(feature = "chrono")))]
+    let nanos = timestamp.unix_timestamp_nanos().max(0) as u128;
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    let nanos = timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    (nanos / bucket.as_nanos()) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Record};
+    use std::time::Duration;
+
+    struct Insert(usize);
+
+    impl Command<Vec<usize>> for Insert {
+        type Error = ();
+
+        fn apply(&mut self, v: &mut Vec<usize>) -> Result<(), ()> {
+            v.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, v: &mut Vec<usize>) -> Result<(), ()> {
+            v.pop();
+            Ok(())
+        }
+
+        fn region(&self) -> Option<usize> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn regions() {
+        let mut record = Record::default();
+        for line in [1, 2, 1, 1] {
+            record.apply(Insert(line)).unwrap();
+        }
+        record.undo().unwrap().unwrap();
+        let heatmap = record.heatmap(Duration::from_secs(1 << 40));
+        let summary: Vec<_> = heatmap
+            .cells()
+            .iter()
+            .map(|cell| (cell.region(), cell.active(), cell.undone()))
+            .collect();
+        assert_eq!(summary, [(Some(1), 2, 1), (Some(2), 1, 0)]);
+        assert_eq!(heatmap.active(), 3);
+        assert_eq!(heatmap.undone(), 1);
+    }
+}
@@ -1,14 +1,48 @@
+#[cfg(feature = "testing")]
+use crate::testing;
 #[cfg(feature = "display")]
 use crate::Display;
-use crate::{At, Checkpoint, Command, Entry, Queue, Record, RecordBuilder, Signal};
+#[cfg(feature = "queue")]
+use crate::Queue;
+use crate::{
+    slot::State, AsPatch, Command, ConfigError, Entry, EntryView, Error, Mutator, OpContext,
+    Position, ProgressCommand, Record, RecordBuilder, RecordIntoIter, RecordIter, Signal,
+    SignalOrder, Snapshot, SubscriptionId, UnsavedCount,
+};
+#[cfg(feature = "checkpoint")]
+use crate::{Checkpoint, RollbackError};
+#[cfg(feature = "std")]
+use crate::{Clock, Heatmap};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, VecDeque},
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
 #[cfg(feature = "chrono")]
 use chrono::{DateTime, TimeZone};
+#[cfg(feature = "std")]
+use core::time::Duration;
+use core::{
+    fmt, iter, mem,
+    ops::{Range, RangeBounds},
+};
+#[cfg(feature = "std")]
 use rustc_hash::FxHashMap;
 #[cfg(feature = "serde")]
-use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
-#[cfg(feature = "display")]
-use std::fmt;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "std")]
+use std::sync::mpsc::Receiver;
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+use time::OffsetDateTime;
+
+/// The map that is used for the branches, a hash map is not available without the standard library.
+#[cfg(feature = "std")]
+type Map<K, V> = FxHashMap<K, V>;
+#[cfg(not(feature = "std"))]
+type Map<K, V> = BTreeMap<K, V>;
 
 /// A history of commands.
 ///
@@ -16,6 +50,9 @@ use std::fmt;
 /// containing every edit made to the receiver. By switching between different branches in the
 /// tree, the user can get to any previous state of the receiver.
 ///
+/// With the `serde` feature the whole tree is serialized, including every branch, the saved
+/// state, and the timestamps of the commands. The slot is not serialized.
+///
 /// # Examples
 /// ```
 /// # use redo::{Command, History};
@@ -48,9 +85,11 @@ use std::fmt;
 /// ```
 ///
 /// [Record]: struct.Record.html
+// The derived implementations are wrapped by the implementations below, which validate the history.
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
+    serde(remote = "Self"),
     serde(bound(
         serialize = "R: Serialize, C: Serialize",
         deserialize = "R: Deserialize<'de>, C: Deserialize<'de>"
@@ -60,11 +99,31 @@ use std::fmt;
 pub struct History<R, C, F = fn(Signal)> {
     root: usize,
     next: usize,
-    pub(crate) saved: Option<At>,
+    pub(crate) saved: Option<Position>,
     pub(crate) record: Record<R, C, F>,
-    pub(crate) branches: FxHashMap<usize, Branch<C>>,
+    pub(crate) branches: Map<usize, Branch<C>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    meta: Map<usize, BTreeMap<String, String>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    names: BTreeMap<String, usize>,
+    /// The last position in the current branch where another branch diverges.
+    #[cfg_attr(feature = "serde", serde(default))]
+    fork: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    branch_limit: Option<usize>,
+    /// The ids of the branches in the order they were last visited, least recent first.
+    #[cfg_attr(feature = "serde", serde(default))]
+    visited: Vec<usize>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    on_drop: DropHook<C>,
 }
 
+/// A history with a boxed slot that can be sent between threads.
+pub type SendHistory<R, C> = History<R, C, Box<dyn FnMut(Signal) + Send>>;
+
+/// A history with a boxed slot that can not be sent between threads.
+pub type LocalHistory<R, C> = History<R, C, Box<dyn FnMut(Signal)>>;
+
 impl<R, C> History<R, C> {
     /// Returns a new history.
     #[inline]
@@ -74,7 +133,13 @@ impl<R, C> History<R, C> {
             next: 1,
             saved: None,
             record: Record::new(receiver),
-            branches: FxHashMap::default(),
+            branches: Map::default(),
+            meta: Map::default(),
+            names: BTreeMap::new(),
+            fork: None,
+            branch_limit: None,
+            visited: Vec::new(),
+            on_drop: DropHook::default(),
         }
     }
 
@@ -83,6 +148,28 @@ impl<R, C> History<R, C> {
     pub fn builder() -> HistoryBuilder<R, C> {
         HistoryBuilder::new()
     }
+
+    /// Creates a history from a history without a slot and the slot.
+    ///
+    /// This is the inverse of [`into_parts`].
+    ///
+    /// [`into_parts`]: struct.History.html#method.into_parts
+    #[inline]
+    pub fn from_parts<F>(history: History<R, C>, slot: Option<F>) -> History<R, C, F> {
+        History {
+            root: history.root,
+            next: history.next,
+            saved: history.saved,
+            record: Record::from_parts(history.record, slot),
+            branches: history.branches,
+            meta: history.meta,
+            names: history.names,
+            fork: history.fork,
+            branch_limit: history.branch_limit,
+            visited: history.visited,
+            on_drop: history.on_drop,
+        }
+    }
 }
 
 impl<R, C, F> History<R, C, F> {
@@ -125,6 +212,91 @@ impl<R, C, F> History<R, C, F> {
         self.record.limit()
     }
 
+    /// Checks that the current branch is valid, see [`Record::validate`], that the parents
+    /// of every branch lead back to the current branch, that the branches and the saved state
+    /// are within their branches, that the saved state is in only one branch, and that the id
+    /// of the next branch is not in use.
+    ///
+    /// This is done when a history is deserialized, so invalid data is rejected
+    /// instead of causing a panic later when the history is used.
+    ///
+    /// # Errors
+    /// Returns the first invalid value that is found.
+    ///
+    /// [`Record::validate`]: struct.Record.html#method.validate
+    #[inline]
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.record.validate()?;
+        // The current branch is stored in the record, not with the other branches.
+        if self.branches.contains_key(&self.root) {
+            return Err(ConfigError::BranchCycle(self.root));
+        }
+        for &id in self.branches.keys() {
+            let mut parent = self.branches[&id].parent.branch;
+            // Every step must go to another branch, so a longer path has a cycle.
+            for _ in 0..self.branches.len() {
+                if parent == self.root {
+                    break;
+                }
+                parent = match self.branches.get(&parent) {
+                    Some(branch) => branch.parent.branch,
+                    None => return Err(ConfigError::UnknownBranch(parent)),
+                };
+            }
+            if parent != self.root {
+                return Err(ConfigError::BranchCycle(id));
+            }
+        }
+        // The positions in a branch continue from where it diverges from its parent.
+        let len = |id| match self.branches.get(&id) {
+            Some(branch) => Some(branch.parent.current + branch.len()),
+            None if id == self.root => Some(self.len()),
+            None => None,
+        };
+        for (&id, branch) in &self.branches {
+            // The parents have been checked to exist above.
+            let len = len(branch.parent.branch).unwrap();
+            if branch.parent.current > len {
+                return Err(ConfigError::ParentOutOfBounds {
+                    branch: id,
+                    current: branch.parent.current,
+                    len,
+                });
+            }
+        }
+        if let Some(at) = self.saved {
+            match len(at.branch) {
+                Some(len) if at.current > len => {
+                    return Err(ConfigError::SavedOutOfBounds {
+                        saved: at.current,
+                        len,
+                    })
+                }
+                Some(_) => (),
+                None => return Err(ConfigError::UnknownBranch(at.branch)),
+            }
+            // The saved state is kept by the record when it is in the current branch.
+            if self.record.saved.is_some() {
+                return Err(ConfigError::DuplicateSaved);
+            } else if at.branch == self.root {
+                return Err(ConfigError::SavedInRoot);
+            }
+        }
+        match self.branches.keys().chain([&self.root]).max() {
+            Some(&id) if id >= self.next => Err(ConfigError::BranchIdInUse(id)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the maximum number of branches that are kept besides the current branch,
+    /// see [`HistoryBuilder::branch_limit`].
+    ///
+    /// [`HistoryBuilder::branch_limit`]: struct.HistoryBuilder.html#method.branch_limit
+    #[inline]
+    pub fn branch_limit(&self) -> Option<usize> {
+        self.branch_limit
+    }
+
     /// Sets how the signal should be handled when the state changes.
     ///
     /// The previous slot is returned if it exists.
@@ -142,6 +314,12 @@ impl<R, C, F> History<R, C, F> {
             saved: self.saved,
             record: self.record.connect_with(slot),
             branches: self.branches,
+            meta: self.meta,
+            names: self.names,
+            fork: self.fork,
+            branch_limit: self.branch_limit,
+            visited: self.visited,
+            on_drop: self.on_drop,
         }
     }
 
@@ -151,12 +329,183 @@ impl<R, C, F> History<R, C, F> {
         self.record.disconnect()
     }
 
+    /// Adds a slot that receives the signals in addition to the connected slot,
+    /// see [`Record::subscribe`].
+    ///
+    /// [`Record::subscribe`]: struct.Record.html#method.subscribe
+    #[inline]
+    pub fn subscribe(&mut self, slot: F) -> SubscriptionId {
+        self.record.subscribe(slot)
+    }
+
+    /// Removes and returns the subscribed slot with the `id`.
+    #[inline]
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> Option<F> {
+        self.record.unsubscribe(id)
+    }
+
+    /// Returns a receiver that receives the signals in addition to the slots,
+    /// see [`Record::signal_receiver`].
+    ///
+    /// [`Record::signal_receiver`]: struct.Record.html#method.signal_receiver
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn signal_receiver(&mut self) -> Receiver<Signal> {
+        self.record.signal_receiver()
+    }
+
+    /// Sets if panics in the slot should be caught (off by default).
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn catch_slot_panics(&mut self, on: bool) {
+        self.record.catch_slot_panics(on)
+    }
+
+    /// Sets if no-op commands should be stored in the history (off by default).
+    #[inline]
+    pub fn keep_noops(&mut self, on: bool) {
+        self.record.keep_noops(on)
+    }
+
+    /// Sets if annulled commands should cascade (off by default), see [`Record::cascade_annul`].
+    ///
+    /// [`Record::cascade_annul`]: struct.Record.html#method.cascade_annul
+    #[inline]
+    pub fn cascade_annul(&mut self, on: bool) {
+        self.record.cascade_annul(on)
+    }
+
+    /// Returns `true` if the command at `index` was a no-op when it was applied.
+    #[inline]
+    pub fn is_noop(&self, index: usize) -> bool {
+        self.record.is_noop(index)
+    }
+
+    /// Sets if the [`Operation`] signal should be emitted (off by default).
+    ///
+    /// [`Operation`]: enum.Signal.html#variant.Operation
+    #[inline]
+    pub fn emit_operation_ids(&mut self, on: bool) {
+        self.record.emit_operation_ids(on)
+    }
+
+    /// Sets if the signals of operations with several steps should be deferred (off by default),
+    /// see [`Record::defer_signals`].
+    ///
+    /// [`Record::defer_signals`]: struct.Record.html#method.defer_signals
+    #[inline]
+    pub fn defer_signals(&mut self, on: bool) {
+        self.record.defer_signals(on)
+    }
+
+    /// Sets the order the signals caused by one step are emitted in, see [`SignalOrder`].
+    ///
+    /// [`SignalOrder`]: enum.SignalOrder.html
+    #[inline]
+    pub fn signal_order(&mut self, order: SignalOrder) {
+        self.record.signal_order(order)
+    }
+
+    /// Removes and returns the message of the first panic caught in the slot.
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn take_slot_panic(&mut self) -> Option<String> {
+        self.record.take_slot_panic()
+    }
+
+    /// Splits the history into a history without a slot, and the slot.
+    ///
+    /// The returned history can be sent between threads if the receiver and the commands can,
+    /// regardless of the slot. The slot can be attached again with [`from_parts`].
+    ///
+    /// [`from_parts`]: struct.History.html#method.from_parts
+    #[inline]
+    pub fn into_parts(self) -> (History<R, C>, Option<F>) {
+        let (record, slot) = self.record.into_parts();
+        let history = History {
+            root: self.root,
+            next: self.next,
+            saved: self.saved,
+            record,
+            branches: self.branches,
+            meta: self.meta,
+            names: self.names,
+            fork: self.fork,
+            branch_limit: self.branch_limit,
+            visited: self.visited,
+            on_drop: self.on_drop,
+        };
+        (history, slot)
+    }
+
+    /// Returns a clone of the history without the slot, see [`Record::try_clone`].
+    ///
+    /// The hook set by [`on_branch_drop`] is not cloned either.
+    ///
+    /// [`Record::try_clone`]: struct.Record.html#method.try_clone
+    /// [`on_branch_drop`]: struct.History.html#method.on_branch_drop
+    #[inline]
+    pub fn try_clone(&self) -> History<R, C>
+    where
+        R: Clone,
+        C: Clone,
+    {
+        History {
+            root: self.root,
+            next: self.next,
+            saved: self.saved,
+            record: self.record.try_clone(),
+            branches: self.branches.clone(),
+            meta: self.meta.clone(),
+            names: self.names.clone(),
+            fork: self.fork,
+            branch_limit: self.branch_limit,
+            visited: self.visited.clone(),
+            on_drop: DropHook::default(),
+        }
+    }
+
     /// Returns `true` if the receiver is in a saved state, `false` otherwise.
     #[inline]
     pub fn is_saved(&self) -> bool {
         self.record.is_saved()
     }
 
+    /// Returns the branch and position of the saved state, or `None` if the receiver has not been
+    /// saved or the saved state is no longer in the history.
+    #[inline]
+    pub fn saved(&self) -> Option<(usize, usize)> {
+        match self.record.saved() {
+            Some(current) => Some((self.branch(), current)),
+            None => self.saved.map(|saved| (saved.branch, saved.current)),
+        }
+    }
+
+    /// Returns how many commands separate the current state from the saved state,
+    /// or `None` if the receiver has not been saved or the saved state is no longer in the history.
+    ///
+    /// If the saved state is in another branch, the commands are counted along the path
+    /// that [`go_to`] takes to the saved state.
+    ///
+    /// [`go_to`]: struct.History.html#method.go_to
+    #[inline]
+    pub fn unsaved_count(&self) -> Option<UnsavedCount> {
+        if self.record.saved.is_some() {
+            return self.record.unsaved_count();
+        }
+        let saved = self.saved?;
+        let mut fork = saved.current;
+        let mut branch = saved.branch;
+        while branch != self.root {
+            let parent = self.branches.get(&branch)?.parent;
+            fork = fork.min(parent.current);
+            branch = parent.branch;
+        }
+        let current = self.current();
+        let fork = fork.min(current);
+        Some(UnsavedCount::new(current - fork, saved.current - fork))
+    }
+
     /// Returns `true` if the history can undo.
     #[inline]
     pub fn can_undo(&self) -> bool {
@@ -169,12 +518,145 @@ impl<R, C, F> History<R, C, F> {
         self.record.can_redo()
     }
 
+    /// Returns `true` if the history can undo, including into the parent branches.
+    ///
+    /// The current branch always contains the path from the root of the tree, so this is the same
+    /// as [`can_undo`].
+    ///
+    /// [`can_undo`]: struct.History.html#method.can_undo
+    #[inline]
+    pub fn can_undo_deep(&self) -> bool {
+        self.can_undo()
+    }
+
+    /// Returns `true` if the history can redo, or if there is a branch that diverges from
+    /// the current branch at or after the current position, which can be reached with [`go_to`].
+    ///
+    /// This does not walk the tree, since the last position where a branch diverges is kept
+    /// up to date when the tree changes.
+    ///
+    /// [`go_to`]: struct.History.html#method.go_to
+    #[inline]
+    pub fn can_redo_deep(&self) -> bool {
+        self.can_redo() || self.fork.is_some_and(|fork| fork >= self.current())
+    }
+
     /// Returns the current branch.
     #[inline]
     pub fn branch(&self) -> usize {
         self.root
     }
 
+    /// Returns the current branch and the position of the current command in it.
+    #[inline]
+    pub fn position(&self) -> Position {
+        Position::new(self.root, self.current())
+    }
+
+    /// Returns the position of the view cursor in the current branch.
+    #[inline]
+    pub fn view(&self) -> usize {
+        self.record.view()
+    }
+
+    /// Moves the view cursor one command back without changing the receiver.
+    #[inline]
+    pub fn soft_undo(&mut self) -> bool {
+        self.record.soft_undo()
+    }
+
+    /// Moves the view cursor one command forward without changing the receiver.
+    #[inline]
+    pub fn soft_redo(&mut self) -> bool {
+        self.record.soft_redo()
+    }
+
+    /// Returns an iterator over the branches in the history, in no particular order.
+    ///
+    /// The current branch is not included since its commands are in the record,
+    /// see [`commands`].
+    ///
+    /// [`commands`]: struct.History.html#method.commands
+    #[inline]
+    pub fn branches(&self) -> impl Iterator<Item = (usize, &Branch<C>)> {
+        self.branches.iter().map(|(&id, branch)| (id, branch))
+    }
+
+    /// Returns the branch with the `id`.
+    ///
+    /// Returns `None` if the branch does not exist or if it is the current branch.
+    #[inline]
+    pub fn get_branch(&self, id: usize) -> Option<&Branch<C>> {
+        self.branches.get(&id)
+    }
+
+    /// Removes the branch with the `id` and all the branches that diverge from it,
+    /// and returns their commands.
+    ///
+    /// Returns `None` if the branch does not exist or if it is the current branch.
+    /// If the saved state is in a removed branch it is removed.
+    #[inline]
+    pub fn remove_branch(&mut self, id: usize) -> Option<Vec<C>> {
+        if !self.branches.contains_key(&id) {
+            return None;
+        }
+        Some(self.rm_tree(id))
+    }
+
+    /// Removes all branches except the current branch, and returns their commands.
+    ///
+    /// Only the commands that can be reached by undoing and redoing are kept.
+    /// If the saved state is in a removed branch it is removed.
+    #[inline]
+    pub fn prune(&mut self) -> Vec<C> {
+        let root = self.branch();
+        let mut commands = Vec::new();
+        for id in self.children(|at| at.branch == root) {
+            commands.extend(self.rm_tree(id));
+        }
+        debug_assert!(self.branches.is_empty());
+        commands
+    }
+
+    /// Sets a callback that is called with the id and the contents of every branch right before
+    /// it is dropped, e.g. by [`prune`], [`remove_branch`], [`clear`], or when the limit is reached.
+    ///
+    /// This can be used to archive abandoned branches instead of losing them.
+    /// The callback is not cloned or serialized with the history.
+    ///
+    /// [`prune`]: struct.History.html#method.prune
+    /// [`remove_branch`]: struct.History.html#method.remove_branch
+    /// [`clear`]: struct.History.html#method.clear
+    #[inline]
+    pub fn on_branch_drop(&mut self, f: impl FnMut(usize, &Branch<C>) + Send + 'static) {
+        self.on_drop = DropHook(Some(Box::new(f)));
+    }
+
+    /// Sets a callback that is called with every command that is removed from the start of the
+    /// history because the limit is reached, see [`Record::on_evict`].
+    ///
+    /// [`Record::on_evict`]: struct.Record.html#method.on_evict
+    #[inline]
+    pub fn on_evict(&mut self, f: impl Fn(C) + Send + Sync + 'static) {
+        self.record.on_evict(f);
+    }
+
+    /// Removes the callback set by [`on_evict`].
+    ///
+    /// [`on_evict`]: struct.History.html#method.on_evict
+    #[inline]
+    pub fn clear_evict(&mut self) {
+        self.record.clear_evict();
+    }
+
+    /// Removes the callback set by [`on_branch_drop`].
+    ///
+    /// [`on_branch_drop`]: struct.History.html#method.on_branch_drop
+    #[inline]
+    pub fn clear_branch_drop(&mut self) {
+        self.on_drop = DropHook(None);
+    }
+
     /// Returns the position of the current command.
     #[inline]
     pub fn current(&self) -> usize {
@@ -183,13 +665,15 @@ impl<R, C, F> History<R, C, F> {
 
     /// Returns a checkpoint.
     #[inline]
-    pub fn checkpoint(&mut self) -> Checkpoint<History<R, C, F>, C> {
+    #[cfg(feature = "checkpoint")]
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, History<R, C, F>, C> {
         Checkpoint::from(self)
     }
 
     /// Returns a queue.
     #[inline]
-    pub fn queue(&mut self) -> Queue<History<R, C, F>, C> {
+    #[cfg(feature = "queue")]
+    pub fn queue(&mut self) -> Queue<'_, History<R, C, F>, C> {
         Queue::from(self)
     }
 
@@ -207,6 +691,38 @@ impl<R, C, F> History<R, C, F> {
         self.record.as_mut_receiver()
     }
 
+    /// Returns the generation of the receiver, see [`Record::generation`].
+    ///
+    /// Switching branches changes the receiver and therefore increments the generation.
+    ///
+    /// [`Record::generation`]: struct.Record.html#method.generation
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.record.generation()
+    }
+
+    /// Returns the context of the last command that failed to be applied, undone, or redone.
+    #[inline]
+    pub fn error_context(&self) -> Option<&OpContext> {
+        self.record.error_context()
+    }
+
+    /// Returns `true` if the receiver has been mutated through a [`Mutator`].
+    ///
+    /// [`Mutator`]: struct.Mutator.html
+    #[inline]
+    pub fn is_mutated(&self) -> bool {
+        self.record.is_mutated()
+    }
+
+    /// Clears the flag that says if the receiver has been mutated through a [`Mutator`].
+    ///
+    /// [`Mutator`]: struct.Mutator.html
+    #[inline]
+    pub fn clear_mutated(&mut self) {
+        self.record.clear_mutated()
+    }
+
     /// Consumes the history, returning the `receiver`.
     #[inline]
     pub fn into_receiver(self) -> R {
@@ -218,601 +734,3335 @@ impl<R, C, F> History<R, C, F> {
     pub fn commands(&self) -> impl Iterator<Item = &C> {
         self.record.commands()
     }
-}
 
-impl<R, C: Command<R>, F: FnMut(Signal)> History<R, C, F> {
-    /// Sets the limit of the history and returns the new limit.
+    /// Returns the command at `index` in the branch, or `None` if the branch does not exist
+    /// or `index` is out of bounds.
     ///
-    /// If this limit is reached it will start popping of commands at the beginning
-    /// of the history when new commands are applied. No limit is set by
-    /// default which means it may grow indefinitely.
+    /// The index is the one the command would have if the branch was the current branch,
+    /// so the commands before the start of the branch are looked up in its parents.
+    #[inline]
+    pub fn get(&self, mut branch: usize, index: usize) -> Option<&C> {
+        while branch != self.branch() {
+            let b = self.branches.get(&branch)?;
+            if index >= b.start() {
+                return b
+                    .commands
+                    .get(index - b.start())
+                    .map(|entry| &entry.command);
+            }
+            branch = b.parent();
+        }
+        self.record.get(index)
+    }
+
+    /// Returns an iterator over the commands in the current branch together with their metadata.
+    #[inline]
+    pub fn entries(&self) -> impl Iterator<Item = EntryView<'_, C>> {
+        self.record.entries()
+    }
+
+    /// Returns an iterator over the path from the root of the tree to the current position.
     ///
-    /// If `limit < len` the first commands will be removed until `len == limit`.
-    /// However, if the current active command is going to be removed, the limit is instead
-    /// adjusted to `len - active` so the active command is not removed.
+    /// The path is every command that has been applied to get the receiver to its current state,
+    /// in the order they were applied. A [`Fork`] step is yielded before the command at a position
+    /// where other branches diverge from the path.
     ///
-    /// # Panics
-    /// Panics if `limit` is `0`.
+    /// [`Fork`]: enum.PathStep.html#variant.Fork
     #[inline]
-    pub fn set_limit(&mut self, limit: usize) -> usize {
-        let len = self.len();
-        let limit = self.record.set_limit(limit);
-        let diff = len - self.len();
+    pub fn path(&self) -> impl Iterator<Item = PathStep<'_, C>> {
         let root = self.branch();
-        for current in 0..diff {
-            self.rm_child(root, current);
+        let mut forks = BTreeMap::new();
+        for (&id, branch) in &self.branches {
+            if branch.parent.branch == root && branch.parent.current < self.current() {
+                forks
+                    .entry(branch.parent.current)
+                    .or_insert_with(Vec::new)
+                    .push(id);
+            }
         }
-        for branch in self
-            .branches
-            .values_mut()
-            .filter(|branch| branch.parent.branch == root)
-        {
-            branch.parent.current -= diff;
+        self.record
+            .entries()
+            .take(self.current())
+            .enumerate()
+            .flat_map(move |(i, entry)| {
+                let fork = forks.remove(&i).map(|mut branches| {
+                    branches.sort_unstable();
+                    PathStep::Fork {
+                        position: i,
+                        branches,
+                    }
+                });
+                fork.into_iter().chain(iter::once(PathStep::Entry(entry)))
+            })
+    }
+
+    /// Returns the steps that go from the position `from` to the position `to`,
+    /// without changing the receiver.
+    ///
+    /// The commands are undone back to the last position the two positions have in common,
+    /// and then redone up to `to`, which is also what [`go_to_position`] does. This can be used
+    /// to preview a jump between two states, or to send the steps to a remote replica.
+    /// Returns `None` if one of the positions is not in the history.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Direction, History, Position};
+    /// # #[derive(Debug, PartialEq)]
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = ();
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.pop();
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let mut history = History::default();
+    /// history.apply(Add('a')).unwrap();
+    /// history.apply(Add('b')).unwrap();
+    /// let from = history.position();
+    /// history.undo().unwrap().unwrap();
+    /// history.apply(Add('c')).unwrap();
+    /// let steps = history.path_between(from, history.position()).unwrap();
+    /// assert_eq!(steps, [Direction::Undo(&Add('b')), Direction::Redo(&Add('c'))]);
+    /// assert_eq!(history.as_receiver(), "ac");
+    /// ```
+    ///
+    /// [`go_to_position`]: struct.History.html#method.go_to_position
+    #[inline]
+    pub fn path_between(&self, from: Position, to: Position) -> Option<Vec<Direction<&C>>> {
+        let len = |branch| match self.branches.get(&branch) {
+            Some(b) => Some(b.start() + b.len()),
+            None if branch == self.branch() => Some(self.len()),
+            None => None,
+        };
+        if from.current() > len(from.branch())? || to.current() > len(to.branch())? {
+            return None;
         }
-        limit
+        // The branches from each position up to the current branch, with the positions they start at.
+        let lineage = |mut branch| {
+            let mut lineage = Vec::new();
+            while let Some(b) = self.branches.get(&branch) {
+                lineage.push((branch, b.start()));
+                branch = b.parent();
+            }
+            lineage.push((branch, usize::MAX));
+            lineage
+        };
+        let from_lineage = lineage(from.branch());
+        let to_lineage = lineage(to.branch());
+        let common = from_lineage
+            .iter()
+            .find(|&&(branch, _)| to_lineage.iter().any(|&(b, _)| b == branch))
+            .map(|&(branch, _)| branch)?;
+        // The commands before the start of the branches below the common branch are shared.
+        let shared = |lineage: &[(usize, usize)]| {
+            lineage
+                .iter()
+                .take_while(|&&(branch, _)| branch != common)
+                .map(|&(_, start)| start)
+                .min()
+                .unwrap_or(usize::MAX)
+        };
+        let shared = shared(&from_lineage)
+            .min(shared(&to_lineage))
+            .min(from.current())
+            .min(to.current());
+        let undo = (shared..from.current())
+            .rev()
+            .map(|index| self.get(from.branch(), index).map(Direction::Undo));
+        let redo =
+            (shared..to.current()).map(|index| self.get(to.branch(), index).map(Direction::Redo));
+        undo.chain(redo).collect()
     }
 
-    /// Marks the receiver as currently being in a saved or unsaved state.
+    /// Returns a snapshot of the current branch where each command is mapped with `f`.
     #[inline]
-    pub fn set_saved(&mut self, saved: bool) {
-        self.record.set_saved(saved);
-        self.saved = None;
+    pub fn snapshot_with<T>(&self, f: impl FnMut(&C) -> T) -> Snapshot<T> {
+        Snapshot::new(
+            self.record.commands().map(f).collect(),
+            self.current(),
+            self.record.saved,
+            self.branch(),
+        )
     }
 
-    /// Revert the changes done to the receiver since the saved state.
+    /// Returns the id of the command at `index` in the current branch.
     #[inline]
-    pub fn revert(&mut self) -> Option<Result<(), C::Error>> {
-        if self.record.saved.is_some() {
-            self.record.revert()
-        } else {
-            self.saved
-                .and_then(|saved| self.go_to(saved.branch, saved.current))
-        }
+    pub fn id(&self, index: usize) -> Option<usize> {
+        self.record.id(index)
     }
 
-    /// Removes all commands from the history without undoing them.
+    /// Returns the index of the command with the `id` in the current branch.
     #[inline]
-    pub fn clear(&mut self) {
-        let old = self.branch();
-        self.root = 0;
-        self.next = 1;
-        self.saved = None;
-        self.record.clear();
-        self.branches.clear();
-        if let Some(ref mut slot) = self.record.slot {
-            slot(Signal::Branch { old, new: 0 });
-        }
+    pub fn index_of(&self, id: usize) -> Option<usize> {
+        self.record.index_of(id)
     }
 
-    /// Pushes the command to the top of the history and executes its [`apply`] method.
+    /// Resolves the `id` to the id of the command that currently contains it.
     ///
-    /// # Errors
-    /// If an error occur when executing [`apply`] the error is returned.
+    /// Unlike [`Record::resolve`] all branches are searched.
     ///
-    /// [`apply`]: trait.Command.html#tymethod.apply
+    /// [`Record::resolve`]: struct.Record.html#method.resolve
     #[inline]
-    pub fn apply(&mut self, command: C) -> Result<(), C::Error> {
-        let current = self.current();
-        let saved = self.record.saved.filter(|&saved| saved > current);
-        let (merged, commands) = self.record.__apply(Entry::from(command))?;
-        // Check if the limit has been reached.
-        if !merged && current == self.current() {
-            let root = self.branch();
-            self.rm_child(root, 0);
-            for branch in self
-                .branches
-                .values_mut()
-                .filter(|branch| branch.parent.branch == root)
-            {
-                branch.parent.current -= 1;
-            }
+    pub fn resolve(&self, id: usize) -> Option<usize> {
+        self.record.resolve(id).or_else(|| {
+            self.branches
+                .values()
+                .flat_map(|branch| &branch.commands)
+                .find(|entry| entry.contains(id))
+                .map(|entry| entry.id)
+        })
+    }
+
+    /// Marks the current position as a coarse boundary.
+    #[inline]
+    pub fn set_boundary(&mut self) {
+        self.record.set_boundary()
+    }
+
+    /// Returns `true` if the position `current` in the current branch is a coarse boundary.
+    #[inline]
+    pub fn is_boundary(&self, current: usize) -> bool {
+        self.record.is_boundary(current)
+    }
+
+    /// Sets the metadata `key` of the `branch` to `value`, returning the old value.
+    ///
+    /// The metadata is stored with the history, which makes it possible to attach
+    /// application specific data, like a color or a description, to a branch.
+    /// Nothing is stored if the branch does not exist.
+    #[inline]
+    pub fn set_branch_meta(
+        &mut self,
+        branch: usize,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Option<String> {
+        if branch != self.root && !self.branches.contains_key(&branch) {
+            return None;
         }
-        // Handle new branch.
-        if !commands.is_empty() {
-            let old = self.branch();
-            let new = self.next;
-            self.next += 1;
-            self.branches.insert(
-                old,
-                Branch {
-                    parent: At {
-                        branch: new,
-                        current,
-                    },
-                    commands,
-                },
-            );
-            self.set_root(new, current);
-            match (self.record.saved, saved, self.saved) {
-                (Some(_), None, None) | (None, None, Some(_)) => self.swap_saved(new, old, current),
-                (None, Some(_), None) => {
-                    self.record.saved = saved;
-                    self.swap_saved(old, new, current);
-                }
-                (None, None, None) => (),
-                _ => unreachable!(),
-            }
-            if let Some(ref mut slot) = self.record.slot {
-                slot(Signal::Branch { old, new });
-            }
-        }
-        Ok(())
+        self.meta
+            .entry(branch)
+            .or_default()
+            .insert(key.into(), value.into())
     }
 
-    /// Calls the [`undo`] method for the active command
-    /// and sets the previous one as the new active one.
-    ///
-    /// # Errors
-    /// If an error occur when executing [`undo`] the error is returned.
-    ///
-    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// Returns the metadata `key` of the `branch`.
     #[inline]
-    pub fn undo(&mut self) -> Option<Result<(), C::Error>> {
-        self.record.undo()
+    pub fn branch_meta(&self, branch: usize, key: &str) -> Option<&str> {
+        self.meta.get(&branch)?.get(key).map(String::as_str)
     }
 
-    /// Calls the [`redo`] method for the active command
-    /// and sets the next one as the new active one.
-    ///
-    /// # Errors
-    /// If an error occur when executing [`redo`] the error is returned.
-    ///
-    /// [`redo`]: trait.Command.html#method.redo
+    /// Removes the metadata `key` of the `branch`, returning its value.
     #[inline]
-    pub fn redo(&mut self) -> Option<Result<(), C::Error>> {
-        self.record.redo()
+    pub fn remove_branch_meta(&mut self, branch: usize, key: &str) -> Option<String> {
+        let meta = self.meta.get_mut(&branch)?;
+        let value = meta.remove(key);
+        if meta.is_empty() {
+            self.meta.remove(&branch);
+        }
+        value
     }
 
-    /// Repeatedly calls [`undo`] or [`redo`] until the command in `branch` at `current` is reached.
+    /// Names the `branch`, returning `false` if the branch does not exist.
     ///
-    /// # Errors
-    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    /// Unlike the ids, which can be changed by [`renumber`], the names stay the same until
+    /// they are removed, which makes it possible to refer to a branch as an alternative version.
+    /// A branch has at most one name, and the name is moved if another branch already has it.
+    /// The name is removed when the branch is removed.
     ///
-    /// [`undo`]: trait.Command.html#tymethod.undo
-    /// [`redo`]: trait.Command.html#method.redo
+    /// [`renumber`]: struct.History.html#method.renumber
     #[inline]
-    pub fn go_to(&mut self, branch: usize, current: usize) -> Option<Result<(), C::Error>> {
-        let root = self.root;
-        if root == branch {
-            return self.record.go_to(current);
-        }
-        // Walk the path from `root` to `branch`.
-        for (new, branch) in self.mk_path(branch)? {
-            let old = self.branch();
-            // Walk to `branch.current` either by undoing or redoing.
-            if let Err(err) = self.record.go_to(branch.parent.current).unwrap() {
-                return Some(Err(err));
-            }
-            // Apply the commands in the branch and move older commands into their own branch.
-            for entry in branch.commands {
-                let current = self.current();
-                let saved = self.record.saved.filter(|&saved| saved > current);
-                let commands = match self.record.__apply(entry) {
-                    Ok((_, commands)) => commands,
-                    Err(err) => return Some(Err(err)),
-                };
-                // Handle new branch.
-                if !commands.is_empty() {
-                    self.branches.insert(
-                        self.root,
-                        Branch {
-                            parent: At {
-                                branch: new,
-                                current,
-                            },
-                            commands,
-                        },
-                    );
-                    self.set_root(new, current);
-                    match (self.record.saved, saved, self.saved) {
-                        (Some(_), None, None) | (None, None, Some(_)) => {
-                            self.swap_saved(new, old, current);
-                        }
-                        (None, Some(_), None) => {
-                            self.record.saved = saved;
-                            self.swap_saved(old, new, current);
-                        }
-                        (None, None, None) => (),
-                        _ => unreachable!(),
-                    }
-                }
-            }
-        }
-        if let Err(err) = self.record.go_to(current)? {
-            return Some(Err(err));
-        } else if let Some(ref mut slot) = self.record.slot {
-            slot(Signal::Branch {
-                old: root,
-                new: self.root,
-            });
+    pub fn name_branch(&mut self, branch: usize, name: impl Into<String>) -> bool {
+        if branch != self.root && !self.branches.contains_key(&branch) {
+            return false;
         }
-        Some(Ok(()))
+        self.names.retain(|_, &mut id| id != branch);
+        self.names.insert(name.into(), branch);
+        true
     }
 
-    /// Go back or forward in the history to the command that was made closest to the datetime provided.
-    ///
-    /// This method does not jump across branches.
+    /// Removes the name of the `branch`, returning the name.
     #[inline]
-    #[cfg(feature = "chrono")]
-    pub fn time_travel(&mut self, to: &DateTime<impl TimeZone>) -> Option<Result<(), C::Error>> {
-        self.record.time_travel(to)
+    pub fn unname_branch(&mut self, branch: usize) -> Option<String> {
+        let name = self.branch_name(branch)?.to_string();
+        self.names.remove(&name);
+        Some(name)
     }
 
-    /// Applies each command in the iterator.
+    /// Returns the name of the `branch`.
+    #[inline]
+    pub fn branch_name(&self, branch: usize) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|&(_, &id)| id == branch)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Returns the id of the branch with the `name`.
+    #[inline]
+    pub fn branch_by_name(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// Starts a group of commands.
     ///
-    /// # Errors
-    /// If an error occur when executing [`apply`] the error is returned.
+    /// See [`Record::begin_group`] for more information.
     ///
-    /// [`apply`]: trait.Command.html#tymethod.apply
+    /// [`Record::begin_group`]: struct.Record.html#method.begin_group
     #[inline]
-    pub fn extend(&mut self, commands: impl IntoIterator<Item = C>) -> Result<(), C::Error> {
-        for command in commands {
-            self.apply(command)?;
-        }
-        Ok(())
+    pub fn begin_group(&mut self) {
+        self.record.begin_group()
     }
 
-    /// Sets the `root`.
+    /// Ends a group of commands.
     #[inline]
-    fn set_root(&mut self, root: usize, current: usize) {
-        let old = self.branch();
-        self.root = root;
-        debug_assert_ne!(old, root);
-        // Handle the child branches.
-        for branch in self
-            .branches
-            .values_mut()
-            .filter(|branch| branch.parent.branch == old && branch.parent.current <= current)
-        {
-            branch.parent.branch = root;
-        }
+    pub fn end_group(&mut self) {
+        self.record.end_group()
     }
 
-    /// Swap the saved state if needed.
+    /// Returns `true` if a group of commands has been started.
     #[inline]
-    fn swap_saved(&mut self, old: usize, new: usize, current: usize) {
-        debug_assert_ne!(old, new);
-        if let Some(At { current: saved, .. }) = self
-            .saved
-            .filter(|at| at.branch == new && at.current <= current)
-        {
-            self.saved = None;
-            self.record.saved = Some(saved);
-            if let Some(ref mut slot) = self.record.slot {
-                slot(Signal::Saved(true));
-            }
-        } else if let Some(saved) = self.record.saved {
-            self.saved = Some(At {
-                branch: old,
-                current: saved,
-            });
-            self.record.saved = None;
-            if let Some(ref mut slot) = self.record.slot {
-                slot(Signal::Saved(false));
-            }
-        }
+    pub fn in_group(&self) -> bool {
+        self.record.in_group()
     }
 
-    /// Remove all children of the command at the given position.
+    /// Returns the ids of the branches whose parent matches `f`, in the order they were created.
     #[inline]
-    fn rm_child(&mut self, branch: usize, current: usize) {
-        // We need to check if any of the branches had the removed node as root.
-        let mut dead: Vec<_> = self
+    fn children(&self, f: impl Fn(Position) -> bool) -> Vec<usize> {
+        let mut children: Vec<_> = self
             .branches
             .iter()
-            .filter(|&(_, child)| child.parent == At { branch, current })
+            .filter(|&(_, child)| f(child.parent))
             .map(|(&id, _)| id)
             .collect();
+        children.sort_unstable();
+        children
+    }
+
+    /// Removes the branch and all the branches that diverge from it, and returns their commands.
+    #[inline]
+    fn rm_tree(&mut self, id: usize) -> Vec<C> {
+        self.rm_tree_with(id, |_| ())
+    }
+
+    /// Removes the branch and all the branches that diverge from it, calling `f` with the id
+    /// of every removed branch, and returns their commands.
+    #[inline]
+    fn rm_tree_with(&mut self, id: usize, mut f: impl FnMut(usize)) -> Vec<C> {
+        let mut dead = vec![id];
+        let mut commands = Vec::new();
         while let Some(parent) = dead.pop() {
+            f(parent);
             // Remove the dead branch.
-            self.branches.remove(&parent).unwrap();
+            let branch = self.branches.remove(&parent).unwrap();
+            self.on_drop.call(parent, &branch);
+            self.meta.remove(&parent);
+            self.names.retain(|_, &mut id| id != parent);
+            self.visited.retain(|&id| id != parent);
             self.saved = self.saved.filter(|saved| saved.branch != parent);
+            commands.extend(branch.commands.into_iter().map(|entry| entry.command));
             // Add the children of the dead branch so they are removed too.
-            dead.extend(
-                self.branches
+            dead.extend(self.children(|at| at.branch == parent).into_iter().rev());
+        }
+        self.update_fork();
+        commands
+    }
+
+    /// Returns the position in the current branch where the path to the branch with the id forks,
+    /// or `None` if a branch on the path is not in the history.
+    #[inline]
+    fn fork_of(&self, id: usize) -> Option<Position> {
+        let root = self.branch();
+        let mut at = self.branches.get(&id)?.parent;
+        while at.branch != root {
+            at = self.branches.get(&at.branch)?.parent;
+        }
+        Some(at)
+    }
+
+    /// Moves the branches back by the number of commands before them that the record removed
+    /// because they had expired or died, see [`Record::expired`], like [`retain`] does.
+    ///
+    /// Returns `true` if any commands were removed.
+    ///
+    /// [`Record::expired`]: struct.Record.html#method.expired
+    /// [`retain`]: struct.History.html#method.retain
+    #[inline]
+    fn shift_forks(&mut self) -> bool {
+        let removed = match self.record.removed.replace(Vec::new()) {
+            Some(removed) if !removed.is_empty() => removed,
+            _ => return false,
+        };
+        // The branches on a path that is being walked are not moved, since they fork after
+        // the commands that are removed by undoing to them.
+        let deltas: Vec<_> = self
+            .branches
+            .keys()
+            .filter_map(|&id| {
+                let fork = self.fork_of(id)?.current;
+                let moved = removed
                     .iter()
-                    .filter(|&(_, child)| child.parent.branch == parent)
-                    .map(|(&id, _)| id),
-            )
+                    .fold(fork, |fork, &index| fork - usize::from(index < fork));
+                Some((id, fork - moved))
+            })
+            .filter(|&(_, delta)| delta > 0)
+            .collect();
+        for (id, delta) in deltas {
+            self.branches.get_mut(&id).unwrap().parent.current -= delta;
+            if self.saved.is_some_and(|saved| saved.branch == id) {
+                self.saved = None;
+            }
         }
+        true
     }
 
-    /// Create a path between the current branch and the `to` branch.
+    /// Updates the last position in the current branch where another branch diverges.
     #[inline]
-    fn mk_path(&mut self, mut to: usize) -> Option<impl Iterator<Item = (usize, Branch<C>)>> {
-        debug_assert_ne!(self.branch(), to);
-        let mut dest = self.branches.remove(&to)?;
-        let mut i = dest.parent.branch;
-        let mut path = vec![(to, dest)];
-        while i != self.branch() {
-            dest = self.branches.remove(&i).unwrap();
-            to = i;
-            i = dest.parent.branch;
-            path.push((to, dest));
+    fn update_fork(&mut self) {
+        let root = self.branch();
+        self.fork = self
+            .branches
+            .values()
+            .filter(|branch| branch.parent.branch == root)
+            .map(|branch| branch.parent.current)
+            .max();
+    }
+
+    /// Returns the state that is reported through signals.
+    #[inline]
+    pub(crate) fn state(&self) -> State {
+        State {
+            branch: self.branch(),
+            ..self.record.state()
         }
-        Some(path.into_iter().rev())
     }
-}
 
-impl<R, C: ToString, F> History<R, C, F> {
-    /// Returns the string of the command which will be undone in the next call to [`undo`].
+    /// Returns the current position encoded as a string.
     ///
-    /// [`undo`]: struct.History.html#method.undo
+    /// The string is `@` followed by the [id] of the current command, or only `@` before the first
+    /// command. Unlike the branch ids the command ids never change, so the string can be turned back
+    /// into a position with [`decode_position`] after the branches have been renumbered, and after
+    /// the history has been serialized and deserialized.
+    ///
+    /// [id]: struct.History.html#method.id
+    /// [`decode_position`]: struct.History.html#method.decode_position
     #[inline]
-    pub fn to_undo_string(&self) -> Option<String> {
-        self.record.to_undo_string()
+    pub fn encode_position(&self) -> String {
+        match self.current().checked_sub(1) {
+            Some(index) => format!("@{}", self.record.commands[index].id),
+            None => String::from("@"),
+        }
     }
 
-    /// Returns the string of the command which will be redone in the next call to [`redo`].
+    /// Decodes a position encoded by [`encode_position`].
     ///
-    /// [`redo`]: struct.History.html#method.redo
+    /// Returns `None` if the string is malformed or the command is no longer in the history.
+    ///
+    /// [`encode_position`]: struct.History.html#method.encode_position
     #[inline]
-    pub fn to_redo_string(&self) -> Option<String> {
-        self.record.to_redo_string()
+    pub fn decode_position(&self, position: &str) -> Option<Position> {
+        let id = position.trim().strip_prefix('@')?;
+        if id.is_empty() {
+            return Some(Position::new(self.branch(), 0));
+        }
+        let id = id.parse().ok()?;
+        if let Some(index) = self.record.commands.iter().position(|e| e.contains(id)) {
+            return Some(Position::new(self.branch(), index + 1));
+        }
+        self.branches.iter().find_map(|(&branch, b)| {
+            let index = b.commands.iter().position(|e| e.contains(id))?;
+            Some(Position::new(branch, b.parent.current + index + 1))
+        })
     }
 
     /// Returns a structure for configurable formatting of the record.
     #[inline]
     #[cfg(feature = "display")]
-    pub fn display(&self) -> Display<Self> {
+    pub fn display(&self) -> Display<'_, Self> {
         Display::from(self)
     }
 }
 
-impl<R: Default, C> Default for History<R, C> {
+impl<R, C: Command<R>, F: FnMut(Signal)> History<R, C, F> {
+    /// Sets the limit of the history and returns the new limit.
+    ///
+    /// If this limit is reached it will start popping of commands at the beginning
+    /// of the history when new commands are applied. No limit is set by
+    /// default which means it may grow indefinitely.
+    ///
+    /// If `limit < len` the first commands will be removed until `len == limit`.
+    /// However, if the current active command is going to be removed, the limit is instead
+    /// adjusted to `len - active` so the active command is not removed.
+    ///
+    /// # Panics
+    /// Panics if `limit` is `0`, see [`try_set_limit`].
+    ///
+    /// [`try_set_limit`]: struct.History.html#method.try_set_limit
     #[inline]
-    fn default() -> History<R, C> {
-        History::new(R::default())
+    pub fn set_limit(&mut self, limit: usize) -> usize {
+        match self.try_set_limit(limit) {
+            Ok(limit) => limit,
+            Err(error) => panic!("{}", error),
+        }
     }
-}
 
-impl<R, C, F> AsRef<R> for History<R, C, F> {
+    /// Sets the limit of the history and returns the new limit, see [`set_limit`].
+    ///
+    /// # Errors
+    /// Returns [`ConfigError::ZeroLimit`] if `limit` is `0`, and leaves the history unchanged.
+    ///
+    /// [`set_limit`]: struct.History.html#method.set_limit
+    /// [`ConfigError::ZeroLimit`]: enum.ConfigError.html#variant.ZeroLimit
     #[inline]
-    fn as_ref(&self) -> &R {
-        self.as_receiver()
+    pub fn try_set_limit(&mut self, limit: usize) -> Result<usize, ConfigError> {
+        let len = self.len();
+        let limit = self.record.try_set_limit(limit)?;
+        self.evict(len - self.len());
+        Ok(limit)
     }
-}
 
-impl<R, C, F> AsMut<R> for History<R, C, F> {
+    /// Returns a guard that gives mutable access to the receiver.
+    ///
+    /// See [`Mutator`] for more information.
+    ///
+    /// [`Mutator`]: struct.Mutator.html
     #[inline]
-    fn as_mut(&mut self) -> &mut R {
-        self.as_mut_receiver()
+    pub fn mutate(&mut self) -> Mutator<'_, R, F> {
+        self.record.mutate()
     }
-}
 
-impl<R, C> From<R> for History<R, C> {
+    /// Marks the receiver as currently being in a saved or unsaved state.
     #[inline]
-    fn from(receiver: R) -> Self {
-        History::new(receiver)
+    pub fn set_saved(&mut self, saved: bool) {
+        self.record.set_saved(saved);
+        self.saved = None;
     }
-}
 
-impl<R, C, F> From<Record<R, C, F>> for History<R, C, F> {
+    /// Compacts the branch ids so they are numbered from `0` in the order they were created.
+    ///
+    /// Returns a map from the old ids to the new ids. The [`Renumbered`] signal is emitted
+    /// if any ids were changed.
+    ///
+    /// [`Renumbered`]: enum.Signal.html#variant.Renumbered
     #[inline]
-    fn from(record: Record<R, C, F>) -> Self {
-        History {
-            root: 0,
-            next: 1,
-            saved: None,
-            record,
-            branches: FxHashMap::default(),
+    pub fn renumber(&mut self) -> BTreeMap<usize, usize> {
+        let mut ids: Vec<_> = self.branches.keys().copied().collect();
+        ids.push(self.root);
+        ids.sort_unstable();
+        let map: BTreeMap<_, _> = ids
+            .into_iter()
+            .enumerate()
+            .map(|(new, old)| (old, new))
+            .collect();
+        self.next = map.len();
+        if map.iter().all(|(old, new)| old == new) {
+            return map;
+        }
+        self.meta = mem::take(&mut self.meta)
+            .into_iter()
+            .map(|(id, meta)| (map[&id], meta))
+            .collect();
+        self.names.values_mut().for_each(|id| *id = map[id]);
+        self.visited.iter_mut().for_each(|id| *id = map[id]);
+        self.root = map[&self.root];
+        self.record.branch = self.root;
+        if let Some(ref mut saved) = self.saved {
+            saved.branch = map[&saved.branch];
         }
+        self.branches = mem::take(&mut self.branches)
+            .into_iter()
+            .map(|(id, mut branch)| {
+                branch.parent.branch = map[&branch.parent.branch];
+                (map[&id], branch)
+            })
+            .collect();
+        self.record.slot.next_operation();
+        self.record.slot.emit(Signal::Renumbered);
+        map
     }
-}
 
-#[cfg(feature = "display")]
-impl<R, C: fmt::Display, F> fmt::Display for History<R, C, F> {
+    /// Revert the changes done to the receiver since the saved state.
     #[inline]
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        (&self.display() as &dyn fmt::Display).fmt(f)
+    pub fn revert(&mut self) -> Option<Result<(), C::Error>> {
+        if self.record.saved.is_some() {
+            self.transaction(|history| history.record.revert())
+        } else {
+            self.saved
+                .and_then(|saved| self.go_to(saved.branch, saved.current))
+        }
     }
-}
 
-/// A branch in the history.
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
-pub(crate) struct Branch<C> {
-    pub(crate) parent: At,
-    pub(crate) commands: VecDeque<Entry<C>>,
-}
+    /// Goes to the `position`, switching to its branch if needed, see [`go_to`].
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, History};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = ();
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.pop();
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let mut history = History::default();
+    /// history.apply(Add('a')).unwrap();
+    /// history.apply(Add('b')).unwrap();
+    /// let ab = history.position();
+    /// history.undo().unwrap().unwrap();
+    /// history.apply(Add('c')).unwrap();
+    /// history.go_to_position(ab).unwrap().unwrap();
+    /// assert_eq!(history.as_receiver(), "ab");
+    /// assert_eq!(history.position(), ab);
+    /// ```
+    ///
+    /// [`go_to`]: struct.History.html#method.go_to
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn go_to_position(&mut self, position: Position) -> Option<Result<(), C::Error>> {
+        self.go_to(position.branch(), position.current())
+    }
 
-/// Builder for a History.
-///
-/// # Examples
-/// ```
-/// # use redo::{Command, History};
-/// # struct Add(char);
-/// # impl Command<String> for Add {
-/// #     type Error = ();
-/// #     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> { Ok(()) }
-/// #     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> { Ok(()) }
-/// # }
-/// # fn foo() -> History<String, Add> {
-/// History::builder()
-///     .capacity(100)
-///     .limit(100)
-///     .saved(false)
-///     .default()
-/// # }
-/// ```
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
-pub struct HistoryBuilder<R, C> {
-    inner: RecordBuilder<R, C>,
-}
+    /// Goes to the saved state, switching to its branch if needed.
+    ///
+    /// Returns `None` if there is no saved state.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn go_to_saved(&mut self) -> Option<Result<(), C::Error>> {
+        self.go_to_saved_offset(0)
+    }
 
-impl<R, C> HistoryBuilder<R, C> {
-    /// Returns a builder for a history.
+    /// Goes to the state `offset` commands after the saved state, or before it if `offset` is negative.
+    ///
+    /// If the saved state is in another branch, the position is relative to that branch.
+    /// Returns `None` if there is no saved state or if the position is outside the branch.
     #[inline]
-    pub fn new() -> HistoryBuilder<R, C> {
-        HistoryBuilder {
-            inner: Record::builder(),
+    pub fn go_to_saved_offset(&mut self, offset: isize) -> Option<Result<(), C::Error>> {
+        if self.record.saved.is_some() {
+            self.transaction(|history| history.record.go_to_saved_offset(offset))
+        } else {
+            let saved = self.saved?;
+            let current = saved.current.checked_add_signed(offset)?;
+            self.go_to(saved.branch, current)
         }
     }
 
-    /// Sets the capacity for the history.
+    /// Goes to the position of the view cursor, changing the receiver.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
     #[inline]
-    pub fn capacity(mut self, capacity: usize) -> HistoryBuilder<R, C> {
-        self.inner = self.inner.capacity(capacity);
-        self
+    pub fn materialize(&mut self) -> Option<Result<(), C::Error>> {
+        self.transaction(|history| history.record.materialize())
     }
 
-    /// Sets the `limit` for the history.
+    /// Removes all commands from the history without undoing them.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.transaction(|history| {
+            history.root = 0;
+            history.record.branch = 0;
+            history.next = 1;
+            history.saved = None;
+            history.record.clear();
+            let mut branches: Vec<_> = mem::take(&mut history.branches).into_iter().collect();
+            branches.sort_unstable_by_key(|&(id, _)| id);
+            for (id, branch) in branches {
+                history.on_drop.call(id, &branch);
+            }
+            history.meta.clear();
+            history.names.clear();
+            history.visited.clear();
+        });
+    }
+
+    /// Removes the commands in the current branch for which `f` returns `false`.
     ///
-    /// # Panics
-    /// Panics if `limit` is `0`.
+    /// See [`Record::retain`] for how the receiver is updated. The commands in the other branches
+    /// are kept, but the branches are moved back by the number of removed commands before them.
+    /// If the saved state is in a branch that was moved it is removed.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`Record::retain`]: struct.Record.html#method.retain
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
     #[inline]
-    pub fn limit(mut self, limit: usize) -> HistoryBuilder<R, C> {
-        self.inner = self.inner.limit(limit);
-        self
+    pub fn retain(&mut self, f: impl FnMut(&C) -> bool) -> Result<(), C::Error> {
+        let keep: Vec<_> = self.record.commands().map(f).collect();
+        if keep.iter().all(|&keep| keep) {
+            return Ok(());
+        }
+        // The amount each branch is moved back, found from the ancestor that is a child of root.
+        let deltas: Vec<_> = self
+            .branches
+            .keys()
+            .map(|&id| {
+                let at = self.fork_of(id).unwrap();
+                let delta = keep[..at.current].iter().filter(|&&keep| !keep).count();
+                (id, delta)
+            })
+            .collect();
+        let mut keep = keep.into_iter();
+        self.transaction(|history| {
+            let len = history.record.len();
+            let result = history.record.retain(|_| keep.next().unwrap());
+            // The commands are not removed if the record failed to undo.
+            if history.record.len() == len {
+                return result;
+            }
+            for (id, delta) in deltas {
+                history.branches.get_mut(&id).unwrap().parent.current -= delta;
+                if delta > 0 && history.saved.is_some_and(|saved| saved.branch == id) {
+                    history.saved = None;
+                }
+            }
+            result
+        })
     }
 
-    /// Sets if the receiver is initially in a saved state.
-    /// By default the receiver is in a saved state.
+    /// Pushes the command to the top of the history and executes its [`apply`] method.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
     #[inline]
-    pub fn saved(mut self, saved: bool) -> HistoryBuilder<R, C> {
-        self.inner = self.inner.saved(saved);
-        self
+    pub fn apply(&mut self, command: C) -> Result<(), C::Error> {
+        self.transaction(|history| {
+            let entry = history.record.entry(command);
+            history.__apply(entry)
+        })
     }
 
-    /// Builds the history.
+    /// Pushes the command to the top of the history with the `label`,
+    /// and executes its [`apply`] method, see [`Record::apply_with_label`].
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    /// [`Record::apply_with_label`]: struct.Record.html#method.apply_with_label
     #[inline]
-    pub fn build(self, receiver: impl Into<R>) -> History<R, C> {
-        History {
-            root: 0,
-            next: 1,
-            saved: None,
-            record: self.inner.build(receiver),
-            branches: FxHashMap::default(),
-        }
+    pub fn apply_with_label(
+        &mut self,
+        command: C,
+        label: impl Into<String>,
+    ) -> Result<(), C::Error> {
+        self.transaction(|history| {
+            let mut entry = history.record.entry(command);
+            entry.set_label(label.into());
+            history.__apply(entry)
+        })
     }
 
-    /// Builds the history with the slot.
     #[inline]
-    pub fn build_with<F>(self, receiver: impl Into<R>, slot: F) -> History<R, C, F> {
-        History {
-            root: 0,
-            next: 1,
-            saved: None,
-            record: self.inner.build_with(receiver, slot),
-            branches: FxHashMap::default(),
+    fn __apply(&mut self, entry: Entry<C>) -> Result<(), C::Error> {
+        self.__apply_with(entry, C::apply)
+    }
+
+    #[inline]
+    fn __apply_with(
+        &mut self,
+        entry: Entry<C>,
+        apply: impl FnOnce(&mut C, &mut R) -> Result<(), C::Error>,
+    ) -> Result<(), C::Error> {
+        let current = self.current();
+        let saved = self.record.saved.filter(|&saved| saved > current);
+        let (merged, commands) = self.record.__apply_with(entry, apply)?;
+        // Check if the limit has been reached.
+        let evicted = if merged {
+            0
+        } else {
+            current + 1 - self.current()
+        };
+        self.evict(evicted);
+        // Handle new branch.
+        if !commands.is_empty() {
+            let old = self.branch();
+            // Annulled commands are removed from the record, so the branch starts where it ends.
+            let start = current.min(self.current());
+            let annulled = current - start;
+            if annulled > 0 {
+                // The branches that diverge after the annulled commands can no longer be reached.
+                for at in start + 1..=current {
+                    self.rm_child(old, at);
+                }
+                for branch in self.branches.values_mut() {
+                    if branch.parent.branch == old && branch.parent.current > current {
+                        branch.parent.current -= annulled;
+                    }
+                }
+            }
+            let saved = saved.map(|saved| saved - annulled);
+            let new = self.next;
+            self.next += 1;
+            self.branches.insert(
+                old,
+                Branch {
+                    parent: Position {
+                        branch: new,
+                        current: start,
+                    },
+                    commands,
+                },
+            );
+            self.visit(old);
+            event!(DEBUG, old, new, current = start, "created branch");
+            self.set_root(new, start);
+            match (self.record.saved, saved, self.saved) {
+                // The saved state is before the fork, so it is kept in the current branch.
+                (Some(_), None, None) => (),
+                (None, None, Some(_)) => self.swap_saved(new, old, start),
+                (None, Some(_), None) => {
+                    self.record.saved = saved;
+                    self.swap_saved(old, new, start);
+                }
+                (None, None, None) => (),
+                _ => unreachable!(),
+            }
+            self.evict_branches();
         }
+        Ok(())
     }
-}
 
-impl<R, C> Default for HistoryBuilder<R, C> {
+    /// Calls the [`undo`] method for the active command
+    /// and sets the previous one as the new active one.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
     #[inline]
-    fn default() -> Self {
-        HistoryBuilder::new()
+    pub fn undo(&mut self) -> Option<Result<(), C::Error>> {
+        self.transaction(|history| history.record.undo())
     }
-}
 
-impl<R: Default, C> HistoryBuilder<R, C> {
-    /// Creates the history with a default `receiver`.
+    /// Calls the [`redo`] method for the active command
+    /// and sets the next one as the new active one.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned.
+    ///
+    /// [`redo`]: trait.Command.html#method.redo
     #[inline]
-    pub fn default(self) -> History<R, C> {
-        self.build(R::default())
+    pub fn redo(&mut self) -> Option<Result<(), C::Error>> {
+        self.transaction(|history| history.record.redo())
     }
 
-    /// Creates the history with a default `receiver`.
+    /// Calls [`undo`] up to `n` times, only emitting the signals once if they are deferred.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned.
+    ///
+    /// [`undo`]: struct.History.html#method.undo
     #[inline]
-    pub fn default_with<F>(self, slot: F) -> History<R, C, F> {
-        self.build_with(R::default(), slot)
+    pub fn undo_n(&mut self, n: usize) -> Option<Result<(), C::Error>> {
+        self.transaction(|history| history.record.undo_n(n))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::{Command, History};
+    /// Calls [`redo`] up to `n` times, only emitting the signals once if they are deferred.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned.
+    ///
+    /// [`redo`]: struct.History.html#method.redo
+    #[inline]
+    pub fn redo_n(&mut self, n: usize) -> Option<Result<(), C::Error>> {
+        self.transaction(|history| history.record.redo_n(n))
+    }
 
-    struct Add(char);
+    /// Undoes all the commands in the current branch, see [`Record::undo_all`].
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned together with the index of
+    /// the command that failed.
+    ///
+    /// [`Record::undo_all`]: struct.Record.html#method.undo_all
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    #[inline]
+    pub fn undo_all(&mut self) -> Option<Result<(), Error<C::Error>>> {
+        self.transaction(|history| history.record.undo_all())
+    }
 
-    impl Command<String> for Add {
-        type Error = &'static str;
+    /// Redoes all the commands in the current branch, see [`Record::redo_all`].
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned together with the index of
+    /// the command that failed.
+    ///
+    /// [`Record::redo_all`]: struct.Record.html#method.redo_all
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn redo_all(&mut self) -> Option<Result<(), Error<C::Error>>> {
+        self.transaction(|history| history.record.redo_all())
+    }
 
-        fn apply(&mut self, receiver: &mut String) -> Result<(), Self::Error> {
-            receiver.push(self.0);
-            Ok(())
-        }
+    /// Undoes the commands until the previous coarse boundary is reached.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    #[inline]
+    pub fn undo_coarse(&mut self) -> Option<Result<(), C::Error>> {
+        self.transaction(|history| history.record.undo_coarse())
+    }
 
-        fn undo(&mut self, receiver: &mut String) -> Result<(), Self::Error> {
-            self.0 = receiver.pop().ok_or("`receiver` is empty")?;
-            Ok(())
+    /// Redoes the commands until the next coarse boundary is reached.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned.
+    ///
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn redo_coarse(&mut self) -> Option<Result<(), C::Error>> {
+        self.transaction(|history| history.record.redo_coarse())
+    }
+
+    /// Repeatedly calls [`undo`] or [`redo`] until the command in `branch` at `current` is reached.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn go_to(&mut self, branch: usize, current: usize) -> Option<Result<(), C::Error>> {
+        if self.root == branch {
+            return self.transaction(|history| history.record.__go_to(current));
+        }
+        // The position must be in the branch, or the receiver would be changed for nothing.
+        let target = self.branches.get(&branch)?;
+        if current > target.parent.current + target.commands.len() {
+            return None;
         }
+        event!(
+            DEBUG,
+            from = self.root,
+            to = branch,
+            current,
+            "switching branch"
+        );
+        self.transaction(|history| history.__go_to(branch, current))
     }
 
-    #[test]
-    fn go_to() {
-        //          m
-        //          |
-        //    j  k  l
-        //     \ | /
-        //       i
-        //       |
-        // e  g  h
-        // |  | /
-        // d  f  p - q *
-        // | /  /
-        // c  n - o
-        // | /
-        // b
-        // |
-        // a
-        let mut history = History::default();
+    /// Goes to the end of the branch with the `name`.
+    ///
+    /// Returns `None` if there is no branch with the name.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn switch_to(&mut self, name: &str) -> Option<Result<(), C::Error>> {
+        let branch = self.branch_by_name(name)?;
+        let current = match self.branches.get(&branch) {
+            Some(branch) => branch.parent.current + branch.commands.len(),
+            None => self.len(),
+        };
+        self.go_to(branch, current)
+    }
+
+    /// Moves the commands of the branch onto the end of its parent branch and removes the branch.
+    ///
+    /// The history goes to the end of the parent branch, and the commands in the branch are
+    /// redone on top of it, which supports keeping the result of an experiment without applying
+    /// each command again by hand. The branches that diverge from the branch are moved to the
+    /// parent branch, and the commands get new ids.
+    /// Returns `None` if the branch does not exist or if it is the current branch.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned, and the
+    /// history is left at the command that failed.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, History};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = &'static str;
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+    /// #         self.0 = s.pop().ok_or("`s` is empty")?;
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let mut history = History::default();
+    /// history.extend(vec![Add('a'), Add('b')]).unwrap();
+    /// let main = history.branch();
+    /// history.undo().unwrap().unwrap();
+    /// history.apply(Add('x')).unwrap();
+    /// let experiment = history.branch();
+    /// history.go_to(main, 2).unwrap().unwrap();
+    /// history.squash_branch(experiment).unwrap().unwrap();
+    /// assert_eq!(history.as_receiver(), "abx");
+    /// assert_eq!(history.branches().count(), 0);
+    /// ```
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn squash_branch(&mut self, id: usize) -> Option<Result<(), C::Error>> {
+        let parent = self.branches.get(&id)?.parent;
+        self.transaction(|history| {
+            // Go to the end of the parent branch.
+            if parent.branch != history.branch() {
+                let branch = &history.branches[&parent.branch];
+                let tip = branch.parent.current + branch.len();
+                if let Err(err) = history.go_to(parent.branch, tip)? {
+                    return Some(Err(err));
+                }
+            }
+            if let Err(err) = history.record.__go_to(history.len())? {
+                return Some(Err(err));
+            }
+            // The positions in the branch and the branches that diverge from it are moved by `delta`.
+            let delta = history.len() - parent.current;
+            let mut moved = vec![id];
+            let mut i = 0;
+            while let Some(&branch) = moved.get(i) {
+                moved.extend(history.children(|at| at.branch == branch));
+                i += 1;
+            }
+            let branch = history.branches.remove(&id).unwrap();
+            let root = history.root;
+            for (_, child) in history
+                .branches
+                .iter_mut()
+                .filter(|(branch, _)| moved.contains(branch))
+            {
+                child.parent.current += delta;
+                if child.parent.branch == id {
+                    child.parent.branch = root;
+                }
+            }
+            if let Some(saved) = history
+                .saved
+                .as_mut()
+                .filter(|saved| moved.contains(&saved.branch))
+            {
+                saved.current += delta;
+            }
+            let saved = history.saved.filter(|saved| saved.branch == id);
+            history.meta.remove(&id);
+            history.names.retain(|_, &mut branch| branch != id);
+            history.visited.retain(|&branch| branch != id);
+            let len = branch.len();
+            let evicted = match history.record.__append(branch.commands, len) {
+                Ok(evicted) => evicted,
+                Err(err) => return Some(Err(err)),
+            };
+            history.evict(evicted);
+            if let Some(saved) = saved {
+                history.saved = None;
+                history.record.saved = saved.current.checked_sub(evicted);
+            }
+            Some(Ok(()))
+        })
+    }
+
+    #[inline]
+    fn __go_to(&mut self, branch: usize, current: usize) -> Option<Result<(), C::Error>> {
+        // Walk to where the path forks first, since the commands removed on the way move it back.
+        loop {
+            let fork = self.fork_of(branch)?;
+            if let Err(err) = self.record.__go_to(fork.current)? {
+                return Some(Err(err));
+            }
+            if !self.shift_forks() {
+                break;
+            }
+        }
+        // Walk the path from `root` to `branch`.
+        for (new, branch) in self.mk_path(branch)? {
+            let old = self.branch();
+            // Walk to `branch.current` either by undoing or redoing.
+            if let Err(err) = self.record.__go_to(branch.parent.current)? {
+                return Some(Err(err));
+            }
+            self.shift_forks();
+            // Apply the commands in the branch and move older commands into their own branch.
+            for entry in branch.commands {
+                let current = self.current();
+                let saved = self.record.saved.filter(|&saved| saved > current);
+                let commands = match self.record.__restore(entry) {
+                    Ok(commands) => commands,
+                    Err(err) => return Some(Err(err)),
+                };
+                // Handle new branch.
+                if !commands.is_empty() {
+                    self.branches.insert(
+                        self.root,
+                        Branch {
+                            parent: Position {
+                                branch: new,
+                                current,
+                            },
+                            commands,
+                        },
+                    );
+                    self.visit(self.root);
+                    self.set_root(new, current);
+                    match (self.record.saved, saved, self.saved) {
+                        // The saved state is before the fork, so it is kept in the current branch.
+                        (Some(_), None, None) => (),
+                        (None, None, Some(_)) => self.swap_saved(new, old, current),
+                        (None, Some(_), None) => {
+                            self.record.saved = saved;
+                            self.swap_saved(old, new, current);
+                        }
+                        (None, None, None) => (),
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+        // The saved state can be in the commands that were applied from the last branch.
+        if let Some(saved) = self.saved.filter(|saved| saved.branch == self.root) {
+            self.saved = None;
+            self.record.saved = Some(saved.current);
+        }
+        self.record.__go_to(current)
+    }
+
+    /// Returns the number of commands in the current branch before the current position
+    /// that can no longer be undone, see [`Record::expired`].
+    ///
+    /// [`Record::expired`]: struct.Record.html#method.expired
+    #[inline]
+    pub fn expired(&self) -> usize {
+        self.record.expired()
+    }
+
+    /// Calls [`prefetch`] on the commands in the current branch in the `range`.
+    ///
+    /// # Panics
+    /// Panics if the `range` is out of bounds.
+    ///
+    /// [`prefetch`]: trait.Command.html#method.prefetch
+    #[inline]
+    pub fn prefetch(&mut self, range: impl RangeBounds<usize>) {
+        self.record.prefetch(range)
+    }
+
+    /// Calls [`prefetch`] on the commands around the current position, see [`Record::idle`].
+    ///
+    /// [`prefetch`]: trait.Command.html#method.prefetch
+    /// [`Record::idle`]: struct.Record.html#method.idle
+    #[inline]
+    pub fn idle(&mut self, depth: usize) {
+        self.record.idle(depth)
+    }
+
+    /// Go back or forward in the history to the command that was made closest to the datetime provided.
+    ///
+    /// This method does not jump across branches.
+    #[inline]
+    #[cfg(feature = "chrono")]
+    pub fn time_travel(&mut self, to: &DateTime<impl TimeZone>) -> Option<Result<(), C::Error>> {
+        self.transaction(|history| history.record.time_travel(to))
+    }
+
+    /// Go back or forward in the history to the command that was made closest to the datetime provided.
+    ///
+    /// This method does not jump across branches.
+    #[inline]
+    #[cfg(all(feature = "time", not(feature = "chrono")))]
+    pub fn time_travel(&mut self, to: &OffsetDateTime) -> Option<Result<(), C::Error>> {
+        self.transaction(|history| history.record.time_travel(to))
+    }
+
+    /// Applies each command in the iterator.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    pub fn extend(&mut self, commands: impl IntoIterator<Item = C>) -> Result<(), C::Error> {
+        self.transaction(|history| {
+            for command in commands {
+                history.apply(command)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Returns a heatmap of the commands in the history grouped into time buckets of length `bucket`.
+    ///
+    /// The commands in the other branches are counted as undone.
+    ///
+    /// # Panics
+    /// Panics if `bucket` is zero.
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn heatmap(&self, bucket: Duration) -> Heatmap {
+        let current = self.current();
+        let record = self
+            .record
+            .commands
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (entry, entry.command.region(), i >= current));
+        let branches = self
+            .branches
+            .values()
+            .flat_map(|branch| branch.commands.iter())
+            .map(|entry| (entry, entry.command.region(), true));
+        Heatmap::new(bucket, record.chain(branches))
+    }
+
+    /// Applies each command in the iterator, canceling all of them if one fails.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the commands that were applied are canceled,
+    /// and the error is returned together with the result of canceling them.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    #[cfg(feature = "checkpoint")]
+    pub fn extend_atomic(
+        &mut self,
+        commands: impl IntoIterator<Item = C>,
+    ) -> Result<(), RollbackError<C::Error>> {
+        self.transaction(|history| {
+            let mut checkpoint = history.checkpoint();
+            checkpoint
+                .extend(commands)
+                .map_err(|error| checkpoint.rollback(error))
+        })
+    }
+
+    /// Calls `f` and adds the context of the operation that failed to the error,
+    /// see [`Record::with_context`].
+    ///
+    /// [`Record::with_context`]: struct.Record.html#method.with_context
+    #[inline]
+    pub fn with_context<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, C::Error>,
+    ) -> Result<T, Error<C::Error>> {
+        self.record.failure = None;
+        f(self).map_err(|error| Error::new(self.record.failure, error))
+    }
+
+    /// Performs `f` as one operation, only emitting the net signals when it is done if they are deferred.
+    #[inline]
+    pub(crate) fn transaction<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.record.slot.begin(self.state());
+        // Keep track of the commands the record removes, so the branches can be moved back.
+        self.record.removed.get_or_insert_with(Vec::new);
+        let t = f(self);
+        self.shift_forks();
+        self.update_fork();
+        self.record.slot.end(self.state());
+        t
+    }
+
+    /// Sets the `root`.
+    #[inline]
+    fn set_root(&mut self, root: usize, current: usize) {
+        let old = self.branch();
+        self.root = root;
+        self.record.branch = root;
+        debug_assert_ne!(old, root);
+        // Handle the child branches.
+        for branch in self
+            .branches
+            .values_mut()
+            .filter(|branch| branch.parent.branch == old && branch.parent.current <= current)
+        {
+            branch.parent.branch = root;
+        }
+    }
+
+    /// Sets the saved state to the position in the branch, without emitting any signals.
+    #[inline]
+    #[cfg(feature = "checkpoint")]
+    pub(crate) fn restore_saved(&mut self, saved: Option<Position>) {
+        match saved {
+            Some(saved) if saved.branch == self.branch() => {
+                self.record.saved = Some(saved.current);
+                self.saved = None;
+            }
+            Some(saved) => {
+                self.record.saved = None;
+                self.saved = Some(saved);
+            }
+            None => {
+                self.record.saved = None;
+                self.saved = None;
+            }
+        }
+    }
+
+    /// Swap the saved state if needed.
+    ///
+    /// This is always called inside a transaction which emits the saved signal.
+    #[inline]
+    fn swap_saved(&mut self, old: usize, new: usize, current: usize) {
+        debug_assert_ne!(old, new);
+        if let Some(Position { current: saved, .. }) = self
+            .saved
+            .filter(|at| at.branch == new && at.current <= current)
+        {
+            self.saved = None;
+            self.record.saved = Some(saved);
+        } else if let Some(saved) = self.record.saved {
+            self.saved = Some(Position {
+                branch: old,
+                current: saved,
+            });
+            self.record.saved = None;
+        }
+    }
+
+    /// Marks the branch as the most recently visited one if the number of branches is limited.
+    #[inline]
+    fn visit(&mut self, id: usize) {
+        if self.branch_limit.is_some() {
+            self.visited.retain(|&branch| branch != id);
+            self.visited.push(id);
+        }
+    }
+
+    /// Removes the least recently visited branches until the branch limit is no longer exceeded.
+    #[inline]
+    fn evict_branches(&mut self) {
+        let limit = match self.branch_limit {
+            Some(limit) => limit,
+            None => return,
+        };
+        while self.branches.len() > limit {
+            // Branches that have not been visited, e.g. in a deserialized history, are the oldest.
+            let id = self
+                .branches
+                .keys()
+                .copied()
+                .filter(|id| !self.visited.contains(id))
+                .min()
+                .or_else(|| {
+                    let branches = &self.branches;
+                    self.visited
+                        .iter()
+                        .copied()
+                        .find(|id| branches.contains_key(id))
+                })
+                .unwrap();
+            let mut evicted = Vec::new();
+            self.rm_tree_with(id, |id| evicted.push(id));
+            for id in evicted {
+                event!(DEBUG, id, "evicted branch");
+                self.record.slot.emit(Signal::BranchEvicted(id));
+            }
+        }
+    }
+
+    /// Fixes up the branches after `evicted` commands have been removed from the front of the record.
+    #[inline]
+    fn evict(&mut self, evicted: usize) {
+        if evicted == 0 {
+            return;
+        }
+        let root = self.branch();
+        for current in 0..evicted {
+            self.rm_child(root, current);
+        }
+        // Every remaining branch shares the removed commands, so all positions are moved back.
+        for branch in self.branches.values_mut() {
+            branch.parent.current -= evicted;
+        }
+        self.saved = self.saved.and_then(|saved| {
+            let current = saved.current.checked_sub(evicted)?;
+            Some(Position { current, ..saved })
+        });
+        self.update_fork();
+    }
+
+    /// Remove all children of the command at the given position.
+    #[inline]
+    fn rm_child(&mut self, branch: usize, current: usize) {
+        for id in self.children(|at| at == Position { branch, current }) {
+            self.rm_tree(id);
+        }
+    }
+
+    /// Create a path between the current branch and the `to` branch.
+    #[inline]
+    fn mk_path(&mut self, mut to: usize) -> Option<impl Iterator<Item = (usize, Branch<C>)>> {
+        debug_assert_ne!(self.branch(), to);
+        let mut dest = self.branches.remove(&to)?;
+        let mut i = dest.parent.branch;
+        let mut path = vec![(to, dest)];
+        while i != self.branch() {
+            dest = self.branches.remove(&i).unwrap();
+            to = i;
+            i = dest.parent.branch;
+            path.push((to, dest));
+        }
+        Some(path.into_iter().rev())
+    }
+}
+
+impl<R, C: ProgressCommand<R>, F: FnMut(Signal)> History<R, C, F> {
+    /// Pushes the command to the top of the history and executes its [`apply_with_progress`]
+    /// method, see [`Record::apply_with_progress`].
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply_with_progress`] the error is returned.
+    ///
+    /// [`apply_with_progress`]: trait.ProgressCommand.html#method.apply_with_progress
+    /// [`Record::apply_with_progress`]: struct.Record.html#method.apply_with_progress
+    #[inline]
+    pub fn apply_with_progress(
+        &mut self,
+        command: C,
+        mut progress: impl FnMut(f32),
+    ) -> Result<(), C::Error> {
+        self.transaction(|history| {
+            let entry = history.record.entry(command);
+            history.__apply_with(entry, |command, receiver| {
+                command.apply_with_progress(receiver, &mut progress)
+            })
+        })
+    }
+}
+
+impl<R, C: AsPatch, F> History<R, C, F> {
+    /// Returns an iterator over the patches of the commands in the current branch in the `range`,
+    /// see [`Record::export_patches`].
+    ///
+    /// # Panics
+    /// Panics if the `range` is out of bounds.
+    ///
+    /// [`Record::export_patches`]: struct.Record.html#method.export_patches
+    #[inline]
+    pub fn export_patches(
+        &self,
+        range: impl RangeBounds<usize>,
+    ) -> impl Iterator<Item = C::Patch> + '_ {
+        self.record.export_patches(range)
+    }
+
+    /// Returns an iterator over the inverse patches of the commands in the current branch
+    /// in the `range`, see [`Record::export_inverse_patches`].
+    ///
+    /// # Panics
+    /// Panics if the `range` is out of bounds.
+    ///
+    /// [`Record::export_inverse_patches`]: struct.Record.html#method.export_inverse_patches
+    #[inline]
+    pub fn export_inverse_patches(
+        &self,
+        range: impl RangeBounds<usize>,
+    ) -> impl Iterator<Item = Option<C::Patch>> + '_ {
+        self.record.export_inverse_patches(range)
+    }
+}
+
+impl<R, C: ToString, F> History<R, C, F> {
+    /// Returns the string of the command which will be undone in the next call to [`undo`].
+    ///
+    /// [`undo`]: struct.History.html#method.undo
+    #[inline]
+    pub fn to_undo_string(&self) -> Option<String> {
+        self.record.to_undo_string()
+    }
+
+    /// Returns the string of the command which will be redone in the next call to [`redo`].
+    ///
+    /// [`redo`]: struct.History.html#method.redo
+    #[inline]
+    pub fn to_redo_string(&self) -> Option<String> {
+        self.record.to_redo_string()
+    }
+
+    /// Returns a snapshot of the current branch with the strings of the commands.
+    #[inline]
+    pub fn snapshot(&self) -> Snapshot<String> {
+        self.snapshot_with(C::to_string)
+    }
+
+    /// Returns a stable textual dump of the history.
+    ///
+    /// See the [`testing`] module for the format.
+    ///
+    /// [`testing`]: testing/index.html
+    #[inline]
+    #[cfg(feature = "testing")]
+    pub fn dump(&self) -> String {
+        let root = self.branch();
+        let mut dump = format!("current {}\n", self.current());
+        match (self.record.saved, self.saved) {
+            (Some(saved), _) => dump.push_str(&format!("saved {} {}\n", root, saved)),
+            (None, Some(Position { branch, current })) => {
+                dump.push_str(&format!("saved {} {}\n", branch, current))
+            }
+            (None, None) => dump.push_str("saved none\n"),
+        }
+        dump.push_str(&format!("branch {} {} 0\n", root, root));
+        testing::commands(&mut dump, 0, &self.record.commands);
+        let mut branches: Vec<_> = self.branches.iter().collect();
+        branches.sort_by_key(|&(&id, _)| id);
+        for (id, branch) in branches {
+            let Position {
+                branch: parent,
+                current,
+            } = branch.parent;
+            dump.push_str(&format!("branch {} {} {}\n", id, parent, current));
+            testing::commands(&mut dump, current, &branch.commands);
+        }
+        dump
+    }
+
+    /// Returns the undo tree in the Graphviz DOT format.
+    ///
+    /// Each command is a node named by its position, as in `"branch:current"`, with an edge from
+    /// the command before it. The empty state before the first command is the `root` node.
+    /// The current and the saved state are marked in the labels.
+    #[inline]
+    pub fn to_dot(&self) -> String {
+        let saved = self.saved();
+        let marks = |at: Position| {
+            let mut marks = String::new();
+            if at.current == self.current() && (at.current == 0 || at.branch == self.branch()) {
+                marks.push_str(" (current)");
+            }
+            if let Some((branch, current)) = saved {
+                if at.current == current && (current == 0 || at.branch == branch) {
+                    marks.push_str(" (saved)");
+                }
+            }
+            marks
+        };
+        let root = marks(Position::default());
+        let mut dot = format!(
+            "digraph history {{\n    root [label=\"{}\"];\n",
+            root.trim_start()
+        );
+        let node = |dot: &mut String, at: Position, command: &C| {
+            let mut label = String::new();
+            for c in command.to_string().chars() {
+                match c {
+                    '\\' => label.push_str("\\\\"),
+                    '"' => label.push_str("\\\""),
+                    '\n' => label.push_str("\\n"),
+                    c => label.push(c),
+                }
+            }
+            let parent = self.dot_node(Position {
+                current: at.current - 1,
+                ..at
+            });
+            dot.push_str(&format!(
+                "    \"{}:{}\" [label=\"{}{}\"];\n    {} -> \"{}:{}\";\n",
+                at.branch,
+                at.current,
+                label,
+                marks(at),
+                parent,
+                at.branch,
+                at.current
+            ));
+        };
+        for (i, entry) in self.record.commands.iter().enumerate() {
+            let at = Position {
+                branch: self.branch(),
+                current: i + 1,
+            };
+            node(&mut dot, at, &entry.command);
+        }
+        let mut branches: Vec<_> = self.branches.iter().collect();
+        branches.sort_by_key(|&(&id, _)| id);
+        for (&id, branch) in branches {
+            for (current, command) in branch.range().zip(branch.commands()) {
+                node(
+                    &mut dot,
+                    Position {
+                        branch: id,
+                        current,
+                    },
+                    command,
+                );
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Returns the name of the node at the position in the DOT output.
+    #[inline]
+    fn dot_node(&self, mut at: Position) -> String {
+        loop {
+            if at.current == 0 {
+                return "root".into();
+            }
+            match self.branches.get(&at.branch) {
+                // The position is before the start of the branch, so it is in its parent.
+                Some(branch) if at.current <= branch.start() => {
+                    at.branch = branch.parent.branch;
+                }
+                _ => return format!("\"{}:{}\"", at.branch, at.current),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<R: Serialize, C: Serialize, F> Serialize for History<R, C, F> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        History::serialize(self, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, R: Deserialize<'de>, C: Deserialize<'de>, F> Deserialize<'de> for History<R, C, F> {
+    /// Deserializes the history and rejects it if it is invalid, see [`validate`].
+    ///
+    /// [`validate`]: struct.History.html#method.validate
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut history = History::deserialize(deserializer)?;
+        history.validate().map_err(de::Error::custom)?;
+        let branches = history.branches.values_mut();
+        history
+            .record
+            .fix_ids(branches.flat_map(|branch| branch.commands.iter_mut()));
+        Ok(history)
+    }
+}
+
+impl<R: Default, C> Default for History<R, C> {
+    #[inline]
+    fn default() -> History<R, C> {
+        History::new(R::default())
+    }
+}
+
+impl<R, C, F> AsRef<R> for History<R, C, F> {
+    #[inline]
+    fn as_ref(&self) -> &R {
+        self.as_receiver()
+    }
+}
+
+impl<R, C, F> AsMut<R> for History<R, C, F> {
+    #[inline]
+    fn as_mut(&mut self) -> &mut R {
+        self.as_mut_receiver()
+    }
+}
+
+impl<R, C> From<R> for History<R, C> {
+    #[inline]
+    fn from(receiver: R) -> Self {
+        History::new(receiver)
+    }
+}
+
+impl<R, C, F> From<Record<R, C, F>> for History<R, C, F> {
+    #[inline]
+    fn from(mut record: Record<R, C, F>) -> Self {
+        record.branch = 0;
+        History {
+            root: 0,
+            next: 1,
+            saved: None,
+            record,
+            branches: Map::default(),
+            meta: Map::default(),
+            names: BTreeMap::new(),
+            fork: None,
+            branch_limit: None,
+            visited: Vec::new(),
+            on_drop: DropHook::default(),
+        }
+    }
+}
+
+impl<R, C, F> IntoIterator for History<R, C, F> {
+    type Item = (usize, C);
+    type IntoIter = HistoryIntoIter<C>;
+
+    /// Consumes the history, returning an iterator over the commands and the ids of their branches.
+    ///
+    /// The commands in the current branch come first, followed by the commands in the other
+    /// branches in the order of their ids.
+    #[inline]
+    fn into_iter(self) -> HistoryIntoIter<C> {
+        let mut branches: Vec<_> = self
+            .branches
+            .into_iter()
+            .map(|(id, branch)| (id, branch.commands))
+            .collect();
+        // The branches are popped from the back.
+        branches.sort_unstable_by(|(a, _), (b, _)| b.cmp(a));
+        HistoryIntoIter {
+            branch: self.root,
+            commands: self.record.into_iter(),
+            branches,
+        }
+    }
+}
+
+impl<'a, R, C, F> IntoIterator for &'a History<R, C, F> {
+    type Item = (usize, &'a C);
+    type IntoIter = HistoryIter<'a, C>;
+
+    /// Returns an iterator over the commands in all the branches and the ids of their branches.
+    ///
+    /// The commands in the current branch come first, followed by the commands in the other
+    /// branches in the order of their ids.
+    #[inline]
+    fn into_iter(self) -> HistoryIter<'a, C> {
+        let mut branches: Vec<_> = self
+            .branches
+            .iter()
+            .map(|(&id, branch)| (id, branch))
+            .collect();
+        // The branches are popped from the back.
+        branches.sort_unstable_by(|(a, _), (b, _)| b.cmp(a));
+        HistoryIter {
+            branch: self.root,
+            commands: RecordIter(self.record.commands.iter()),
+            branches,
+        }
+    }
+}
+
+/// An iterator over the commands in all the branches of a history.
+///
+/// Created by iterating over a reference to a [`History`].
+///
+/// [`History`]: struct.History.html
+#[derive(Clone, Debug)]
+pub struct HistoryIter<'a, C> {
+    branch: usize,
+    commands: RecordIter<'a, C>,
+    branches: Vec<(usize, &'a Branch<C>)>,
+}
+
+impl<'a, C> Iterator for HistoryIter<'a, C> {
+    type Item = (usize, &'a C);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, &'a C)> {
+        loop {
+            if let Some(command) = self.commands.next() {
+                return Some((self.branch, command));
+            }
+            let (branch, next) = self.branches.pop()?;
+            self.branch = branch;
+            self.commands = RecordIter(next.commands.iter());
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.commands.len() + self.branches.iter().map(|(_, b)| b.len()).sum::<usize>();
+        (len, Some(len))
+    }
+}
+
+impl<C> ExactSizeIterator for HistoryIter<'_, C> {}
+
+/// An iterator that moves the commands out of all the branches of a history.
+///
+/// Created by calling `into_iter` on a [`History`].
+///
+/// [`History`]: struct.History.html
+#[derive(Debug)]
+pub struct HistoryIntoIter<C> {
+    branch: usize,
+    commands: RecordIntoIter<C>,
+    branches: Vec<(usize, VecDeque<Entry<C>>)>,
+}
+
+impl<C> Iterator for HistoryIntoIter<C> {
+    type Item = (usize, C);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, C)> {
+        loop {
+            if let Some(command) = self.commands.next() {
+                return Some((self.branch, command));
+            }
+            let (branch, next) = self.branches.pop()?;
+            self.branch = branch;
+            self.commands = RecordIntoIter(next.into_iter());
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.commands.len() + self.branches.iter().map(|(_, b)| b.len()).sum::<usize>();
+        (len, Some(len))
+    }
+}
+
+impl<C> ExactSizeIterator for HistoryIntoIter<C> {}
+
+#[cfg(feature = "display")]
+impl<R, C: fmt::Display, F> fmt::Display for History<R, C, F> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (&self.display() as &dyn fmt::Display).fmt(f)
+    }
+}
+
+/// A step on the path from the root of a history to the current position.
+///
+/// Returned by [`History::path`].
+///
+/// [`History::path`]: struct.History.html#method.path
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum PathStep<'a, C> {
+    /// Other branches diverge from the path after the command at `position`.
+    Fork {
+        /// The position the branches diverge from.
+        position: usize,
+        /// The ids of the branches, in the order they were created.
+        branches: Vec<usize>,
+    },
+    /// A command on the path.
+    Entry(EntryView<'a, C>),
+}
+
+/// A step on the path between two positions in a history.
+///
+/// Returned by [`History::path_between`].
+///
+/// [`History::path_between`]: struct.History.html#method.path_between
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum Direction<C> {
+    /// The command is undone.
+    Undo(C),
+    /// The command is redone.
+    Redo(C),
+}
+
+/// A branch in the history.
+///
+/// A branch holds the commands that were undone when a new command was applied,
+/// and knows where in its parent branch it diverges.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Branch<C> {
+    pub(crate) parent: Position,
+    pub(crate) commands: VecDeque<Entry<C>>,
+}
+
+impl<C> Branch<C> {
+    /// Returns the id of the parent branch.
+    #[inline]
+    pub fn parent(&self) -> usize {
+        self.parent.branch
+    }
+
+    /// Returns the position in the parent branch where the branch diverges.
+    #[inline]
+    pub fn start(&self) -> usize {
+        self.parent.current
+    }
+
+    /// Returns the range of positions the commands in the branch have when it is the current branch.
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.start() + 1..self.start() + self.len() + 1
+    }
+
+    /// Returns the number of commands in the branch.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns `true` if the branch is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Returns an iterator over the commands in the branch.
+    #[inline]
+    pub fn commands(&self) -> impl Iterator<Item = &C> {
+        self.commands.iter().map(|entry| &entry.command)
+    }
+}
+
+type DropFn<C> = dyn FnMut(usize, &Branch<C>) + Send;
+
+/// The callback that is called when a branch is dropped.
+struct DropHook<C>(Option<Box<DropFn<C>>>);
+
+impl<C> DropHook<C> {
+    #[inline]
+    fn call(&mut self, id: usize, branch: &Branch<C>) {
+        if let Some(f) = &mut self.0 {
+            f(id, branch);
+        }
+    }
+}
+
+impl<C> Default for DropHook<C> {
+    #[inline]
+    fn default() -> Self {
+        DropHook(None)
+    }
+}
+
+impl<C> Clone for DropHook<C> {
+    #[inline]
+    fn clone(&self) -> Self {
+        DropHook(None)
+    }
+}
+
+impl<C> PartialEq for DropHook<C> {
+    #[inline]
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<C> Eq for DropHook<C> {}
+
+impl<C> fmt::Debug for DropHook<C> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("DropHook").field(&self.0.is_some()).finish()
+    }
+}
+
+/// Builder for a History.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, History};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> { Ok(()) }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> { Ok(()) }
+/// # }
+/// # fn foo() -> History<String, Add> {
+/// History::builder()
+///     .capacity(100)
+///     .limit(100)
+///     .saved(false)
+///     .default()
+/// # }
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct HistoryBuilder<R, C> {
+    inner: RecordBuilder<R, C>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    branch_limit: Option<usize>,
+}
+
+impl<R, C> HistoryBuilder<R, C> {
+    /// Returns a builder for a history.
+    #[inline]
+    pub fn new() -> HistoryBuilder<R, C> {
+        HistoryBuilder {
+            inner: Record::builder(),
+            branch_limit: None,
+        }
+    }
+
+    /// Sets the capacity for the history.
+    #[inline]
+    pub fn capacity(mut self, capacity: usize) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.capacity(capacity);
+        self
+    }
+
+    /// Sets the `limit` for the history.
+    ///
+    /// # Panics
+    /// Panics if `limit` is `0`, see [`try_limit`].
+    ///
+    /// [`try_limit`]: struct.HistoryBuilder.html#method.try_limit
+    #[inline]
+    pub fn limit(mut self, limit: usize) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.limit(limit);
+        self
+    }
+
+    /// Sets the `limit` for the history, see [`RecordBuilder::try_limit`].
+    ///
+    /// # Errors
+    /// Returns [`ConfigError::ZeroLimit`] if `limit` is `0`.
+    ///
+    /// [`RecordBuilder::try_limit`]: struct.RecordBuilder.html#method.try_limit
+    /// [`ConfigError::ZeroLimit`]: enum.ConfigError.html#variant.ZeroLimit
+    #[inline]
+    pub fn try_limit(mut self, limit: usize) -> Result<HistoryBuilder<R, C>, ConfigError> {
+        self.inner = self.inner.try_limit(limit)?;
+        Ok(self)
+    }
+
+    /// Sets the maximum number of branches that are kept besides the current branch.
+    ///
+    /// When a new branch is created and there are more than `limit` branches, the branches that
+    /// were visited least recently are removed together with the branches that diverge from them.
+    /// The [`BranchEvicted`] signal is emitted for every removed branch, and the callback set by
+    /// [`on_branch_drop`] is called with them. By default there is no limit.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, History};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = ();
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.pop();
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let mut history = History::builder().branch_limit(1).default();
+    /// history.apply(Add('a')).unwrap();
+    /// let a = history.branch();
+    /// history.undo().unwrap().unwrap();
+    /// history.apply(Add('b')).unwrap();
+    /// let b = history.branch();
+    /// history.undo().unwrap().unwrap();
+    /// history.apply(Add('c')).unwrap();
+    /// assert!(history.get_branch(a).is_none());
+    /// assert!(history.get_branch(b).is_some());
+    /// ```
+    ///
+    /// [`BranchEvicted`]: enum.Signal.html#variant.BranchEvicted
+    /// [`on_branch_drop`]: struct.History.html#method.on_branch_drop
+    #[inline]
+    pub fn branch_limit(mut self, limit: usize) -> HistoryBuilder<R, C> {
+        self.branch_limit = Some(limit);
+        self
+    }
+
+    /// Limits the total weight of the commands in the current branch to `max`,
+    /// see [`RecordBuilder::limit_by`].
+    ///
+    /// [`RecordBuilder::limit_by`]: struct.RecordBuilder.html#method.limit_by
+    #[inline]
+    pub fn limit_by(
+        mut self,
+        weigh: impl Fn(&C) -> usize + Send + Sync + 'static,
+        max: usize,
+    ) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.limit_by(weigh, max);
+        self
+    }
+
+    /// Sets if the receiver is initially in a saved state.
+    /// By default the receiver is in a saved state.
+    #[inline]
+    pub fn saved(mut self, saved: bool) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.saved(saved);
+        self
+    }
+
+    /// Sets the time within which a command must be applied after the previous command
+    /// for them to be merged, see [`RecordBuilder::merge_within`].
+    ///
+    /// [`RecordBuilder::merge_within`]: struct.RecordBuilder.html#method.merge_within
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn merge_within(mut self, duration: Duration) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.merge_within(duration);
+        self
+    }
+
+    /// Sets the clock that is used for the timestamps of the commands,
+    /// see [`RecordBuilder::clock`].
+    ///
+    /// [`RecordBuilder::clock`]: struct.RecordBuilder.html#method.clock
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn clock(mut self, clock: impl Clock + 'static) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.clock(clock);
+        self
+    }
+
+    /// Sets if commands can be merged into the command at the saved state,
+    /// see [`RecordBuilder::merge_across_saved`].
+    ///
+    /// [`RecordBuilder::merge_across_saved`]: struct.RecordBuilder.html#method.merge_across_saved
+    #[inline]
+    pub fn merge_across_saved(mut self, on: bool) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.merge_across_saved(on);
+        self
+    }
+
+    /// Takes a snapshot of the receiver every `n` commands, see [`RecordBuilder::snapshot_every`].
+    ///
+    /// [`RecordBuilder::snapshot_every`]: struct.RecordBuilder.html#method.snapshot_every
+    #[inline]
+    pub fn snapshot_every<S: Send + Sync + 'static>(
+        mut self,
+        n: usize,
+        save: impl Fn(&R) -> S + Send + Sync + 'static,
+        restore: impl Fn(&S, &mut R) + Send + Sync + 'static,
+    ) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.snapshot_every(n, save, restore);
+        self
+    }
+
+    /// Sets a hook that is called with the command and the receiver before the command is applied,
+    /// see [`RecordBuilder::on_before_apply`].
+    ///
+    /// [`RecordBuilder::on_before_apply`]: struct.RecordBuilder.html#method.on_before_apply
+    #[inline]
+    pub fn on_before_apply(
+        mut self,
+        f: impl Fn(&C, &R) + Send + Sync + 'static,
+    ) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.on_before_apply(f);
+        self
+    }
+
+    /// Sets a hook that is called with the command and the receiver after the command is applied,
+    /// see [`RecordBuilder::on_after_apply`].
+    ///
+    /// [`RecordBuilder::on_after_apply`]: struct.RecordBuilder.html#method.on_after_apply
+    #[inline]
+    pub fn on_after_apply(
+        mut self,
+        f: impl Fn(&C, &R) + Send + Sync + 'static,
+    ) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.on_after_apply(f);
+        self
+    }
+
+    /// Sets a hook that is called with the command and the receiver before the command is undone,
+    /// see [`RecordBuilder::on_before_undo`].
+    ///
+    /// [`RecordBuilder::on_before_undo`]: struct.RecordBuilder.html#method.on_before_undo
+    #[inline]
+    pub fn on_before_undo(
+        mut self,
+        f: impl Fn(&C, &R) + Send + Sync + 'static,
+    ) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.on_before_undo(f);
+        self
+    }
+
+    /// Sets a hook that is called with the command and the receiver after the command is undone,
+    /// see [`RecordBuilder::on_after_undo`].
+    ///
+    /// [`RecordBuilder::on_after_undo`]: struct.RecordBuilder.html#method.on_after_undo
+    #[inline]
+    pub fn on_after_undo(
+        mut self,
+        f: impl Fn(&C, &R) + Send + Sync + 'static,
+    ) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.on_after_undo(f);
+        self
+    }
+
+    /// Sets a hook that is called with the command and the receiver before the command is redone,
+    /// see [`RecordBuilder::on_before_redo`].
+    ///
+    /// [`RecordBuilder::on_before_redo`]: struct.RecordBuilder.html#method.on_before_redo
+    #[inline]
+    pub fn on_before_redo(
+        mut self,
+        f: impl Fn(&C, &R) + Send + Sync + 'static,
+    ) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.on_before_redo(f);
+        self
+    }
+
+    /// Sets a hook that is called with the command and the receiver after the command is redone,
+    /// see [`RecordBuilder::on_after_redo`].
+    ///
+    /// [`RecordBuilder::on_after_redo`]: struct.RecordBuilder.html#method.on_after_redo
+    #[inline]
+    pub fn on_after_redo(
+        mut self,
+        f: impl Fn(&C, &R) + Send + Sync + 'static,
+    ) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.on_after_redo(f);
+        self
+    }
+
+    /// Sets the initial commands of the root branch and the position of the current command,
+    /// see [`RecordBuilder::entries`].
+    ///
+    /// # Panics
+    /// Panics when the history is built if `current` is greater than the number of commands,
+    /// or if the number of commands is greater than the limit.
+    ///
+    /// [`RecordBuilder::entries`]: struct.RecordBuilder.html#method.entries
+    #[inline]
+    pub fn entries(
+        mut self,
+        commands: impl IntoIterator<Item = C>,
+        current: usize,
+    ) -> HistoryBuilder<R, C> {
+        self.inner = self.inner.entries(commands, current);
+        self
+    }
+
+    /// Builds the history.
+    #[inline]
+    pub fn build(self, receiver: impl Into<R>) -> History<R, C> {
+        History {
+            root: 0,
+            next: 1,
+            saved: None,
+            record: self.inner.build(receiver),
+            branches: Map::default(),
+            meta: Map::default(),
+            names: BTreeMap::new(),
+            fork: None,
+            branch_limit: self.branch_limit,
+            visited: Vec::new(),
+            on_drop: DropHook::default(),
+        }
+    }
+
+    /// Builds the history, or returns an error if the initial commands are invalid,
+    /// see [`RecordBuilder::try_build`].
+    ///
+    /// # Errors
+    /// Returns the same errors as [`RecordBuilder::try_build`].
+    ///
+    /// [`RecordBuilder::try_build`]: struct.RecordBuilder.html#method.try_build
+    #[inline]
+    pub fn try_build(self, receiver: impl Into<R>) -> Result<History<R, C>, ConfigError> {
+        self.inner.check()?;
+        Ok(self.build(receiver))
+    }
+
+    /// Builds the history with the slot.
+    #[inline]
+    pub fn build_with<F: FnMut(Signal)>(self, receiver: impl Into<R>, slot: F) -> History<R, C, F> {
+        History {
+            root: 0,
+            next: 1,
+            saved: None,
+            record: self.inner.build_with(receiver, slot),
+            branches: Map::default(),
+            meta: Map::default(),
+            names: BTreeMap::new(),
+            fork: None,
+            branch_limit: self.branch_limit,
+            visited: Vec::new(),
+            on_drop: DropHook::default(),
+        }
+    }
+}
+
+impl<R, C> Default for HistoryBuilder<R, C> {
+    #[inline]
+    fn default() -> Self {
+        HistoryBuilder::new()
+    }
+}
+
+impl<R: Default, C> HistoryBuilder<R, C> {
+    /// Creates the history with a default `receiver`.
+    #[inline]
+    pub fn default(self) -> History<R, C> {
+        self.build(R::default())
+    }
+
+    /// Creates the history with a default `receiver`.
+    #[inline]
+    pub fn default_with<F: FnMut(Signal)>(self, slot: F) -> History<R, C, F> {
+        self.build_with(R::default(), slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "serde")]
+    use crate::value;
+    use crate::{
+        Branch, Command, ConfigError, Direction, History, Merge, PathStep, Position, Signal,
+    };
+    use std::{
+        fmt,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    struct Add(char);
+
+    impl fmt::Display for Add {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl Command<String> for Add {
+        type Error = &'static str;
+
+        fn apply(&mut self, receiver: &mut String) -> Result<(), Self::Error> {
+            receiver.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, receiver: &mut String) -> Result<(), Self::Error> {
+            self.0 = receiver.pop().ok_or("`receiver` is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn go_to() {
+        //          m
+        //          |
+        //    j  k  l
+        //     \ | /
+        //       i
+        //       |
+        // e  g  h
+        // |  | /
+        // d  f  p - q *
+        // | /  /
+        // c  n - o
+        // | /
+        // b
+        // |
+        // a
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.apply(Add('c')).unwrap();
+        history.apply(Add('d')).unwrap();
+        history.apply(Add('e')).unwrap();
+        assert_eq!(history.as_receiver(), "abcde");
+        history.undo().unwrap().unwrap();
+        history.undo().unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abc");
+        let abcde = history.branch();
+        history.apply(Add('f')).unwrap();
+        history.apply(Add('g')).unwrap();
+        assert_eq!(history.as_receiver(), "abcfg");
+        history.undo().unwrap().unwrap();
+        let abcfg = history.branch();
+        history.apply(Add('h')).unwrap();
+        history.apply(Add('i')).unwrap();
+        history.apply(Add('j')).unwrap();
+        assert_eq!(history.as_receiver(), "abcfhij");
+        history.undo().unwrap().unwrap();
+        let abcfhij = history.branch();
+        history.apply(Add('k')).unwrap();
+        assert_eq!(history.as_receiver(), "abcfhik");
+        history.undo().unwrap().unwrap();
+        let abcfhik = history.branch();
+        history.apply(Add('l')).unwrap();
+        assert_eq!(history.as_receiver(), "abcfhil");
+        history.apply(Add('m')).unwrap();
+        assert_eq!(history.as_receiver(), "abcfhilm");
+        let abcfhilm = history.branch();
+        history.go_to(abcde, 2).unwrap().unwrap();
+        history.apply(Add('n')).unwrap();
+        history.apply(Add('o')).unwrap();
+        assert_eq!(history.as_receiver(), "abno");
+        history.undo().unwrap().unwrap();
+        let abno = history.branch();
+        history.apply(Add('p')).unwrap();
+        history.apply(Add('q')).unwrap();
+        assert_eq!(history.as_receiver(), "abnpq");
+
+        let abnpq = history.branch();
+        history.go_to(abcde, 5).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abcde");
+        history.go_to(abcfg, 5).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abcfg");
+        history.go_to(abcfhij, 7).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abcfhij");
+        history.go_to(abcfhik, 7).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abcfhik");
+        history.go_to(abcfhilm, 8).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abcfhilm");
+        history.go_to(abno, 4).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abno");
+        history.go_to(abnpq, 5).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abnpq");
+    }
+
+    // The time is a `SystemTime` without chrono or time, which makes the conversions redundant.
+    #[test]
+    #[cfg(feature = "std")]
+    #[allow(clippy::useless_conversion)]
+    fn eq() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let history = |secs| {
+            let mut history = History::builder()
+                .clock(move || (UNIX_EPOCH + Duration::from_secs(secs)).into())
+                .default();
+            for c in "abc".chars() {
+                history.apply(Add(c)).unwrap();
+            }
+            history.undo().unwrap().unwrap();
+            history.apply(Add('d')).unwrap();
+            history
+        };
+        // The commands were applied at different times, which is ignored.
+        assert_eq!(history(1), history(2));
+        let mut other = history(1);
+        other.undo().unwrap().unwrap();
+        assert_ne!(history(1), other);
+    }
+
+    #[test]
+    fn renumber() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('b')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('c')).unwrap();
+        history.apply(Add('d')).unwrap();
+        // Removes the `a` and `b` branches.
+        history.set_limit(1);
+        history.undo().unwrap().unwrap();
+        history.apply(Add('e')).unwrap();
+        assert_eq!(history.branch(), 3);
+        let map = history.renumber();
+        assert_eq!(map.into_iter().collect::<Vec<_>>(), [(2, 0), (3, 1)]);
+        assert_eq!(history.branch(), 1);
+        history.go_to(0, 1).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "cd");
+        history.go_to(1, 1).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "ce");
+        history.apply(Add('f')).unwrap();
+        assert_eq!(history.branch(), 1);
+        history.undo().unwrap().unwrap();
+        history.apply(Add('g')).unwrap();
+        assert_eq!(history.branch(), 2);
+    }
+
+    #[test]
+    fn squash_branch() {
+        let mut history = History::default();
+        history.extend("abc".chars().map(Add)).unwrap();
+        let main = history.branch();
+        history.go_to(main, 1).unwrap().unwrap();
+        history.extend("xy".chars().map(Add)).unwrap();
+        let experiment = history.branch();
+        history.set_saved(true);
+        history.undo().unwrap().unwrap();
+        history.apply(Add('z')).unwrap();
+        let side = history.branch();
+        history.go_to(main, 3).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abc");
+        assert!(history.squash_branch(main).is_none());
+        history.squash_branch(side).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abcxz");
+        assert_eq!(history.branch(), main);
+        assert_eq!(history.branches().count(), 1);
+        assert_eq!(history.get_branch(experiment).unwrap().parent(), main);
+        assert_eq!(history.get_branch(experiment).unwrap().start(), 4);
+        history.go_to_saved().unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abcxy");
+        assert!(history.is_saved());
+        history.squash_branch(main).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abcxyz");
+        assert_eq!(history.branches().count(), 0);
+    }
+
+    #[test]
+    fn squash_branch_limit() {
+        let mut history = History::builder().limit(2).default();
+        history.apply(Add('a')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('q')).unwrap();
+        let q = history.branch();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        let main = history.branch();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('x')).unwrap();
+        let x = history.branch();
+        history.go_to(main, 2).unwrap().unwrap();
+        assert_eq!(history.get_branch(q).unwrap().start(), 0);
+        // The `a` command is removed, so the branch that diverges before it is removed too.
+        history.squash_branch(x).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abx");
+        assert_eq!(history.len(), 2);
+        assert!(history.get_branch(q).is_none());
+        assert!(history.go_to(q, 1).is_none());
+        assert_eq!(history.branches().count(), 0);
+    }
+
+    #[test]
+    fn named_branches() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        assert!(history.name_branch(history.branch(), "draft"));
+        assert!(!history.name_branch(100, "missing"));
+        history.undo().unwrap().unwrap();
+        history.apply(Add('c')).unwrap();
+        history.apply(Add('d')).unwrap();
+        assert!(history.name_branch(history.branch(), "experiment"));
+        history.switch_to("draft").unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "ab");
+        assert_eq!(history.branch_name(history.branch()), Some("draft"));
+        history.switch_to("experiment").unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "acd");
+        assert!(history.switch_to("missing").is_none());
+        let experiment = history.branch();
+        assert!(history.name_branch(experiment, "final"));
+        assert_eq!(history.branch_by_name("experiment"), None);
+        assert_eq!(
+            history.unname_branch(experiment),
+            Some(String::from("final"))
+        );
+        assert_eq!(history.branch_name(experiment), None);
+        // Removing the branch removes its name.
+        history.set_limit(1);
+        assert_eq!(history.branch_by_name("draft"), None);
+    }
+
+    #[test]
+    fn branch_meta() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        let ab = history.branch();
+        history.set_branch_meta(ab, "color", "red");
+        history.undo().unwrap().unwrap();
+        history.apply(Add('c')).unwrap();
+        let ac = history.branch();
+        assert_eq!(history.set_branch_meta(ac, "color", "blue"), None);
+        assert_eq!(
+            history.set_branch_meta(ac, "color", "green"),
+            Some("blue".into())
+        );
+        assert_eq!(history.set_branch_meta(100, "color", "red"), None);
+        assert_eq!(history.branch_meta(ab, "color"), Some("red"));
+        assert_eq!(history.branch_meta(ac, "color"), Some("green"));
+        assert_eq!(history.branch_meta(100, "color"), None);
+        assert_eq!(
+            history.remove_branch_meta(ac, "color"),
+            Some("green".into())
+        );
+        assert_eq!(history.branch_meta(ac, "color"), None);
+        // Removing the branch removes its metadata.
+        history.set_limit(1);
+        history.apply(Add('d')).unwrap();
+        assert_eq!(history.branch_meta(ab, "color"), None);
+    }
+
+    #[test]
+    fn retain() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.apply(Add('c')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        let abc = 0;
+        history.retain(|add| add.0 != 'a').unwrap();
+        assert_eq!(history.as_receiver(), "bd");
+        history.go_to(abc, 2).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "bc");
+    }
+
+    /// Removes its own char when undone, and can no longer be undone when locked.
+    struct Lockable(char, Arc<AtomicBool>);
+
+    impl Command<String> for Lockable {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            let index = s.rfind(self.0).ok_or("`s` does not contain the char")?;
+            s.remove(index);
+            Ok(())
+        }
+
+        fn undoable(&self, _: &String) -> bool {
+            !self.1.load(Ordering::SeqCst)
+        }
+    }
+
+    #[test]
+    fn expired_moves_branches() {
+        let locked = Arc::new(AtomicBool::new(false));
+        let mut history = History::default();
+        history.apply(Lockable('a', Arc::default())).unwrap();
+        history.apply(Lockable('b', Arc::clone(&locked))).unwrap();
+        history.apply(Lockable('c', Arc::default())).unwrap();
+        history.apply(Lockable('d', Arc::default())).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Lockable('x', Arc::default())).unwrap();
+        history.go_to(0, 4).unwrap().unwrap();
+        history.set_saved(true);
+        history.go_to(1, 4).unwrap().unwrap();
+        // Lock `b`, which is removed when it is reached, but leaves its change behind.
+        locked.store(true, Ordering::SeqCst);
+        for _ in 0..3 {
+            history.undo().unwrap().unwrap();
+        }
+        assert_eq!(history.as_receiver(), "b");
+        assert_eq!(history.len(), 3);
+        // The branch of `d` now forks after `a` and `c`, and its saved state is gone.
+        assert_eq!(history.get_branch(0).unwrap().parent, Position::new(1, 2));
+        assert_eq!(history.saved(), None);
+        assert!(history.go_to(0, 4).is_none());
+        assert_eq!(history.as_receiver(), "b");
+        history.go_to(0, 3).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "bacd");
+        assert_eq!(history.position(), Position::new(0, 3));
+    }
+
+    #[test]
+    fn encode_position() {
+        let mut history = History::default();
         history.apply(Add('a')).unwrap();
         history.apply(Add('b')).unwrap();
         history.apply(Add('c')).unwrap();
+        let abc = history.encode_position();
+        history.undo().unwrap().unwrap();
         history.apply(Add('d')).unwrap();
-        history.apply(Add('e')).unwrap();
-        assert_eq!(history.as_receiver(), "abcde");
+        let abd = history.encode_position();
+        assert_ne!(abc, abd);
+        let position = history.decode_position(&abc).unwrap();
+        history.go_to_position(position).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abc");
+        let position = history.decode_position(&abd).unwrap();
+        history.go_to_position(position).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abd");
+        let root = history.decode_position("@").unwrap();
+        history.go_to_position(root).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "");
+        assert_eq!(history.decode_position("@9"), None);
+        assert_eq!(history.decode_position("0:1"), None);
+        assert_eq!(history.decode_position("@a"), None);
+    }
+
+    #[test]
+    fn encode_position_renumbered() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('b')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('c')).unwrap();
+        let c = history.encode_position();
+        history.go_to(1, 1).unwrap().unwrap();
+        let b = history.encode_position();
+        // Dropping the branch of `a` makes the branch of `b` get a new id.
+        let a = history
+            .branches()
+            .find(|(_, branch)| branch.len() == 1)
+            .unwrap()
+            .0;
+        history.remove_branch(a);
+        assert!(!history.renumber().is_empty());
+        let go_to = |history: &mut History<String, Add>, encoded: &str| {
+            let position = history.decode_position(encoded).unwrap();
+            history.go_to_position(position).unwrap().unwrap();
+            history.as_receiver().clone()
+        };
+        assert_eq!(go_to(&mut history, &c), "c");
+        assert_eq!(go_to(&mut history, &b), "b");
+        #[cfg(feature = "serde")]
+        {
+            let mut restored: History<String, Add> =
+                value::from_value(value::to_value(&history)).unwrap();
+            assert_eq!(go_to(&mut restored, &c), "c");
+            assert_eq!(go_to(&mut restored, &b), "b");
+        }
+    }
+
+    #[test]
+    fn position() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        let ab = history.position();
+        assert_eq!(ab.to_string().parse(), Ok(ab));
+        let encoded = history.encode_position();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('c')).unwrap();
+        let ac = history.position();
+        assert_ne!(ab, ac);
+        assert_eq!(history.decode_position(&encoded), Some(ab));
+        #[cfg(feature = "checkpoint")]
+        {
+            let mut checkpoint = history.checkpoint();
+            checkpoint
+                .go_to(ab.branch(), ab.current())
+                .unwrap()
+                .unwrap();
+            checkpoint.cancel().unwrap();
+            assert_eq!(history.position(), ac);
+        }
+        history.go_to_position(ab).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "ab");
+        assert_eq!(history.position(), Position::from((ab.branch(), 2)));
+    }
+
+    #[test]
+    fn branches() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.apply(Add('c')).unwrap();
         history.undo().unwrap().unwrap();
         history.undo().unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        assert_eq!(history.branches().count(), 1);
+        let (id, branch) = history.branches().next().unwrap();
+        assert_eq!(branch.parent(), history.branch());
+        assert_eq!(branch.start(), 1);
+        assert_eq!(branch.range(), 2..4);
+        assert_eq!(branch.commands().map(|add| add.0).collect::<String>(), "bc");
+        assert!(history.get_branch(history.branch()).is_none());
+        history.go_to(id, 3).unwrap().unwrap();
         assert_eq!(history.as_receiver(), "abc");
-        let abcde = history.branch();
+    }
+
+    #[test]
+    fn annul_into_branch() {
+        struct Edit(char, Option<char>);
+
+        impl Command<String> for Edit {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                match self.0 {
+                    '-' => self.1 = s.pop(),
+                    c => s.push(c),
+                }
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                match self.0 {
+                    '-' => s.extend(self.1.take()),
+                    _ => drop(s.pop()),
+                }
+                Ok(())
+            }
+
+            fn merge(&mut self, edit: Self) -> Merge<Self> {
+                if self.0 != '-' && edit.0 == '-' {
+                    Merge::Annul
+                } else {
+                    Merge::No(edit)
+                }
+            }
+        }
+
+        let mut history = History::default();
+        history.apply(Edit('a', None)).unwrap();
+        history.apply(Edit('b', None)).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Edit('-', None)).unwrap();
+        assert_eq!(history.as_receiver(), "");
+        assert_eq!(history.len(), 0);
+        let (b, branch) = history.branches().next().unwrap();
+        assert_eq!(branch.start(), 0);
+        history.go_to(b, 1).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "b");
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn path_between() {
+        let mut history = History::default();
+        for c in "abcd".chars() {
+            history.apply(Add(c)).unwrap();
+        }
+        history.go_to(0, 2).unwrap().unwrap();
+        history.apply(Add('e')).unwrap();
         history.apply(Add('f')).unwrap();
-        history.apply(Add('g')).unwrap();
-        assert_eq!(history.as_receiver(), "abcfg");
         history.undo().unwrap().unwrap();
-        let abcfg = history.branch();
+        history.apply(Add('g')).unwrap();
+        history.go_to(0, 3).unwrap().unwrap();
         history.apply(Add('h')).unwrap();
+        history.go_to(0, 1).unwrap().unwrap();
         history.apply(Add('i')).unwrap();
+        let mut positions = Vec::new();
+        let branches: Vec<_> = history
+            .branches()
+            .map(|(id, b)| (id, b.start() + b.len()))
+            .collect();
+        for (branch, len) in branches
+            .into_iter()
+            .chain([(history.branch(), history.len())])
+        {
+            positions.extend((0..=len).map(|current| Position::new(branch, current)));
+        }
+        for &from in &positions {
+            for &to in &positions {
+                let steps: Vec<_> = history
+                    .path_between(from, to)
+                    .unwrap()
+                    .into_iter()
+                    .map(|step| match step {
+                        Direction::Undo(add) => Direction::Undo(add.0),
+                        Direction::Redo(add) => Direction::Redo(add.0),
+                    })
+                    .collect();
+                history.go_to_position(from).unwrap().unwrap();
+                let mut s = history.as_receiver().clone();
+                for step in &steps {
+                    match step {
+                        Direction::Undo(c) => assert_eq!(s.pop(), Some(*c)),
+                        Direction::Redo(c) => s.push(*c),
+                    }
+                }
+                history.go_to_position(to).unwrap().unwrap();
+                assert_eq!(&s, history.as_receiver());
+            }
+        }
+        assert_eq!(
+            history
+                .path_between(Position::new(0, 9), history.position())
+                .map(|steps| steps.len()),
+            None
+        );
+    }
+
+    #[test]
+    fn get() {
+        let mut history = History::default();
+        for c in "abc".chars() {
+            history.apply(Add(c)).unwrap();
+        }
+        history.go_to(0, 1).unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        let (old, _) = history.branches().next().unwrap();
+        let get = |branch, index| history.get(branch, index).map(|add| add.0);
+        assert_eq!(get(history.branch(), 0), Some('a'));
+        assert_eq!(get(history.branch(), 1), Some('d'));
+        assert_eq!(get(history.branch(), 2), None);
+        assert_eq!(get(old, 0), Some('a'));
+        assert_eq!(get(old, 2), Some('c'));
+        assert_eq!(get(old, 3), None);
+        assert_eq!(get(99, 0), None);
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut history = History::default();
+        history.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        let abc = history.branch();
+        history.undo().unwrap().unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        let ad = history.branch();
+        let borrowed: Vec<_> = (&history).into_iter().map(|(b, add)| (b, add.0)).collect();
+        assert_eq!(borrowed, [(ad, 'a'), (ad, 'd'), (abc, 'b'), (abc, 'c')]);
+        assert_eq!((&history).into_iter().len(), 4);
+        let owned: Vec<_> = history.into_iter().map(|(b, add)| (b, add.0)).collect();
+        assert_eq!(owned, borrowed);
+    }
+
+    #[test]
+    fn prune() {
+        let mut history = History::default();
+        history.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        history.undo().unwrap().unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        let bc = history.branches().next().unwrap().0;
+        history.go_to(bc, 2).unwrap().unwrap();
+        history.apply(Add('e')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('f')).unwrap();
+        assert_eq!(history.branches().count(), 3);
+        let be = history.branches().find(|(_, b)| b.len() == 2).unwrap().0;
+        assert!(history.remove_branch(history.branch()).is_none());
+        let removed = history.remove_branch(be).unwrap();
+        assert_eq!(removed.iter().map(|add| add.0).collect::<String>(), "bec");
+        assert!(history.remove_branch(be).is_none());
+        assert_eq!(history.branches().count(), 1);
+        history.undo().unwrap().unwrap();
+        history.apply(Add('g')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('h')).unwrap();
+        assert_eq!(history.branches().count(), 3);
+        let removed = history.prune();
+        assert_eq!(removed.iter().map(|add| add.0).collect::<String>(), "dfg");
+        assert_eq!(history.branches().count(), 0);
+        assert_eq!(history.as_receiver(), "ah");
+    }
+
+    #[test]
+    fn path() {
+        let mut history = History::default();
+        history.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        history.undo().unwrap().unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        history.apply(Add('e')).unwrap();
+        history.undo().unwrap().unwrap();
+        let path: Vec<_> = history
+            .path()
+            .map(|step| match step {
+                PathStep::Fork { position, branches } => format!("{}{:?}", position, branches),
+                PathStep::Entry(entry) => entry.command().0.to_string(),
+            })
+            .collect();
+        let bc = history.branches().next().unwrap().0;
+        assert_eq!(path, ["a".into(), format!("1[{}]", bc), "d".into()]);
+    }
+
+    #[test]
+    fn to_dot() {
+        let mut history = History::default();
+        history.extend(vec![Add('a'), Add('b')]).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('"')).unwrap();
+        let b = history.branches().next().unwrap().0;
+        assert_eq!(
+            history.to_dot(),
+            format!(
+                "digraph history {{\n    \
+                 root [label=\"(saved)\"];\n    \
+                 \"{0}:1\" [label=\"a\"];\n    \
+                 root -> \"{0}:1\";\n    \
+                 \"{0}:2\" [label=\"\\\" (current)\"];\n    \
+                 \"{0}:1\" -> \"{0}:2\";\n    \
+                 \"{1}:2\" [label=\"b\"];\n    \
+                 \"{0}:1\" -> \"{1}:2\";\n\
+                 }}\n",
+                history.branch(),
+                b
+            )
+        );
+    }
+
+    #[test]
+    fn can_redo_deep() {
+        let mut history = History::default();
+        assert!(!history.can_undo_deep());
+        assert!(!history.can_redo_deep());
+        history.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        history.go_to(0, 1).unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        assert!(history.can_undo_deep());
+        assert!(!history.can_redo_deep());
+        // The branch with `b` and `c` diverges at the current position once `d` is removed.
+        history.retain(|add| add.0 != 'd').unwrap();
+        assert_eq!(history.as_receiver(), "a");
+        assert!(!history.can_redo());
+        assert!(history.can_redo_deep());
+        history.prune();
+        assert!(!history.can_redo_deep());
+    }
+
+    #[test]
+    fn on_branch_drop() {
+        let archive = Arc::new(Mutex::new(Vec::new()));
+        let mut history = History::builder().limit(3).default();
+        let sink = Arc::clone(&archive);
+        history.on_branch_drop(move |_, branch: &Branch<Add>| {
+            let commands = branch.commands().map(|add| add.0).collect::<String>();
+            sink.lock().unwrap().push(commands);
+        });
+        history.extend(vec![Add('a'), Add('b')]).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('c')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        history.prune();
+        assert_eq!(*archive.lock().unwrap(), ["ac", "b"]);
+        history.extend(vec![Add('e'), Add('f')]).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('g')).unwrap();
+        // The branch with `f` is dropped when the commands before it are removed by the limit.
+        history.extend(vec![Add('h'), Add('i')]).unwrap();
+        assert_eq!(*archive.lock().unwrap(), ["ac", "b"]);
         history.apply(Add('j')).unwrap();
-        assert_eq!(history.as_receiver(), "abcfhij");
+        assert_eq!(*archive.lock().unwrap(), ["ac", "b", "f"]);
         history.undo().unwrap().unwrap();
-        let abcfhij = history.branch();
         history.apply(Add('k')).unwrap();
-        assert_eq!(history.as_receiver(), "abcfhik");
+        history.clear();
+        assert_eq!(*archive.lock().unwrap(), ["ac", "b", "f", "j"]);
+    }
+
+    #[test]
+    fn branch_limit() {
+        let signals = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&signals);
+        let mut history = History::builder()
+            .branch_limit(2)
+            .build_with(String::new(), move |signal| {
+                sink.lock().unwrap().push(signal)
+            });
+        history.apply(Add('a')).unwrap();
+        let a = history.branch();
         history.undo().unwrap().unwrap();
-        let abcfhik = history.branch();
-        history.apply(Add('l')).unwrap();
-        assert_eq!(history.as_receiver(), "abcfhil");
-        history.apply(Add('m')).unwrap();
-        assert_eq!(history.as_receiver(), "abcfhilm");
-        let abcfhilm = history.branch();
-        history.go_to(abcde, 2).unwrap().unwrap();
-        history.apply(Add('n')).unwrap();
-        history.apply(Add('o')).unwrap();
-        assert_eq!(history.as_receiver(), "abno");
+        history.apply(Add('b')).unwrap();
+        let b = history.branch();
         history.undo().unwrap().unwrap();
-        let abno = history.branch();
-        history.apply(Add('p')).unwrap();
-        history.apply(Add('q')).unwrap();
-        assert_eq!(history.as_receiver(), "abnpq");
+        history.apply(Add('c')).unwrap();
+        let c = history.branch();
+        assert_eq!(history.branches().count(), 2);
+        // Visiting `a` makes `b` the least recently visited branch.
+        history.go_to(a, 1).unwrap().unwrap();
+        signals.lock().unwrap().clear();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        assert_eq!(history.as_receiver(), "d");
+        assert!(history.get_branch(a).is_some());
+        assert!(history.get_branch(b).is_none());
+        assert!(history.get_branch(c).is_some());
+        assert_eq!(
+            signals.lock().unwrap().last(),
+            Some(&Signal::BranchEvicted(b))
+        );
+    }
 
-        let abnpq = history.branch();
-        history.go_to(abcde, 5).unwrap().unwrap();
-        assert_eq!(history.as_receiver(), "abcde");
-        history.go_to(abcfg, 5).unwrap().unwrap();
-        assert_eq!(history.as_receiver(), "abcfg");
-        history.go_to(abcfhij, 7).unwrap().unwrap();
-        assert_eq!(history.as_receiver(), "abcfhij");
-        history.go_to(abcfhik, 7).unwrap().unwrap();
-        assert_eq!(history.as_receiver(), "abcfhik");
-        history.go_to(abcfhilm, 8).unwrap().unwrap();
-        assert_eq!(history.as_receiver(), "abcfhilm");
-        history.go_to(abno, 4).unwrap().unwrap();
-        assert_eq!(history.as_receiver(), "abno");
-        history.go_to(abnpq, 5).unwrap().unwrap();
-        assert_eq!(history.as_receiver(), "abnpq");
+    #[test]
+    fn limit_by() {
+        let weigh = |add: &Add| if add.0 == 'x' { 3 } else { 1 };
+        let mut history = History::builder().limit_by(weigh, 3).default();
+        history.extend(vec![Add('a'), Add('b')]).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('c')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        history.apply(Add('e')).unwrap();
+        assert_eq!(history.branches().count(), 2);
+        history.apply(Add('x')).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.branches().count(), 0);
+        assert_eq!(history.as_receiver(), "dex");
+        history.undo().unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "de");
+    }
+
+    #[test]
+    fn go_to_saved() {
+        let mut history = History::default();
+        history.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        history.undo().unwrap().unwrap();
+        history.set_saved(true);
+        let ab = history.branch();
+        assert_eq!(history.saved(), Some((ab, 2)));
+        history.undo().unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        assert_eq!(history.saved(), Some((ab, 2)));
+        assert_ne!(history.branch(), ab);
+        history.go_to_saved().unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "ab");
+        assert!(history.is_saved());
+        assert_eq!(history.saved(), Some((history.branch(), 2)));
+        history.set_saved(false);
+        assert_eq!(history.saved(), None);
+        assert!(history.go_to_saved().is_none());
+    }
+
+    #[test]
+    fn unsaved_count() {
+        let mut history = History::default();
+        assert!(history.unsaved_count().unwrap().is_saved());
+        history.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        history.undo().unwrap().unwrap();
+        history.set_saved(true);
+        assert!(history.unsaved_count().unwrap().is_saved());
+        history.undo().unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        history.apply(Add('e')).unwrap();
+        let unsaved = history.unsaved_count().unwrap();
+        assert_eq!((unsaved.undo(), unsaved.redo()), (2, 1));
+        history.go_to(history.branch(), 0).unwrap().unwrap();
+        let unsaved = history.unsaved_count().unwrap();
+        assert_eq!((unsaved.undo(), unsaved.redo()), (0, 2));
+        history.go_to_saved().unwrap().unwrap();
+        assert_eq!(history.unsaved_count().unwrap().count(), 0);
+        history.set_saved(false);
+        assert_eq!(history.unsaved_count(), None);
+    }
+
+    #[test]
+    fn fuzz() {
+        // Uses the same generator as the fuzz test of the record, with another seed.
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % n as u64) as usize
+        };
+        for _ in 0..100 {
+            let mut history = History::builder().limit(1 + next(5)).default();
+            for _ in 0..100 {
+                match next(6) {
+                    0 | 1 => history.apply(Add('a')).unwrap(),
+                    2 => {
+                        history.undo();
+                    }
+                    3 => {
+                        history.redo();
+                    }
+                    4 => history.set_saved(next(2) == 0),
+                    _ => {
+                        let ends: Vec<_> = history
+                            .branches()
+                            .map(|(id, branch)| (id, branch.range().end - 1))
+                            .collect();
+                        if !ends.is_empty() {
+                            let (branch, current) = ends[next(ends.len())];
+                            history.go_to(branch, current).unwrap().unwrap();
+                        }
+                    }
+                }
+                assert!(history.current() <= history.len());
+                assert!(history.len() <= history.limit());
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde() {
+        let mut history = History::default();
+        for c in "abcde".chars() {
+            history.apply(Add(c)).unwrap();
+        }
+        history.go_to(0, 2).unwrap().unwrap();
+        history.set_saved(true);
+        history.apply(Add('f')).unwrap();
+        history.apply(Add('g')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('h')).unwrap();
+        let value = value::to_value(&history);
+        let mut restored: History<String, Add> = value::from_value(value).unwrap();
+        assert_eq!(restored.as_receiver(), "abfh");
+        assert_eq!(restored.branch(), history.branch());
+        assert_eq!(restored.current(), history.current());
+        assert_eq!(restored.branches().count(), 2);
+        assert_eq!(restored.saved(), history.saved());
+        #[cfg(feature = "std")]
+        {
+            let timestamps = |history: &History<String, Add>| {
+                history
+                    .entries()
+                    .map(|entry| entry.timestamp())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(timestamps(&restored), timestamps(&history));
+        }
+        // Undo back into the old branches of the deserialized history.
+        let branch = |history: &History<String, Add>, len| {
+            history.branches().find(|(_, b)| b.len() == len).unwrap().0
+        };
+        let old = branch(&restored, 3);
+        restored.go_to(old, 5).unwrap().unwrap();
+        assert_eq!(restored.as_receiver(), "abcde");
+        assert!(!restored.is_saved());
+        restored.go_to_saved().unwrap().unwrap();
+        assert_eq!(restored.as_receiver(), "ab");
+        assert!(restored.is_saved());
+        let old = branch(&restored, 1);
+        restored.go_to(old, 4).unwrap().unwrap();
+        assert_eq!(restored.as_receiver(), "abfg");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn deserialize_without_ids() {
+        let mut history = History::default();
+        history.extend("abc".chars().map(Add)).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        let old = history.branches().next().unwrap().0;
+        let mut value = value::to_value(&history);
+        for key in ["id", "merged", "next_id"] {
+            value.remove(key);
+        }
+        let mut restored: History<String, Add> = value::from_value(value).unwrap();
+        restored.go_to(old, 3).unwrap().unwrap();
+        restored.apply(Add('e')).unwrap();
+        // The commands in every branch get their own ids.
+        let mut ids: Vec<_> = (0..4).map(|i| restored.id(i).unwrap()).collect();
+        let branch = restored.branches().next().unwrap().1;
+        ids.extend(branch.commands.iter().map(|entry| entry.id));
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), 5);
+    }
+
+    #[test]
+    fn go_to_restores_entries() {
+        struct Strict(char, Arc<Mutex<bool>>);
+
+        impl Command<String> for Strict {
+            type Error = &'static str;
+
+            fn apply(&mut self, receiver: &mut String) -> Result<(), Self::Error> {
+                receiver.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, receiver: &mut String) -> Result<(), Self::Error> {
+                receiver.pop().ok_or("`receiver` is empty")?;
+                Ok(())
+            }
+
+            fn validate(&self, _: &String) -> Result<(), Self::Error> {
+                if *self.1.lock().unwrap() {
+                    Err("invalid")
+                } else {
+                    Ok(())
+                }
+            }
+
+            fn is_noop(&self, _: &String) -> bool {
+                *self.1.lock().unwrap()
+            }
+        }
+
+        let strict = Arc::new(Mutex::new(false));
+        let mut history = History::default();
+        history.apply(Strict('a', Arc::clone(&strict))).unwrap();
+        history.apply(Strict('b', Arc::clone(&strict))).unwrap();
+        history.undo().unwrap().unwrap();
+        let ab = history.branch();
+        history.apply(Strict('c', Arc::clone(&strict))).unwrap();
+        let ac = history.branch();
+        *strict.lock().unwrap() = true;
+        // The commands in the branch are restored without being validated or skipped.
+        history.go_to(ab, 2).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "ab");
+        assert_eq!(history.len(), 2);
+        history.go_to(ac, 2).unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "ac");
+        assert_eq!(history.len(), 2);
+    }
+
+    #[test]
+    fn saved_before_fork() {
+        let mut history = History::default();
+        history.apply(Add('a')).unwrap();
+        history.set_saved(true);
+        history.apply(Add('b')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('c')).unwrap();
+        assert_eq!(history.validate(), Ok(()));
+        history.go_to(0, 2).unwrap().unwrap();
+        assert_eq!(history.validate(), Ok(()));
+        history.undo().unwrap().unwrap();
+        assert!(history.is_saved());
+    }
+
+    #[test]
+    fn validate() {
+        let mut history = History::default();
+        for c in "abc".chars() {
+            history.apply(Add(c)).unwrap();
+        }
+        history.go_to(0, 1).unwrap().unwrap();
+        history.apply(Add('d')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('e')).unwrap();
+        assert_eq!(history.validate(), Ok(()));
+        let ids: Vec<_> = history.branches().map(|(id, _)| id).collect();
+        let mut invalid = history.clone();
+        invalid.saved = Some(crate::Position {
+            branch: 99,
+            current: 0,
+        });
+        assert_eq!(invalid.validate(), Err(ConfigError::UnknownBranch(99)));
+        let mut invalid = history.clone();
+        invalid.branches.get_mut(&ids[0]).unwrap().parent.branch = ids[1];
+        invalid.branches.get_mut(&ids[1]).unwrap().parent.branch = ids[0];
+        assert!(matches!(
+            invalid.validate(),
+            Err(ConfigError::BranchCycle(_))
+        ));
+        let mut invalid = history.clone();
+        invalid.branches.get_mut(&ids[0]).unwrap().parent.current = 4;
+        assert_eq!(
+            invalid.validate(),
+            Err(ConfigError::ParentOutOfBounds {
+                branch: ids[0],
+                current: 4,
+                len: 2
+            })
+        );
+        let mut invalid = history.clone();
+        invalid.saved = Some(crate::Position {
+            branch: ids[0],
+            current: 4,
+        });
+        assert_eq!(
+            invalid.validate(),
+            Err(ConfigError::SavedOutOfBounds { saved: 4, len: 3 })
+        );
+        let mut invalid = history.clone();
+        invalid.saved = Some(crate::Position::new(ids[0], 1));
+        invalid.record.saved = Some(0);
+        assert_eq!(invalid.validate(), Err(ConfigError::DuplicateSaved));
+        #[cfg(feature = "serde")]
+        assert!(value::from_value::<History<String, Add>>(value::to_value(&invalid)).is_err());
+        let mut invalid = history.clone();
+        invalid.record.saved = None;
+        invalid.saved = Some(crate::Position::new(invalid.root, 1));
+        assert_eq!(invalid.validate(), Err(ConfigError::SavedInRoot));
+        let mut invalid = history.clone();
+        invalid.next -= 1;
+        assert_eq!(
+            invalid.validate(),
+            Err(ConfigError::BranchIdInUse(invalid.next))
+        );
+        #[cfg(feature = "serde")]
+        {
+            let error = value::from_value::<History<String, Add>>(value::to_value(&invalid));
+            assert!(error.is_err());
+            let restored = value::from_value::<History<String, Add>>(value::to_value(&history));
+            assert!(restored.is_ok());
+        }
     }
 }
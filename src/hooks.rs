@@ -0,0 +1,87 @@
+use crate::ignored::Ignored;
+use alloc::sync::Arc;
+use core::fmt;
+
+type HookFn<R, C> = dyn Fn(&C, &R) + Send + Sync;
+type EvictFn<C> = dyn Fn(C) + Send + Sync;
+type Hook<F> = Ignored<Option<Arc<F>>>;
+
+/// The hooks that are called before and after the commands are executed,
+/// and when the commands are evicted by the limit.
+///
+/// The hooks are shared when the record is cloned, and they are ignored when comparing
+/// and hashing records. Like the other callbacks that are stored in the record they must be
+/// `Send` and `Sync`, so the record can still be sent to and shared between threads.
+#[derive(Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub(crate) struct Hooks<R, C> {
+    pub(crate) before_apply: Hook<HookFn<R, C>>,
+    pub(crate) after_apply: Hook<HookFn<R, C>>,
+    pub(crate) before_undo: Hook<HookFn<R, C>>,
+    pub(crate) after_undo: Hook<HookFn<R, C>>,
+    pub(crate) before_redo: Hook<HookFn<R, C>>,
+    pub(crate) after_redo: Hook<HookFn<R, C>>,
+    pub(crate) evict: Hook<EvictFn<C>>,
+}
+
+impl<R, C> Hooks<R, C> {
+    /// Calls the evict hook with the command if it is set, otherwise the command is dropped.
+    #[inline]
+    pub(crate) fn evict(&self, command: C) {
+        if let Some(f) = &*self.evict {
+            f(command);
+        }
+    }
+}
+
+/// Calls the hook if it is set.
+#[inline]
+pub(crate) fn call<R, C>(hook: &Hook<HookFn<R, C>>, command: &C, receiver: &R) {
+    if let Some(f) = &**hook {
+        f(command, receiver);
+    }
+}
+
+impl<R, C> Default for Hooks<R, C> {
+    #[inline]
+    fn default() -> Self {
+        Hooks {
+            before_apply: Ignored(None),
+            after_apply: Ignored(None),
+            before_undo: Ignored(None),
+            after_undo: Ignored(None),
+            before_redo: Ignored(None),
+            after_redo: Ignored(None),
+            evict: Ignored(None),
+        }
+    }
+}
+
+impl<R, C> Clone for Hooks<R, C> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Hooks {
+            before_apply: self.before_apply.clone(),
+            after_apply: self.after_apply.clone(),
+            before_undo: self.before_undo.clone(),
+            after_undo: self.after_undo.clone(),
+            before_redo: self.before_redo.clone(),
+            after_redo: self.after_redo.clone(),
+            evict: self.evict.clone(),
+        }
+    }
+}
+
+impl<R, C> fmt::Debug for Hooks<R, C> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("before_apply", &self.before_apply.is_some())
+            .field("after_apply", &self.after_apply.is_some())
+            .field("before_undo", &self.before_undo.is_some())
+            .field("after_undo", &self.after_undo.is_some())
+            .field("before_redo", &self.before_redo.is_some())
+            .field("after_redo", &self.after_redo.is_some())
+            .field("evict", &self.evict.is_some())
+            .finish()
+    }
+}
@@ -14,11 +14,37 @@
 //! * [Command] provides the base functionality for all commands.
 //! * [Record] provides linear undo-redo functionality.
 //! * [History] provides non-linear undo-redo functionality that allows you to jump between different branches.
+//! * [FixedRecord], also named `ArrayRecord`, provides linear undo-redo functionality with a fixed capacity and no allocations.
 //! * [Queue] wraps a [Record] or [History] and extends them with queue functionality.
 //! * [Checkpoint] wraps a [Record] or [History] and extends them with checkpoint functionality.
+//! * [MultiCheckpoint] commits or cancels the changes to several records or histories together.
+//! * [Composite] combines several commands into a single command.
+//! * [dynamic] allows commands of different types to be stored in the same record.
+//! * [convert] converts the error types of commands so they can be stored in the same record.
+//! * [Capturing] wraps a command and restores the state it changed when it is undone.
+//! * [Snapshotting] wraps a command and restores the receiver from a snapshot if the command is expensive to undo.
+//! * [Player] replays a log of commands without any undo-redo bookkeeping.
+//! * [Group] manages several records or histories, and forwards the commands to the active one.
+//! * [Mirror] keeps two records in lock-step and detects where their receivers diverge.
+//! * [PersistentRecord] writes every operation to a [Storage] so the record can be reopened.
+//! * [Recorder] records a [Session] that can be written to a file and replayed on a fresh receiver.
+//! * [Event] converts the commands of a [Record] to and from an event stream.
+//! * [LoggedCommand] exports the commands of a [Record] so it can be replayed deterministically.
+//! * [AsPatch] exports commands as patches for systems that consume patches instead of commands.
+//! * [History], [Queue], and [Checkpoint] are provided when the `history`, `queue`, and `checkpoint`
+//!   features are enabled, which they are by default.
 //! * Configurable display formatting is provided when the `display` feature is enabled.
-//! * Time stamps and time travel is provided when the `chrono` feature is enabled.
+//! * Asynchronous commands are provided when the `async` feature is enabled.
+//! * A record that can be shared between threads is provided when the `sync` feature is enabled.
+//! * A compact storage for records with millions of small commands is provided when the `compact` feature is enabled.
+//! * Ready-made text editing commands are provided when the `text` feature is enabled.
+//! * The crate can be used without the standard library by disabling the default `std` feature,
+//!   which only requires `alloc`. Time stamps, signal receivers, caught slot panics, sessions, groups, and heatmaps need `std`.
+//! * Time stamps and time travel is provided when the `chrono` feature is enabled,
+//!   or with the [time] crate instead when the `time` feature is enabled. If both are enabled `chrono` is used.
 //! * Serialization and deserialization is provided when the `serde` feature is enabled.
+//! * Stable textual dumps for golden-file tests are provided when the `testing` feature is enabled.
+//! * Events for the [tracing] crate are emitted when the `tracing` feature is enabled.
 //!
 //! # Concepts
 //!
@@ -28,6 +54,8 @@
 //! * The receiver can be marked as being saved to disk and the data-structures can track the saved state and tell the user
 //!   when it changes.
 //! * The amount of changes being tracked can be configured by the user so only the `n` most recent changes are stored.
+//! * Commands that are expensive to clone can be stored in an `Arc`, which is cloned on write, so a record
+//!   and its clones share the commands until they are changed.
 //!
 //! # Examples
 //!
@@ -81,12 +109,32 @@
 //! [Record]: struct.Record.html
 //! [Timeline]: struct.Timeline.html
 //! [History]: struct.History.html
+//! [FixedRecord]: struct.FixedRecord.html
 //! [Queue]: struct.Queue.html
 //! [Checkpoint]: struct.Checkpoint.html
+//! [MultiCheckpoint]: struct.MultiCheckpoint.html
+//! [Composite]: struct.Composite.html
+//! [dynamic]: dynamic/index.html
+//! [convert]: convert/index.html
+//! [Capturing]: struct.Capturing.html
+//! [Snapshotting]: struct.Snapshotting.html
+//! [Event]: struct.Event.html
+//! [LoggedCommand]: struct.LoggedCommand.html
+//! [AsPatch]: trait.AsPatch.html
+//! [Player]: struct.Player.html
+//! [Group]: struct.Group.html
+//! [Mirror]: struct.Mirror.html
+//! [PersistentRecord]: struct.PersistentRecord.html
+//! [Storage]: trait.Storage.html
+//! [Recorder]: struct.Recorder.html
+//! [Session]: struct.Session.html
 //! [merge]: trait.Command.html#method.merge
 //! [undo]: https://github.com/evenorog/undo
+//! [tracing]: https://docs.rs/tracing
+//! [time]: https://docs.rs/time
 
 #![doc(html_root_url = "https://docs.rs/redo/latest")]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![deny(
     bad_style,
     bare_trait_objects,
@@ -97,26 +145,135 @@
     unstable_features
 )]
 
+extern crate alloc;
+
+/// Emits a `tracing` event with the `redo` target if the `tracing` feature is enabled.
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!(target: "redo", tracing::Level::$level, $($arg)+);
+    };
+}
+
+mod arc;
+#[cfg(feature = "async")]
+mod asynchronous;
+mod cache;
+mod capture;
+#[cfg(feature = "checkpoint")]
 mod checkpoint;
+#[cfg(feature = "std")]
+mod clock;
+#[cfg(feature = "compact")]
+mod compact;
+mod composite;
+mod config;
+mod context;
+pub mod convert;
+mod cursor;
 #[cfg(feature = "display")]
 mod display;
+pub mod dynamic;
+mod event;
+mod fixed;
+#[cfg(feature = "std")]
+mod group;
+#[cfg(feature = "std")]
+mod heatmap;
+#[cfg(feature = "history")]
 mod history;
+mod hooks;
+mod ignored;
+mod macros;
+mod mirror;
+mod mutator;
+mod patch;
+mod player;
+mod progress;
+#[cfg(feature = "queue")]
 mod queue;
 mod record;
+mod replay;
+#[cfg(feature = "std")]
+mod session;
+mod slot;
+mod snapshot;
+mod stack;
+mod storage;
+#[cfg(feature = "sync")]
+mod sync;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "text")]
+pub mod text;
+#[cfg(all(test, feature = "serde"))]
+mod value;
+mod weight;
 
+#[cfg(feature = "compact")]
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+#[cfg(not(feature = "compact"))]
+use alloc::vec::Vec;
 #[cfg(feature = "chrono")]
 use chrono::{DateTime, Utc};
+use core::{error, fmt, str::FromStr, time::Duration};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::fmt;
+#[cfg(all(feature = "std", not(any(feature = "chrono", feature = "time"))))]
+use std::time::SystemTime;
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+use time::OffsetDateTime;
 
+#[cfg(feature = "async")]
+pub use self::asynchronous::{AsyncCommand, AsyncRecord};
+#[cfg(feature = "checkpoint")]
+pub use self::checkpoint::{Checkpoint, MultiCheckpoint, RollbackError};
+#[cfg(feature = "compact")]
+use self::compact::Extra;
+#[cfg(all(feature = "std", feature = "compact"))]
+use self::compact::Timestamp;
 #[cfg(feature = "display")]
-pub use self::display::Display;
+pub use self::display::{Display, Formatted};
+#[cfg(feature = "history")]
+pub use self::history::{
+    Branch, Direction, History, HistoryBuilder, HistoryIntoIter, HistoryIter, LocalHistory,
+    PathStep, SendHistory,
+};
+use self::ignored::Ignored;
+#[cfg(feature = "queue")]
+pub use self::queue::Queue;
+#[cfg(feature = "serde")]
+pub use self::record::ValidationError;
+#[cfg(feature = "sync")]
+pub use self::sync::SharedRecord;
 pub use self::{
-    checkpoint::Checkpoint,
-    history::{History, HistoryBuilder},
-    queue::Queue,
-    record::{Record, RecordBuilder},
+    capture::{Capturing, Snapshotting},
+    composite::Composite,
+    config::ConfigError,
+    context::{Error, Op, OpContext},
+    event::Event,
+    fixed::{ArrayRecord, FixedRecord},
+    mirror::Mirror,
+    mutator::Mutator,
+    patch::AsPatch,
+    player::Player,
+    progress::ProgressCommand,
+    record::{
+        EntryView, LocalRecord, Record, RecordBuilder, RecordIntoIter, RecordIter, SendRecord,
+        UnsavedCount,
+    },
+    replay::LoggedCommand,
+    slot::{SignalOrder, SubscriptionId},
+    snapshot::Snapshot,
+    storage::{LogEntry, PersistentRecord, Storage, StorageError},
+};
+#[cfg(feature = "std")]
+pub use self::{
+    clock::Clock,
+    group::{Group, GroupSignal},
+    heatmap::{Heatmap, HeatmapCell},
+    session::{Recorder, Session, SessionOp},
 };
 
 /// Base functionality for all commands.
@@ -203,6 +360,109 @@ pub trait Command<R> {
     fn is_dead(&self) -> bool {
         false
     }
+
+    /// Says if the command can still be undone.
+    ///
+    /// This models operations that become irreversible after some time or when a condition
+    /// is met, like sending an email or committing a transaction. A command that is no longer
+    /// undoable is treated as dead when it is about to be undone, which means that it is removed
+    /// without being undone and its changes are kept in the receiver.
+    /// The default implementation returns `true`.
+    #[inline]
+    fn undoable(&self, receiver: &R) -> bool {
+        let _ = receiver;
+        true
+    }
+
+    /// Says if applying the command would leave the receiver unchanged.
+    ///
+    /// This is checked before the command is applied. By default a no-op command is neither applied
+    /// nor stored, which keeps commands like setting a value to the value it already has out of the
+    /// record. The record can instead be told to keep them, in which case they are marked as no-ops.
+    /// The default implementation returns `false`.
+    #[inline]
+    fn is_noop(&self, receiver: &R) -> bool {
+        let _ = receiver;
+        false
+    }
+
+    /// Checks if the command can be applied to the receiver in its current state.
+    ///
+    /// This is checked before the command is applied, and if an error is returned the command
+    /// is neither applied nor stored, and the error is returned instead. This keeps commands that
+    /// were created for an older state of the receiver, e.g. commands in a [`Queue`], from
+    /// corrupting the receiver when the state has changed underneath them.
+    /// The default implementation returns `Ok`.
+    ///
+    /// [`Queue`]: struct.Queue.html
+    #[inline]
+    fn validate(&self, receiver: &R) -> Result<(), Self::Error> {
+        let _ = receiver;
+        Ok(())
+    }
+
+    /// A hint of which region of the receiver the command changes, e.g. a line number.
+    ///
+    /// This is used by [`Heatmap`] to group the commands. The default implementation returns `None`.
+    ///
+    /// [`Heatmap`]: struct.Heatmap.html
+    #[inline]
+    fn region(&self) -> Option<usize> {
+        None
+    }
+
+    /// A hint of how expensive it is to undo the command.
+    ///
+    /// This is used by [`Snapshotting`] to decide if the receiver should be snapshotted
+    /// before the command is applied. The default implementation returns `0`.
+    ///
+    /// [`Snapshotting`]: struct.Snapshotting.html
+    #[inline]
+    fn cost(&self) -> usize {
+        0
+    }
+
+    /// Loads the data the command needs to be undone or redone ahead of time.
+    ///
+    /// This is called by [`Record::prefetch`] and [`Record::idle`], which lets commands that keep
+    /// their data somewhere slow, like on disk, load it before the user performs a deep undo.
+    /// It can be called many times, so it should return quickly if the data is already loaded.
+    /// The default implementation does nothing.
+    ///
+    /// [`Record::prefetch`]: struct.Record.html#method.prefetch
+    /// [`Record::idle`]: struct.Record.html#method.idle
+    #[inline]
+    fn prefetch(&mut self) {}
+
+    /// Says if the command and the `command` applied after it commute,
+    /// which means that they can be undone in any order.
+    ///
+    /// This is used by [`Record::undo_at`], which undoes the command directly instead of undoing
+    /// and redoing the commands applied after it, if it commutes with all of them.
+    /// The default implementation returns `false`.
+    ///
+    /// [`Record::undo_at`]: struct.Record.html#method.undo_at
+    #[inline]
+    fn transpose(&self, command: &Self) -> bool
+    where
+        Self: Sized,
+    {
+        let _ = command;
+        false
+    }
+
+    /// Says if the command can be skipped by restoring a snapshot of the receiver.
+    ///
+    /// This is used by the snapshots of [`RecordBuilder::snapshot_every`], which replace the undo
+    /// and redo calls of the skipped commands. It is only correct if the command keeps no state of
+    /// its own that is updated when it is undone or redone, like the data a command captures when
+    /// it is applied. The default implementation returns `false`.
+    ///
+    /// [`RecordBuilder::snapshot_every`]: struct.RecordBuilder.html#method.snapshot_every
+    #[inline]
+    fn restorable(&self) -> bool {
+        false
+    }
 }
 
 /// The signal sent when the record, the history, or the receiver changes.
@@ -210,6 +470,15 @@ pub trait Command<R> {
 /// When one of these states changes, they will send a corresponding signal to the user.
 /// For example, if the record can no longer redo any commands, it sends a `Redo(false)`
 /// signal to tell the user.
+///
+/// The signals caused by one step of an operation are always emitted in the order given by
+/// [`SignalOrder`], which by default is `Current`, `Annulled`, `Merged`, `Undo`, `Redo`, `Saved`,
+/// and `Branch`, preceded by `Operation` if enabled. Operations that consist of several steps,
+/// like `go_to`, `extend`, and committing a queue, emit the signals of each step when it is done.
+/// If the signals are deferred with `defer_signals`, they only emit the net changes once the whole
+/// operation is done, even if it fails halfway, so the slot never observes an intermediate state.
+///
+/// [`SignalOrder`]: enum.SignalOrder.html
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
 pub enum Signal {
@@ -235,6 +504,34 @@ pub enum Signal {
         /// The new current command.
         new: usize,
     },
+    /// Says that the applied command was merged into the command with the id `into`.
+    ///
+    /// References to the merged command can be repaired with `resolve`.
+    Merged {
+        /// The id of the command that the applied command was merged into.
+        into: usize,
+    },
+    /// Says that the applied command and the command with the id `with` annulled each other,
+    /// and that both were removed.
+    ///
+    /// If the record cascades annulled commands, it can be followed by `Merged`.
+    Annulled {
+        /// The id of the command that the applied command annulled.
+        with: usize,
+    },
+    /// Says that the following signals are caused by the operation with the id.
+    ///
+    /// This is only emitted if enabled with `emit_operation_ids`, and always comes
+    /// before the other signals caused by the operation.
+    Operation(usize),
+    /// Says that the receiver was mutated outside of the commands while there were commands to redo.
+    ///
+    /// This is emitted when a `Mutator` is dropped.
+    Mutated,
+    /// Says that the branch ids have been renumbered.
+    ///
+    /// This is only emitted from `History`.
+    Renumbered,
     /// Says if the current branch has changed.
     ///
     /// This is only emitted from `History`.
@@ -244,6 +541,10 @@ pub enum Signal {
         /// The new root.
         new: usize,
     },
+    /// Says that the branch with the id was removed because the branch limit was reached.
+    ///
+    /// This is only emitted from `History`.
+    BranchEvicted(usize),
 }
 
 /// The result of merging two commands.
@@ -258,29 +559,369 @@ pub enum Merge<C> {
     Annul,
 }
 
-/// A position in a history tree.
+impl<C> Merge<C> {
+    /// Maps the unmerged command with `f`.
+    #[inline]
+    pub(crate) fn map<D>(self, f: impl FnOnce(C) -> D) -> Merge<D> {
+        match self {
+            Merge::Yes => Merge::Yes,
+            Merge::No(command) => Merge::No(f(command)),
+            Merge::Annul => Merge::Annul,
+        }
+    }
+}
+
+/// The time when a command was applied, as it is returned to the user.
+#[cfg(feature = "chrono")]
+type Time = DateTime<Utc>;
+/// The time when a command was applied, as it is returned to the user.
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+type Time = OffsetDateTime;
+/// The time when a command was applied, as it is returned to the user.
+#[cfg(all(feature = "std", not(any(feature = "chrono", feature = "time"))))]
+type Time = SystemTime;
+/// The time when a command was applied.
+#[cfg(all(feature = "std", not(feature = "compact")))]
+type Timestamp = Time;
+/// The time when a command was applied, which is not tracked without the standard library.
+#[cfg(not(feature = "std"))]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
-struct At {
+struct Timestamp;
+
+/// Returns the current time.
+#[inline]
+fn now() -> Timestamp {
+    #[cfg(all(feature = "std", feature = "compact"))]
+    return Timestamp::now();
+    #[cfg(all(feature = "chrono", not(feature = "compact")))]
+    return Utc::now();
+    #[cfg(all(feature = "time", not(any(feature = "chrono", feature = "compact"))))]
+    return OffsetDateTime::now_utc();
+    #[cfg(all(
+        feature = "std",
+        not(any(feature = "chrono", feature = "time", feature = "compact"))
+    ))]
+    return SystemTime::now();
+    #[cfg(not(feature = "std"))]
+    return Timestamp;
+}
+
+/// Returns the time new entries are stamped with.
+///
+/// The current time is only recorded by default when the `chrono` or `time` feature is enabled.
+/// Otherwise the entries are stamped with the Unix epoch, and the record only replaces it with
+/// the current time when a clock or `merge_within` is set, which keeps a clock call out of every
+/// apply for the users that do not need the time.
+#[inline]
+fn stamp() -> Ignored<Timestamp> {
+    #[cfg(any(feature = "chrono", feature = "time", not(feature = "std")))]
+    return Ignored(now());
+    #[cfg(all(feature = "std", not(any(feature = "chrono", feature = "time"))))]
+    return Ignored(timestamp(SystemTime::UNIX_EPOCH));
+}
+
+/// Returns the time from `from` to `to`, or `None` if `to` is before `from`.
+///
+/// Without the standard library the time is not tracked, so it always returns `None`.
+#[inline]
+fn elapsed(from: Timestamp, to: Timestamp) -> Option<Duration> {
+    #[cfg(all(feature = "std", feature = "compact"))]
+    return to.since(from);
+    #[cfg(all(feature = "chrono", not(feature = "compact")))]
+    return (to - from).to_std().ok();
+    #[cfg(all(feature = "time", not(any(feature = "chrono", feature = "compact"))))]
+    return core::convert::TryFrom::try_from(to - from).ok();
+    #[cfg(all(
+        feature = "std",
+        not(any(feature = "chrono", feature = "time", feature = "compact"))
+    ))]
+    return to.duration_since(from).ok();
+    #[cfg(not(feature = "std"))]
+    return {
+        let _ = (from, to);
+        None
+    };
+}
+
+/// Converts the timestamp to the time that is returned to the user.
+#[inline]
+#[cfg(feature = "std")]
+fn time(timestamp: Timestamp) -> Time {
+    #[cfg(feature = "compact")]
+    return timestamp.to_time();
+    #[cfg(not(feature = "compact"))]
+    return timestamp;
+}
+
+/// Converts the time that is returned to the user to a timestamp.
+#[inline]
+#[cfg(feature = "std")]
+fn timestamp(time: Time) -> Timestamp {
+    #[cfg(feature = "compact")]
+    return Timestamp::from_time(time);
+    #[cfg(not(feature = "compact"))]
+    return time;
+}
+
+/// A position in a history, given by a branch and the position of the current command in it.
+///
+/// It is returned by [`History::position`] and can be used to return to the position later
+/// with [`History::go_to_position`]. The position of a record is always in the branch `0`.
+///
+/// [`History::position`]: struct.History.html#method.position
+/// [`History::go_to_position`]: struct.History.html#method.go_to_position
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Position {
     branch: usize,
     current: usize,
 }
 
+impl Position {
+    /// Returns the position of the command at `current` in the `branch`.
+    #[inline]
+    pub fn new(branch: usize, current: usize) -> Position {
+        Position { branch, current }
+    }
+
+    /// Returns the branch.
+    #[inline]
+    pub fn branch(&self) -> usize {
+        self.branch
+    }
+
+    /// Returns the position of the current command in the branch.
+    #[inline]
+    pub fn current(&self) -> usize {
+        self.current
+    }
+}
+
+impl From<(usize, usize)> for Position {
+    #[inline]
+    fn from((branch, current): (usize, usize)) -> Position {
+        Position::new(branch, current)
+    }
+}
+
+impl From<Position> for (usize, usize) {
+    #[inline]
+    fn from(position: Position) -> (usize, usize) {
+        (position.branch, position.current)
+    }
+}
+
+impl fmt::Display for Position {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.branch, self.current)
+    }
+}
+
+impl FromStr for Position {
+    type Err = ParsePositionError;
+
+    /// Parses a position from the `branch:current` form it is displayed in.
+    #[inline]
+    fn from_str(s: &str) -> Result<Position, ParsePositionError> {
+        let (branch, current) = s.trim().split_once(':').ok_or(ParsePositionError)?;
+        let branch = branch.parse().map_err(|_| ParsePositionError)?;
+        let current = current.parse().map_err(|_| ParsePositionError)?;
+        Ok(Position::new(branch, current))
+    }
+}
+
+/// The error returned when a [`Position`] can not be parsed from a string.
+///
+/// [`Position`]: struct.Position.html
+#[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct ParsePositionError;
+
+impl fmt::Display for ParsePositionError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the position must have the form `branch:current`")
+    }
+}
+
+impl error::Error for ParsePositionError {}
+
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
 struct Entry<C> {
     command: C,
-    #[cfg(feature = "chrono")]
-    timestamp: DateTime<Utc>,
+    /// The id of the command, which is given by the record if it was serialized without one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    id: usize,
+    /// The ids of the commands that has been merged into this one.
+    #[cfg(not(feature = "compact"))]
+    #[cfg_attr(feature = "serde", serde(default))]
+    merged: Vec<usize>,
+    /// The merged ids and the label, which are only allocated when they are set.
+    #[cfg(feature = "compact")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    extra: Option<Box<Extra>>,
+    /// `true` if a coarse step ends with this command.
+    #[cfg_attr(feature = "serde", serde(default))]
+    boundary: bool,
+    /// `true` if the command is undone and redone together with the previous command.
+    #[cfg_attr(feature = "serde", serde(default))]
+    joined: bool,
+    /// `true` if the command left the receiver unchanged when it was applied.
+    #[cfg_attr(feature = "serde", serde(default))]
+    noop: bool,
+    /// The label the command was applied with.
+    #[cfg(not(feature = "compact"))]
+    #[cfg_attr(feature = "serde", serde(default))]
+    label: Option<String>,
+    /// The time is only serialized with `chrono` in older versions, so it defaults to a new stamp.
+    /// It is ignored when comparing and hashing entries, so equal records compare equal.
+    #[cfg_attr(feature = "serde", serde(default = "stamp"))]
+    timestamp: Ignored<Timestamp>,
 }
 
-impl<C> From<C> for Entry<C> {
+impl<C> Entry<C> {
     #[inline]
-    fn from(command: C) -> Self {
+    fn new(command: C, id: usize) -> Self {
         Entry {
             command,
-            #[cfg(feature = "chrono")]
-            timestamp: Utc::now(),
+            id,
+            #[cfg(not(feature = "compact"))]
+            merged: Vec::new(),
+            #[cfg(feature = "compact")]
+            extra: None,
+            boundary: false,
+            joined: false,
+            noop: false,
+            #[cfg(not(feature = "compact"))]
+            label: None,
+            timestamp: stamp(),
+        }
+    }
+
+    /// Returns `true` if the entry has the id, or a command with the id has been merged into it.
+    #[inline]
+    fn contains(&self, id: usize) -> bool {
+        self.id == id || self.merged().contains(&id)
+    }
+
+    /// Returns the ids of the commands that has been merged into this one.
+    #[inline]
+    #[cfg(not(feature = "compact"))]
+    fn merged(&self) -> &[usize] {
+        &self.merged
+    }
+
+    /// Returns the ids of the commands that has been merged into this one.
+    #[inline]
+    #[cfg(feature = "compact")]
+    fn merged(&self) -> &[usize] {
+        self.extra.as_ref().map_or(&[], |extra| &extra.merged)
+    }
+
+    /// Returns the label the command was applied with.
+    #[inline]
+    #[cfg(not(feature = "compact"))]
+    fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Returns the label the command was applied with.
+    #[inline]
+    #[cfg(feature = "compact")]
+    fn label(&self) -> Option<&str> {
+        self.extra.as_ref()?.label.as_deref()
+    }
+
+    /// Sets the label of the command.
+    #[inline]
+    #[cfg(not(feature = "compact"))]
+    fn set_label(&mut self, label: String) {
+        self.label = Some(label);
+    }
+
+    /// Sets the label of the command.
+    #[inline]
+    #[cfg(feature = "compact")]
+    fn set_label(&mut self, label: String) {
+        self.extra.get_or_insert_with(Box::default).label = Some(label);
+    }
+
+    /// Adds the id and the merged ids of the entry that is merged into this one,
+    /// and keeps the first label.
+    #[inline]
+    #[cfg(not(feature = "compact"))]
+    fn absorb(&mut self, id: usize, merged: Vec<usize>, label: Option<String>) {
+        self.merged.push(id);
+        self.merged.extend(merged);
+        if self.label.is_none() {
+            self.label = label;
+        }
+    }
+
+    /// Adds the id and the merged ids of the entry that is merged into this one,
+    /// and keeps the first label.
+    #[inline]
+    #[cfg(feature = "compact")]
+    fn absorb(&mut self, id: usize, extra: Option<Box<Extra>>) {
+        let into = self.extra.get_or_insert_with(Box::default);
+        into.merged.push(id);
+        if let Some(extra) = extra {
+            into.merged.extend(extra.merged);
+            if into.label.is_none() {
+                into.label = extra.label;
+            }
+        }
+    }
+}
+
+impl<C> Entry<C> {
+    /// Returns the entry of the logged command.
+    #[inline]
+    fn from_logged(logged: LoggedCommand<C>) -> Self {
+        let mut entry = Entry::new(logged.command, logged.id);
+        entry.boundary = logged.boundary;
+        entry.joined = logged.joined;
+        entry.noop = logged.noop;
+        #[cfg(not(feature = "compact"))]
+        {
+            entry.merged = logged.merged;
+            entry.label = logged.label;
+        }
+        #[cfg(feature = "compact")]
+        if !logged.merged.is_empty() || logged.label.is_some() {
+            entry.extra = Some(Box::new(Extra {
+                merged: logged.merged,
+                label: logged.label,
+            }));
+        }
+        entry
+    }
+
+    /// Returns the logged command of the entry.
+    #[inline]
+    fn to_logged(&self, undone: bool) -> LoggedCommand<&C> {
+        LoggedCommand {
+            id: self.id,
+            command: &self.command,
+            undone,
+            merged: self.merged().to_vec(),
+            label: self.label().map(ToString::to_string),
+            noop: self.noop,
+            boundary: self.boundary,
+            joined: self.joined,
+        }
+    }
+}
+
+impl<C: ToString> Entry<C> {
+    /// Returns the label of the command if it has one, and the string of the command otherwise.
+    #[inline]
+    fn to_label_string(&self) -> String {
+        match self.label() {
+            Some(label) => label.to_string(),
+            None => self.command.to_string(),
         }
     }
 }
@@ -308,9 +949,44 @@ impl<R, C: Command<R>> Command<R> for Entry<C> {
     where
         Self: Sized,
     {
-        match self.command.merge(command.command) {
-            Merge::Yes => Merge::Yes,
-            Merge::No(command) => Merge::No(Entry::from(command)),
+        let Entry {
+            command,
+            id,
+            #[cfg(not(feature = "compact"))]
+            merged,
+            #[cfg(feature = "compact")]
+            extra,
+            boundary,
+            joined,
+            noop,
+            #[cfg(not(feature = "compact"))]
+            label,
+            timestamp,
+        } = command;
+        match self.command.merge(command) {
+            Merge::Yes => {
+                #[cfg(not(feature = "compact"))]
+                self.absorb(id, merged, label);
+                #[cfg(feature = "compact")]
+                self.absorb(id, extra);
+                self.boundary |= boundary;
+                self.noop &= noop;
+                Merge::Yes
+            }
+            Merge::No(command) => Merge::No(Entry {
+                command,
+                id,
+                #[cfg(not(feature = "compact"))]
+                merged,
+                #[cfg(feature = "compact")]
+                extra,
+                boundary,
+                joined,
+                noop,
+                #[cfg(not(feature = "compact"))]
+                label,
+                timestamp,
+            }),
             Merge::Annul => Merge::Annul,
         }
     }
@@ -319,6 +995,46 @@ impl<R, C: Command<R>> Command<R> for Entry<C> {
     fn is_dead(&self) -> bool {
         self.command.is_dead()
     }
+
+    #[inline]
+    fn restorable(&self) -> bool {
+        self.command.restorable()
+    }
+
+    #[inline]
+    fn undoable(&self, receiver: &R) -> bool {
+        self.command.undoable(receiver)
+    }
+
+    #[inline]
+    fn is_noop(&self, receiver: &R) -> bool {
+        self.command.is_noop(receiver)
+    }
+
+    #[inline]
+    fn validate(&self, receiver: &R) -> Result<(), <Self as Command<R>>::Error> {
+        self.command.validate(receiver)
+    }
+
+    #[inline]
+    fn region(&self) -> Option<usize> {
+        self.command.region()
+    }
+
+    #[inline]
+    fn cost(&self) -> usize {
+        self.command.cost()
+    }
+
+    #[inline]
+    fn prefetch(&mut self) {
+        self.command.prefetch();
+    }
+
+    #[inline]
+    fn transpose(&self, entry: &Self) -> bool {
+        self.command.transpose(&entry.command)
+    }
 }
 
 impl<C: fmt::Display> fmt::Display for Entry<C> {
@@ -0,0 +1,87 @@
+/// Defines a command that sets a field of the receiver to a new value.
+///
+/// `set_command!(SetName, Person.name: String)` defines the command `SetName(String)` for the
+/// receiver `Person`. Applying the command swaps the value in the command with the value in the
+/// field, which means that the command holds the previous value until it is undone. The field can
+/// be nested, like `Person.address.city`, and the struct can be given attributes and a visibility.
+/// The commands never fail, so the error type is [`Infallible`].
+///
+/// # Examples
+/// ```
+/// # use redo::{set_command, Record};
+/// struct Person {
+///     name: String,
+/// }
+///
+/// set_command!(#[derive(Debug)] pub SetName, Person.name: String);
+///
+/// let mut record = Record::new(Person { name: "Ann".into() });
+/// record.apply(SetName("Bob".into())).unwrap();
+/// assert_eq!(record.as_receiver().name, "Bob");
+/// record.undo().unwrap().unwrap();
+/// assert_eq!(record.as_receiver().name, "Ann");
+/// ```
+///
+/// [`Infallible`]: https://doc.rust-lang.org/core/convert/enum.Infallible.html
+#[macro_export]
+macro_rules! set_command {
+    ($(#[$meta:meta])* $vis:vis $name:ident, $receiver:ident $(. $field:ident)+ : $value:ty) => {
+        $(#[$meta])*
+        $vis struct $name(pub $value);
+
+        impl $crate::Command<$receiver> for $name {
+            type Error = ::core::convert::Infallible;
+
+            #[inline]
+            fn apply(&mut self, receiver: &mut $receiver) -> ::core::result::Result<(), Self::Error> {
+                ::core::mem::swap(&mut receiver $(.$field)+, &mut self.0);
+                Ok(())
+            }
+
+            #[inline]
+            fn undo(&mut self, receiver: &mut $receiver) -> ::core::result::Result<(), Self::Error> {
+                ::core::mem::swap(&mut receiver $(.$field)+, &mut self.0);
+                Ok(())
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Record};
+
+    #[derive(Default)]
+    struct Address {
+        city: String,
+    }
+
+    #[derive(Default)]
+    struct Person {
+        name: String,
+        address: Address,
+    }
+
+    set_command!(SetName, Person.name: String);
+    set_command!(SetCity, Person.address.city: String);
+
+    #[test]
+    fn set_command() {
+        let mut record = Record::<Person, SetName>::default();
+        record.apply(SetName("a".into())).unwrap();
+        record.apply(SetName("b".into())).unwrap();
+        record.go_to(0).unwrap().unwrap();
+        assert_eq!(record.as_receiver().name, "");
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver().name, "a");
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver().name, "b");
+
+        let mut person = Person::default();
+        let mut city = SetCity("x".into());
+        Command::apply(&mut city, &mut person).unwrap();
+        assert_eq!(person.address.city, "x");
+        Command::undo(&mut city, &mut person).unwrap();
+        assert_eq!(person.address.city, "");
+    }
+}
@@ -0,0 +1,228 @@
+use crate::{Command, Record};
+
+/// A pair of records that are kept in lock-step.
+///
+/// Every command is cloned and applied to both the primary and the secondary record, and every
+/// undo and redo is performed on both. After each operation the receivers are compared, and the
+/// first operation where they differ, or where only one of the records failed, is remembered.
+/// This makes it possible to run a new implementation of the receiver next to the old one
+/// under real user sessions, and detect where they diverge.
+///
+/// The results of the operations are always the results of the primary record.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, Mirror};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+/// # impl Clone for Add {
+/// #     fn clone(&self) -> Add {
+/// #         Add(self.0)
+/// #     }
+/// # }
+/// # fn main() -> Result<(), ()> {
+/// let mut mirror = Mirror::new(String::new());
+/// mirror.apply(Add('a'))?;
+/// mirror.apply(Add('b'))?;
+/// mirror.undo().unwrap()?;
+/// assert_eq!(mirror.primary().as_receiver(), "a");
+/// assert_eq!(mirror.secondary().as_receiver(), "a");
+/// assert!(!mirror.is_diverged());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Mirror<R, C> {
+    primary: Record<R, C>,
+    secondary: Record<R, C>,
+    operations: usize,
+    diverged: Option<usize>,
+}
+
+impl<R: Clone, C> Mirror<R, C> {
+    /// Returns a new mirror where the secondary record gets a clone of the receiver.
+    #[inline]
+    pub fn new(receiver: impl Into<R>) -> Mirror<R, C> {
+        let receiver = receiver.into();
+        Mirror::from_records(Record::new(receiver.clone()), Record::new(receiver))
+    }
+}
+
+impl<R, C> Mirror<R, C> {
+    /// Returns a new mirror of the two records.
+    ///
+    /// This can be used to mirror two different receivers, e.g. an old and a new model.
+    #[inline]
+    pub fn from_records(primary: Record<R, C>, secondary: Record<R, C>) -> Mirror<R, C> {
+        Mirror {
+            primary,
+            secondary,
+            operations: 0,
+            diverged: None,
+        }
+    }
+
+    /// Returns a reference to the primary record.
+    #[inline]
+    pub fn primary(&self) -> &Record<R, C> {
+        &self.primary
+    }
+
+    /// Returns a reference to the secondary record.
+    #[inline]
+    pub fn secondary(&self) -> &Record<R, C> {
+        &self.secondary
+    }
+
+    /// Returns the number of operations that has been performed on the records.
+    #[inline]
+    pub fn operations(&self) -> usize {
+        self.operations
+    }
+
+    /// Returns `true` if the records have diverged.
+    #[inline]
+    pub fn is_diverged(&self) -> bool {
+        self.diverged.is_some()
+    }
+
+    /// Returns the number of the first operation where the records diverged, starting at `1`.
+    #[inline]
+    pub fn diverged(&self) -> Option<usize> {
+        self.diverged
+    }
+
+    /// Consumes the mirror, returning the primary and the secondary record.
+    #[inline]
+    pub fn into_records(self) -> (Record<R, C>, Record<R, C>) {
+        (self.primary, self.secondary)
+    }
+}
+
+impl<R: PartialEq, C: Command<R> + Clone> Mirror<R, C> {
+    /// Pushes the command on top of both records and executes its [`apply`] method.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] on the primary record the error is returned.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    pub fn apply(&mut self, command: C) -> Result<(), C::Error> {
+        let secondary = self.secondary.apply(command.clone()).is_ok();
+        let primary = self.primary.apply(command);
+        self.check(primary.is_ok() == secondary);
+        primary
+    }
+
+    /// Calls the [`undo`] method for the active command in both records.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] on the primary record the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    #[inline]
+    pub fn undo(&mut self) -> Option<Result<(), C::Error>> {
+        let secondary = self.secondary.undo().map(|result| result.is_ok());
+        let primary = self.primary.undo();
+        self.check(primary.as_ref().map(Result::is_ok) == secondary);
+        primary
+    }
+
+    /// Calls the [`redo`] method for the active command in both records.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] on the primary record the error is returned.
+    ///
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn redo(&mut self) -> Option<Result<(), C::Error>> {
+        let secondary = self.secondary.redo().map(|result| result.is_ok());
+        let primary = self.primary.redo();
+        self.check(primary.as_ref().map(Result::is_ok) == secondary);
+        primary
+    }
+
+    /// Repeatedly calls [`undo`] or [`redo`] on both records until the command at `current` is reached.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] on the primary record the error is
+    /// returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn go_to(&mut self, current: usize) -> Option<Result<(), C::Error>> {
+        let secondary = self.secondary.go_to(current).map(|result| result.is_ok());
+        let primary = self.primary.go_to(current);
+        self.check(primary.as_ref().map(Result::is_ok) == secondary);
+        primary
+    }
+
+    /// Counts the operation, and remembers it if the records have diverged.
+    #[inline]
+    fn check(&mut self, same_result: bool) {
+        self.operations += 1;
+        let same = same_result
+            && self.primary.current() == self.secondary.current()
+            && self.primary.as_receiver() == self.secondary.as_receiver();
+        if !same && self.diverged.is_none() {
+            self.diverged = Some(self.operations);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Mirror, Record};
+
+    #[derive(Clone)]
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            self.0 = s.pop().ok_or("`s` is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn diverged() {
+        let mut mirror = Mirror::new("");
+        mirror.apply(Add('a')).unwrap();
+        mirror.apply(Add('b')).unwrap();
+        mirror.undo().unwrap().unwrap();
+        mirror.go_to(2).unwrap().unwrap();
+        assert_eq!(mirror.operations(), 4);
+        assert!(!mirror.is_diverged());
+
+        let mut mirror = Mirror::from_records(Record::new("x"), Record::new(""));
+        assert_eq!(mirror.apply(Add('a')), Ok(()));
+        assert_eq!(mirror.diverged(), Some(1));
+        // Only the secondary record fails to undo the command.
+        let mut mirror = Mirror::from_records(Record::new("x"), Record::new("x"));
+        mirror.apply(Add('a')).unwrap();
+        mirror.secondary.as_mut_receiver().clear();
+        assert_eq!(mirror.undo(), Some(Ok(())));
+        assert_eq!(mirror.diverged(), Some(2));
+        let (primary, secondary) = mirror.into_records();
+        assert_eq!(primary.as_receiver(), "x");
+        assert_eq!(secondary.as_receiver(), "");
+    }
+}
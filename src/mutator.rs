@@ -0,0 +1,94 @@
+use crate::{slot::Slot, Signal};
+use core::ops::{Deref, DerefMut};
+
+/// A guard that gives mutable access to the receiver outside of the commands.
+///
+/// Changing the receiver behind the back of the commands can make the commands that can be
+/// redone invalid. The guard makes this observable: the record remembers that the receiver was
+/// mutated, and if there were commands to redo the [`Mutated`] signal is emitted when the guard
+/// is dropped. The receiver is only considered mutated if it has been mutably dereferenced.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, Record};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+/// let mut record = Record::default();
+/// record.apply(Add('a')).unwrap();
+/// assert_eq!(record.mutate().len(), 1);
+/// assert!(!record.is_mutated());
+/// record.mutate().push('b');
+/// assert!(record.is_mutated());
+/// ```
+///
+/// [`Mutated`]: enum.Signal.html#variant.Mutated
+#[derive(Debug)]
+pub struct Mutator<'a, R, F: FnMut(Signal) = fn(Signal)> {
+    receiver: &'a mut R,
+    slot: &'a mut Slot<F>,
+    mutated: &'a mut bool,
+    generation: &'a mut u64,
+    can_redo: bool,
+    changed: bool,
+}
+
+impl<'a, R, F: FnMut(Signal)> Mutator<'a, R, F> {
+    #[inline]
+    pub(crate) fn new(
+        receiver: &'a mut R,
+        slot: &'a mut Slot<F>,
+        mutated: &'a mut bool,
+        generation: &'a mut u64,
+        can_redo: bool,
+    ) -> Mutator<'a, R, F> {
+        Mutator {
+            receiver,
+            slot,
+            mutated,
+            generation,
+            can_redo,
+            changed: false,
+        }
+    }
+}
+
+impl<R, F: FnMut(Signal)> Deref for Mutator<'_, R, F> {
+    type Target = R;
+
+    #[inline]
+    fn deref(&self) -> &R {
+        self.receiver
+    }
+}
+
+impl<R, F: FnMut(Signal)> DerefMut for Mutator<'_, R, F> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut R {
+        self.changed = true;
+        self.receiver
+    }
+}
+
+impl<R, F: FnMut(Signal)> Drop for Mutator<'_, R, F> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.changed {
+            *self.mutated = true;
+            *self.generation += 1;
+            if self.can_redo {
+                self.slot.next_operation();
+                self.slot.emit(Signal::Mutated);
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+/// A command that can be exported as a patch.
+///
+/// External systems, like sync engines and review tools, usually consume patches in a generic
+/// format, e.g. JSON Patch or a custom delta format, instead of the command types of the
+/// application. Implementing this trait for the commands makes it possible to export the
+/// commands in a record as patches with [`export_patches`].
+///
+/// # Examples
+/// ```
+/// # use redo::{AsPatch, Command, Record};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+/// impl AsPatch for Add {
+///     type Patch = String;
+///
+///     fn as_patch(&self) -> String {
+///         format!("+{}", self.0)
+///     }
+/// }
+///
+/// let mut record = Record::default();
+/// record.apply(Add('a')).unwrap();
+/// record.apply(Add('b')).unwrap();
+/// record.apply(Add('c')).unwrap();
+/// let patches: Vec<_> = record.export_patches(1..).collect();
+/// assert_eq!(patches, ["+b", "+c"]);
+/// ```
+///
+/// [`export_patches`]: struct.Record.html#method.export_patches
+pub trait AsPatch {
+    /// The patch type.
+    type Patch;
+
+    /// Returns the patch that describes the changes made when the command is applied or redone.
+    fn as_patch(&self) -> Self::Patch;
+
+    /// Returns the patch that describes the changes made when the command is undone.
+    ///
+    /// The default implementation returns `None`, which means that the inverse patch is unknown.
+    #[inline]
+    fn as_inverse_patch(&self) -> Option<Self::Patch> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AsPatch, Command, Record};
+
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = ();
+
+        fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+            s.pop();
+            Ok(())
+        }
+    }
+
+    impl AsPatch for Add {
+        type Patch = (char, char);
+
+        fn as_patch(&self) -> (char, char) {
+            ('+', self.0)
+        }
+
+        fn as_inverse_patch(&self) -> Option<(char, char)> {
+            Some(('-', self.0))
+        }
+    }
+
+    #[test]
+    fn export() {
+        let mut record = Record::default();
+        record.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        record.undo().unwrap().unwrap();
+        let patches: Vec<_> = record.export_patches(..record.current()).collect();
+        assert_eq!(patches, [('+', 'a'), ('+', 'b')]);
+        let inverse: Vec<_> = record
+            .export_inverse_patches(..)
+            .collect::<Option<_>>()
+            .unwrap();
+        assert_eq!(inverse, [('-', 'c'), ('-', 'b'), ('-', 'a')]);
+        assert_eq!(record.export_patches(3..).count(), 0);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::Command;
+use alloc::vec::Vec;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A player that replays a log of commands on a receiver.
+///
+/// The player only calls the [`apply`] method on the commands. It moves backward by restoring
+/// the initial receiver and replaying the commands from the start. Since no undo-redo bookkeeping
+/// is done, it works well for building test fixtures and demos from recorded sessions.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, Player};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, _: &mut String) -> Result<(), ()> {
+/// #         unreachable!()
+/// #     }
+/// # }
+/// # fn main() -> Result<(), ()> {
+/// let mut player = Player::new(String::new(), vec![Add('a'), Add('b'), Add('c')]);
+/// player.step().unwrap()?;
+/// assert_eq!(player.as_receiver(), "a");
+/// player.seek(3).unwrap()?;
+/// assert_eq!(player.as_receiver(), "abc");
+/// player.seek(1).unwrap()?;
+/// assert_eq!(player.as_receiver(), "a");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`apply`]: trait.Command.html#tymethod.apply
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Player<R, C> {
+    initial: R,
+    receiver: R,
+    commands: Vec<C>,
+    current: usize,
+}
+
+impl<R: Clone, C> Player<R, C> {
+    /// Returns a new player that replays the `commands` on the `receiver`.
+    #[inline]
+    pub fn new(receiver: impl Into<R>, commands: impl IntoIterator<Item = C>) -> Player<R, C> {
+        let initial = receiver.into();
+        Player {
+            receiver: initial.clone(),
+            initial,
+            commands: commands.into_iter().collect(),
+            current: 0,
+        }
+    }
+}
+
+impl<R, C> Player<R, C> {
+    /// Returns the number of commands in the log.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns `true` if the log is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Returns the number of commands that has been replayed.
+    #[inline]
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Returns an iterator over the commands in the log.
+    #[inline]
+    pub fn commands(&self) -> impl Iterator<Item = &C> {
+        self.commands.iter()
+    }
+
+    /// Returns a reference to the receiver.
+    #[inline]
+    pub fn as_receiver(&self) -> &R {
+        &self.receiver
+    }
+
+    /// Consumes the player, returning the receiver.
+    #[inline]
+    pub fn into_receiver(self) -> R {
+        self.receiver
+    }
+}
+
+impl<R: Clone, C: Command<R>> Player<R, C> {
+    /// Applies the next command in the log.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned
+    /// and the command is not counted as replayed.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    pub fn step(&mut self) -> Option<Result<(), C::Error>> {
+        let command = self.commands.get_mut(self.current)?;
+        if let Err(error) = command.apply(&mut self.receiver) {
+            return Some(Err(error));
+        }
+        self.current += 1;
+        Some(Ok(()))
+    }
+
+    /// Replays the log until `current` commands has been applied.
+    ///
+    /// If `current` is before the current position the receiver is reset to its initial state
+    /// before the commands are replayed.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned
+    /// and the player stops at the command that failed.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    pub fn seek(&mut self, current: usize) -> Option<Result<(), C::Error>> {
+        if current > self.len() {
+            return None;
+        }
+        if current < self.current {
+            self.rewind();
+        }
+        while self.current < current {
+            if let Err(error) = self.step().unwrap() {
+                return Some(Err(error));
+            }
+        }
+        Some(Ok(()))
+    }
+
+    /// Resets the receiver to its initial state.
+    #[inline]
+    pub fn rewind(&mut self) {
+        self.receiver = self.initial.clone();
+        self.current = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Player};
+
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            if self.0 == '!' {
+                return Err("invalid");
+            }
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, _: &mut String) -> Result<(), Self::Error> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn seek() {
+        let commands = vec![Add('a'), Add('b'), Add('!'), Add('c')];
+        let mut player: Player<String, _> = Player::new("", commands);
+        assert_eq!(player.seek(2), Some(Ok(())));
+        assert_eq!(player.as_receiver(), "ab");
+        assert_eq!(player.seek(4), Some(Err("invalid")));
+        assert_eq!(player.current(), 2);
+        assert_eq!(player.seek(1), Some(Ok(())));
+        assert_eq!(player.as_receiver(), "a");
+        assert!(player.seek(5).is_none());
+        player.rewind();
+        assert_eq!(player.as_receiver(), "");
+        assert_eq!(player.step(), Some(Ok(())));
+        assert_eq!(player.as_receiver(), "a");
+    }
+}
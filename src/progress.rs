@@ -0,0 +1,114 @@
+use crate::Command;
+
+/// A command that reports its progress while it is applied.
+///
+/// Heavy commands, like transforms of large files, can take a long time to apply. Implementing
+/// this trait for the commands makes it possible to drive a progress bar from the command with
+/// [`Record::apply_with_progress`], while the record keeps track of the command as usual.
+/// Only the first application reports progress, undoing and redoing the command does not.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, ProgressCommand, Record};
+/// struct Fill(usize);
+///
+/// impl Command<String> for Fill {
+///     type Error = ();
+///
+///     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+///         self.apply_with_progress(s, &mut |_| ())
+///     }
+///
+///     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+///         s.truncate(s.len() - self.0);
+///         Ok(())
+///     }
+/// }
+///
+/// impl ProgressCommand<String> for Fill {
+///     fn apply_with_progress(
+///         &mut self,
+///         s: &mut String,
+///         progress: &mut dyn FnMut(f32),
+///     ) -> Result<(), ()> {
+///         for i in 0..self.0 {
+///             s.push('a');
+///             progress((i + 1) as f32 / self.0 as f32);
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut record = Record::default();
+/// let mut reported = Vec::new();
+/// record
+///     .apply_with_progress(Fill(4), |p| reported.push(p))
+///     .unwrap();
+/// assert_eq!(reported, [0.25, 0.5, 0.75, 1.0]);
+/// assert_eq!(record.as_receiver(), "aaaa");
+/// record.undo().unwrap().unwrap();
+/// assert_eq!(record.as_receiver(), "");
+/// ```
+///
+/// [`Record::apply_with_progress`]: struct.Record.html#method.apply_with_progress
+pub trait ProgressCommand<R>: Command<R> {
+    /// Applies the command on the receiver like [`apply`], calling `progress` with the fraction
+    /// of the work that is done, from `0.0` to `1.0`.
+    ///
+    /// The default implementation calls [`apply`] and reports `1.0` if it succeeds.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    fn apply_with_progress(
+        &mut self,
+        receiver: &mut R,
+        progress: &mut dyn FnMut(f32),
+    ) -> Result<(), Self::Error> {
+        self.apply(receiver)?;
+        progress(1.0);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, ProgressCommand, Record};
+
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = ();
+
+        fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+            if self.0 == '!' {
+                return Err(());
+            }
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+            self.0 = s.pop().ok_or(())?;
+            Ok(())
+        }
+    }
+
+    impl ProgressCommand<String> for Add {}
+
+    #[test]
+    fn default_progress() {
+        let mut record = Record::default();
+        let mut reported = Vec::new();
+        record
+            .apply_with_progress(Add('a'), |p| reported.push(p))
+            .unwrap();
+        assert!(record
+            .apply_with_progress(Add('!'), |p| reported.push(p))
+            .is_err());
+        assert_eq!(reported, [1.0]);
+        assert_eq!(record.len(), 1);
+        record.undo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "a");
+    }
+}
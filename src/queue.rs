@@ -1,4 +1,15 @@
-use crate::{Checkpoint, Command, History, Record, Signal};
+#[cfg(feature = "history")]
+use crate::History;
+#[cfg(feature = "checkpoint")]
+use crate::{Checkpoint, RollbackError};
+use crate::{Command, Composite, Record, Signal};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    mem,
+};
 
 /// A command queue wrapper.
 ///
@@ -35,7 +46,7 @@ use crate::{Checkpoint, Command, History, Record, Signal};
 #[derive(Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Queue<'a, T, C> {
     inner: &'a mut T,
-    queue: Vec<Action<C>>,
+    queue: Vec<Action<'a, C>>,
 }
 
 impl<'a, T, C> From<&'a mut T> for Queue<'a, T, C> {
@@ -109,9 +120,105 @@ impl<'a, T, C> Queue<'a, T, C> {
         self.queue.push(Action::Redo);
     }
 
+    /// Queues an `apply` action that is skipped if `guard` returns `false` when the queue is committed.
+    ///
+    /// This makes it possible to build the queue up front, and decide which of the commands
+    /// to apply when the queue is committed.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// # use std::cell::Cell;
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = ();
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         self.0 = s.pop().ok_or(())?;
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let selected = Cell::new(true);
+    /// let mut record = Record::default();
+    /// let mut queue = record.queue();
+    /// queue.apply(Add('a'));
+    /// queue.apply_if(Add('b'), || selected.get());
+    /// selected.set(false);
+    /// queue.commit().unwrap();
+    /// assert_eq!(record.as_receiver(), "a");
+    /// ```
+    #[inline]
+    pub fn apply_if(&mut self, command: C, guard: impl FnOnce() -> bool + 'a) {
+        self.queue
+            .push(Action::ApplyIf(command, Guard(Box::new(guard))));
+    }
+
+    /// Queues a label, which marks the position in the queue for [`commit_until`].
+    ///
+    /// The label does nothing when the queue is committed.
+    ///
+    /// [`commit_until`]: struct.Queue.html#method.commit_until
+    #[inline]
+    pub fn label(&mut self, label: impl Into<String>) {
+        self.queue.push(Action::Label(label.into()));
+    }
+
     /// Cancels the queued actions.
     #[inline]
     pub fn cancel(self) {}
+
+    /// Removes the label and the actions that were queued after it.
+    ///
+    /// Returns `false` if there is no label with the name in the queue.
+    #[inline]
+    fn truncate_at(&mut self, label: &str) -> bool {
+        let at = self
+            .queue
+            .iter()
+            .position(|action| matches!(action, Action::Label(l) if l == label));
+        match at {
+            Some(at) => {
+                self.queue.truncate(at);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Combines each run of consecutive `apply` actions into a single composite command.
+    #[inline]
+    fn compose(queue: Vec<Action<'a, C>>) -> Vec<Action<'a, C>>
+    where
+        C: From<Composite<C>>,
+    {
+        let mut actions = Vec::new();
+        let mut composite = Composite::new();
+        for action in queue {
+            match action {
+                Action::Apply(command) => composite.push(command),
+                Action::ApplyIf(command, guard) => {
+                    if guard.check() {
+                        composite.push(command);
+                    }
+                }
+                Action::Label(_) => {}
+                action => {
+                    if !composite.is_empty() {
+                        let command = C::from(mem::take(&mut composite));
+                        actions.push(Action::Apply(command));
+                    }
+                    actions.push(action);
+                }
+            }
+        }
+        if !composite.is_empty() {
+            actions.push(Action::Apply(C::from(composite)));
+        }
+        actions
+    }
 }
 
 impl<T, C> Extend<C> for Queue<'_, T, C> {
@@ -132,42 +239,149 @@ impl<R, C: Command<R>, F: FnMut(Signal)> Queue<'_, Record<R, C, F>, C> {
 
     /// Applies the actions that is queued.
     ///
+    /// If the signals are deferred, they are emitted once all the actions have been applied.
+    ///
     /// # Errors
     /// If an error occurs, it stops applying the actions and returns the error.
     #[inline]
     pub fn commit(self) -> Result<(), C::Error> {
-        for action in self.queue {
-            match action {
-                Action::Apply(command) => self.inner.apply(command)?,
-                Action::Undo => {
-                    if let Some(Err(error)) = self.inner.undo() {
-                        return Err(error);
+        let Queue { inner, queue } = self;
+        inner.transaction(|inner| {
+            for action in queue {
+                match action {
+                    Action::Apply(command) => inner.apply(command)?,
+                    Action::ApplyIf(command, guard) => {
+                        if guard.check() {
+                            inner.apply(command)?;
+                        }
                     }
-                }
-                Action::Redo => {
-                    if let Some(Err(error)) = self.inner.redo() {
-                        return Err(error);
+                    Action::Undo => {
+                        if let Some(Err(error)) = inner.undo() {
+                            return Err(error);
+                        }
                     }
+                    Action::Redo => {
+                        if let Some(Err(error)) = inner.redo() {
+                            return Err(error);
+                        }
+                    }
+                    Action::GoTo(_, current) => {
+                        if let Some(Err(error)) = inner.go_to(current) {
+                            return Err(error);
+                        }
+                    }
+                    Action::Label(_) => {}
                 }
-                Action::GoTo(_, current) => {
-                    if let Some(Err(error)) = self.inner.go_to(current) {
-                        return Err(error);
+            }
+            Ok(())
+        })
+    }
+
+    /// Applies the actions that were queued before `label`, and cancels the rest.
+    ///
+    /// Returns `Ok(false)` without applying any actions if `label` is not in the queue.
+    /// If the signals are deferred, they are emitted once all the actions have been applied.
+    ///
+    /// # Errors
+    /// If an error occurs, it stops applying the actions and returns the error.
+    #[inline]
+    pub fn commit_until(mut self, label: &str) -> Result<bool, C::Error> {
+        if !self.truncate_at(label) {
+            return Ok(false);
+        }
+        self.commit().map(|_| true)
+    }
+
+    /// Applies the actions that is queued, canceling all of them if one fails.
+    ///
+    /// If the signals are deferred, they are emitted once all the actions have been applied or canceled.
+    ///
+    /// # Errors
+    /// If an error occurs the actions that were applied are canceled,
+    /// and the error is returned together with the result of canceling them.
+    #[inline]
+    #[cfg(feature = "checkpoint")]
+    pub fn commit_atomic(self) -> Result<(), RollbackError<C::Error>> {
+        self.commit_if(|_| true).map(|_| ())
+    }
+
+    /// Applies the actions that is queued, and keeps them only if `f` returns `true`
+    /// for the receiver afterwards.
+    ///
+    /// Returns `Ok(true)` if the changes were kept, and `Ok(false)` if they were canceled.
+    /// If the signals are deferred, they are emitted once all the actions have been applied or canceled.
+    ///
+    /// # Errors
+    /// If an error occurs the actions that were applied are canceled,
+    /// and the error is returned together with the result of canceling them.
+    /// If the changes are rejected by `f` but can not be canceled, [`Rejected`] is returned.
+    ///
+    /// [`Rejected`]: enum.RollbackError.html#variant.Rejected
+    #[inline]
+    #[cfg(feature = "checkpoint")]
+    pub fn commit_if(self, f: impl FnOnce(&R) -> bool) -> Result<bool, RollbackError<C::Error>> {
+        let Queue { inner, queue } = self;
+        inner.transaction(|inner| {
+            let mut checkpoint = inner.checkpoint();
+            for action in queue {
+                let result = match action {
+                    Action::Apply(command) => checkpoint.apply(command),
+                    Action::ApplyIf(command, guard) => {
+                        if guard.check() {
+                            checkpoint.apply(command)
+                        } else {
+                            Ok(())
+                        }
                     }
+                    Action::Label(_) => Ok(()),
+                    Action::Undo => checkpoint.undo().unwrap_or(Ok(())),
+                    Action::Redo => checkpoint.redo().unwrap_or(Ok(())),
+                    Action::GoTo(_, current) => checkpoint.go_to(current).unwrap_or(Ok(())),
+                };
+                if let Err(error) = result {
+                    return Err(checkpoint.rollback(error));
                 }
             }
+            if f(checkpoint.as_receiver()) {
+                return Ok(true);
+            }
+            checkpoint.cancel().map_err(RollbackError::Rejected)?;
+            Ok(false)
+        })
+    }
+
+    /// Applies the actions that is queued, combining consecutive commands into one command.
+    ///
+    /// Each run of queued `apply` actions is applied as a single [`Composite`] command,
+    /// which means that it is undone and redone in one step.
+    ///
+    /// # Errors
+    /// If an error occurs, it stops applying the actions and returns the error.
+    ///
+    /// [`Composite`]: struct.Composite.html
+    #[inline]
+    pub fn commit_composite(self) -> Result<(), C::Error>
+    where
+        C: From<Composite<C>>,
+    {
+        let Queue { inner, queue } = self;
+        Queue {
+            inner,
+            queue: Queue::<Record<R, C, F>, C>::compose(queue),
         }
-        Ok(())
+        .commit()
     }
 
     /// Returns a checkpoint.
     #[inline]
-    pub fn checkpoint(&mut self) -> Checkpoint<Record<R, C, F>, C> {
+    #[cfg(feature = "checkpoint")]
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, Record<R, C, F>, C> {
         self.inner.checkpoint()
     }
 
     /// Returns a queue.
     #[inline]
-    pub fn queue(&mut self) -> Queue<Record<R, C, F>, C> {
+    pub fn queue(&mut self) -> Queue<'_, Record<R, C, F>, C> {
         self.inner.queue()
     }
 
@@ -200,6 +414,7 @@ impl<R, C, F> AsMut<R> for Queue<'_, Record<R, C, F>, C> {
     }
 }
 
+#[cfg(feature = "history")]
 impl<R, C: Command<R>, F: FnMut(Signal)> Queue<'_, History<R, C, F>, C> {
     /// Queues a `go_to` action.
     #[inline]
@@ -209,42 +424,151 @@ impl<R, C: Command<R>, F: FnMut(Signal)> Queue<'_, History<R, C, F>, C> {
 
     /// Applies the actions that is queued.
     ///
+    /// If the signals are deferred, they are emitted once all the actions have been applied.
+    ///
     /// # Errors
     /// If an error occurs, it stops applying the actions and returns the error.
     #[inline]
     pub fn commit(self) -> Result<(), C::Error> {
-        for action in self.queue {
-            match action {
-                Action::Apply(command) => self.inner.apply(command)?,
-                Action::Undo => {
-                    if let Some(Err(error)) = self.inner.undo() {
-                        return Err(error);
+        let Queue { inner, queue } = self;
+        inner.transaction(|inner| {
+            for action in queue {
+                match action {
+                    Action::Apply(command) => inner.apply(command)?,
+                    Action::ApplyIf(command, guard) => {
+                        if guard.check() {
+                            inner.apply(command)?;
+                        }
                     }
-                }
-                Action::Redo => {
-                    if let Some(Err(error)) = self.inner.redo() {
-                        return Err(error);
+                    Action::Undo => {
+                        if let Some(Err(error)) = inner.undo() {
+                            return Err(error);
+                        }
+                    }
+                    Action::Redo => {
+                        if let Some(Err(error)) = inner.redo() {
+                            return Err(error);
+                        }
                     }
+                    Action::GoTo(branch, current) => {
+                        if let Some(Err(error)) = inner.go_to(branch, current) {
+                            return Err(error);
+                        }
+                    }
+                    Action::Label(_) => {}
                 }
-                Action::GoTo(branch, current) => {
-                    if let Some(Err(error)) = self.inner.go_to(branch, current) {
-                        return Err(error);
+            }
+            Ok(())
+        })
+    }
+
+    /// Applies the actions that were queued before `label`, and cancels the rest.
+    ///
+    /// Returns `Ok(false)` without applying any actions if `label` is not in the queue.
+    /// If the signals are deferred, they are emitted once all the actions have been applied.
+    ///
+    /// # Errors
+    /// If an error occurs, it stops applying the actions and returns the error.
+    #[inline]
+    pub fn commit_until(mut self, label: &str) -> Result<bool, C::Error> {
+        if !self.truncate_at(label) {
+            return Ok(false);
+        }
+        self.commit().map(|_| true)
+    }
+
+    /// Applies the actions that is queued, canceling all of them if one fails.
+    ///
+    /// If the signals are deferred, they are emitted once all the actions have been applied or canceled.
+    ///
+    /// # Errors
+    /// If an error occurs the actions that were applied are canceled,
+    /// and the error is returned together with the result of canceling them.
+    #[inline]
+    #[cfg(feature = "checkpoint")]
+    pub fn commit_atomic(self) -> Result<(), RollbackError<C::Error>> {
+        self.commit_if(|_| true).map(|_| ())
+    }
+
+    /// Applies the actions that is queued, and keeps them only if `f` returns `true`
+    /// for the receiver afterwards.
+    ///
+    /// Returns `Ok(true)` if the changes were kept, and `Ok(false)` if they were canceled.
+    /// If the signals are deferred, they are emitted once all the actions have been applied or canceled.
+    ///
+    /// # Errors
+    /// If an error occurs the actions that were applied are canceled,
+    /// and the error is returned together with the result of canceling them.
+    /// If the changes are rejected by `f` but can not be canceled, [`Rejected`] is returned.
+    ///
+    /// [`Rejected`]: enum.RollbackError.html#variant.Rejected
+    #[inline]
+    #[cfg(feature = "checkpoint")]
+    pub fn commit_if(self, f: impl FnOnce(&R) -> bool) -> Result<bool, RollbackError<C::Error>> {
+        let Queue { inner, queue } = self;
+        inner.transaction(|inner| {
+            let mut checkpoint = inner.checkpoint();
+            for action in queue {
+                let result = match action {
+                    Action::Apply(command) => checkpoint.apply(command),
+                    Action::ApplyIf(command, guard) => {
+                        if guard.check() {
+                            checkpoint.apply(command)
+                        } else {
+                            Ok(())
+                        }
                     }
+                    Action::Label(_) => Ok(()),
+                    Action::Undo => checkpoint.undo().unwrap_or(Ok(())),
+                    Action::Redo => checkpoint.redo().unwrap_or(Ok(())),
+                    Action::GoTo(branch, current) => {
+                        checkpoint.go_to(branch, current).unwrap_or(Ok(()))
+                    }
+                };
+                if let Err(error) = result {
+                    return Err(checkpoint.rollback(error));
                 }
             }
+            if f(checkpoint.as_receiver()) {
+                return Ok(true);
+            }
+            checkpoint.cancel().map_err(RollbackError::Rejected)?;
+            Ok(false)
+        })
+    }
+
+    /// Applies the actions that is queued, combining consecutive commands into one command.
+    ///
+    /// Each run of queued `apply` actions is applied as a single [`Composite`] command,
+    /// which means that it is undone and redone in one step.
+    ///
+    /// # Errors
+    /// If an error occurs, it stops applying the actions and returns the error.
+    ///
+    /// [`Composite`]: struct.Composite.html
+    #[inline]
+    pub fn commit_composite(self) -> Result<(), C::Error>
+    where
+        C: From<Composite<C>>,
+    {
+        let Queue { inner, queue } = self;
+        Queue {
+            inner,
+            queue: Queue::<History<R, C, F>, C>::compose(queue),
         }
-        Ok(())
+        .commit()
     }
 
     /// Returns a checkpoint.
     #[inline]
-    pub fn checkpoint(&mut self) -> Checkpoint<History<R, C, F>, C> {
+    #[cfg(feature = "checkpoint")]
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, History<R, C, F>, C> {
         self.inner.checkpoint()
     }
 
     /// Returns a queue.
     #[inline]
-    pub fn queue(&mut self) -> Queue<History<R, C, F>, C> {
+    pub fn queue(&mut self) -> Queue<'_, History<R, C, F>, C> {
         self.inner.queue()
     }
 
@@ -263,6 +587,7 @@ impl<R, C: Command<R>, F: FnMut(Signal)> Queue<'_, History<R, C, F>, C> {
     }
 }
 
+#[cfg(feature = "history")]
 impl<R, C, F> AsRef<R> for Queue<'_, History<R, C, F>, C> {
     #[inline]
     fn as_ref(&self) -> &R {
@@ -270,6 +595,7 @@ impl<R, C, F> AsRef<R> for Queue<'_, History<R, C, F>, C> {
     }
 }
 
+#[cfg(feature = "history")]
 impl<R, C, F> AsMut<R> for Queue<'_, History<R, C, F>, C> {
     #[inline]
     fn as_mut(&mut self) -> &mut R {
@@ -278,17 +604,66 @@ impl<R, C, F> AsMut<R> for Queue<'_, History<R, C, F>, C> {
 }
 
 /// An action that can be applied to a Record or History.
-#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
-enum Action<C> {
+#[derive(Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+enum Action<'a, C> {
     Apply(C),
+    ApplyIf(C, Guard<'a>),
     Undo,
     Redo,
     GoTo(usize, usize),
+    Label(String),
+}
+
+/// The condition of an `apply` action, which is checked when the queue is committed.
+///
+/// The closure is ignored when comparing and hashing queues.
+struct Guard<'a>(Box<dyn FnOnce() -> bool + 'a>);
+
+impl Guard<'_> {
+    #[inline]
+    fn check(self) -> bool {
+        (self.0)()
+    }
+}
+
+impl PartialEq for Guard<'_> {
+    #[inline]
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Guard<'_> {}
+
+impl PartialOrd for Guard<'_> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Guard<'_> {
+    #[inline]
+    fn cmp(&self, _: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl Hash for Guard<'_> {
+    #[inline]
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+impl fmt::Debug for Guard<'_> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Guard")
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Command, Record};
+    use crate::{Command, Composite, Record};
     use std::error;
 
     struct Add(char);
@@ -330,4 +705,92 @@ mod tests {
         q1.commit().unwrap();
         assert_eq!(record.as_receiver(), "abc");
     }
+
+    #[test]
+    #[cfg(feature = "checkpoint")]
+    fn commit_if() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        let mut queue = record.queue();
+        queue.apply(Add('b'));
+        queue.undo();
+        queue.undo();
+        queue.apply(Add('c'));
+        assert!(!queue.commit_if(|s| s.len() > 1).unwrap());
+        assert_eq!(record.as_receiver(), "a");
+        assert_eq!(record.len(), 1);
+        let mut queue = record.queue();
+        queue.apply(Add('b'));
+        assert!(queue.commit_if(|s| s.len() > 1).unwrap());
+        assert_eq!(record.as_receiver(), "ab");
+    }
+
+    #[test]
+    fn commit_until() {
+        let selected = std::cell::Cell::new(true);
+        let mut record = Record::default();
+        let mut queue = record.queue();
+        queue.apply(Add('a'));
+        queue.apply_if(Add('b'), || selected.get());
+        queue.apply_if(Add('c'), || false);
+        queue.label("preview");
+        queue.apply(Add('d'));
+        assert!(!queue.commit_until("missing").unwrap());
+        assert_eq!(record.as_receiver(), "");
+        let mut queue = record.queue();
+        queue.apply(Add('a'));
+        queue.apply_if(Add('b'), || selected.get());
+        queue.apply_if(Add('c'), || false);
+        queue.label("preview");
+        queue.apply(Add('d'));
+        selected.set(false);
+        assert!(queue.commit_until("preview").unwrap());
+        assert_eq!(record.as_receiver(), "a");
+        assert_eq!(record.len(), 1);
+    }
+
+    #[test]
+    fn commit_composite() {
+        enum Edit {
+            Add(Add),
+            Composite(Composite<Edit>),
+        }
+
+        impl Command<String> for Edit {
+            type Error = Box<dyn error::Error>;
+
+            fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+                match self {
+                    Edit::Add(add) => add.apply(s),
+                    Edit::Composite(composite) => composite.apply(s),
+                }
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+                match self {
+                    Edit::Add(add) => add.undo(s),
+                    Edit::Composite(composite) => composite.undo(s),
+                }
+            }
+        }
+
+        impl From<Composite<Edit>> for Edit {
+            fn from(composite: Composite<Edit>) -> Self {
+                Edit::Composite(composite)
+            }
+        }
+
+        let mut record = Record::default();
+        let mut queue = record.queue();
+        queue.apply(Edit::Add(Add('a')));
+        queue.apply(Edit::Add(Add('b')));
+        queue.undo();
+        queue.apply(Edit::Add(Add('c')));
+        queue.commit_composite().unwrap();
+        assert_eq!(record.as_receiver(), "c");
+        assert_eq!(record.len(), 1);
+        record.undo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "c");
+    }
 }
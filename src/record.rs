@@ -1,19 +1,60 @@
+#[cfg(feature = "testing")]
+use crate::testing;
 #[cfg(feature = "display")]
 use crate::Display;
-use crate::{Checkpoint, Command, Entry, History, Merge, Queue, Signal};
-#[cfg(feature = "serde")]
-use serde::{Deserialize, Serialize};
-#[cfg(feature = "display")]
-use std::fmt;
-use std::{collections::VecDeque, marker::PhantomData, num::NonZeroUsize};
-#[cfg(feature = "chrono")]
-use {
-    chrono::{DateTime, TimeZone, Utc},
-    std::cmp::Ordering,
+#[cfg(feature = "history")]
+use crate::History;
+#[cfg(feature = "queue")]
+use crate::Queue;
+#[cfg(any(feature = "chrono", feature = "time"))]
+use crate::Time;
+use crate::{
+    cache::SnapshotCache,
+    cursor::Cursor,
+    elapsed,
+    hooks::{self, Hooks},
+    ignored::Ignored,
+    slot::{SignalOrder, Slot, State, SubscriptionId},
+    stack::{self, Pushed},
+    weight::WeightLimit,
+    AsPatch, Command, ConfigError, Entry, Error, Event, LoggedCommand, Merge, Mutator, Op,
+    OpContext, ProgressCommand, Signal, Snapshot, Timestamp,
+};
+#[cfg(feature = "std")]
+use crate::{clock::SharedClock, now, time, Clock, Heatmap};
+#[cfg(feature = "checkpoint")]
+use crate::{Checkpoint, RollbackError};
+#[cfg(feature = "testing")]
+use alloc::format;
+use alloc::{
+    boxed::Box,
+    collections::{vec_deque, BTreeMap, VecDeque},
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
 };
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, TimeZone, Utc};
+#[cfg(any(feature = "chrono", feature = "time"))]
+use core::cmp::Ordering;
+#[cfg(any(feature = "display", feature = "serde"))]
+use core::fmt;
+#[cfg(feature = "serde")]
+use core::{error, iter};
+use core::{marker::PhantomData, mem, num::NonZeroUsize, ops::RangeBounds, time::Duration};
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "std")]
+use std::sync::mpsc::Receiver;
+#[cfg(all(feature = "std", not(any(feature = "chrono", feature = "time"))))]
+use std::time::SystemTime;
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+use time::OffsetDateTime;
 
-#[allow(unsafe_code)]
-const MAX_LIMIT: NonZeroUsize = unsafe { NonZeroUsize::new_unchecked(usize::max_value()) };
+const MAX_LIMIT: NonZeroUsize = match NonZeroUsize::new(usize::MAX) {
+    Some(limit) => limit,
+    None => unreachable!(),
+};
 
 /// A record of commands.
 ///
@@ -58,7 +99,12 @@ const MAX_LIMIT: NonZeroUsize = unsafe { NonZeroUsize::new_unchecked(usize::max_
 ///
 /// [`builder`]: struct.RecordBuilder.html
 /// [signal]: enum.Signal.html
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+// The derived implementations are wrapped by the implementations below, which validate the record.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(remote = "Self")
+)]
 #[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Record<R, C, F = fn(Signal)> {
     pub(crate) commands: VecDeque<Entry<C>>,
@@ -66,10 +112,66 @@ pub struct Record<R, C, F = fn(Signal)> {
     current: usize,
     limit: NonZeroUsize,
     pub(crate) saved: Option<usize>,
-    #[cfg_attr(feature = "serde", serde(default = "Option::default", skip))]
-    pub(crate) slot: Option<F>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    next_id: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    mutated: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) keep_noops: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    cascade_annul: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    merge_within: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    merge_across_saved: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    last_applied: Ignored<Option<Timestamp>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    view: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    group: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    group_first: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    generation: u64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) failure: Option<OpContext>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) branch: usize,
+    #[cfg_attr(feature = "serde", serde(default = "Hooks::default", skip))]
+    hooks: Hooks<R, C>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    nav: Option<Navigation>,
+    #[cfg_attr(feature = "serde", serde(default = "SnapshotCache::default", skip))]
+    cache: SnapshotCache<R>,
+    #[cfg_attr(feature = "serde", serde(default = "WeightLimit::default", skip))]
+    weight: WeightLimit<C>,
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "serde", serde(default = "SharedClock::default", skip))]
+    clock: SharedClock,
+    /// The named marks, and the id of the command at each mark or `None` for the start.
+    #[cfg_attr(feature = "serde", serde(default))]
+    marks: BTreeMap<String, Option<usize>>,
+    /// The indices of the commands removed by `undo` and `redo`, if a history keeps track of them.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) removed: Ignored<Option<Vec<usize>>>,
+    #[cfg_attr(feature = "serde", serde(default = "Slot::default", skip))]
+    pub(crate) slot: Slot<F>,
+}
+
+/// The positions that can be navigated back and forward to.
+#[derive(Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+struct Navigation {
+    back: Vec<usize>,
+    forward: Vec<usize>,
 }
 
+/// A record with a boxed slot that can be sent between threads.
+pub type SendRecord<R, C> = Record<R, C, Box<dyn FnMut(Signal) + Send>>;
+
+/// A record with a boxed slot that can not be sent between threads.
+pub type LocalRecord<R, C> = Record<R, C, Box<dyn FnMut(Signal)>>;
+
 impl<R, C> Record<R, C> {
     /// Returns a new record.
     #[inline]
@@ -80,7 +182,28 @@ impl<R, C> Record<R, C> {
             current: 0,
             limit: MAX_LIMIT,
             saved: Some(0),
-            slot: None,
+            next_id: 0,
+            mutated: false,
+            keep_noops: false,
+            cascade_annul: false,
+            merge_within: None,
+            merge_across_saved: false,
+            last_applied: Ignored(None),
+            view: None,
+            group: 0,
+            group_first: false,
+            generation: 0,
+            failure: None,
+            branch: 0,
+            hooks: Hooks::default(),
+            nav: None,
+            cache: SnapshotCache::default(),
+            weight: WeightLimit::default(),
+            #[cfg(feature = "std")]
+            clock: SharedClock::default(),
+            marks: BTreeMap::new(),
+            removed: Ignored::default(),
+            slot: Slot::default(),
         }
     }
 
@@ -89,6 +212,44 @@ impl<R, C> Record<R, C> {
     pub fn builder() -> RecordBuilder<R, C> {
         RecordBuilder::new()
     }
+
+    /// Creates a record from a record without a slot and the slot.
+    ///
+    /// This is the inverse of [`into_parts`].
+    ///
+    /// [`into_parts`]: struct.Record.html#method.into_parts
+    #[inline]
+    pub fn from_parts<F>(record: Record<R, C>, slot: Option<F>) -> Record<R, C, F> {
+        Record {
+            commands: record.commands,
+            receiver: record.receiver,
+            current: record.current,
+            limit: record.limit,
+            saved: record.saved,
+            next_id: record.next_id,
+            mutated: record.mutated,
+            keep_noops: record.keep_noops,
+            cascade_annul: record.cascade_annul,
+            merge_within: record.merge_within,
+            merge_across_saved: record.merge_across_saved,
+            last_applied: record.last_applied,
+            view: record.view,
+            group: record.group,
+            group_first: record.group_first,
+            generation: record.generation,
+            failure: record.failure,
+            branch: record.branch,
+            hooks: record.hooks,
+            nav: record.nav,
+            cache: record.cache,
+            weight: record.weight,
+            #[cfg(feature = "std")]
+            clock: record.clock,
+            marks: record.marks,
+            removed: record.removed,
+            slot: slot.map_or_else(Slot::default, Slot::from),
+        }
+    }
 }
 
 impl<R, C, F> Record<R, C, F> {
@@ -137,12 +298,40 @@ impl<R, C, F> Record<R, C, F> {
         self.limit.get()
     }
 
+    /// Checks that the positions in the record are within its commands and its limit.
+    ///
+    /// This is done when a record is deserialized, so invalid data is rejected
+    /// instead of causing a panic later when the record is used.
+    ///
+    /// # Errors
+    /// Returns the first invalid value that is found.
+    #[inline]
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let len = self.len();
+        if self.current > len {
+            return Err(ConfigError::CurrentOutOfBounds {
+                current: self.current,
+                len,
+            });
+        }
+        if len > self.limit() {
+            return Err(ConfigError::LimitExceeded {
+                len,
+                limit: self.limit(),
+            });
+        }
+        match self.saved {
+            Some(saved) if saved > len => Err(ConfigError::SavedOutOfBounds { saved, len }),
+            _ => Ok(()),
+        }
+    }
+
     /// Sets how the signal should be handled when the state changes.
     ///
     /// The previous slot is returned if it exists.
     #[inline]
     pub fn connect(&mut self, slot: F) -> Option<F> {
-        self.slot.replace(slot)
+        self.slot.f.replace(slot)
     }
 
     /// Creates a new record that uses the provided slot.
@@ -154,20 +343,452 @@ impl<R, C, F> Record<R, C, F> {
             current: self.current,
             limit: self.limit,
             saved: self.saved,
-            slot: Some(slot),
+            next_id: self.next_id,
+            mutated: self.mutated,
+            keep_noops: self.keep_noops,
+            cascade_annul: self.cascade_annul,
+            merge_within: self.merge_within,
+            merge_across_saved: self.merge_across_saved,
+            last_applied: self.last_applied,
+            view: self.view,
+            group: self.group,
+            group_first: self.group_first,
+            generation: self.generation,
+            failure: self.failure,
+            branch: self.branch,
+            hooks: self.hooks,
+            nav: self.nav,
+            cache: self.cache,
+            weight: self.weight,
+            #[cfg(feature = "std")]
+            clock: self.clock,
+            marks: self.marks,
+            removed: self.removed,
+            slot: Slot::from(slot),
         }
     }
 
     /// Removes and returns the slot.
     #[inline]
     pub fn disconnect(&mut self) -> Option<F> {
-        self.slot.take()
+        self.slot.f.take()
+    }
+
+    /// Adds a slot that receives the signals in addition to the connected slot.
+    ///
+    /// Any number of slots can be subscribed, and they receive the signals in the order they
+    /// were added, after the connected slot. The returned id is used to remove the slot with
+    /// [`unsubscribe`]. The subscribed slots are dropped by [`connect_with`] and [`into_parts`].
+    ///
+    /// [`unsubscribe`]: struct.Record.html#method.unsubscribe
+    /// [`connect_with`]: struct.Record.html#method.connect_with
+    /// [`into_parts`]: struct.Record.html#method.into_parts
+    #[inline]
+    pub fn subscribe(&mut self, slot: F) -> SubscriptionId {
+        self.slot.subscribe(slot)
+    }
+
+    /// Removes and returns the subscribed slot with the `id`.
+    #[inline]
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> Option<F> {
+        self.slot.unsubscribe(id)
+    }
+
+    /// Returns a receiver that receives the signals in addition to the slots.
+    ///
+    /// It can be polled instead of reacting to the signals in a slot, which fits well with
+    /// immediate mode user interfaces. Any number of receivers can be created, and a receiver
+    /// only receives the signals emitted after it was created. The receivers stop receiving
+    /// signals after [`connect_with`] and [`into_parts`].
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record, Signal};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = ();
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.pop();
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let mut record = Record::default();
+    /// let signals = record.signal_receiver();
+    /// record.apply(Add('a')).unwrap();
+    /// let signals: Vec<_> = signals.try_iter().collect();
+    /// assert_eq!(signals, vec![Signal::Current { old: 0, new: 1 }, Signal::Undo(true), Signal::Saved(false)]);
+    /// ```
+    ///
+    /// [`connect_with`]: struct.Record.html#method.connect_with
+    /// [`into_parts`]: struct.Record.html#method.into_parts
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn signal_receiver(&mut self) -> Receiver<Signal> {
+        self.slot.receiver()
+    }
+
+    /// Sets a callback that is called with every command that is removed from the start of the
+    /// record because the limit is reached, right before it is dropped.
+    ///
+    /// This can be used to release external resources that are held by the commands.
+    /// The callback is shared with the clones of the record, and it is not serialized.
+    #[inline]
+    pub fn on_evict(&mut self, f: impl Fn(C) + Send + Sync + 'static) {
+        self.hooks.evict = Ignored(Some(Arc::new(f)));
+    }
+
+    /// Removes the callback set by [`on_evict`].
+    ///
+    /// [`on_evict`]: struct.Record.html#method.on_evict
+    #[inline]
+    pub fn clear_evict(&mut self) {
+        self.hooks.evict = Ignored(None);
+    }
+
+    /// Sets if the jumps made by [`go_to`] should be tracked (off by default).
+    ///
+    /// When on, the position before every jump is remembered, so [`nav_back`] can return to it,
+    /// and [`nav_forward`] can return to the position [`nav_back`] left. This is useful when
+    /// jumping around in the record is part of the workflow. Turning it off forgets the positions.
+    ///
+    /// [`go_to`]: struct.Record.html#method.go_to
+    /// [`nav_back`]: struct.Record.html#method.nav_back
+    /// [`nav_forward`]: struct.Record.html#method.nav_forward
+    #[inline]
+    pub fn track_navigation(&mut self, on: bool) {
+        if !on {
+            self.nav = None;
+        } else if self.nav.is_none() {
+            self.nav = Some(Navigation::default());
+        }
+    }
+
+    /// Returns `true` if there is a position to navigate back to.
+    #[inline]
+    pub fn can_nav_back(&self) -> bool {
+        self.nav.as_ref().is_some_and(|nav| !nav.back.is_empty())
+    }
+
+    /// Returns `true` if there is a position to navigate forward to.
+    #[inline]
+    pub fn can_nav_forward(&self) -> bool {
+        self.nav.as_ref().is_some_and(|nav| !nav.forward.is_empty())
+    }
+
+    /// Sets if panics in the slot should be caught (off by default).
+    ///
+    /// When on, a panic in the slot does not unwind through the record, and the remaining
+    /// signals are still delivered. The panic can be retrieved with [`take_slot_panic`].
+    ///
+    /// [`take_slot_panic`]: struct.Record.html#method.take_slot_panic
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn catch_slot_panics(&mut self, on: bool) {
+        self.slot.catch = on;
+    }
+
+    /// Sets if no-op commands should be stored in the record (off by default).
+    ///
+    /// When off, commands whose [`is_noop`] method returns `true` are neither applied nor stored.
+    /// When on, they are applied and stored but marked as no-ops, see [`is_noop`][Record::is_noop].
+    ///
+    /// [`is_noop`]: trait.Command.html#method.is_noop
+    #[inline]
+    pub fn keep_noops(&mut self, on: bool) {
+        self.keep_noops = on;
+    }
+
+    /// Sets if annulled commands should cascade (off by default).
+    ///
+    /// When on, after a command is annulled the record tries to [`merge`] the two commands
+    /// that are now on top of it, and keeps doing so as long as they merge or annul each other.
+    /// This makes sequences like insert, delete, insert, delete fully collapse.
+    /// Commands are only merged across the saved state if [`merge_across_saved`] is on.
+    ///
+    /// [`merge`]: trait.Command.html#method.merge
+    /// [`merge_across_saved`]: struct.RecordBuilder.html#method.merge_across_saved
+    #[inline]
+    pub fn cascade_annul(&mut self, on: bool) {
+        self.cascade_annul = on;
+    }
+
+    /// Returns `true` if the command at `index` was a no-op when it was applied.
+    #[inline]
+    pub fn is_noop(&self, index: usize) -> bool {
+        self.commands.get(index).is_some_and(|entry| entry.noop)
+    }
+
+    /// Sets if the [`Operation`] signal should be emitted (off by default).
+    ///
+    /// When on, the signals caused by one operation are preceded by an `Operation` signal with
+    /// a unique id, which makes it possible for the slot to batch its reactions to the signals.
+    ///
+    /// [`Operation`]: enum.Signal.html#variant.Operation
+    #[inline]
+    pub fn emit_operation_ids(&mut self, on: bool) {
+        self.slot.operations = on;
+    }
+
+    /// Sets if the signals of operations with several steps should be deferred (off by default).
+    ///
+    /// When off, every step of an operation like [`go_to`] or [`extend`] emits its signals when
+    /// it is done. When on, the signals are only emitted once the whole operation is done, and
+    /// only the net changes are emitted, even if it fails halfway. This means that the slot never
+    /// observes an intermediate state.
+    ///
+    /// [`go_to`]: struct.Record.html#method.go_to
+    /// [`extend`]: struct.Record.html#method.extend
+    #[inline]
+    pub fn defer_signals(&mut self, on: bool) {
+        self.slot.defer = on;
+    }
+
+    /// Sets the order the signals caused by one step are emitted in, see [`SignalOrder`].
+    ///
+    /// [`SignalOrder`]: enum.SignalOrder.html
+    #[inline]
+    pub fn signal_order(&mut self, order: SignalOrder) {
+        self.slot.order = order;
+    }
+
+    /// Removes and returns the message of the first panic caught in the slot.
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn take_slot_panic(&mut self) -> Option<String> {
+        self.slot.panic.take()
+    }
+
+    /// Splits the record into a record without a slot, and the slot.
+    ///
+    /// The returned record can be sent between threads if the receiver and the commands can,
+    /// regardless of the slot. The slot can be attached again with [`from_parts`].
+    ///
+    /// [`from_parts`]: struct.Record.html#method.from_parts
+    #[inline]
+    pub fn into_parts(self) -> (Record<R, C>, Option<F>) {
+        let record = Record {
+            commands: self.commands,
+            receiver: self.receiver,
+            current: self.current,
+            limit: self.limit,
+            saved: self.saved,
+            next_id: self.next_id,
+            mutated: self.mutated,
+            keep_noops: self.keep_noops,
+            cascade_annul: self.cascade_annul,
+            merge_within: self.merge_within,
+            merge_across_saved: self.merge_across_saved,
+            last_applied: self.last_applied,
+            view: self.view,
+            group: self.group,
+            group_first: self.group_first,
+            generation: self.generation,
+            failure: self.failure,
+            branch: self.branch,
+            hooks: self.hooks,
+            nav: self.nav,
+            cache: self.cache,
+            weight: self.weight,
+            #[cfg(feature = "std")]
+            clock: self.clock,
+            marks: self.marks,
+            removed: self.removed,
+            slot: Slot::default(),
+        };
+        (record, self.slot.f)
+    }
+
+    /// Returns a clone of the record without the slot.
+    ///
+    /// The receiver and the commands are cloned, and the commands keep their time stamps.
+    /// The connected and subscribed slots are not cloned, which means that the record does not
+    /// have to be cloneable when it has a slot. The hooks are shared with the clone.
+    ///
+    /// Commands that are expensive to clone can be stored in an [`Arc`], which makes the clone
+    /// share them until one of the records changes them.
+    ///
+    /// [`Arc`]: https://doc.rust-lang.org/std/sync/struct.Arc.html
+    #[inline]
+    pub fn try_clone(&self) -> Record<R, C>
+    where
+        R: Clone,
+        C: Clone,
+    {
+        Record {
+            commands: self.commands.clone(),
+            receiver: self.receiver.clone(),
+            current: self.current,
+            limit: self.limit,
+            saved: self.saved,
+            next_id: self.next_id,
+            mutated: self.mutated,
+            keep_noops: self.keep_noops,
+            cascade_annul: self.cascade_annul,
+            merge_within: self.merge_within,
+            merge_across_saved: self.merge_across_saved,
+            last_applied: self.last_applied,
+            view: self.view,
+            group: self.group,
+            group_first: self.group_first,
+            generation: self.generation,
+            failure: self.failure,
+            branch: self.branch,
+            hooks: self.hooks.clone(),
+            nav: self.nav.clone(),
+            cache: self.cache.clone(),
+            weight: self.weight.clone(),
+            #[cfg(feature = "std")]
+            clock: self.clock.clone(),
+            marks: self.marks.clone(),
+            removed: Ignored::default(),
+            slot: Slot::default(),
+        }
     }
 
     /// Returns `true` if the receiver is in a saved state, `false` otherwise.
     #[inline]
     pub fn is_saved(&self) -> bool {
-        self.saved.map_or(false, |saved| saved == self.current())
+        self.saved == Some(self.current())
+    }
+
+    /// Returns the position of the saved state, or `None` if the receiver has not been saved
+    /// or the saved state is no longer in the record.
+    #[inline]
+    pub fn saved(&self) -> Option<usize> {
+        self.saved
+    }
+
+    /// Marks the current position with `name`, replacing the previous mark with the name.
+    ///
+    /// Unlike the saved state, there can be any number of marks, which can be used to keep track
+    /// of several generations of autosaves. A mark is removed when the command at it is removed
+    /// from the record, e.g. by the limit, or when another command is merged into it.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = ();
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.pop();
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let mut record = Record::default();
+    /// record.apply(Add('a')).unwrap();
+    /// record.mark("autosave-1");
+    /// record.apply(Add('b')).unwrap();
+    /// record.mark("autosave-2");
+    /// record.apply(Add('c')).unwrap();
+    /// assert_eq!(record.marks().collect::<Vec<_>>(), [("autosave-1", 1), ("autosave-2", 2)]);
+    /// record.go_to_mark("autosave-1").unwrap().unwrap();
+    /// assert_eq!(record.as_receiver(), "a");
+    /// ```
+    #[inline]
+    pub fn mark(&mut self, name: impl Into<String>) {
+        let at = self.id_at(self.current);
+        self.marks.insert(name.into(), at);
+    }
+
+    /// Removes the mark with `name`, returning its position if it was in the record.
+    #[inline]
+    pub fn unmark(&mut self, name: &str) -> Option<usize> {
+        let at = self.marks.remove(name)?;
+        self.position_of(at)
+    }
+
+    /// Returns the position of the mark with `name`, or `None` if there is no such mark.
+    #[inline]
+    pub fn mark_position(&self, name: &str) -> Option<usize> {
+        self.marks.get(name).and_then(|&at| self.position_of(at))
+    }
+
+    /// Returns an iterator over the names and positions of the marks, ordered by name.
+    #[inline]
+    pub fn marks(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.marks
+            .iter()
+            .filter_map(move |(name, &at)| Some((name.as_str(), self.position_of(at)?)))
+    }
+
+    /// Returns the id of the command at `position`, or `None` for the start of the record.
+    #[inline]
+    fn id_at(&self, position: usize) -> Option<usize> {
+        position.checked_sub(1).map(|index| self.commands[index].id)
+    }
+
+    /// Returns the position of the mark that is at the command with the id `at`.
+    #[inline]
+    fn position_of(&self, at: Option<usize>) -> Option<usize> {
+        match at {
+            Some(id) => self
+                .commands
+                .iter()
+                .position(|entry| entry.id == id)
+                .map(|index| index + 1),
+            None => Some(0),
+        }
+    }
+
+    /// Keeps track of the index of a command that was removed by `undo` or `redo`.
+    #[inline]
+    fn removed(&mut self, index: usize) {
+        if let Some(removed) = &mut *self.removed {
+            removed.push(index);
+        }
+    }
+
+    /// Removes the marks at the start of the record and the marks at commands that have been removed.
+    #[inline]
+    fn retain_marks(&mut self) {
+        let commands = &self.commands;
+        self.marks
+            .retain(|_, at| at.is_some_and(|id| commands.iter().any(|entry| entry.id == id)));
+    }
+
+    /// Returns how many commands separate the current state from the saved state,
+    /// or `None` if the receiver has not been saved or the saved state is no longer in the record.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = ();
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.pop();
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let mut record = Record::default();
+    /// record.set_saved(true);
+    /// record.apply(Add('a')).unwrap();
+    /// record.apply(Add('b')).unwrap();
+    /// record.apply(Add('c')).unwrap();
+    /// let unsaved = record.unsaved_count().unwrap();
+    /// assert_eq!(unsaved.count(), 3);
+    /// assert_eq!(unsaved.undo(), 3);
+    /// ```
+    #[inline]
+    pub fn unsaved_count(&self) -> Option<UnsavedCount> {
+        let saved = self.saved?;
+        Some(UnsavedCount::new(
+            self.current.saturating_sub(saved),
+            saved.saturating_sub(self.current),
+        ))
     }
 
     /// Returns `true` if the record can undo.
@@ -184,16 +805,57 @@ impl<R, C, F> Record<R, C, F> {
 
     /// Returns a checkpoint.
     #[inline]
-    pub fn checkpoint(&mut self) -> Checkpoint<Record<R, C, F>, C> {
+    #[cfg(feature = "checkpoint")]
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, Record<R, C, F>, C> {
         Checkpoint::from(self)
     }
 
     /// Returns a queue.
     #[inline]
-    pub fn queue(&mut self) -> Queue<Record<R, C, F>, C> {
+    #[cfg(feature = "queue")]
+    pub fn queue(&mut self) -> Queue<'_, Record<R, C, F>, C> {
         Queue::from(self)
     }
 
+    /// Upgrades the record into a history.
+    ///
+    /// The commands, the current position, the saved state, and the slot are kept, so linear
+    /// undo-redo can be switched to branching undo-redo without losing anything.
+    /// The commands after the current position can still be redone,
+    /// and they are moved into a new branch when a command is applied.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = ();
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.pop();
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let mut record = Record::default();
+    /// record.apply(Add('a')).unwrap();
+    /// record.apply(Add('b')).unwrap();
+    /// record.undo().unwrap().unwrap();
+    /// let mut history = record.into_history();
+    /// history.apply(Add('c')).unwrap();
+    /// assert_eq!(history.branches().count(), 1);
+    /// let (branch, _) = history.branches().next().unwrap();
+    /// history.go_to(branch, 2).unwrap().unwrap();
+    /// assert_eq!(history.as_receiver(), "ab");
+    /// ```
+    #[inline]
+    #[cfg(feature = "history")]
+    pub fn into_history(self) -> History<R, C, F> {
+        History::from(self)
+    }
+
     /// Returns a reference to the `receiver`.
     #[inline]
     pub fn as_receiver(&self) -> &R {
@@ -205,9 +867,47 @@ impl<R, C, F> Record<R, C, F> {
     /// This method should **only** be used when doing changes that should not be able to be undone.
     #[inline]
     pub fn as_mut_receiver(&mut self) -> &mut R {
+        self.cache.clear();
         &mut self.receiver
     }
 
+    /// Returns the generation of the receiver.
+    ///
+    /// The generation is incremented every time the receiver is changed through the record,
+    /// i.e. when a command is applied, undone, or redone, or when the receiver is mutated through
+    /// a [`Mutator`]. Caches that depend on the receiver can compare it to the generation they were
+    /// built at to cheaply check if they are stale. Changes made through [`as_mut_receiver`] are not
+    /// counted, and the generation starts at `0` when the record is created or deserialized.
+    ///
+    /// [`Mutator`]: struct.Mutator.html
+    /// [`as_mut_receiver`]: struct.Record.html#method.as_mut_receiver
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns the context of the last command that failed to be applied, undone, or redone.
+    #[inline]
+    pub fn error_context(&self) -> Option<&OpContext> {
+        self.failure.as_ref()
+    }
+
+    /// Returns `true` if the receiver has been mutated through a [`Mutator`].
+    ///
+    /// [`Mutator`]: struct.Mutator.html
+    #[inline]
+    pub fn is_mutated(&self) -> bool {
+        self.mutated
+    }
+
+    /// Clears the flag that says if the receiver has been mutated through a [`Mutator`].
+    ///
+    /// [`Mutator`]: struct.Mutator.html
+    #[inline]
+    pub fn clear_mutated(&mut self) {
+        self.mutated = false;
+    }
+
     /// Consumes the record, returning the `receiver`.
     #[inline]
     pub fn into_receiver(self) -> R {
@@ -219,266 +919,844 @@ impl<R, C, F> Record<R, C, F> {
     pub fn commands(&self) -> impl Iterator<Item = &C> {
         self.commands.iter().map(|entry| &entry.command)
     }
-}
 
-impl<R, C: Command<R>, F: FnMut(Signal)> Record<R, C, F> {
-    /// Sets the limit of the record and returns the new limit.
+    /// Returns the command at `index`, or `None` if it is out of bounds.
     ///
-    /// If this limit is reached it will start popping of commands at the beginning
-    /// of the record when new commands are applied. No limit is set by
-    /// default which means it may grow indefinitely.
+    /// The command at `index` is the one that is undone when going from position `index + 1`
+    /// to `index`, which makes this a cheap way to fetch the commands a list view shows.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&C> {
+        self.commands.get(index).map(|entry| &entry.command)
+    }
+
+    /// Returns a snapshot of the record where each command is mapped with `f`.
+    #[inline]
+    pub fn snapshot_with<T>(&self, f: impl FnMut(&C) -> T) -> Snapshot<T> {
+        Snapshot::new(
+            self.commands().map(f).collect(),
+            self.current,
+            self.saved,
+            0,
+        )
+    }
+
+    /// Returns an iterator over the commands in the record together with their metadata.
+    #[inline]
+    pub fn entries(&self) -> impl Iterator<Item = EntryView<'_, C>> {
+        self.commands.iter().enumerate().map(move |(i, entry)| {
+            EntryView::new(entry, i + 1 == self.current, self.saved == Some(i + 1))
+        })
+    }
+
+    /// Returns an iterator over the commands in the record as events.
     ///
-    /// If `0 < limit < len` the first commands will be removed until `len == limit`.
-    /// However, if the current active command is going to be removed, the limit is instead
-    /// adjusted to `len - active` so the active command is not removed.
+    /// The commands after the current position are marked as inverse.
+    #[inline]
+    pub fn events(&self) -> impl Iterator<Item = Event<&C>> {
+        self.commands
+            .iter()
+            .enumerate()
+            .map(move |(i, entry)| Event::new(entry.id, &entry.command, i >= self.current))
+    }
+
+    /// Returns the log of the commands in the record, in the order they were applied.
     ///
-    /// # Panics
-    /// Panics if `limit` is `0`.
+    /// The commands after the current position are marked as undone. The log can be given to
+    /// [`replay`] to reconstruct the record, see [`LoggedCommand`] for what it contains.
+    ///
+    /// [`replay`]: struct.Record.html#method.replay
+    /// [`LoggedCommand`]: struct.LoggedCommand.html
     #[inline]
-    pub fn set_limit(&mut self, limit: usize) -> usize {
-        self.limit = NonZeroUsize::new(limit).expect("limit can not be `0`");
-        if limit < self.len() {
-            let old = self.current();
-            let could_undo = self.can_undo();
-            let was_saved = self.is_saved();
-            let begin = old.min(self.len() - limit);
-            self.commands = self.commands.split_off(begin);
-            self.limit = NonZeroUsize::new(self.len()).unwrap();
-            self.current -= begin;
-            // Check if the saved state has been removed.
-            self.saved = self.saved.and_then(|saved| saved.checked_sub(begin));
-            let new = self.current();
-            let can_undo = self.can_undo();
-            let is_saved = self.is_saved();
-            if let Some(ref mut slot) = self.slot {
-                if old != new {
-                    slot(Signal::Current { old, new });
-                }
-                if could_undo != can_undo {
-                    slot(Signal::Undo(can_undo));
-                }
-                if was_saved != is_saved {
-                    slot(Signal::Saved(is_saved));
-                }
-            }
-        }
-        self.limit()
+    pub fn export_log(&self) -> Vec<LoggedCommand<&C>> {
+        self.commands
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| entry.to_logged(i >= self.current))
+            .collect()
     }
 
-    /// Marks the receiver as currently being in a saved or unsaved state.
+    /// Returns the position of the view cursor.
+    ///
+    /// The view cursor is moved by [`soft_undo`] and [`soft_redo`] without changing the receiver,
+    /// and is the same as the current position when it has not been moved. It is reset when
+    /// the current position changes.
+    ///
+    /// [`soft_undo`]: struct.Record.html#method.soft_undo
+    /// [`soft_redo`]: struct.Record.html#method.soft_redo
     #[inline]
-    pub fn set_saved(&mut self, saved: bool) {
-        let was_saved = self.is_saved();
-        if saved {
-            self.saved = Some(self.current());
-            if let Some(ref mut slot) = self.slot {
-                if !was_saved {
-                    slot(Signal::Saved(true));
-                }
-            }
-        } else {
-            self.saved = None;
-            if let Some(ref mut slot) = self.slot {
-                if was_saved {
-                    slot(Signal::Saved(false));
-                }
-            }
-        }
+    pub fn view(&self) -> usize {
+        self.view
+            .filter(|&view| view <= self.len())
+            .unwrap_or(self.current)
     }
 
-    /// Revert the changes done to the receiver since the saved state.
+    /// Moves the view cursor one command back without changing the receiver.
+    ///
+    /// Returns `false` if the view cursor is already at the start of the record.
     #[inline]
-    pub fn revert(&mut self) -> Option<Result<(), C::Error>> {
-        self.saved.and_then(|saved| self.go_to(saved))
+    pub fn soft_undo(&mut self) -> bool {
+        let view = self.view();
+        self.view = view.checked_sub(1).or(Some(view));
+        view > 0
     }
 
-    /// Removes all commands from the record without undoing them.
+    /// Moves the view cursor one command forward without changing the receiver.
+    ///
+    /// Returns `false` if the view cursor is already at the end of the record.
     #[inline]
-    pub fn clear(&mut self) {
-        let old = self.current();
-        let could_undo = self.can_undo();
-        let could_redo = self.can_redo();
-        self.commands.clear();
-        self.saved = if self.is_saved() { Some(0) } else { None };
-        self.current = 0;
-        if let Some(ref mut slot) = self.slot {
-            if old != 0 {
-                slot(Signal::Current { old, new: 0 });
-            }
-            if could_undo {
-                slot(Signal::Undo(false));
-            }
-            if could_redo {
-                slot(Signal::Redo(false));
-            }
-        }
+    pub fn soft_redo(&mut self) -> bool {
+        let view = self.view();
+        let can_redo = view < self.len();
+        self.view = Some(view + usize::from(can_redo));
+        can_redo
     }
 
-    /// Pushes the command on top of the record and executes its [`apply`] method.
+    /// Returns the id of the command at `index`.
     ///
-    /// # Errors
-    /// If an error occur when executing [`apply`] the error is returned.
+    /// Each applied command is given a unique id which, unlike its index, does not change
+    /// when commands are removed from the start of the record.
+    #[inline]
+    pub fn id(&self, index: usize) -> Option<usize> {
+        self.commands.get(index).map(|entry| entry.id)
+    }
+
+    /// Returns the index of the command with the `id`.
+    #[inline]
+    pub fn index_of(&self, id: usize) -> Option<usize> {
+        self.commands.iter().position(|entry| entry.id == id)
+    }
+
+    /// Resolves the `id` to the id of the command that currently contains it.
     ///
-    /// [`apply`]: trait.Command.html#tymethod.apply
+    /// If the command with the `id` has been merged into another command, the id of that command is returned.
+    /// Returns `None` if the command has been removed from the record.
     #[inline]
-    pub fn apply(&mut self, command: C) -> Result<(), C::Error> {
-        self.__apply(Entry::from(command)).map(|_| ())
+    pub fn resolve(&self, id: usize) -> Option<usize> {
+        self.commands
+            .iter()
+            .find(|entry| entry.contains(id))
+            .map(|entry| entry.id)
     }
 
+    /// Marks the current position as a coarse boundary.
+    ///
+    /// The [`undo_coarse`] and [`redo_coarse`] methods moves between these boundaries
+    /// instead of between the individual commands. The start and the end of the record
+    /// are always boundaries.
+    ///
+    /// [`undo_coarse`]: struct.Record.html#method.undo_coarse
+    /// [`redo_coarse`]: struct.Record.html#method.redo_coarse
     #[inline]
-    pub(crate) fn __apply(
-        &mut self,
-        mut entry: Entry<C>,
-    ) -> Result<(bool, VecDeque<Entry<C>>), C::Error> {
-        if entry.is_dead() {
-            return Ok((false, VecDeque::new()));
-        }
-        if let Err(error) = entry.apply(&mut self.receiver) {
-            return Err(error);
+    pub fn set_boundary(&mut self) {
+        if let Some(entry) = self
+            .current
+            .checked_sub(1)
+            .and_then(|i| self.commands.get_mut(i))
+        {
+            entry.boundary = true;
         }
-        let current = self.current();
-        let could_undo = self.can_undo();
-        let could_redo = self.can_redo();
-        let was_saved = self.is_saved();
-        // Pop off all elements after len from record.
+    }
+
+    /// Returns `true` if the position `current` is a coarse boundary.
+    #[inline]
+    pub fn is_boundary(&self, current: usize) -> bool {
+        current == 0
+            || current == self.len()
+            || self
+                .commands
+                .get(current - 1)
+                .is_some_and(|entry| entry.boundary)
+    }
+
+    /// Returns a new entry with a unique id.
+    #[inline]
+    pub(crate) fn entry(&mut self, command: C) -> Entry<C> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut entry = Entry::new(command, id);
+        // The flag is cleared when the entry is pushed, since the command can be skipped or merged.
+        entry.joined = self.group > 0 && !self.group_first;
+        #[cfg(feature = "std")]
+        if let Some(timestamp) = self.clock.now() {
+            *entry.timestamp = timestamp;
+        } else if cfg!(not(any(feature = "chrono", feature = "time")))
+            && self.merge_within.is_some()
+        {
+            // Without `chrono` or `time` the time is only recorded when it is needed to merge.
+            *entry.timestamp = now();
+        }
+        entry
+    }
+
+    /// Starts a group of commands.
+    ///
+    /// All commands applied until [`end_group`] is called are undone and redone
+    /// together in a single step. Groups can be nested, in which case the commands
+    /// are grouped until the outermost group is ended.
+    ///
+    /// [`end_group`]: struct.Record.html#method.end_group
+    #[inline]
+    pub fn begin_group(&mut self) {
+        if self.group == 0 {
+            self.group_first = true;
+        }
+        self.group += 1;
+    }
+
+    /// Ends a group of commands.
+    ///
+    /// Does nothing if no group has been started.
+    #[inline]
+    pub fn end_group(&mut self) {
+        self.group = self.group.saturating_sub(1);
+    }
+
+    /// Returns `true` if a group of commands has been started.
+    #[inline]
+    pub fn in_group(&self) -> bool {
+        self.group > 0
+    }
+
+    /// Returns the state that is reported through signals.
+    #[inline]
+    pub(crate) fn state(&self) -> State {
+        State {
+            current: self.current(),
+            branch: 0,
+            can_undo: self.can_undo(),
+            can_redo: self.can_redo(),
+            is_saved: self.is_saved(),
+        }
+    }
+
+    /// Returns a structure for configurable formatting of the record.
+    #[inline]
+    #[cfg(feature = "display")]
+    pub fn display(&self) -> Display<'_, Self> {
+        Display::from(self)
+    }
+}
+
+impl<R, C: Command<R>, F: FnMut(Signal)> Record<R, C, F> {
+    /// Sets the limit of the record and returns the new limit.
+    ///
+    /// If this limit is reached it will start popping of commands at the beginning
+    /// of the record when new commands are applied. No limit is set by
+    /// default which means it may grow indefinitely.
+    ///
+    /// If `0 < limit < len` the first commands will be removed until `len == limit`.
+    /// However, if the current active command is going to be removed, the limit is instead
+    /// adjusted to `len - active` so the active command is not removed.
+    ///
+    /// # Panics
+    /// Panics if `limit` is `0`, see [`try_set_limit`].
+    ///
+    /// [`try_set_limit`]: struct.Record.html#method.try_set_limit
+    #[inline]
+    pub fn set_limit(&mut self, limit: usize) -> usize {
+        match self.try_set_limit(limit) {
+            Ok(limit) => limit,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    /// Sets the limit of the record and returns the new limit, see [`set_limit`].
+    ///
+    /// # Errors
+    /// Returns [`ConfigError::ZeroLimit`] if `limit` is `0`, and leaves the record unchanged.
+    ///
+    /// [`set_limit`]: struct.Record.html#method.set_limit
+    /// [`ConfigError::ZeroLimit`]: enum.ConfigError.html#variant.ZeroLimit
+    #[inline]
+    pub fn try_set_limit(&mut self, limit: usize) -> Result<usize, ConfigError> {
+        self.slot.next_operation();
+        let limit = NonZeroUsize::new(limit).ok_or(ConfigError::ZeroLimit)?;
+        self.transaction(|record| {
+            record.limit = limit;
+            let limit = limit.get();
+            if limit < record.len() {
+                let old = record.current();
+                let len = record.len();
+                let begin = old.min(len - limit);
+                let evict = |position| Cursor::new(position, len)?.evict(begin).map(Cursor::get);
+                let kept = record.commands.split_off(begin);
+                for entry in mem::replace(&mut record.commands, kept) {
+                    record.cache.remove(entry.id);
+                    record.hooks.evict(entry.command);
+                }
+                record.limit = NonZeroUsize::new(record.len()).unwrap();
+                record.retain_marks();
+                // The current command is never removed, so this always succeeds.
+                record.current = evict(old).unwrap_or(0);
+                // Check if the saved state has been removed.
+                record.saved = record.saved.and_then(evict);
+            }
+            Ok(record.limit())
+        })
+    }
+
+    /// Returns a guard that gives mutable access to the receiver.
+    ///
+    /// Unlike [`as_mut_receiver`], mutations made through the guard are tracked,
+    /// see [`Mutator`] for more information.
+    ///
+    /// [`as_mut_receiver`]: struct.Record.html#method.as_mut_receiver
+    /// [`Mutator`]: struct.Mutator.html
+    #[inline]
+    pub fn mutate(&mut self) -> Mutator<'_, R, F> {
+        self.cache.clear();
+        let can_redo = self.can_redo();
+        Mutator::new(
+            &mut self.receiver,
+            &mut self.slot,
+            &mut self.mutated,
+            &mut self.generation,
+            can_redo,
+        )
+    }
+
+    /// Marks the receiver as currently being in a saved or unsaved state.
+    #[inline]
+    pub fn set_saved(&mut self, saved: bool) {
+        self.transaction(|record| {
+            record.saved = if saved { Some(record.current()) } else { None };
+        });
+    }
+
+    /// Revert the changes done to the receiver since the saved state.
+    #[inline]
+    pub fn revert(&mut self) -> Option<Result<(), C::Error>> {
+        self.saved.and_then(|saved| self.go_to(saved))
+    }
+
+    /// Goes to the saved state.
+    ///
+    /// Returns `None` if there is no saved state.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn go_to_saved(&mut self) -> Option<Result<(), C::Error>> {
+        self.go_to_saved_offset(0)
+    }
+
+    /// Goes to the mark with `name`, see [`mark`].
+    ///
+    /// Returns `None` if there is no such mark.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`mark`]: struct.Record.html#method.mark
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn go_to_mark(&mut self, name: &str) -> Option<Result<(), C::Error>> {
+        let current = self.mark_position(name)?;
+        self.go_to(current)
+    }
+
+    /// Goes to the state `offset` commands after the saved state, or before it if `offset` is negative.
+    ///
+    /// This makes it easy to step through the changes made since the receiver was saved.
+    /// Returns `None` if there is no saved state or if the position is outside the record.
+    #[inline]
+    pub fn go_to_saved_offset(&mut self, offset: isize) -> Option<Result<(), C::Error>> {
+        let current = Cursor::new(self.saved?, self.len())?.offset(offset)?;
+        self.go_to(current.get())
+    }
+
+    /// Goes to the position of the view cursor, changing the receiver.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn materialize(&mut self) -> Option<Result<(), C::Error>> {
+        let view = self.view();
+        self.view = None;
+        self.go_to(view)
+    }
+
+    /// Removes all commands from the record without undoing them.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.transaction(|record| {
+            record.view = None;
+            // Only the marks at the current position are kept, and they are moved to the start.
+            let at = record.id_at(record.current);
+            record.marks.retain(|_, mark| *mark == at);
+            record.marks.values_mut().for_each(|mark| *mark = None);
+            record.commands.clear();
+            record.cache.clear();
+            record.saved = if record.is_saved() { Some(0) } else { None };
+            record.current = 0;
+        });
+    }
+
+    /// Pushes the command on top of the record and executes its [`apply`] method.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    pub fn apply(&mut self, command: C) -> Result<(), C::Error> {
+        let entry = self.entry(command);
+        self.__apply(entry).map(|_| ())
+    }
+
+    /// Pushes the command on top of the record with the `label`, and executes its [`apply`] method.
+    ///
+    /// The label is a human readable name of the action, that is returned by [`EntryView::label`],
+    /// and used instead of the string of the command by [`to_undo_string`], [`to_redo_string`],
+    /// and [`Display`]. This is useful when the same command type is used for many actions.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = ();
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.pop();
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// # impl std::fmt::Display for Add {
+    /// #     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    /// #         write!(f, "Add {}", self.0)
+    /// #     }
+    /// # }
+    /// let mut record = Record::default();
+    /// record.apply_with_label(Add('!'), "Insert exclamation mark").unwrap();
+    /// record.apply(Add('a')).unwrap();
+    /// record.undo().unwrap().unwrap();
+    /// assert_eq!(record.to_undo_string().unwrap(), "Insert exclamation mark");
+    /// assert_eq!(record.to_redo_string().unwrap(), "Add a");
+    /// ```
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    /// [`EntryView::label`]: struct.EntryView.html#method.label
+    /// [`to_undo_string`]: struct.Record.html#method.to_undo_string
+    /// [`to_redo_string`]: struct.Record.html#method.to_redo_string
+    /// [`Display`]: struct.Display.html
+    #[inline]
+    pub fn apply_with_label(
+        &mut self,
+        command: C,
+        label: impl Into<String>,
+    ) -> Result<(), C::Error> {
+        let mut entry = self.entry(command);
+        entry.set_label(label.into());
+        self.__apply(entry).map(|_| ())
+    }
+
+    #[inline]
+    pub(crate) fn __apply(
+        &mut self,
+        entry: Entry<C>,
+    ) -> Result<(bool, VecDeque<Entry<C>>), C::Error> {
+        self.__apply_with(entry, C::apply)
+    }
+
+    /// Pushes the entry on top of the record, using `apply` to apply the command.
+    #[inline]
+    pub(crate) fn __apply_with(
+        &mut self,
+        entry: Entry<C>,
+        apply: impl FnOnce(&mut C, &mut R) -> Result<(), C::Error>,
+    ) -> Result<(bool, VecDeque<Entry<C>>), C::Error> {
+        self.transaction(|record| record.push_entry(entry, apply))
+    }
+
+    /// Pushes the entry like `__apply_with`, in the transaction that emits its signals.
+    #[inline]
+    fn push_entry(
+        &mut self,
+        mut entry: Entry<C>,
+        apply: impl FnOnce(&mut C, &mut R) -> Result<(), C::Error>,
+    ) -> Result<(bool, VecDeque<Entry<C>>), C::Error> {
+        self.view = None;
+        if entry.is_dead() {
+            return Ok((false, VecDeque::new()));
+        }
+        if let Err(error) = entry.validate(&self.receiver) {
+            self.fail(Op::Validate, self.current, *entry.timestamp);
+            return Err(error);
+        }
+        entry.noop = entry.is_noop(&self.receiver);
+        // Skipped no-ops are reported like merged commands since the record is left untouched.
+        if entry.noop && !self.keep_noops {
+            return Ok((true, VecDeque::new()));
+        }
+        hooks::call(&self.hooks.before_apply, &entry.command, &self.receiver);
+        if let Err(error) = apply(&mut entry.command, &mut self.receiver) {
+            self.fail(Op::Apply, self.current, *entry.timestamp);
+            return Err(error);
+        }
+        hooks::call(&self.hooks.after_apply, &entry.command, &self.receiver);
+        self.generation += 1;
+        let current = self.current();
+        let was_saved = self.is_saved();
+        // Pop off all elements after len from record.
         let v = self.commands.split_off(current);
         debug_assert_eq!(current, self.len());
+        v.iter().for_each(|entry| self.cache.remove(entry.id));
         // Check if the saved state was popped off.
         self.saved = self.saved.filter(|&saved| saved <= current);
+        // Only merge the commands if they were applied close enough in time.
+        let timestamp = *entry.timestamp;
+        let within = self.merge_within.is_none_or(|within| {
+            self.last_applied
+                .and_then(|last| elapsed(last, timestamp))
+                .is_some_and(|elapsed| elapsed <= within)
+        });
+        *self.last_applied = Some(timestamp);
         // Try to merge commands unless the receiver is in a saved state.
-        let merged = match self.commands.back_mut() {
-            Some(ref mut last) if !was_saved => last.merge(entry),
-            _ => Merge::No(entry),
-        };
-        let merged_or_annulled = match merged {
-            Merge::Yes => true,
-            Merge::Annul => {
-                self.commands.pop_back();
+        let merge = (!was_saved || self.merge_across_saved) && within;
+        let mut into = None;
+        let mut annulled = None;
+        let merged_or_annulled = match self.push_back(entry, |last, entry| {
+            if merge {
+                last.merge(entry)
+            } else {
+                Merge::No(entry)
+            }
+        }) {
+            Pushed::Merged => {
+                into = self.commands.back().map(|last| last.id);
+                self.cache.remove(into.unwrap());
+                self.marks.retain(|_, &mut at| at != into);
                 true
             }
-            // If commands are not merged or annulled push it onto the record.
-            Merge::No(entry) => {
-                // If limit is reached, pop off the first command.
-                if self.limit() == self.current() {
-                    self.commands.pop_front();
-                    self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
-                } else {
-                    self.current += 1;
+            Pushed::Annulled(last) => {
+                annulled = Some(last.id);
+                self.cache.remove(last.id);
+                *self.last_applied = None;
+                if self.cascade_annul {
+                    into = self.cascade();
                 }
-                self.commands.push_back(entry);
+                true
+            }
+            Pushed::Added(_) => {
+                self.group_first = false;
                 false
             }
         };
+        self.take_snapshot();
         debug_assert_eq!(self.current(), self.len());
-        if let Some(ref mut slot) = self.slot {
-            // We emit this signal even if the commands might have been merged.
-            slot(Signal::Current {
-                old: current,
-                new: self.current,
-            });
-            if could_redo {
-                slot(Signal::Redo(false));
-            }
-            if !could_undo {
-                slot(Signal::Undo(true));
-            }
-            if was_saved {
-                slot(Signal::Saved(false));
-            }
+        // We emit this signal even if the commands might have been merged.
+        self.slot.emit(Signal::Current {
+            old: current,
+            new: self.current,
+        });
+        event!(
+            TRACE,
+            current = self.current,
+            branch = self.branch,
+            "applied command"
+        );
+        if let Some(with) = annulled {
+            event!(DEBUG, with, branch = self.branch, "annulled commands");
+            self.slot.emit(Signal::Annulled { with });
+        }
+        if let Some(into) = into {
+            event!(DEBUG, into, branch = self.branch, "merged commands");
+            self.slot.emit(Signal::Merged { into });
         }
         Ok((merged_or_annulled, v))
     }
 
+    /// Pushes an entry that has been undone back on top of the record by redoing it.
+    ///
+    /// Unlike `__apply`, the entry is never validated, skipped, merged, or passed to the hooks,
+    /// so the entries of a branch are restored as they were.
+    #[inline]
+    #[cfg(feature = "history")]
+    pub(crate) fn __restore(&mut self, entry: Entry<C>) -> Result<VecDeque<Entry<C>>, C::Error> {
+        self.transaction(|record| record.restore_entry(entry))
+    }
+
+    /// Restores the entry like `__restore`, in the transaction that emits its signals.
+    #[inline]
+    #[cfg(feature = "history")]
+    fn restore_entry(&mut self, mut entry: Entry<C>) -> Result<VecDeque<Entry<C>>, C::Error> {
+        self.view = None;
+        if let Err(error) = entry.redo(&mut self.receiver) {
+            self.fail(Op::Redo, self.current, *entry.timestamp);
+            return Err(error);
+        }
+        self.generation += 1;
+        *self.last_applied = None;
+        let current = self.current();
+        let v = self.commands.split_off(current);
+        v.iter().for_each(|entry| self.cache.remove(entry.id));
+        self.saved = self.saved.filter(|&saved| saved <= current);
+        self.push_back(entry, |_, entry| Merge::No(entry));
+        self.take_snapshot();
+        self.slot.emit(Signal::Current {
+            old: current,
+            new: self.current,
+        });
+        event!(
+            TRACE,
+            current = self.current,
+            branch = self.branch,
+            "restored command"
+        );
+        Ok(v)
+    }
+
+    /// Calls `f` and adds the context of the operation that failed to the error.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = &'static str;
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+    /// #         self.0 = s.pop().ok_or("`s` is empty")?;
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let mut record = Record::default();
+    /// record.apply(Add('a')).unwrap();
+    /// record.as_mut_receiver().clear();
+    /// let error = record.with_context(|record| record.undo().unwrap()).unwrap_err();
+    /// assert_eq!(error.to_string(), "undo of entry 0 on branch 0 failed: `s` is empty");
+    /// ```
+    #[inline]
+    pub fn with_context<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, C::Error>,
+    ) -> Result<T, Error<C::Error>> {
+        self.failure = None;
+        f(self).map_err(|error| Error::new(self.failure, error))
+    }
+
+    #[inline]
+    fn fail(&mut self, op: Op, index: usize, timestamp: Timestamp) {
+        event!(WARN, %op, index, branch = self.branch, "command failed");
+        self.failure = Some(OpContext::new(op, index, self.branch, timestamp));
+    }
+
+    /// Merges the two commands on top of the record until they no longer merge or annul,
+    /// and returns the id of the command they were merged into, if any.
+    #[inline]
+    fn cascade(&mut self) -> Option<usize> {
+        let mut into = None;
+        // The saved state must not be in the commands that are merged, unless merging across it is on.
+        while self.len() >= 2
+            && (self.merge_across_saved || self.saved.is_none_or(|saved| saved + 2 <= self.current))
+        {
+            let current = self.current;
+            let last = self.commands.pop_back().unwrap();
+            self.current -= 1;
+            self.cache.remove(last.id);
+            let back = self.commands.back_mut().unwrap();
+            self.cache.remove(back.id);
+            match back.merge(last) {
+                Merge::Yes => {
+                    into = self.commands.back().map(|last| last.id);
+                    self.marks.retain(|_, &mut at| at != into);
+                }
+                Merge::Annul => {
+                    self.commands.pop_back();
+                    self.current -= 1;
+                    into = None;
+                }
+                Merge::No(last) => {
+                    self.commands.push_back(last);
+                    self.current += 1;
+                    break;
+                }
+            }
+            // The saved state no longer exists if it was in the commands that were merged.
+            self.saved = self.saved.filter(|&saved| saved + 2 <= current);
+        }
+        into
+    }
+
+    /// Returns the number of commands before the current position that can no longer be undone.
+    ///
+    /// These commands are removed without being undone when they are reached, see [`undoable`].
+    ///
+    /// [`undoable`]: trait.Command.html#method.undoable
+    #[inline]
+    pub fn expired(&self) -> usize {
+        (0..self.current).filter(|&i| self.is_expired(i)).count()
+    }
+
+    /// Returns `true` if the command at `index` is dead or can no longer be undone.
+    #[inline]
+    fn is_expired(&self, index: usize) -> bool {
+        let entry = &self.commands[index];
+        entry.is_dead() || !entry.undoable(&self.receiver)
+    }
+
     /// Calls the [`undo`] method for the active command and sets
     /// the previous one as the new active one.
     ///
+    /// If the command is part of a group, all the commands in the group are undone.
+    ///
     /// # Errors
     /// If an error occur when executing [`undo`] the error is returned.
     ///
     /// [`undo`]: ../trait.Command.html#tymethod.undo
     #[inline]
     pub fn undo(&mut self) -> Option<Result<(), C::Error>> {
-        let was_saved = self.is_saved();
-        let old = self.current();
+        if !self.can_undo() || !self.commands[self.current - 1].joined {
+            return self.__undo();
+        }
+        self.transaction(|record| loop {
+            if let Err(error) = record.__undo()? {
+                return Some(Err(error));
+            }
+            if !record.commands[record.current].joined || !record.can_undo() {
+                return Some(Ok(()));
+            }
+        })
+    }
+
+    /// Undoes a single command, ignoring groups.
+    #[inline]
+    pub(crate) fn __undo(&mut self) -> Option<Result<(), C::Error>> {
+        self.transaction(Record::undo_entry)
+    }
+
+    /// Undoes a single command like `__undo`, in the transaction that emits its signals.
+    #[inline]
+    fn undo_entry(&mut self) -> Option<Result<(), C::Error>> {
+        self.view = None;
         loop {
             if !self.can_undo() {
                 return None;
-            } else if self.commands[self.current - 1].is_dead() {
-                self.current -= 1;
-                self.commands.remove(self.current).unwrap();
+            } else if self.is_expired(self.current - 1) {
+                stack::expire(&mut self.commands, &mut self.current, &mut self.saved);
+                self.removed(self.current);
+                self.cache.clear();
+                self.retain_marks();
             } else {
                 break;
             }
         }
-        if let Err(error) = self.commands[self.current - 1].undo(&mut self.receiver) {
+        let entry = &mut self.commands[self.current - 1];
+        hooks::call(&self.hooks.before_undo, &entry.command, &self.receiver);
+        if let Err(error) = entry.undo(&mut self.receiver) {
+            let timestamp = *entry.timestamp;
+            self.fail(Op::Undo, self.current - 1, timestamp);
             return Some(Err(error));
         }
+        hooks::call(&self.hooks.after_undo, &entry.command, &self.receiver);
+        *self.last_applied = None;
+        self.generation += 1;
         self.current -= 1;
-        let len = self.len();
-        let is_saved = self.is_saved();
-        if let Some(ref mut slot) = self.slot {
-            slot(Signal::Current {
-                old,
-                new: self.current,
-            });
-            if old == len {
-                slot(Signal::Redo(true));
-            }
-            if old == 1 {
-                slot(Signal::Undo(false));
-            }
-            if was_saved != is_saved {
-                slot(Signal::Saved(is_saved));
-            }
-        }
+        self.take_snapshot();
+        event!(
+            TRACE,
+            current = self.current,
+            branch = self.branch,
+            "undid command"
+        );
         Some(Ok(()))
     }
 
     /// Calls the [`redo`] method for the active command and sets
     /// the next one as the new active one.
     ///
+    /// If the command is part of a group, all the commands in the group are redone.
+    ///
     /// # Errors
     /// If an error occur when applying [`redo`] the error is returned.
     ///
     /// [`redo`]: trait.Command.html#method.redo
     #[inline]
     pub fn redo(&mut self) -> Option<Result<(), C::Error>> {
-        let was_saved = self.is_saved();
-        let old = self.current();
+        if !self
+            .commands
+            .get(self.current + 1)
+            .is_some_and(|entry| entry.joined)
+        {
+            return self.__redo();
+        }
+        self.transaction(|record| loop {
+            if let Err(error) = record.__redo()? {
+                return Some(Err(error));
+            }
+            if !record
+                .commands
+                .get(record.current)
+                .is_some_and(|entry| entry.joined)
+            {
+                return Some(Ok(()));
+            }
+        })
+    }
+
+    /// Redoes a single command, ignoring groups.
+    #[inline]
+    pub(crate) fn __redo(&mut self) -> Option<Result<(), C::Error>> {
+        self.transaction(Record::redo_entry)
+    }
+
+    /// Redoes a single command like `__redo`, in the transaction that emits its signals.
+    #[inline]
+    fn redo_entry(&mut self) -> Option<Result<(), C::Error>> {
+        self.view = None;
         loop {
             if !self.can_redo() {
                 return None;
             } else if self.commands[self.current].is_dead() {
-                self.commands.remove(self.current).unwrap();
+                stack::bury(&mut self.commands, self.current, &mut self.saved);
+                self.removed(self.current);
+                self.cache.clear();
+                self.retain_marks();
             } else {
                 break;
             }
         }
-        if let Err(error) = self.commands[self.current].redo(&mut self.receiver) {
+        let entry = &mut self.commands[self.current];
+        hooks::call(&self.hooks.before_redo, &entry.command, &self.receiver);
+        if let Err(error) = entry.redo(&mut self.receiver) {
+            let timestamp = *entry.timestamp;
+            self.fail(Op::Redo, self.current, timestamp);
             return Some(Err(error));
         }
+        hooks::call(&self.hooks.after_redo, &entry.command, &self.receiver);
+        *self.last_applied = None;
+        self.generation += 1;
         self.current += 1;
-        let len = self.len();
-        let is_saved = self.is_saved();
-        if let Some(ref mut slot) = self.slot {
-            slot(Signal::Current {
-                old,
-                new: self.current,
-            });
-            if old == len - 1 {
-                slot(Signal::Redo(false));
-            }
-            if old == 0 {
-                slot(Signal::Undo(true));
-            }
-            if was_saved != is_saved {
-                slot(Signal::Saved(is_saved));
-            }
-        }
+        self.take_snapshot();
+        event!(
+            TRACE,
+            current = self.current,
+            branch = self.branch,
+            "redid command"
+        );
         Some(Ok(()))
     }
 
     /// Repeatedly calls [`undo`] or [`redo`] until the command at `current` is reached.
+    /// Returns `None` if `current` is out of bounds, or if dead commands were removed while
+    /// redoing so that `current` can no longer be reached.
     ///
     /// # Errors
     /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
@@ -487,400 +1765,4157 @@ impl<R, C: Command<R>, F: FnMut(Signal)> Record<R, C, F> {
     /// [`redo`]: trait.Command.html#method.redo
     #[inline]
     pub fn go_to(&mut self, current: usize) -> Option<Result<(), C::Error>> {
-        if current > self.len() {
-            return None;
-        }
-        let could_undo = self.can_undo();
-        let could_redo = self.can_redo();
-        let was_saved = self.is_saved();
         let old = self.current();
-        // Temporarily remove slot so they are not called each iteration.
-        let slot = self.slot.take();
-        while self.current() != current {
-            // Decide if we need to undo or redo to reach current.
-            let f = if current > self.current() {
-                Record::redo
-            } else {
-                Record::undo
-            };
-            if let Err(err) = f(self).unwrap() {
-                return Some(Err(err));
+        event!(
+            DEBUG,
+            from = old,
+            to = current,
+            branch = self.branch,
+            "going to command"
+        );
+        let result = self.__go_to(current)?;
+        if let Some(nav) = self.nav.as_mut().filter(|_| old != current) {
+            if result.is_ok() {
+                nav.back.push(old);
+                nav.forward.clear();
             }
         }
-        // Add slot back.
-        self.slot = slot;
-        let can_undo = self.can_undo();
-        let can_redo = self.can_redo();
-        let is_saved = self.is_saved();
-        if let Some(ref mut slot) = self.slot {
-            if old != self.current {
-                slot(Signal::Current {
-                    old,
-                    new: self.current,
-                });
-            }
-            if could_undo != can_undo {
-                slot(Signal::Undo(can_undo));
-            }
-            if could_redo != can_redo {
-                slot(Signal::Redo(can_redo));
-            }
-            if was_saved != is_saved {
-                slot(Signal::Saved(is_saved));
+        Some(result)
+    }
+
+    /// Goes back to the position before the last jump made by [`go_to`],
+    /// see [`track_navigation`].
+    ///
+    /// Returns `None` if there is no position to go back to. Positions that no longer exist,
+    /// because the commands after them were removed, and the current position are skipped.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`go_to`]: struct.Record.html#method.go_to
+    /// [`track_navigation`]: struct.Record.html#method.track_navigation
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn nav_back(&mut self) -> Option<Result<(), C::Error>> {
+        self.navigate(|nav| &mut nav.back, |nav| &mut nav.forward)
+    }
+
+    /// Goes forward to the position left by the last call to [`nav_back`].
+    ///
+    /// Returns `None` if there is no position to go forward to.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`nav_back`]: struct.Record.html#method.nav_back
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn nav_forward(&mut self) -> Option<Result<(), C::Error>> {
+        self.navigate(|nav| &mut nav.forward, |nav| &mut nav.back)
+    }
+
+    /// Goes to the last position in the `from` stack and pushes the current position to the `to` stack.
+    #[inline]
+    fn navigate(
+        &mut self,
+        from: fn(&mut Navigation) -> &mut Vec<usize>,
+        to: fn(&mut Navigation) -> &mut Vec<usize>,
+    ) -> Option<Result<(), C::Error>> {
+        let len = self.len();
+        let old = self.current();
+        let nav = self.nav.as_mut()?;
+        let current = loop {
+            match from(nav).pop()? {
+                current if current <= len && current != old => break current,
+                _ => continue,
             }
+        };
+        let result = self.__go_to(current)?;
+        let nav = self.nav.as_mut().unwrap();
+        if result.is_ok() {
+            to(nav).push(old);
+        } else {
+            from(nav).push(current);
         }
-        Some(Ok(()))
+        Some(result)
     }
 
-    /// Go back or forward in the record to the command that was made closest to the datetime provided.
     #[inline]
-    #[cfg(feature = "chrono")]
-    pub fn time_travel(&mut self, to: &DateTime<impl TimeZone>) -> Option<Result<(), C::Error>> {
-        let to = to.with_timezone(&Utc);
-        let current = match self.commands.as_slices() {
-            ([], []) => return None,
-            (start, []) => match start.binary_search_by(|entry| entry.timestamp.cmp(&to)) {
-                Ok(current) | Err(current) => current,
-            },
-            ([], end) => match end.binary_search_by(|entry| entry.timestamp.cmp(&to)) {
-                Ok(current) | Err(current) => current,
-            },
-            (start, end) => match start.last().unwrap().timestamp.cmp(&to) {
-                Ordering::Less => match start.binary_search_by(|entry| entry.timestamp.cmp(&to)) {
-                    Ok(current) | Err(current) => current,
-                },
-                Ordering::Equal => start.len(),
-                Ordering::Greater => match end.binary_search_by(|entry| entry.timestamp.cmp(&to)) {
-                    Ok(current) | Err(current) => start.len() + current,
-                },
-            },
+    pub(crate) fn __go_to(&mut self, current: usize) -> Option<Result<(), C::Error>> {
+        if current > self.len() {
+            return None;
+        }
+        // If the signals are deferred, they are only emitted once the whole operation is done.
+        self.transaction(|record| {
+            record.restore_snapshot(current);
+            while record.current() != current {
+                // Decide if we need to undo or redo to reach current.
+                let f = if current > record.current() {
+                    Record::__redo
+                } else {
+                    Record::__undo
+                };
+                // The record is shorter than `current` if redo removed dead commands.
+                if let Err(err) = f(record)? {
+                    return Some(Err(err));
+                }
+            }
+            Some(Ok(()))
+        })
+    }
+
+    /// Takes a snapshot of the receiver if the record is at a snapshot position.
+    #[inline]
+    fn take_snapshot(&mut self) {
+        if let Some(entry) = self.current.checked_sub(1).map(|i| &self.commands[i]) {
+            self.cache.take(self.current, entry.id, &self.receiver);
+        }
+    }
+
+    /// Restores the snapshot closest to `current` if it is closer than the current position,
+    /// and the commands between the snapshot and the current position can be skipped.
+    #[inline]
+    fn restore_snapshot(&mut self, current: usize) {
+        let distance = current.abs_diff(self.current);
+        let nearest = (1..=current)
+            .rev()
+            .take_while(|&i| current - i < distance)
+            .find_map(|i| Some((i, self.cache.get(self.commands[i - 1].id)?)))
+            .filter(|&(i, _)| self.can_skip_to(i));
+        if let Some((i, restore)) = nearest {
+            self.slot.next_operation();
+            self.view = None;
+            *self.last_applied = None;
+            restore(&mut self.receiver);
+            self.generation += 1;
+            self.current = i;
+        }
+    }
+
+    /// Says if the record can move to `position` without calling undo or redo on the commands
+    /// in between, which requires them to be restorable and no undo or redo hooks to be set.
+    #[inline]
+    fn can_skip_to(&self, position: usize) -> bool {
+        let hooks = &self.hooks;
+        if position < self.current {
+            // The expired commands must be reached by undo so they are removed.
+            hooks.before_undo.is_none()
+                && hooks.after_undo.is_none()
+                && (position..self.current)
+                    .all(|i| self.commands[i].restorable() && !self.is_expired(i))
+        } else {
+            hooks.before_redo.is_none()
+                && hooks.after_redo.is_none()
+                && self
+                    .commands
+                    .range(self.current..position)
+                    .all(|entry| entry.restorable() && !entry.is_dead())
+        }
+    }
+
+    /// Calls [`prefetch`] on the commands in the `range`.
+    ///
+    /// # Panics
+    /// Panics if the `range` is out of bounds.
+    ///
+    /// [`prefetch`]: trait.Command.html#method.prefetch
+    #[inline]
+    pub fn prefetch(&mut self, range: impl RangeBounds<usize>) {
+        self.commands.range_mut(range).for_each(Entry::prefetch);
+    }
+
+    /// Calls [`prefetch`] on the `depth` commands that will be undone next,
+    /// followed by the `depth` commands that will be redone next.
+    ///
+    /// This is meant to be called when the application is idle, so the commands are ready
+    /// before the user scrolls through the history or performs a deep undo.
+    ///
+    /// [`prefetch`]: trait.Command.html#method.prefetch
+    #[inline]
+    pub fn idle(&mut self, depth: usize) {
+        let current = self.current();
+        let end = current.saturating_add(depth).min(self.len());
+        let start = current.saturating_sub(depth);
+        self.commands
+            .range_mut(start..current)
+            .rev()
+            .for_each(Entry::prefetch);
+        self.prefetch(current..end);
+    }
+
+    /// Removes the commands for which `f` returns `false`.
+    ///
+    /// The record first undoes the commands down to the first removed command, then removes
+    /// the commands, and finally redoes the remaining commands up to the current position,
+    /// which is moved back by the number of removed commands before it. If a command is removed
+    /// before the saved state, the receiver is no longer considered saved.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned and no commands are removed.
+    /// If an error occur when executing [`redo`] the error is returned after the commands have been removed.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn retain(&mut self, mut f: impl FnMut(&C) -> bool) -> Result<(), C::Error> {
+        // Navigating can remove expired commands, so the commands are tracked by their ids.
+        let removed: Vec<_> = self
+            .commands
+            .iter()
+            .filter(|entry| !f(&entry.command))
+            .map(|entry| entry.id)
+            .collect();
+        let first = match self
+            .commands
+            .iter()
+            .position(|entry| removed.first() == Some(&entry.id))
+        {
+            Some(first) => first,
+            None => return Ok(()),
         };
-        self.go_to(current)
+        let below: Vec<_> = self
+            .commands
+            .range(..self.current)
+            .map(|entry| entry.id)
+            .filter(|id| !removed.contains(id))
+            .collect();
+        self.transaction(|record| {
+            if first < record.current() {
+                if let Some(Err(err)) = record.__go_to(first) {
+                    return Err(err);
+                }
+            }
+            record.commands.retain(|entry| !removed.contains(&entry.id));
+            record.cache.clear();
+            record.saved = record.saved.filter(|&saved| saved <= first);
+            record.retain_marks();
+            let current = record
+                .commands
+                .iter()
+                .filter(|entry| below.contains(&entry.id))
+                .count();
+            record.__go_to(current).unwrap_or(Ok(()))
+        })
     }
 
-    /// Applies each command in the iterator.
+    /// Undoes the command at `index` and removes it from the record, keeping the changes of the
+    /// commands applied after it.
+    ///
+    /// If the command [transposes] with every command applied after it, it is undone directly.
+    /// Otherwise the commands applied after it are undone, the command is undone and removed,
+    /// and the commands are redone, like [`retain`]. The current position is moved back by one.
+    /// If the saved state is after the command, the receiver is no longer considered saved.
+    /// Returns `None` if there is no applied command at `index`.
     ///
     /// # Errors
-    /// If an error occur when executing [`apply`] the error is returned
-    /// and the remaining commands in the iterator are discarded.
+    /// If an error occur when executing [`undo`] the error is returned and the command is not removed.
+    /// If an error occur when executing [`redo`] the error is returned after the command has been removed.
     ///
-    /// [`apply`]: trait.Command.html#tymethod.apply
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// struct Add(char);
+    ///
+    /// impl Command<String> for Add {
+    ///     type Error = &'static str;
+    ///
+    ///     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+    ///         s.push(self.0);
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+    ///         self.0 = s.pop().ok_or("`s` is empty")?;
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let mut record = Record::default();
+    /// record.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+    /// record.undo_at(1).unwrap().unwrap();
+    /// assert_eq!(record.as_receiver(), "ac");
+    /// assert_eq!(record.len(), 2);
+    /// ```
+    ///
+    /// [transposes]: trait.Command.html#method.transpose
+    /// [`retain`]: struct.Record.html#method.retain
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
     #[inline]
-    pub fn extend(&mut self, commands: impl IntoIterator<Item = C>) -> Result<(), C::Error> {
-        for command in commands {
-            self.apply(command)?;
+    pub fn undo_at(&mut self, index: usize) -> Option<Result<(), C::Error>> {
+        let current = self.current();
+        if index >= current {
+            return None;
         }
-        Ok(())
+        let target = &self.commands[index];
+        let id = target.id;
+        let transposes = self
+            .commands
+            .range(index + 1..current)
+            .all(|entry| target.transpose(entry));
+        Some(self.transaction(|record| {
+            let len = record.len();
+            if !transposes {
+                if let Some(Err(err)) = record.__go_to(index) {
+                    return Err(err);
+                }
+            } else if !record.is_expired(index) {
+                let entry = &mut record.commands[index];
+                hooks::call(&record.hooks.before_undo, &entry.command, &record.receiver);
+                if let Err(error) = entry.undo(&mut record.receiver) {
+                    let timestamp = *entry.timestamp;
+                    record.fail(Op::Undo, index, timestamp);
+                    return Err(error);
+                }
+                hooks::call(&record.hooks.after_undo, &entry.command, &record.receiver);
+                record.generation += 1;
+            }
+            // The command has already been removed by the undo if it expired.
+            if let Some(at) = record.commands.iter().position(|entry| entry.id == id) {
+                let entry = record.commands.remove(at).unwrap();
+                record.cache.clear();
+                // The next command starts the group if the removed command did.
+                if let Some(next) = record.commands.get_mut(at) {
+                    next.joined &= entry.joined;
+                }
+                if record.current > at {
+                    record.current -= 1;
+                }
+            }
+            record.saved = record.saved.filter(|&saved| saved <= index);
+            *record.last_applied = None;
+            record.view = None;
+            if transposes {
+                Ok(())
+            } else {
+                record
+                    .__go_to(current - (len - record.len()))
+                    .unwrap_or(Ok(()))
+            }
+        }))
     }
-}
 
-impl<R, C: ToString, F> Record<R, C, F> {
-    /// Returns the string of the command which will be undone in the next call to [`undo`].
+    /// Moves the commands from `at` to the end of the record into a new record.
     ///
-    /// [`undo`]: struct.Record.html#method.undo
+    /// The commands that are moved are undone first, and the new record is positioned so that
+    /// [`append`] redoes the same commands when they are attached to another record. This can be
+    /// used to move part of the history of a document to a new document. The returned record
+    /// has no receiver, and the saved state of the moved commands is discarded.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned and nothing is moved.
+    ///
+    /// # Panics
+    /// Panics if `at` is greater than the length of the record.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = &'static str;
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+    /// #         self.0 = s.pop().ok_or("`s` is empty")?;
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let mut a = Record::default();
+    /// a.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+    /// let tail = a.split_off(1).unwrap();
+    /// assert_eq!(a.as_receiver(), "a");
+    /// let mut b = Record::new("x");
+    /// b.append(tail).unwrap();
+    /// assert_eq!(b.as_receiver(), "xbc");
+    /// assert_eq!(b.len(), 2);
+    /// ```
+    ///
+    /// [`append`]: struct.Record.html#method.append
+    /// [`undo`]: trait.Command.html#tymethod.undo
     #[inline]
-    pub fn to_undo_string(&self) -> Option<String> {
-        if self.can_undo() {
-            Some(self.commands[self.current - 1].command.to_string())
-        } else {
-            None
+    pub fn split_off(&mut self, at: usize) -> Result<Record<(), C>, C::Error> {
+        assert!(at <= self.len(), "`at` is out of bounds");
+        let current = self.current();
+        self.transaction(|record| {
+            if current > at {
+                if let Some(Err(err)) = record.__go_to(at) {
+                    return Err(err);
+                }
+            }
+            let mut commands = record.commands.split_off(at);
+            if let Some(first) = commands.front_mut() {
+                first.joined = false;
+            }
+            record.cache.clear();
+            record.saved = record.saved.filter(|&saved| saved <= at);
+            let mut tail = Record::new(());
+            tail.next_id = commands.iter().map(|entry| entry.id + 1).max().unwrap_or(0);
+            tail.current = current.saturating_sub(at);
+            tail.commands = commands;
+            // The tail has no receiver, so it is not in a saved state.
+            tail.saved = None;
+            Ok(tail)
+        })
+    }
+
+    /// Attaches the commands of `other` after the current position of the record.
+    ///
+    /// The commands after the current position are removed, like when a command is applied.
+    /// The commands before the current position of `other` are redone on the receiver,
+    /// and the rest of them can be redone later. This is the counterpart of [`split_off`],
+    /// and the saved state of `other` is discarded. If the limit is reached, the oldest
+    /// commands are removed, and if too few of them have been redone, the newest commands
+    /// of `other` are removed too.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned, and the record is
+    /// left at the command that failed.
+    ///
+    /// [`split_off`]: struct.Record.html#method.split_off
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn append<R2, F2>(&mut self, other: Record<R2, C, F2>) -> Result<(), C::Error> {
+        self.__append(other.commands, other.current).map(|_| ())
+    }
+
+    /// Attaches the commands after the current position and redoes the first `current` of them.
+    ///
+    /// Returns the number of commands that were removed from the front to stay within the limit.
+    #[inline]
+    pub(crate) fn __append(
+        &mut self,
+        commands: VecDeque<Entry<C>>,
+        current: usize,
+    ) -> Result<usize, C::Error> {
+        self.transaction(|record| {
+            record.view = None;
+            let end = record.current();
+            for entry in record.commands.split_off(end) {
+                record.cache.remove(entry.id);
+            }
+            record.saved = record.saved.filter(|&saved| saved <= end);
+            for (i, mut entry) in commands.into_iter().enumerate() {
+                entry.id = record.next_id;
+                entry.joined &= i > 0;
+                record.next_id += 1;
+                record.commands.push_back(entry);
+            }
+            for _ in 0..current {
+                // Redo returns `None` when the dead commands at the end have been removed.
+                match record.__redo() {
+                    Some(Ok(())) => (),
+                    Some(Err(err)) => return Err(err),
+                    None => break,
+                }
+            }
+            // The commands that have been redone are evicted first, like when a command is applied.
+            let excess = record.len().saturating_sub(record.limit());
+            let evicted = excess.min(record.current);
+            record.evict_front(evicted);
+            if record.len() > record.limit() {
+                // The rest of the excess can only be taken from the commands that are not redone.
+                let limit = record.limit();
+                for entry in record.commands.split_off(limit) {
+                    record.cache.remove(entry.id);
+                    record.hooks.evict(entry.command);
+                }
+                record.saved = record.saved.filter(|&saved| saved <= limit);
+                record.retain_marks();
+            }
+            Ok(evicted)
+        })
+    }
+
+    /// Calls `f` with the index and a mutable reference of every command in the record,
+    /// which is useful for patching the stored commands, e.g. after the ids they refer to
+    /// have been rebased by an external sync.
+    ///
+    /// The receiver is left untouched, so the edits must not change what the commands do to the
+    /// receiver at their current position. `f` returns `true` if it changed the command.
+    /// If a changed command is between the current position and the saved state, going back to the
+    /// saved state would no longer restore it, and the receiver is no longer considered saved.
+    #[inline]
+    pub fn edit_commands(&mut self, mut f: impl FnMut(usize, &mut C) -> bool) {
+        let mut changed = false;
+        let (start, end) = match self.saved {
+            Some(saved) => (saved.min(self.current), saved.max(self.current)),
+            None => (0, 0),
+        };
+        let mut edited = false;
+        for (i, entry) in self.commands.iter_mut().enumerate() {
+            if f(i, &mut entry.command) {
+                edited = true;
+                changed |= (start..end).contains(&i);
+            }
+        }
+        if edited {
+            self.cache.clear();
+        }
+        if changed {
+            self.saved = None;
         }
     }
 
-    /// Returns the string of the command which will be redone in the next call to [`redo`].
+    /// Mutates the current command with `f` and updates the receiver accordingly.
     ///
-    /// [`redo`]: struct.Record.html#method.redo
+    /// The command is undone before `f` is called, and redone afterwards, so the receiver
+    /// always reflects the mutated command. This is useful when the command is continuously
+    /// updated, e.g. with the final position of an ongoing drag.
+    /// If the saved state includes the current command it is no longer considered saved.
+    /// Returns `None` if there is no current command, or if it has expired and can no longer be undone.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned and `f` is not called.
+    /// If an error occur when executing [`redo`] the error is returned and the command is left
+    /// in its undone state.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
     #[inline]
-    pub fn to_redo_string(&self) -> Option<String> {
-        if self.can_redo() {
-            Some(self.commands[self.current].command.to_string())
-        } else {
-            None
+    pub fn amend_current_with(&mut self, f: impl FnOnce(&mut C)) -> Option<Result<(), C::Error>> {
+        if !self.can_undo() || self.is_expired(self.current - 1) {
+            return None;
         }
+        self.transaction(|record| {
+            if let Err(error) = record.__undo()? {
+                return Some(Err(error));
+            }
+            let current = record.current();
+            f(&mut record.commands[current].command);
+            record.cache.clear();
+            record.saved = record.saved.filter(|&saved| saved <= current);
+            record.__redo()
+        })
     }
 
-    /// Returns a structure for configurable formatting of the record.
+    /// Calls [`undo`] up to `n` times, stopping early if there is nothing more to undo.
+    ///
+    /// If the signals are deferred, they are only emitted once all the commands have been undone.
+    /// Returns `None` if nothing could be undone.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned.
+    ///
+    /// [`undo`]: struct.Record.html#method.undo
     #[inline]
-    #[cfg(feature = "display")]
-    pub fn display(&self) -> Display<Self> {
-        Display::from(self)
+    pub fn undo_n(&mut self, n: usize) -> Option<Result<(), C::Error>> {
+        if n == 0 || !self.can_undo() {
+            return None;
+        }
+        self.transaction(|record| {
+            for _ in 0..n {
+                match record.undo() {
+                    Some(Ok(())) => (),
+                    Some(Err(error)) => return Some(Err(error)),
+                    None => break,
+                }
+            }
+            Some(Ok(()))
+        })
     }
-}
 
-impl<R: Default, C> Default for Record<R, C> {
+    /// Calls [`redo`] up to `n` times, stopping early if there is nothing more to redo.
+    ///
+    /// If the signals are deferred, they are only emitted once all the commands have been redone.
+    /// Returns `None` if nothing could be redone.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned.
+    ///
+    /// [`redo`]: struct.Record.html#method.redo
     #[inline]
-    fn default() -> Record<R, C> {
-        Record::new(R::default())
+    pub fn redo_n(&mut self, n: usize) -> Option<Result<(), C::Error>> {
+        if n == 0 || !self.can_redo() {
+            return None;
+        }
+        self.transaction(|record| {
+            for _ in 0..n {
+                match record.redo() {
+                    Some(Ok(())) => (),
+                    Some(Err(error)) => return Some(Err(error)),
+                    None => break,
+                }
+            }
+            Some(Ok(()))
+        })
     }
-}
 
-impl<R, C, F> AsRef<R> for Record<R, C, F> {
+    /// Undoes all the commands, only emitting the signals once if they are deferred.
+    ///
+    /// Returns `None` if there is nothing to undo.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned together with the index of
+    /// the command that failed, see [`Error::context`]. The record is left at the position where
+    /// the failure occurred, so the traversal can be resumed by calling this method again.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = &'static str;
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+    /// #         self.0 = s.pop().ok_or("`s` is empty")?;
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let mut record = Record::default();
+    /// record.apply(Add('a')).unwrap();
+    /// record.apply(Add('b')).unwrap();
+    /// record.as_mut_receiver().truncate(1);
+    /// record.apply(Add('c')).unwrap();
+    /// let error = record.undo_all().unwrap().unwrap_err();
+    /// assert_eq!(error.context().unwrap().index(), 0);
+    /// assert_eq!(record.current(), 1);
+    /// ```
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`Error::context`]: struct.Error.html#method.context
     #[inline]
-    fn as_ref(&self) -> &R {
-        self.as_receiver()
+    pub fn undo_all(&mut self) -> Option<Result<(), Error<C::Error>>> {
+        let n = self.current();
+        self.with_context(|record| record.undo_n(n).transpose())
+            .transpose()
     }
-}
 
-impl<R, C, F> AsMut<R> for Record<R, C, F> {
+    /// Redoes all the commands, only emitting the signals once if they are deferred.
+    ///
+    /// Returns `None` if there is nothing to redo.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned together with the index of
+    /// the command that failed, see [`Error::context`]. The record is left at the position where
+    /// the failure occurred, so the traversal can be resumed by calling this method again.
+    ///
+    /// [`redo`]: trait.Command.html#method.redo
+    /// [`Error::context`]: struct.Error.html#method.context
     #[inline]
-    fn as_mut(&mut self) -> &mut R {
-        self.as_mut_receiver()
+    pub fn redo_all(&mut self) -> Option<Result<(), Error<C::Error>>> {
+        let n = self.len() - self.current();
+        self.with_context(|record| record.redo_n(n).transpose())
+            .transpose()
     }
-}
 
-impl<R, C> From<R> for Record<R, C> {
+    /// Undoes the commands until the previous coarse boundary is reached.
+    ///
+    /// If the signals are deferred, they are only emitted once all the commands have been undone.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
     #[inline]
-    fn from(receiver: R) -> Record<R, C> {
-        Record::new(receiver)
+    pub fn undo_coarse(&mut self) -> Option<Result<(), C::Error>> {
+        if !self.can_undo() {
+            return None;
+        }
+        let current = self
+            .commands
+            .range(..self.current - 1)
+            .rposition(|entry| entry.boundary)
+            .map_or(0, |i| i + 1);
+        self.go_to(current)
     }
-}
 
-impl<R, C, F> From<History<R, C, F>> for Record<R, C, F> {
+    /// Redoes the commands until the next coarse boundary is reached.
+    ///
+    /// If the signals are deferred, they are only emitted once all the commands have been redone.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned.
+    ///
+    /// [`redo`]: trait.Command.html#method.redo
     #[inline]
-    fn from(history: History<R, C, F>) -> Record<R, C, F> {
-        history.record
+    pub fn redo_coarse(&mut self) -> Option<Result<(), C::Error>> {
+        if !self.can_redo() {
+            return None;
+        }
+        let current = self
+            .commands
+            .range(self.current..)
+            .position(|entry| entry.boundary)
+            .map_or(self.len(), |i| self.current + i + 1);
+        self.go_to(current)
     }
-}
 
-#[cfg(feature = "display")]
-impl<R, C: fmt::Display, F> fmt::Display for Record<R, C, F> {
+    /// Go back or forward in the record to the command that was made closest to the datetime provided.
     #[inline]
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        (&self.display() as &dyn fmt::Display).fmt(f)
+    #[cfg(feature = "chrono")]
+    pub fn time_travel(&mut self, to: &DateTime<impl TimeZone>) -> Option<Result<(), C::Error>> {
+        self.time_travel_to(to.with_timezone(&Utc))
     }
-}
 
-/// Builder for a record.
-///
-/// # Examples
-/// ```
-/// # use redo::{Command, Record};
-/// # struct Add(char);
-/// # impl Command<String> for Add {
-/// #     type Error = ();
-/// #     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> { Ok(()) }
-/// #     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> { Ok(()) }
-/// # }
-/// # fn foo() -> Record<String, Add> {
-/// Record::builder()
-///     .capacity(100)
-///     .limit(100)
-///     .saved(false)
-///     .default()
-/// # }
-/// ```
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
-pub struct RecordBuilder<R, C> {
-    commands: PhantomData<C>,
-    receiver: PhantomData<R>,
-    capacity: usize,
-    limit: NonZeroUsize,
-    saved: bool,
+    /// Go back or forward in the record to the command that was made closest to the datetime provided.
+    #[inline]
+    #[cfg(all(feature = "time", not(feature = "chrono")))]
+    pub fn time_travel(&mut self, to: &OffsetDateTime) -> Option<Result<(), C::Error>> {
+        self.time_travel_to(*to)
+    }
+
+    #[inline]
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    fn time_travel_to(&mut self, to: Time) -> Option<Result<(), C::Error>> {
+        let current = match self.commands.as_slices() {
+            ([], []) => return None,
+            (start, []) => match start.binary_search_by(|entry| time(*entry.timestamp).cmp(&to)) {
+                Ok(current) | Err(current) => current,
+            },
+            ([], end) => match end.binary_search_by(|entry| time(*entry.timestamp).cmp(&to)) {
+                Ok(current) | Err(current) => current,
+            },
+            (start, end) => match time(*start.last().unwrap().timestamp).cmp(&to) {
+                Ordering::Less => {
+                    match start.binary_search_by(|entry| time(*entry.timestamp).cmp(&to)) {
+                        Ok(current) | Err(current) => current,
+                    }
+                }
+                Ordering::Equal => start.len(),
+                Ordering::Greater => {
+                    match end.binary_search_by(|entry| time(*entry.timestamp).cmp(&to)) {
+                        Ok(current) | Err(current) => start.len() + current,
+                    }
+                }
+            },
+        };
+        self.go_to(current)
+    }
+
+    /// Applies each command in the iterator.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned
+    /// and the remaining commands in the iterator are discarded.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    pub fn extend(&mut self, commands: impl IntoIterator<Item = C>) -> Result<(), C::Error> {
+        self.transaction(|record| {
+            for command in commands {
+                record.apply(command)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Returns a heatmap of the commands in the record grouped into time buckets of length `bucket`.
+    ///
+    /// # Panics
+    /// Panics if `bucket` is zero.
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn heatmap(&self, bucket: Duration) -> Heatmap {
+        Heatmap::new(
+            bucket,
+            self.commands
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| (entry, entry.command.region(), i >= self.current)),
+        )
+    }
+
+    /// Applies each command in the iterator, canceling all of them if one fails.
+    ///
+    /// If the signals are deferred, they are only emitted once all the commands have been applied or canceled.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the commands that were applied are canceled,
+    /// and the error is returned together with the result of canceling them.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    #[cfg(feature = "checkpoint")]
+    pub fn extend_atomic(
+        &mut self,
+        commands: impl IntoIterator<Item = C>,
+    ) -> Result<(), RollbackError<C::Error>> {
+        self.transaction(|record| {
+            let mut checkpoint = record.checkpoint();
+            checkpoint
+                .extend(commands)
+                .map_err(|error| checkpoint.rollback(error))
+        })
+    }
+
+    /// Pushes the entry on top of the record, or merges it into the last entry with `merge`,
+    /// and removes the first commands if the limits are reached, see [`stack::push`].
+    #[inline]
+    fn push_back(
+        &mut self,
+        entry: Entry<C>,
+        merge: impl FnOnce(&mut Entry<C>, Entry<C>) -> Merge<Entry<C>>,
+    ) -> Pushed<Entry<C>> {
+        let limit = self.limit();
+        let pushed = stack::push(
+            &mut self.commands,
+            &mut self.current,
+            &mut self.saved,
+            limit,
+            entry,
+            merge,
+        );
+        if let Pushed::Added(evicted) = pushed {
+            if let Some(entry) = evicted {
+                self.cache.remove(entry.id);
+                self.hooks.evict(entry.command);
+                self.retain_marks();
+            }
+            let excess = self
+                .weight
+                .excess(self.commands.iter().map(|entry| &entry.command));
+            if excess > 0 {
+                self.evict_front(excess);
+            }
+            return Pushed::Added(None);
+        }
+        pushed
+    }
+
+    /// Removes the first `n` commands because the limit is reached.
+    #[inline]
+    fn evict_front(&mut self, n: usize) {
+        let (cache, hooks) = (&mut self.cache, &self.hooks);
+        stack::evict(
+            &mut self.commands,
+            &mut self.current,
+            &mut self.saved,
+            n,
+            |entry| {
+                cache.remove(entry.id);
+                hooks.evict(entry.command);
+            },
+        );
+        self.retain_marks();
+    }
+
+    /// Performs `f` as one operation, only emitting the net signals when it is done if they are deferred.
+    #[inline]
+    pub(crate) fn transaction<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.slot.begin(self.state());
+        let t = f(self);
+        self.slot.end(self.state());
+        t
+    }
 }
 
-impl<R, C> RecordBuilder<R, C> {
-    /// Returns a builder for a record.
+impl<R, C: ProgressCommand<R>, F: FnMut(Signal)> Record<R, C, F> {
+    /// Pushes the command on top of the record and executes its [`apply_with_progress`] method,
+    /// which calls `progress` with the fraction of the work that is done.
+    ///
+    /// The command is handled exactly like it is by [`apply`], e.g. it can be merged with the
+    /// previous command, and it is undone and redone without reporting any progress.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply_with_progress`] the error is returned.
+    ///
+    /// [`apply_with_progress`]: trait.ProgressCommand.html#method.apply_with_progress
+    /// [`apply`]: struct.Record.html#method.apply
     #[inline]
-    pub fn new() -> RecordBuilder<R, C> {
-        RecordBuilder {
-            commands: PhantomData,
-            receiver: PhantomData,
-            capacity: 0,
-            limit: MAX_LIMIT,
-            saved: true,
+    pub fn apply_with_progress(
+        &mut self,
+        command: C,
+        mut progress: impl FnMut(f32),
+    ) -> Result<(), C::Error> {
+        let entry = self.entry(command);
+        self.__apply_with(entry, |command, receiver| {
+            command.apply_with_progress(receiver, &mut progress)
+        })
+        .map(|_| ())
+    }
+}
+
+impl<R, C: Command<R>> Record<R, C> {
+    /// Creates a record from a stream of events.
+    ///
+    /// The commands are applied to the `receiver` up to the first inverse event, and the
+    /// remaining commands are put after the current position so they can be redone.
+    /// The sequence numbers are used as the ids of the commands, so they are expected to be
+    /// increasing. A command whose sequence number is not greater than the ones before it is
+    /// given a new id instead, since the ids of the commands must be unique.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    pub fn from_events(
+        receiver: impl Into<R>,
+        events: impl IntoIterator<Item = Event<C>>,
+    ) -> Result<Record<R, C>, C::Error> {
+        let mut record = Record::new(receiver);
+        let mut applying = true;
+        for event in events {
+            let id = Some(event.sequence())
+                .filter(|&sequence| sequence >= record.next_id)
+                .unwrap_or(record.next_id);
+            applying &= !event.is_inverse();
+            let mut entry = Entry::new(event.into_command(), id);
+            if applying {
+                entry.apply(&mut record.receiver)?;
+                record.current += 1;
+            }
+            record.next_id = id + 1;
+            record.commands.push_back(entry);
+        }
+        Ok(record)
+    }
+
+    /// Reconstructs a record from a log exported by [`export_log`].
+    ///
+    /// The commands are applied to the `receiver` up to the first undone command, and the
+    /// remaining commands are put after the current position so they can be redone.
+    /// The commands are never merged with each other, so the record gets the same commands,
+    /// ids, merged ids, and labels as the record the log was exported from.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned.
+    ///
+    /// [`export_log`]: struct.Record.html#method.export_log
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    pub fn replay(
+        receiver: impl Into<R>,
+        log: impl IntoIterator<Item = LoggedCommand<C>>,
+    ) -> Result<Record<R, C>, C::Error> {
+        let mut record = Record::new(receiver);
+        let mut applying = true;
+        for logged in log {
+            applying &= !logged.is_undone();
+            let mut entry = Entry::from_logged(logged);
+            if applying {
+                entry.apply(&mut record.receiver)?;
+                record.current += 1;
+            }
+            let last = entry
+                .merged()
+                .iter()
+                .fold(entry.id, |last, &id| last.max(id));
+            record.next_id = record.next_id.max(last + 1);
+            record.commands.push_back(entry);
+        }
+        Ok(record)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<R: Clone, C: Command<R>> Record<R, C> {
+    /// Deserializes a record and verifies that its commands can be undone and redone.
+    ///
+    /// The `receiver` is the state the record is expected to be in at its current position.
+    /// A clone of it is used to undo every command down to the start of the record, and then redo
+    /// every command up to the end of the record, before the commands are put back at the current
+    /// position. The receiver stored in the record is not touched. This catches corrupted save files
+    /// before they are handed to the application, and reports the position of the first command that failed.
+    ///
+    /// # Errors
+    /// If the data can not be deserialized, or a command fails to be undone or redone, the error is returned.
+    #[inline]
+    pub fn deserialize_validated<'de, D>(
+        deserializer: D,
+        receiver: &R,
+    ) -> Result<Record<R, C>, ValidationError<D::Error, C::Error>>
+    where
+        R: Deserialize<'de>,
+        C: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let mut record = <Record<R, C> as Deserialize>::deserialize(deserializer)
+            .map_err(ValidationError::Deserialize)?;
+        record.check_commands(receiver)?;
+        Ok(record)
+    }
+
+    /// Undoes and redoes every command on a clone of the `receiver`.
+    #[inline]
+    fn check_commands<D>(&mut self, receiver: &R) -> Result<(), ValidationError<D, C::Error>> {
+        let receiver = mem::replace(&mut self.receiver, receiver.clone());
+        let current = self.current;
+        let result = self.undo_redo_all(current);
+        self.receiver = receiver;
+        result
+    }
+
+    #[inline]
+    fn undo_redo_all<D>(&mut self, current: usize) -> Result<(), ValidationError<D, C::Error>> {
+        let undo = |index| move |error| ValidationError::Undo { index, error };
+        let redo = |index| move |error| ValidationError::Redo { index, error };
+        for index in (0..current).rev() {
+            self.commands[index]
+                .undo(&mut self.receiver)
+                .map_err(undo(index))?;
+        }
+        for index in 0..self.len() {
+            self.commands[index]
+                .redo(&mut self.receiver)
+                .map_err(redo(index))?;
+        }
+        for index in (current..self.len()).rev() {
+            self.commands[index]
+                .undo(&mut self.receiver)
+                .map_err(undo(index))?;
+        }
+        Ok(())
+    }
+}
+
+impl<R, C: AsPatch, F> Record<R, C, F> {
+    /// Returns an iterator over the patches of the commands in the `range`, in the order they were applied.
+    ///
+    /// # Panics
+    /// Panics if the `range` is out of bounds.
+    #[inline]
+    pub fn export_patches(
+        &self,
+        range: impl RangeBounds<usize>,
+    ) -> impl Iterator<Item = C::Patch> + '_ {
+        self.commands
+            .range(range)
+            .map(|entry| entry.command.as_patch())
+    }
+
+    /// Returns an iterator over the inverse patches of the commands in the `range`,
+    /// in the order they would be undone.
+    ///
+    /// # Panics
+    /// Panics if the `range` is out of bounds.
+    #[inline]
+    pub fn export_inverse_patches(
+        &self,
+        range: impl RangeBounds<usize>,
+    ) -> impl Iterator<Item = Option<C::Patch>> + '_ {
+        self.commands
+            .range(range)
+            .rev()
+            .map(|entry| entry.command.as_inverse_patch())
+    }
+}
+
+impl<R, C: ToString, F> Record<R, C, F> {
+    /// Returns the string of the command which will be undone in the next call to [`undo`].
+    ///
+    /// [`undo`]: struct.Record.html#method.undo
+    #[inline]
+    pub fn to_undo_string(&self) -> Option<String> {
+        if self.can_undo() {
+            Some(self.commands[self.current - 1].to_label_string())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the string of the command which will be redone in the next call to [`redo`].
+    ///
+    /// [`redo`]: struct.Record.html#method.redo
+    #[inline]
+    pub fn to_redo_string(&self) -> Option<String> {
+        if self.can_redo() {
+            Some(self.commands[self.current].to_label_string())
+        } else {
+            None
+        }
+    }
+
+    /// Returns a snapshot of the record with the strings of the commands.
+    #[inline]
+    pub fn snapshot(&self) -> Snapshot<String> {
+        self.snapshot_with(C::to_string)
+    }
+
+    /// Returns a stable textual dump of the record.
+    ///
+    /// See the [`testing`] module for the format.
+    ///
+    /// [`testing`]: testing/index.html
+    #[inline]
+    #[cfg(feature = "testing")]
+    pub fn dump(&self) -> String {
+        let mut dump = format!("current {}\n", self.current);
+        match self.saved {
+            Some(saved) => dump.push_str(&format!("saved {}\n", saved)),
+            None => dump.push_str("saved none\n"),
+        }
+        testing::commands(&mut dump, 0, &self.commands);
+        dump
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<R: Serialize, C: Serialize, F> Serialize for Record<R, C, F> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Record::serialize(self, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, R: Deserialize<'de>, C: Deserialize<'de>, F> Deserialize<'de> for Record<R, C, F> {
+    /// Deserializes the record and rejects it if it is invalid, see [`validate`].
+    ///
+    /// [`validate`]: struct.Record.html#method.validate
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut record = Record::deserialize(deserializer)?;
+        record.validate().map_err(de::Error::custom)?;
+        record.fix_ids(iter::empty());
+        Ok(record)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<R, C, F> Record<R, C, F> {
+    /// Numbers the commands and the other `entries` in order if their ids are not unique, which is
+    /// the case when they were serialized before the commands had ids, and moves the next id past
+    /// every id that is in use.
+    #[inline]
+    pub(crate) fn fix_ids<'a>(&mut self, entries: impl Iterator<Item = &'a mut Entry<C>>)
+    where
+        C: 'a,
+    {
+        let mut all: Vec<_> = self.commands.iter_mut().collect();
+        // The entries are pushed one at a time so their lifetime is shortened to the commands'.
+        for entry in entries {
+            all.push(entry);
+        }
+        let mut ids: Vec<_> = all.iter().map(|entry| entry.id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        if ids.len() < all.len() {
+            for (id, entry) in all.iter_mut().enumerate() {
+                entry.id = id;
+            }
+        }
+        let last = all
+            .iter()
+            .flat_map(|entry| entry.merged().iter().chain([&entry.id]))
+            .max();
+        if let Some(&last) = last {
+            self.next_id = self.next_id.max(last + 1);
+        }
+    }
+}
+
+impl<R: Default, C> Default for Record<R, C> {
+    #[inline]
+    fn default() -> Record<R, C> {
+        Record::new(R::default())
+    }
+}
+
+impl<R, C, F> AsRef<R> for Record<R, C, F> {
+    #[inline]
+    fn as_ref(&self) -> &R {
+        self.as_receiver()
+    }
+}
+
+impl<R, C, F> AsMut<R> for Record<R, C, F> {
+    #[inline]
+    fn as_mut(&mut self) -> &mut R {
+        self.as_mut_receiver()
+    }
+}
+
+impl<R, C> From<R> for Record<R, C> {
+    #[inline]
+    fn from(receiver: R) -> Record<R, C> {
+        Record::new(receiver)
+    }
+}
+
+#[cfg(feature = "history")]
+impl<R, C, F> From<History<R, C, F>> for Record<R, C, F> {
+    #[inline]
+    fn from(history: History<R, C, F>) -> Record<R, C, F> {
+        history.record
+    }
+}
+
+impl<R, C, F> IntoIterator for Record<R, C, F> {
+    type Item = C;
+    type IntoIter = RecordIntoIter<C>;
+
+    /// Consumes the record, returning an iterator over the commands from the first to the last.
+    #[inline]
+    fn into_iter(self) -> RecordIntoIter<C> {
+        RecordIntoIter(self.commands.into_iter())
+    }
+}
+
+impl<'a, R, C, F> IntoIterator for &'a Record<R, C, F> {
+    type Item = &'a C;
+    type IntoIter = RecordIter<'a, C>;
+
+    /// Returns an iterator over the commands from the first to the last, see [`commands`].
+    ///
+    /// [`commands`]: struct.Record.html#method.commands
+    #[inline]
+    fn into_iter(self) -> RecordIter<'a, C> {
+        RecordIter(self.commands.iter())
+    }
+}
+
+/// An iterator over the commands in a record.
+///
+/// Created by iterating over a reference to a [`Record`].
+///
+/// [`Record`]: struct.Record.html
+#[derive(Clone, Debug)]
+pub struct RecordIter<'a, C>(pub(crate) vec_deque::Iter<'a, Entry<C>>);
+
+impl<'a, C> Iterator for RecordIter<'a, C> {
+    type Item = &'a C;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a C> {
+        self.0.next().map(|entry| &entry.command)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<C> DoubleEndedIterator for RecordIter<'_, C> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|entry| &entry.command)
+    }
+}
+
+impl<C> ExactSizeIterator for RecordIter<'_, C> {}
+
+/// An iterator that moves the commands out of a record.
+///
+/// Created by calling `into_iter` on a [`Record`].
+///
+/// [`Record`]: struct.Record.html
+#[derive(Debug)]
+pub struct RecordIntoIter<C>(pub(crate) vec_deque::IntoIter<Entry<C>>);
+
+impl<C> Iterator for RecordIntoIter<C> {
+    type Item = C;
+
+    #[inline]
+    fn next(&mut self) -> Option<C> {
+        self.0.next().map(|entry| entry.command)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<C> DoubleEndedIterator for RecordIntoIter<C> {
+    #[inline]
+    fn next_back(&mut self) -> Option<C> {
+        self.0.next_back().map(|entry| entry.command)
+    }
+}
+
+impl<C> ExactSizeIterator for RecordIntoIter<C> {}
+
+#[cfg(feature = "display")]
+impl<R, C: fmt::Display, F> fmt::Display for Record<R, C, F> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (&self.display() as &dyn fmt::Display).fmt(f)
+    }
+}
+
+/// The error returned by [`Record::deserialize_validated`].
+///
+/// [`Record::deserialize_validated`]: struct.Record.html#method.deserialize_validated
+#[cfg(feature = "serde")]
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum ValidationError<D, E> {
+    /// The data could not be deserialized.
+    Deserialize(D),
+    /// The command at `index` could not be undone.
+    Undo {
+        /// The index of the command.
+        index: usize,
+        /// The error returned by the command.
+        error: E,
+    },
+    /// The command at `index` could not be redone.
+    Redo {
+        /// The index of the command.
+        index: usize,
+        /// The error returned by the command.
+        error: E,
+    },
+}
+
+#[cfg(feature = "serde")]
+impl<D: fmt::Display, E: fmt::Display> fmt::Display for ValidationError<D, E> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::Deserialize(error) => error.fmt(f),
+            ValidationError::Undo { index, error } => {
+                write!(f, "command {} could not be undone: {}", index, error)
+            }
+            ValidationError::Redo { index, error } => {
+                write!(f, "command {} could not be redone: {}", index, error)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<D: fmt::Debug + fmt::Display, E: fmt::Debug + fmt::Display> error::Error
+    for ValidationError<D, E>
+{
+}
+
+/// The number of commands that separate the current state from the saved state.
+///
+/// Returned by [`unsaved_count`]. The saved state is reached by undoing [`undo`] commands and then
+/// redoing [`redo`] commands. In a record only one of them is non-zero, while in a history both are
+/// non-zero if the saved state is in another branch.
+///
+/// [`unsaved_count`]: struct.Record.html#method.unsaved_count
+/// [`undo`]: struct.UnsavedCount.html#method.undo
+/// [`redo`]: struct.UnsavedCount.html#method.redo
+#[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct UnsavedCount {
+    undo: usize,
+    redo: usize,
+}
+
+impl UnsavedCount {
+    #[inline]
+    pub(crate) fn new(undo: usize, redo: usize) -> UnsavedCount {
+        UnsavedCount { undo, redo }
+    }
+
+    /// Returns the number of commands that must be undone to reach the saved state,
+    /// which are the changes made since the receiver was saved.
+    #[inline]
+    pub fn undo(&self) -> usize {
+        self.undo
+    }
+
+    /// Returns the number of commands that must be redone to reach the saved state,
+    /// which are the changes that have been undone since the receiver was saved.
+    #[inline]
+    pub fn redo(&self) -> usize {
+        self.redo
+    }
+
+    /// Returns the total number of commands that separate the current state from the saved state.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.undo + self.redo
+    }
+
+    /// Returns `true` if the receiver is in the saved state.
+    #[inline]
+    pub fn is_saved(&self) -> bool {
+        self.count() == 0
+    }
+}
+
+/// A view of a command in the record together with its metadata.
+///
+/// Returned by [`entries`].
+///
+/// [`entries`]: struct.Record.html#method.entries
+#[derive(Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct EntryView<'a, C> {
+    entry: &'a Entry<C>,
+    current: bool,
+    saved: bool,
+}
+
+impl<C> Clone for EntryView<'_, C> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for EntryView<'_, C> {}
+
+impl<'a, C> EntryView<'a, C> {
+    #[inline]
+    pub(crate) fn new(entry: &'a Entry<C>, current: bool, saved: bool) -> EntryView<'a, C> {
+        EntryView {
+            entry,
+            current,
+            saved,
+        }
+    }
+
+    /// Returns a reference to the command.
+    #[inline]
+    pub fn command(&self) -> &'a C {
+        &self.entry.command
+    }
+
+    /// Returns the label the command was applied with, if any.
+    ///
+    /// If commands are merged, the label of the first command that has one is kept.
+    #[inline]
+    pub fn label(&self) -> Option<&'a str> {
+        self.entry.label()
+    }
+
+    /// Returns the id of the command.
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.entry.id
+    }
+
+    /// Returns the time the command was applied.
+    #[inline]
+    #[cfg(feature = "chrono")]
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        time(*self.entry.timestamp)
+    }
+
+    /// Returns the time the command was applied.
+    #[inline]
+    #[cfg(all(feature = "time", not(feature = "chrono")))]
+    pub fn timestamp(&self) -> OffsetDateTime {
+        time(*self.entry.timestamp)
+    }
+
+    /// Returns the time the command was applied.
+    ///
+    /// Without the `chrono` or `time` feature the time is only recorded if a clock or
+    /// `merge_within` is set, otherwise this returns the Unix epoch.
+    #[inline]
+    #[cfg(all(feature = "std", not(any(feature = "chrono", feature = "time"))))]
+    pub fn timestamp(&self) -> SystemTime {
+        time(*self.entry.timestamp)
+    }
+
+    /// Returns `true` if the command is at the current position.
+    #[inline]
+    pub fn is_current(&self) -> bool {
+        self.current
+    }
+
+    /// Returns `true` if the command is at the saved position.
+    #[inline]
+    pub fn is_saved(&self) -> bool {
+        self.saved
+    }
+}
+
+/// Builder for a record.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, Record};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> { Ok(()) }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> { Ok(()) }
+/// # }
+/// # fn foo() -> Record<String, Add> {
+/// Record::builder()
+///     .capacity(100)
+///     .limit(100)
+///     .saved(false)
+///     .default()
+/// # }
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct RecordBuilder<R, C> {
+    commands: Vec<C>,
+    receiver: PhantomData<R>,
+    capacity: usize,
+    limit: NonZeroUsize,
+    saved: bool,
+    current: usize,
+    merge_within: Option<Duration>,
+    merge_across_saved: bool,
+    #[cfg_attr(feature = "serde", serde(default = "Hooks::default", skip))]
+    hooks: Hooks<R, C>,
+    #[cfg_attr(feature = "serde", serde(default = "SnapshotCache::default", skip))]
+    cache: SnapshotCache<R>,
+    #[cfg_attr(feature = "serde", serde(default = "WeightLimit::default", skip))]
+    weight: WeightLimit<C>,
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "serde", serde(default = "SharedClock::default", skip))]
+    clock: SharedClock,
+}
+
+impl<R, C> RecordBuilder<R, C> {
+    /// Returns a builder for a record.
+    #[inline]
+    pub fn new() -> RecordBuilder<R, C> {
+        RecordBuilder {
+            commands: Vec::new(),
+            receiver: PhantomData,
+            capacity: 0,
+            limit: MAX_LIMIT,
+            saved: true,
+            current: 0,
+            merge_within: None,
+            merge_across_saved: false,
+            hooks: Hooks::default(),
+            cache: SnapshotCache::default(),
+            weight: WeightLimit::default(),
+            #[cfg(feature = "std")]
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Sets the capacity for the record.
+    #[inline]
+    pub fn capacity(mut self, capacity: usize) -> RecordBuilder<R, C> {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets the `limit` of the record.
+    ///
+    /// # Panics
+    /// Panics if `limit` is `0`, see [`try_limit`].
+    ///
+    /// [`try_limit`]: struct.RecordBuilder.html#method.try_limit
+    #[inline]
+    pub fn limit(mut self, limit: usize) -> RecordBuilder<R, C> {
+        match NonZeroUsize::new(limit) {
+            Some(limit) => self.limit = limit,
+            None => panic!("{}", ConfigError::ZeroLimit),
+        }
+        self
+    }
+
+    /// Sets the `limit` of the record, see [`limit`].
+    ///
+    /// # Errors
+    /// Returns [`ConfigError::ZeroLimit`] if `limit` is `0`.
+    ///
+    /// [`limit`]: struct.RecordBuilder.html#method.limit
+    /// [`ConfigError::ZeroLimit`]: enum.ConfigError.html#variant.ZeroLimit
+    #[inline]
+    pub fn try_limit(mut self, limit: usize) -> Result<RecordBuilder<R, C>, ConfigError> {
+        self.limit = NonZeroUsize::new(limit).ok_or(ConfigError::ZeroLimit)?;
+        Ok(self)
+    }
+
+    /// Limits the total weight of the commands in the record to `max`, where the weight of
+    /// each command is given by `weigh`.
+    ///
+    /// This works together with the [`limit`] on the number of commands, and is useful when
+    /// the commands hold a varying amount of memory, e.g. the pixels an image edit replaced.
+    /// When a command is pushed onto the record, the first commands are removed until the
+    /// total weight is at most `max`. The command that was pushed is never removed, even if
+    /// its own weight is greater than `max`. Since the weights are summed from the top of the
+    /// record every time a command is pushed, `weigh` should be cheap to call.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// # struct Add(String);
+    /// # impl Command<String> for Add {
+    /// #     type Error = ();
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.push_str(&self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.truncate(s.len() - self.0.len());
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let mut record = Record::builder()
+    ///     .limit_by(|add: &Add| add.0.len(), 5)
+    ///     .default();
+    /// record.apply(Add("abc".into())).unwrap();
+    /// record.apply(Add("de".into())).unwrap();
+    /// assert_eq!(record.len(), 2);
+    /// record.apply(Add("f".into())).unwrap();
+    /// assert_eq!(record.len(), 2);
+    /// assert_eq!(record.as_receiver(), "abcdef");
+    /// ```
+    ///
+    /// [`limit`]: struct.RecordBuilder.html#method.limit
+    #[inline]
+    pub fn limit_by(
+        mut self,
+        weigh: impl Fn(&C) -> usize + Send + Sync + 'static,
+        max: usize,
+    ) -> RecordBuilder<R, C> {
+        self.weight = WeightLimit::new(weigh, max);
+        self
+    }
+
+    /// Sets if the receiver is initially in a saved state.
+    /// By default the receiver is in a saved state.
+    #[inline]
+    pub fn saved(mut self, saved: bool) -> RecordBuilder<R, C> {
+        self.saved = saved;
+        self
+    }
+
+    /// Sets the time within which a command must be applied after the previous command
+    /// for them to be merged.
+    ///
+    /// When set, the [`merge`] method is only called if the command is applied within `duration`
+    /// of the previous command on top of the record, which makes it easy to coalesce keystrokes
+    /// that are typed in a quick succession. The time is measured from the last command that was
+    /// merged into it, and commands are not merged after an undo or redo.
+    /// By default commands are always offered to be merged.
+    ///
+    /// [`merge`]: trait.Command.html#method.merge
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn merge_within(mut self, duration: Duration) -> RecordBuilder<R, C> {
+        self.merge_within = Some(duration);
+        self
+    }
+
+    /// Sets the clock that is used for the timestamps of the commands.
+    ///
+    /// The timestamps are used by [`merge_within`] and [`time_travel`], so a mock clock makes
+    /// it possible to test them without waiting. By default the system time is used, which
+    /// without the `chrono` or `time` feature is only recorded when [`merge_within`] is set.
+    ///
+    /// [`merge_within`]: struct.RecordBuilder.html#method.merge_within
+    /// [`time_travel`]: struct.Record.html#method.time_travel
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn clock(mut self, clock: impl Clock + 'static) -> RecordBuilder<R, C> {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Sets if commands can be merged into the command at the saved state.
+    ///
+    /// By default commands are not merged when the receiver is in a saved state, since the saved
+    /// state would be lost. When this is on, the commands are merged anyway and the receiver is
+    /// no longer considered saved, which suits applications that save often.
+    #[inline]
+    pub fn merge_across_saved(mut self, on: bool) -> RecordBuilder<R, C> {
+        self.merge_across_saved = on;
+        self
+    }
+
+    /// Takes a snapshot of the receiver every `n` commands, which [`go_to`] and [`time_travel`]
+    /// restore to replay only the commands between the snapshot and the target.
+    ///
+    /// This is useful when the commands are expensive, since jumping far back in the record would
+    /// otherwise undo every command in between. The snapshot is made with `save`, and `restore`
+    /// puts the receiver back into the state of the snapshot. The snapshots are taken when the
+    /// record reaches a multiple of `n`, and are discarded when the commands before them change.
+    ///
+    /// A snapshot is only restored if every command it skips returns `true` from [`restorable`],
+    /// none of them are expired or dead, and no undo or redo hooks are set, since the skipped
+    /// commands are neither undone nor redone. Otherwise the commands are undone or redone as usual.
+    ///
+    /// # Examples
+    /// ```
+    /// # use redo::{Command, Record};
+    /// # struct Add(char);
+    /// # impl Command<String> for Add {
+    /// #     type Error = ();
+    /// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.push(self.0);
+    /// #         Ok(())
+    /// #     }
+    /// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+    /// #         s.pop();
+    /// #         Ok(())
+    /// #     }
+    /// #     fn restorable(&self) -> bool {
+    /// #         true
+    /// #     }
+    /// # }
+    /// let mut record = Record::builder()
+    ///     .snapshot_every(100, String::clone, |snapshot, s| s.clone_from(snapshot))
+    ///     .build("");
+    /// for _ in 0..1000 {
+    ///     record.apply(Add('a')).unwrap();
+    /// }
+    /// // Restores the snapshot at 100 and redoes a single command.
+    /// record.go_to(101).unwrap().unwrap();
+    /// assert_eq!(record.as_receiver().len(), 101);
+    /// ```
+    ///
+    /// [`go_to`]: struct.Record.html#method.go_to
+    /// [`time_travel`]: struct.Record.html#method.time_travel
+    /// [`restorable`]: trait.Command.html#method.restorable
+    #[inline]
+    pub fn snapshot_every<S: Send + Sync + 'static>(
+        mut self,
+        n: usize,
+        save: impl Fn(&R) -> S + Send + Sync + 'static,
+        restore: impl Fn(&S, &mut R) + Send + Sync + 'static,
+    ) -> RecordBuilder<R, C> {
+        self.cache = SnapshotCache::new(n, save, restore);
+        self
+    }
+
+    /// Sets a hook that is called with the command and the receiver before the command is applied.
+    ///
+    /// Unlike the signals, the hooks have access to the command, which makes them useful for
+    /// logging an audit trail, or for only updating the part of the receiver that is changed.
+    /// The "after" hooks are only called if the command succeeded. The hooks are shared with
+    /// the clones of the record.
+    #[inline]
+    pub fn on_before_apply(
+        mut self,
+        f: impl Fn(&C, &R) + Send + Sync + 'static,
+    ) -> RecordBuilder<R, C> {
+        self.hooks.before_apply = Ignored(Some(Arc::new(f)));
+        self
+    }
+
+    /// Sets a hook that is called with the command and the receiver after the command is applied.
+    #[inline]
+    pub fn on_after_apply(
+        mut self,
+        f: impl Fn(&C, &R) + Send + Sync + 'static,
+    ) -> RecordBuilder<R, C> {
+        self.hooks.after_apply = Ignored(Some(Arc::new(f)));
+        self
+    }
+
+    /// Sets a hook that is called with the command and the receiver before the command is undone.
+    #[inline]
+    pub fn on_before_undo(
+        mut self,
+        f: impl Fn(&C, &R) + Send + Sync + 'static,
+    ) -> RecordBuilder<R, C> {
+        self.hooks.before_undo = Ignored(Some(Arc::new(f)));
+        self
+    }
+
+    /// Sets a hook that is called with the command and the receiver after the command is undone.
+    #[inline]
+    pub fn on_after_undo(
+        mut self,
+        f: impl Fn(&C, &R) + Send + Sync + 'static,
+    ) -> RecordBuilder<R, C> {
+        self.hooks.after_undo = Ignored(Some(Arc::new(f)));
+        self
+    }
+
+    /// Sets a hook that is called with the command and the receiver before the command is redone.
+    #[inline]
+    pub fn on_before_redo(
+        mut self,
+        f: impl Fn(&C, &R) + Send + Sync + 'static,
+    ) -> RecordBuilder<R, C> {
+        self.hooks.before_redo = Ignored(Some(Arc::new(f)));
+        self
+    }
+
+    /// Sets a hook that is called with the command and the receiver after the command is redone.
+    #[inline]
+    pub fn on_after_redo(
+        mut self,
+        f: impl Fn(&C, &R) + Send + Sync + 'static,
+    ) -> RecordBuilder<R, C> {
+        self.hooks.after_redo = Ignored(Some(Arc::new(f)));
+        self
+    }
+
+    /// Sets the initial commands of the record and the position of the current command.
+    ///
+    /// The commands are not applied to the receiver, which is expected to already be in the state
+    /// at the `current` position. This is used to restore a session where the receiver and its
+    /// commands were stored separately. If the receiver is initially in a saved state,
+    /// it is saved at the `current` position.
+    ///
+    /// # Panics
+    /// Panics when the record is built if `current` is greater than the number of commands,
+    /// or if the commands exceed the limit or the weight limit, see [`limit_by`].
+    ///
+    /// [`limit_by`]: struct.RecordBuilder.html#method.limit_by
+    #[inline]
+    pub fn entries(
+        mut self,
+        commands: impl IntoIterator<Item = C>,
+        current: usize,
+    ) -> RecordBuilder<R, C> {
+        self.commands = commands.into_iter().collect();
+        self.current = current;
+        self
+    }
+
+    /// Builds the record.
+    ///
+    /// # Panics
+    /// Panics if the initial commands are invalid, see [`entries`] and [`try_build`].
+    ///
+    /// [`entries`]: struct.RecordBuilder.html#method.entries
+    /// [`try_build`]: struct.RecordBuilder.html#method.try_build
+    #[inline]
+    pub fn build(self, receiver: impl Into<R>) -> Record<R, C> {
+        self.record(receiver.into(), Slot::default())
+    }
+
+    /// Builds the record, or returns an error if the initial commands are invalid,
+    /// see [`entries`].
+    ///
+    /// # Errors
+    /// Returns [`ConfigError::CurrentOutOfBounds`] if `current` is greater than the number of
+    /// commands, and [`ConfigError::LimitExceeded`] if the number of commands is greater than the limit.
+    /// The commands also exceed the limit if their total weight is greater than the maximum
+    /// weight of [`limit_by`], and then the limit is the number of commands that fit.
+    ///
+    /// [`entries`]: struct.RecordBuilder.html#method.entries
+    /// [`ConfigError::CurrentOutOfBounds`]: enum.ConfigError.html#variant.CurrentOutOfBounds
+    /// [`ConfigError::LimitExceeded`]: enum.ConfigError.html#variant.LimitExceeded
+    /// [`limit_by`]: struct.RecordBuilder.html#method.limit_by
+    #[inline]
+    pub fn try_build(self, receiver: impl Into<R>) -> Result<Record<R, C>, ConfigError> {
+        self.check()?;
+        Ok(self.build(receiver))
+    }
+
+    /// Builds the record with the slot.
+    ///
+    /// If the record has initial commands, the slot receives the signals that
+    /// describe how the record differs from an empty record.
+    ///
+    /// # Panics
+    /// Panics if the initial commands are invalid, see [`entries`].
+    ///
+    /// [`entries`]: struct.RecordBuilder.html#method.entries
+    #[inline]
+    pub fn build_with<F: FnMut(Signal)>(self, receiver: impl Into<R>, slot: F) -> Record<R, C, F> {
+        let initial = !self.commands.is_empty();
+        let mut record = self.record(receiver.into(), Slot::from(slot));
+        if initial {
+            record.slot.begin(State {
+                is_saved: true,
+                ..State::default()
+            });
+            record.slot.end(record.state());
+        }
+        record
+    }
+
+    /// Checks that the initial commands are valid.
+    #[inline]
+    pub(crate) fn check(&self) -> Result<(), ConfigError> {
+        let len = self.commands.len();
+        // The number of commands that fit within the weight limit.
+        let fits = len - self.weight.excess(self.commands.iter());
+        if self.current > len {
+            Err(ConfigError::CurrentOutOfBounds {
+                current: self.current,
+                len,
+            })
+        } else if len > self.limit.get() {
+            Err(ConfigError::LimitExceeded {
+                len,
+                limit: self.limit.get(),
+            })
+        } else if len > fits {
+            Err(ConfigError::LimitExceeded { len, limit: fits })
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn record<F>(self, receiver: R, slot: Slot<F>) -> Record<R, C, F> {
+        if let Err(error) = self.check() {
+            panic!("{}", error);
+        }
+        let len = self.commands.len();
+        let mut commands = VecDeque::with_capacity(self.capacity.max(len));
+        commands.extend(
+            self.commands
+                .into_iter()
+                .enumerate()
+                .map(|(id, command)| Entry::new(command, id)),
+        );
+        Record {
+            commands,
+            receiver,
+            current: self.current,
+            limit: self.limit,
+            saved: if self.saved { Some(self.current) } else { None },
+            next_id: len,
+            mutated: false,
+            keep_noops: false,
+            cascade_annul: false,
+            merge_within: self.merge_within,
+            merge_across_saved: self.merge_across_saved,
+            last_applied: Ignored(None),
+            view: None,
+            group: 0,
+            group_first: false,
+            generation: 0,
+            failure: None,
+            branch: 0,
+            hooks: self.hooks,
+            nav: None,
+            cache: self.cache,
+            weight: self.weight,
+            #[cfg(feature = "std")]
+            clock: self.clock,
+            marks: BTreeMap::new(),
+            removed: Ignored::default(),
+            slot,
+        }
+    }
+}
+
+impl<R, C> Default for RecordBuilder<R, C> {
+    #[inline]
+    fn default() -> Self {
+        RecordBuilder::new()
+    }
+}
+
+impl<R: Default, C> RecordBuilder<R, C> {
+    /// Creates the record with a default `receiver`.
+    #[inline]
+    pub fn default(self) -> Record<R, C> {
+        self.build(R::default())
+    }
+
+    /// Creates the record with a default `receiver`.
+    #[inline]
+    pub fn default_with<F: FnMut(Signal)>(self, slot: F) -> Record<R, C, F> {
+        self.build_with(R::default(), slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, ConfigError, Event, LocalRecord, Merge, Op, Record, Signal, SignalOrder};
+    use std::{
+        cell::{Cell, RefCell},
+        mem,
+        rc::Rc,
+        sync::{Arc, Mutex},
+    };
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            self.0 = s.pop().ok_or("`s` is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn set_limit() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        record.apply(Add('d')).unwrap();
+        record.apply(Add('e')).unwrap();
+
+        record.set_limit(3);
+        assert_eq!(record.current(), 3);
+        assert_eq!(record.limit(), 3);
+        assert_eq!(record.len(), 3);
+        assert!(record.can_undo());
+        assert!(!record.can_redo());
+
+        record.clear();
+        assert_eq!(record.set_limit(5), 5);
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        record.apply(Add('d')).unwrap();
+        record.apply(Add('e')).unwrap();
+
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+
+        record.set_limit(2);
+        assert_eq!(record.current(), 0);
+        assert_eq!(record.limit(), 3);
+        assert_eq!(record.len(), 3);
+        assert!(!record.can_undo());
+        assert!(record.can_redo());
+
+        record.redo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+
+        record.clear();
+        assert_eq!(record.set_limit(5), 5);
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        record.apply(Add('d')).unwrap();
+        record.apply(Add('e')).unwrap();
+
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+
+        record.set_limit(2);
+        assert_eq!(record.current(), 0);
+        assert_eq!(record.limit(), 5);
+        assert_eq!(record.len(), 5);
+        assert!(!record.can_undo());
+        assert!(record.can_redo());
+
+        record.redo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+    }
+
+    #[test]
+    fn go_to() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        record.apply(Add('d')).unwrap();
+        record.apply(Add('e')).unwrap();
+
+        record.go_to(0).unwrap().unwrap();
+        assert_eq!(record.current(), 0);
+        assert_eq!(record.as_receiver(), "");
+        record.go_to(5).unwrap().unwrap();
+        assert_eq!(record.current(), 5);
+        assert_eq!(record.as_receiver(), "abcde");
+        record.go_to(1).unwrap().unwrap();
+        assert_eq!(record.current(), 1);
+        assert_eq!(record.as_receiver(), "a");
+        record.go_to(4).unwrap().unwrap();
+        assert_eq!(record.current(), 4);
+        assert_eq!(record.as_receiver(), "abcd");
+        record.go_to(2).unwrap().unwrap();
+        assert_eq!(record.current(), 2);
+        assert_eq!(record.as_receiver(), "ab");
+        record.go_to(3).unwrap().unwrap();
+        assert_eq!(record.current(), 3);
+        assert_eq!(record.as_receiver(), "abc");
+        assert!(record.go_to(6).is_none());
+        assert_eq!(record.current(), 3);
+    }
+
+    #[test]
+    fn signals() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record =
+            Record::builder().default_with(move |signal| slot.borrow_mut().push(signal));
+        let take = || signals.borrow_mut().drain(..).collect::<Vec<_>>();
+        record.apply(Add('a')).unwrap();
+        assert_eq!(
+            take(),
+            [
+                Signal::Current { old: 0, new: 1 },
+                Signal::Undo(true),
+                Signal::Saved(false)
+            ]
+        );
+        // Every step emits its own signals.
+        record.extend(vec![Add('b'), Add('c')]).unwrap();
+        assert_eq!(
+            take(),
+            [
+                Signal::Current { old: 1, new: 2 },
+                Signal::Current { old: 2, new: 3 }
+            ]
+        );
+        record.go_to(1).unwrap().unwrap();
+        assert_eq!(
+            take(),
+            [
+                Signal::Current { old: 3, new: 2 },
+                Signal::Redo(true),
+                Signal::Current { old: 2, new: 1 }
+            ]
+        );
+        record.signal_order(SignalOrder::CurrentLast);
+        record.undo().unwrap().unwrap();
+        assert_eq!(
+            take(),
+            [
+                Signal::Undo(false),
+                Signal::Saved(true),
+                Signal::Current { old: 1, new: 0 }
+            ]
+        );
+        record.apply(Add('d')).unwrap();
+        assert_eq!(
+            take(),
+            [
+                Signal::Undo(true),
+                Signal::Redo(false),
+                Signal::Saved(false),
+                Signal::Current { old: 0, new: 1 }
+            ]
+        );
+    }
+
+    #[test]
+    fn deferred_signals() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record =
+            Record::builder().default_with(move |signal| slot.borrow_mut().push(signal));
+        record.defer_signals(true);
+        let take = || signals.borrow_mut().drain(..).collect::<Vec<_>>();
+        record.apply(Add('a')).unwrap();
+        assert_eq!(
+            take(),
+            [
+                Signal::Current { old: 0, new: 1 },
+                Signal::Undo(true),
+                Signal::Saved(false)
+            ]
+        );
+        record.extend(vec![Add('b'), Add('c')]).unwrap();
+        assert_eq!(take(), [Signal::Current { old: 1, new: 3 }]);
+        record.undo().unwrap().unwrap();
+        assert_eq!(
+            take(),
+            [Signal::Current { old: 3, new: 2 }, Signal::Redo(true)]
+        );
+        record.go_to(0).unwrap().unwrap();
+        assert_eq!(
+            take(),
+            [
+                Signal::Current { old: 2, new: 0 },
+                Signal::Undo(false),
+                Signal::Saved(true)
+            ]
+        );
+        record.go_to(3).unwrap().unwrap();
+        take();
+        // Only the net changes are emitted if the operation fails halfway.
+        record.as_mut_receiver().truncate(1);
+        assert!(record.go_to(0).unwrap().is_err());
+        assert_eq!(
+            take(),
+            [Signal::Current { old: 3, new: 2 }, Signal::Redo(true)]
+        );
+        record.undo().unwrap().unwrap_err();
+        record.redo().unwrap().unwrap();
+        assert_eq!(
+            take(),
+            [Signal::Current { old: 2, new: 3 }, Signal::Redo(false)]
+        );
+    }
+
+    /// Appends to the last `Push` and annuls a `Push` that is followed by a `Pop`.
+    enum Edit {
+        Push(String),
+        Pop(char),
+    }
+
+    impl Command<String> for Edit {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            match self {
+                Edit::Push(text) => s.push_str(text),
+                Edit::Pop(c) => *c = s.pop().ok_or("`s` is empty")?,
+            }
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            match self {
+                Edit::Push(text) => s.truncate(s.len() - text.len()),
+                Edit::Pop(c) => s.push(*c),
+            }
+            Ok(())
+        }
+
+        fn merge(&mut self, command: Self) -> Merge<Self> {
+            match (self, command) {
+                (Edit::Push(text), Edit::Push(other)) => {
+                    text.push_str(&other);
+                    Merge::Yes
+                }
+                (Edit::Push(text), Edit::Pop(_)) if text.len() == 1 => Merge::Annul,
+                (_, command) => Merge::No(command),
+            }
+        }
+    }
+
+    #[test]
+    fn annul() {
+        let mut record = Record::default();
+        record.apply(Edit::Push("a".into())).unwrap();
+        record.apply(Edit::Pop(' ')).unwrap();
+        assert_eq!(record.as_receiver(), "");
+        assert_eq!(record.current(), 0);
+        assert!(record.is_empty());
+        record.apply(Edit::Push("b".into())).unwrap();
+        assert_eq!(record.current(), 1);
+        assert_eq!(record.len(), 1);
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "");
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "b");
+    }
+
+    #[test]
+    fn apply_signals() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let clone = Rc::clone(&signals);
+        let mut record =
+            Record::builder().default_with(move |signal| clone.borrow_mut().push(signal));
+        record.apply(Edit::Push("a".into())).unwrap();
+        assert_eq!(
+            signals.borrow_mut().split_off(0),
+            [
+                Signal::Current { old: 0, new: 1 },
+                Signal::Undo(true),
+                Signal::Saved(false),
+            ]
+        );
+        record.apply(Edit::Pop(' ')).unwrap();
+        assert_eq!(
+            signals.borrow_mut().split_off(0),
+            [
+                Signal::Current { old: 1, new: 0 },
+                Signal::Annulled { with: 0 },
+                Signal::Undo(false),
+                Signal::Saved(true),
+            ]
+        );
+        // Annulling the only command returns the receiver to the saved state.
+        assert!(record.is_saved());
+    }
+
+    #[test]
+    fn amend_current_with() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.set_saved(true);
+        record
+            .amend_current_with(|add| add.0 = 'c')
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.as_receiver(), "ac");
+        assert!(!record.is_saved());
+        record.undo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ac");
+        record.go_to(0).unwrap().unwrap();
+        assert!(record.amend_current_with(|add| add.0 = 'd').is_none());
+    }
+
+    #[test]
+    fn amend_expired() {
+        struct Push(char, bool);
+
+        impl Command<String> for Push {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.pop();
+                Ok(())
+            }
+
+            fn undoable(&self, _: &String) -> bool {
+                self.1
+            }
+        }
+
+        let mut record = Record::default();
+        record.apply(Push('a', false)).unwrap();
+        assert!(record.amend_current_with(|push| push.0 = 'x').is_none());
+        record.apply(Push('b', true)).unwrap();
+        record.apply(Push('c', false)).unwrap();
+        assert!(record.amend_current_with(|push| push.0 = 'x').is_none());
+        assert_eq!(record.as_receiver(), "abc");
+        assert_eq!(record.len(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn check_commands() {
+        use crate::record::ValidationError;
+
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        record.undo().unwrap().unwrap();
+        let result: Result<_, ValidationError<(), _>> = record.check_commands(&"ab".to_string());
+        assert_eq!(result, Ok(()));
+        assert_eq!(record.as_receiver(), "ab");
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abc");
+        // The receiver is too short for the commands to be undone.
+        let result: Result<_, ValidationError<(), _>> = record.check_commands(&"a".to_string());
+        assert_eq!(
+            result,
+            Err(ValidationError::Undo {
+                index: 1,
+                error: "`s` is empty"
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn deserialize_validated() {
+        use crate::{value, ValidationError};
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Push(char, bool);
+
+        impl Command<String> for Push {
+            type Error = &'static str;
+
+            fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn redo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+                if self.1 {
+                    return Err("corrupted");
+                }
+                self.apply(s)
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+                s.pop().filter(|&c| c == self.0).ok_or("mismatch")?;
+                Ok(())
+            }
+        }
+
+        let mut record = Record::default();
+        record
+            .extend("abc".chars().map(|c| Push(c, false)))
+            .unwrap();
+        record.undo().unwrap().unwrap();
+        let value = value::to_value(&record);
+        let restored =
+            Record::<String, Push>::deserialize_validated(value, &"ab".to_string()).unwrap();
+        assert_eq!(restored.as_receiver(), "ab");
+        assert_eq!(restored.current(), 2);
+        assert_eq!(restored.len(), 3);
+        // The first command does not match the receiver it is undone on.
+        let value = value::to_value(&record);
+        let error = Record::<String, Push>::deserialize_validated(value, &"xb".to_string());
+        assert!(matches!(
+            error,
+            Err(ValidationError::Undo {
+                index: 0,
+                error: "mismatch"
+            })
+        ));
+        record.apply(Push('d', true)).unwrap();
+        let value = value::to_value(&record);
+        let error = Record::<String, Push>::deserialize_validated(value, &"abd".to_string());
+        assert!(matches!(
+            error,
+            Err(ValidationError::Redo {
+                index: 2,
+                error: "corrupted"
+            })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn deserialize_without_ids() {
+        use crate::value;
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Push(char);
+
+        impl Command<String> for Push {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.pop();
+                Ok(())
+            }
+        }
+
+        let mut record = Record::default();
+        record.extend("abc".chars().map(Push)).unwrap();
+        let mut value = value::to_value(&record);
+        value.remove("next_id");
+        let mut restored: Record<String, Push> = value::from_value(value).unwrap();
+        restored.apply(Push('d')).unwrap();
+        assert_eq!(restored.id(3), Some(3));
+        // The commands are numbered in order if they have no ids.
+        let mut value = value::to_value(&record);
+        for key in ["id", "merged", "next_id"] {
+            value.remove(key);
+        }
+        let mut restored: Record<String, Push> = value::from_value(value).unwrap();
+        restored.apply(Push('d')).unwrap();
+        let ids: Vec<_> = (0..4).map(|i| restored.id(i).unwrap()).collect();
+        assert_eq!(ids, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn deserialize_old_entries() {
+        use crate::value;
+
+        let mut record = Record::default();
+        record.extend("abc".chars().map(Add)).unwrap();
+        let mut value = value::to_value(&record);
+        for key in ["boundary", "joined", "noop", "timestamp"] {
+            value.remove(key);
+        }
+        let mut restored: Record<String, Add> = value::from_value(value).unwrap();
+        assert_eq!(restored.as_receiver(), "abc");
+        restored.undo().unwrap().unwrap();
+        assert_eq!(restored.as_receiver(), "ab");
+    }
+
+    #[test]
+    fn into_parts() {
+        fn assert_send<T: Send>(_: &T) {}
+
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let record: LocalRecord<String, Add> =
+            Record::default().connect_with(Box::new(move |signal| slot.borrow_mut().push(signal)));
+        let (record, slot) = record.into_parts();
+        assert_send(&record);
+        let mut record = Record::from_parts(record, slot);
+        record.apply(Add('a')).unwrap();
+        assert!(!signals.borrow().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn try_clone() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record: LocalRecord<String, Add> =
+            Record::default().connect_with(Box::new(move |signal| slot.borrow_mut().push(signal)));
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        signals.borrow_mut().clear();
+        let mut clone = record.try_clone();
+        assert_eq!(clone.as_receiver(), "ab");
+        assert!(record
+            .entries()
+            .zip(clone.entries())
+            .all(|(a, b)| a.timestamp() == b.timestamp()));
+        clone.undo().unwrap().unwrap();
+        assert_eq!(clone.as_receiver(), "a");
+        assert_eq!(record.as_receiver(), "ab");
+        assert!(signals.borrow().is_empty());
+    }
+
+    #[test]
+    fn go_to_saved_offset() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.set_saved(true);
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        record.go_to_saved_offset(1).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        record.go_to_saved_offset(-1).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "");
+        record.go_to_saved_offset(0).unwrap().unwrap();
+        assert!(record.is_saved());
+        assert!(record.go_to_saved_offset(3).is_none());
+        assert!(record.go_to_saved_offset(-2).is_none());
+        record.set_saved(false);
+        assert!(record.go_to_saved_offset(0).is_none());
+    }
+
+    #[test]
+    fn unsaved_count() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.set_saved(false);
+        assert_eq!(record.unsaved_count(), None);
+        record.set_saved(true);
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        let unsaved = record.unsaved_count().unwrap();
+        assert_eq!((unsaved.undo(), unsaved.redo()), (2, 0));
+        record.go_to(0).unwrap().unwrap();
+        let unsaved = record.unsaved_count().unwrap();
+        assert_eq!((unsaved.undo(), unsaved.redo()), (0, 1));
+        assert_eq!(unsaved.count(), 1);
+        record.redo().unwrap().unwrap();
+        assert!(record.unsaved_count().unwrap().is_saved());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn catch_slot_panics() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record = Record::builder().default_with(move |signal| {
+            if let Signal::Undo(_) = signal {
+                panic!("undo");
+            }
+            slot.borrow_mut().push(signal);
+        });
+        record.catch_slot_panics(true);
+        record.apply(Add('a')).unwrap();
+        assert_eq!(record.as_receiver(), "a");
+        assert_eq!(
+            *signals.borrow(),
+            [Signal::Current { old: 0, new: 1 }, Signal::Saved(false)]
+        );
+        assert_eq!(record.take_slot_panic(), Some("undo".into()));
+        assert_eq!(record.take_slot_panic(), None);
+    }
+
+    #[test]
+    fn retain() {
+        let mut record = Record::default();
+        record.extend("abcdef".chars().map(Add)).unwrap();
+        record.go_to(2).unwrap().unwrap();
+        record.set_saved(true);
+        record.go_to(5).unwrap().unwrap();
+        record.retain(|add| add.0 != 'd' && add.0 != 'f').unwrap();
+        assert_eq!(record.as_receiver(), "abce");
+        assert_eq!(record.len(), 4);
+        assert_eq!(record.current(), 4);
+        assert!(record.go_to(2).unwrap().is_ok());
+        assert!(record.is_saved());
+        record.retain(|add| add.0 != 'a').unwrap();
+        assert_eq!(record.as_receiver(), "b");
+        assert_eq!(record.current(), 1);
+        assert!(record.go_to(0).unwrap().is_ok());
+        assert!(!record.is_saved());
+        record.redo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "bc");
+    }
+
+    #[test]
+    fn append_dead() {
+        struct Push(char, Rc<Cell<bool>>);
+
+        impl Command<String> for Push {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.pop();
+                Ok(())
+            }
+
+            fn is_dead(&self) -> bool {
+                self.1.get()
+            }
+        }
+
+        let dead = Rc::new(Cell::new(false));
+        let mut other = Record::default();
+        other.apply(Push('a', Rc::new(Cell::new(false)))).unwrap();
+        other.apply(Push('b', Rc::clone(&dead))).unwrap();
+        dead.set(true);
+        let mut record = Record::default();
+        record.append(other).unwrap();
+        assert_eq!(record.as_receiver(), "a");
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.current(), 1);
+    }
+
+    #[test]
+    fn append_limit() {
+        let mut other = Record::default();
+        for c in "abcd".chars() {
+            other.apply(Add(c)).unwrap();
+        }
+        other.go_to(1).unwrap().unwrap();
+        let mut record = Record::builder().limit(3).default();
+        record.append(other).unwrap();
+        // The redone command is evicted.
+        assert_eq!(record.limit(), 3);
+        assert_eq!(record.as_receiver(), "a");
+        assert_eq!(record.len(), 3);
+        assert_eq!(record.current(), 0);
+        record.go_to(3).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abcd");
+        // The limit is kept for the commands applied later.
+        record.apply(Add('e')).unwrap();
+        assert_eq!(record.limit(), 3);
+        assert_eq!(record.len(), 3);
+        assert_eq!(record.as_receiver(), "abcde");
+
+        let mut other = Record::default();
+        for c in "abcd".chars() {
+            other.apply(Add(c)).unwrap();
+        }
+        other.go_to(0).unwrap().unwrap();
+        let mut record = Record::builder().limit(3).default();
+        record.append(other).unwrap();
+        // Nothing is redone, so the newest command is removed instead.
+        assert_eq!(record.limit(), 3);
+        assert_eq!(record.len(), 3);
+        assert_eq!(record.current(), 0);
+        record.go_to(3).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abc");
+    }
+
+    #[test]
+    fn go_to_dead() {
+        struct Push(char, Rc<Cell<bool>>);
+
+        impl Command<String> for Push {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.pop();
+                Ok(())
+            }
+
+            fn is_dead(&self) -> bool {
+                self.1.get()
+            }
+        }
+
+        let dead = Rc::new(Cell::new(false));
+        let mut record = Record::default();
+        record.apply(Push('a', Rc::new(Cell::new(false)))).unwrap();
+        record.apply(Push('b', Rc::clone(&dead))).unwrap();
+        record.apply(Push('c', Rc::clone(&dead))).unwrap();
+        record.go_to(0).unwrap().unwrap();
+        dead.set(true);
+        // The dead commands are removed while redoing, so the position no longer exists.
+        assert!(record.go_to(3).is_none());
+        assert_eq!(record.as_receiver(), "a");
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.current(), 1);
+    }
+
+    #[test]
+    fn retain_expired() {
+        struct Push(char, bool);
+
+        impl Command<String> for Push {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.remove(s.rfind(self.0).unwrap());
+                Ok(())
+            }
+
+            fn undoable(&self, _: &String) -> bool {
+                self.1
+            }
+        }
+
+        let mut record = Record::default();
+        record.apply(Push('a', true)).unwrap();
+        record.apply(Push('e', false)).unwrap();
+        record.apply(Push('c', true)).unwrap();
+        record.apply(Push('d', true)).unwrap();
+        // The expired command is removed when the record undoes past it.
+        record.retain(|push| push.0 != 'a').unwrap();
+        assert_eq!(record.as_receiver(), "ecd");
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.current(), 2);
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ec");
+    }
+
+    #[test]
+    fn operation_ids() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record =
+            Record::builder().default_with(move |signal| slot.borrow_mut().push(signal));
+        record.emit_operation_ids(true);
+        record.defer_signals(true);
+        record.extend(vec![Add('a'), Add('b')]).unwrap();
+        record.undo().unwrap().unwrap();
+        // Nothing is emitted, so the id is not used.
+        record.set_saved(false);
+        record.go_to(0).unwrap().unwrap();
+        assert_eq!(
+            *signals.borrow(),
+            [
+                Signal::Operation(0),
+                Signal::Current { old: 0, new: 2 },
+                Signal::Undo(true),
+                Signal::Saved(false),
+                Signal::Operation(1),
+                Signal::Current { old: 2, new: 1 },
+                Signal::Redo(true),
+                Signal::Operation(2),
+                Signal::Current { old: 1, new: 0 },
+                Signal::Undo(false),
+            ]
+        );
+    }
+
+    #[test]
+    fn mutate() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record =
+            Record::builder().default_with(move |signal| slot.borrow_mut().push(signal));
+        record.extend(vec![Add('a'), Add('b')]).unwrap();
+        record.mutate().push('c');
+        assert!(record.is_mutated());
+        record.clear_mutated();
+        record.undo().unwrap().unwrap();
+        signals.borrow_mut().clear();
+        assert_eq!(record.mutate().as_str(), "ab");
+        assert!(!record.is_mutated());
+        record.mutate().push('d');
+        assert!(record.is_mutated());
+        assert_eq!(*signals.borrow(), [Signal::Mutated]);
+    }
+
+    #[test]
+    fn marks() {
+        let mut record = Record::builder().limit(3).build(String::new());
+        record.mark("start");
+        record.apply(Add('a')).unwrap();
+        record.mark("a");
+        record.apply(Add('b')).unwrap();
+        record.mark("b");
+        record.apply(Add('c')).unwrap();
+        record.mark("b");
+        assert_eq!(record.mark_position("b"), Some(3));
+        record.go_to_mark("start").unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "");
+        record.go_to_mark("a").unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "a");
+        assert!(record.go_to_mark("missing").is_none());
+        record.redo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        // The first command is evicted by the limit, which removes the marks before it.
+        record.apply(Add('d')).unwrap();
+        assert_eq!(record.marks().collect::<Vec<_>>(), [("b", 2)]);
+        record.undo().unwrap().unwrap();
+        record.apply(Add('e')).unwrap();
+        assert_eq!(record.marks().count(), 1);
+        assert_eq!(record.unmark("b"), Some(2));
+        assert_eq!(record.marks().count(), 0);
+    }
+
+    #[test]
+    fn cascade_removes_marks() {
+        struct Step(i32);
+
+        impl Command<i32> for Step {
+            type Error = ();
+
+            fn apply(&mut self, i: &mut i32) -> Result<(), ()> {
+                *i += self.0;
+                Ok(())
+            }
+
+            fn undo(&mut self, i: &mut i32) -> Result<(), ()> {
+                *i -= self.0;
+                Ok(())
+            }
+
+            fn merge(&mut self, step: Self) -> Merge<Self> {
+                if self.0 + step.0 == 0 {
+                    Merge::Annul
+                } else if self.0 >= 100 {
+                    self.0 += step.0;
+                    Merge::Yes
+                } else {
+                    Merge::No(step)
+                }
+            }
+        }
+
+        let mut record = Record::default();
+        record.cascade_annul(true);
+        record.apply(Step(100)).unwrap();
+        record.mark("m");
+        // The saved state keeps the steps from being merged when they are applied.
+        record.set_saved(true);
+        record.apply(Step(1)).unwrap();
+        record.set_saved(false);
+        record.apply(Step(2)).unwrap();
+        record.apply(Step(-2)).unwrap();
+        assert_eq!(record.len(), 1);
+        assert_eq!(*record.as_receiver(), 101);
+        // The mark no longer points at the state after the first step, so it is removed.
+        assert_eq!(record.mark_position("m"), None);
+        assert!(record.go_to_mark("m").is_none());
+    }
+
+    #[test]
+    fn get() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.get(0).map(|add| add.0), Some('a'));
+        assert_eq!(record.get(1).map(|add| add.0), Some('b'));
+        assert!(record.get(2).is_none());
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut record = Record::default();
+        record.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        record.undo().unwrap().unwrap();
+        let borrowed: String = (&record).into_iter().map(|add| add.0).collect();
+        assert_eq!(borrowed, "abc");
+        assert_eq!(
+            (&record).into_iter().next_back().map(|add| add.0),
+            Some('c')
+        );
+        let mut copy: Record<String, Add> = Record::default();
+        copy.extend(
+            record
+                .into_iter()
+                .map(|add| Add(add.0.to_ascii_uppercase())),
+        )
+        .unwrap();
+        assert_eq!(copy.as_receiver(), "ABC");
+    }
+
+    #[test]
+    #[cfg(feature = "history")]
+    fn into_history() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record =
+            Record::builder().default_with(move |signal| slot.borrow_mut().push(signal));
+        record.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        record.undo().unwrap().unwrap();
+        record.set_saved(true);
+        record.undo().unwrap().unwrap();
+        signals.borrow_mut().clear();
+        let mut history = record.into_history();
+        assert_eq!(history.current(), 1);
+        assert_eq!(history.saved(), Some((history.branch(), 2)));
+        history.apply(Add('d')).unwrap();
+        assert_eq!(history.as_receiver(), "ad");
+        assert!(!signals.borrow().is_empty());
+        history.go_to_saved().unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "ab");
+        history.redo().unwrap().unwrap();
+        assert_eq!(history.as_receiver(), "abc");
+    }
+
+    #[test]
+    fn subscribe() {
+        let menu = Rc::new(RefCell::new(Vec::new()));
+        let title = Rc::new(RefCell::new(Vec::new()));
+        let mut record: LocalRecord<String, Add> = Record::builder().default_with(Box::new(|_| {}));
+        let slot = Rc::clone(&menu);
+        record.subscribe(Box::new(move |signal| slot.borrow_mut().push(signal)));
+        let slot = Rc::clone(&title);
+        let id = record.subscribe(Box::new(move |signal| {
+            if let Signal::Saved(saved) = signal {
+                slot.borrow_mut().push(saved);
+            }
+        }));
+        record.apply(Add('a')).unwrap();
+        assert_eq!(
+            *menu.borrow(),
+            [
+                Signal::Current { old: 0, new: 1 },
+                Signal::Undo(true),
+                Signal::Saved(false)
+            ]
+        );
+        assert_eq!(*title.borrow(), [false]);
+        assert!(record.unsubscribe(id).is_some());
+        assert!(record.unsubscribe(id).is_none());
+        record.undo().unwrap().unwrap();
+        assert_eq!(menu.borrow().len(), 7);
+        assert_eq!(menu.borrow().last(), Some(&Signal::Saved(true)));
+        assert_eq!(*title.borrow(), [false]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn signal_receiver() {
+        let mut record = Record::default();
+        let first = record.signal_receiver();
+        record.apply(Add('a')).unwrap();
+        let second = record.signal_receiver();
+        record.undo().unwrap().unwrap();
+        drop(first);
+        record.redo().unwrap().unwrap();
+        let signals: Vec<_> = second.try_iter().collect();
+        assert_eq!(
+            signals,
+            [
+                Signal::Current { old: 1, new: 0 },
+                Signal::Undo(false),
+                Signal::Redo(true),
+                Signal::Saved(true),
+                Signal::Current { old: 0, new: 1 },
+                Signal::Undo(true),
+                Signal::Redo(false),
+                Signal::Saved(false),
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_every() {
+        struct Push(char, Rc<Cell<usize>>);
+
+        impl Command<String> for Push {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.pop();
+                self.1.set(self.1.get() + 1);
+                Ok(())
+            }
+
+            fn restorable(&self) -> bool {
+                true
+            }
+        }
+
+        let snapshots = Arc::new(Mutex::new(0));
+        let taken = Arc::clone(&snapshots);
+        let undone = Rc::new(Cell::new(0));
+        let mut record = Record::builder()
+            .snapshot_every(
+                2,
+                move |s: &String| {
+                    *taken.lock().unwrap() += 1;
+                    s.clone()
+                },
+                |snapshot, s| s.clone_from(snapshot),
+            )
+            .default();
+        for c in "abcdefg".chars() {
+            record.apply(Push(c, Rc::clone(&undone))).unwrap();
+        }
+        assert_eq!(*snapshots.lock().unwrap(), 3);
+        record.go_to(3).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abc");
+        record.go_to(6).unwrap().unwrap();
+        record.go_to(4).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abcd");
+        assert_eq!(undone.get(), 0);
+        // The snapshots after the current position are discarded.
+        record.apply(Push('x', Rc::clone(&undone))).unwrap();
+        record.apply(Push('y', Rc::clone(&undone))).unwrap();
+        assert_eq!(*snapshots.lock().unwrap(), 4);
+        record.go_to(0).unwrap().unwrap();
+        assert_eq!(undone.get(), 6);
+        record.go_to(6).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abcdxy");
+    }
+
+    #[test]
+    fn split_off_and_append() {
+        let mut a = Record::default();
+        a.extend("abcde".chars().map(Add)).unwrap();
+        a.undo().unwrap().unwrap();
+        let tail = a.split_off(2).unwrap();
+        assert_eq!(a.as_receiver(), "ab");
+        assert_eq!(a.len(), 2);
+        assert_eq!(tail.len(), 3);
+        assert_eq!(tail.current(), 2);
+        let mut b = Record::builder().limit(4).build("xy");
+        b.extend("z".chars().map(Add)).unwrap();
+        b.undo().unwrap().unwrap();
+        b.append(tail).unwrap();
+        assert_eq!(b.as_receiver(), "xycd");
+        assert_eq!(b.len(), 3);
+        b.redo().unwrap().unwrap();
+        assert_eq!(b.as_receiver(), "xycde");
+        b.go_to(0).unwrap().unwrap();
+        assert_eq!(b.as_receiver(), "xy");
+        a.apply(Add('f')).unwrap();
+        assert_eq!(a.as_receiver(), "abf");
+    }
+
+    #[test]
+    fn navigation() {
+        let mut record = Record::default();
+        record.track_navigation(true);
+        for c in "abcde".chars() {
+            record.apply(Add(c)).unwrap();
+        }
+        assert!(!record.can_nav_back());
+        record.go_to(1).unwrap().unwrap();
+        record.go_to(3).unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        record.nav_back().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "a");
+        record.nav_back().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abcde");
+        assert!(record.nav_back().is_none());
+        record.nav_forward().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "a");
+        record.nav_forward().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        assert!(record.nav_forward().is_none());
+        record.go_to(0).unwrap().unwrap();
+        record.apply(Add('f')).unwrap();
+        record.go_to(0).unwrap().unwrap();
+        record.nav_back().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "f");
+        assert!(record.nav_back().is_none());
+        record.track_navigation(false);
+        assert!(record.nav_back().is_none());
+    }
+
+    #[test]
+    fn on_evict() {
+        let evicted = Arc::new(Mutex::new(String::new()));
+        let slot = Arc::clone(&evicted);
+        let mut record = Record::builder().limit(2).default();
+        record.on_evict(move |add: Add| slot.lock().unwrap().push(add.0));
+        record
+            .extend(vec![Add('a'), Add('b'), Add('c'), Add('d')])
+            .unwrap();
+        assert_eq!(*evicted.lock().unwrap(), "ab");
+        record.set_limit(1);
+        assert_eq!(*evicted.lock().unwrap(), "abc");
+        record.clear_evict();
+        record.apply(Add('e')).unwrap();
+        assert_eq!(*evicted.lock().unwrap(), "abc");
+        assert_eq!(record.as_receiver(), "abcde");
+    }
+
+    #[test]
+    fn limit_by() {
+        let evicted = Arc::new(Mutex::new(String::new()));
+        let slot = Arc::clone(&evicted);
+        let weigh = |add: &Add| if add.0 == 'x' { 3 } else { 1 };
+        let mut record = Record::builder().limit_by(weigh, 3).default();
+        record.on_evict(move |add: Add| slot.lock().unwrap().push(add.0));
+        record.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        assert_eq!(record.len(), 3);
+        record.undo().unwrap().unwrap();
+        record.set_saved(true);
+        record.apply(Add('x')).unwrap();
+        assert_eq!(*evicted.lock().unwrap(), "ab");
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.current(), 1);
+        assert_eq!(record.saved(), Some(0));
+        record.apply(Add('d')).unwrap();
+        assert_eq!(record.saved(), None);
+        assert_eq!(*evicted.lock().unwrap(), "abx");
+        assert_eq!(record.as_receiver(), "abxd");
+        record.go_to(0).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abx");
+    }
+
+    #[test]
+    fn limit_by_eq() {
+        let record = Record::<String, Add>::builder()
+            .limit_by(|_| 1, 3)
+            .default();
+        // The weight functions are ignored, but the maximum weight is not.
+        assert_eq!(record, Record::builder().limit_by(|_| 2, 3).default());
+        assert_ne!(record, Record::builder().limit_by(|_| 1, 4).default());
+        assert_ne!(
+            record,
+            Record::builder()
+                .snapshot_every(3, String::clone, |s, r| r.clone_from(s))
+                .limit_by(|_| 1, 3)
+                .default()
+        );
+    }
+
+    #[test]
+    fn hooks() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (a, b, c, d) = (log.clone(), log.clone(), log.clone(), log.clone());
+        let mut record = Record::builder()
+            .on_before_apply(move |add: &Add, s: &String| {
+                a.lock().unwrap().push(format!("apply {} on {}", add.0, s))
+            })
+            .on_after_apply(move |_, s| b.lock().unwrap().push(format!("applied {}", s)))
+            .on_after_undo(move |add, s| {
+                c.lock().unwrap().push(format!("undid {} to {}", add.0, s))
+            })
+            .on_before_redo(move |add, _| d.lock().unwrap().push(format!("redo {}", add.0)))
+            .default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.undo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        // The hooks are shared with the clones.
+        record.try_clone().apply(Add('c')).unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "apply a on ",
+                "applied a",
+                "apply b on a",
+                "applied ab",
+                "undid b to a",
+                "redo b",
+                "apply c on ab",
+                "applied abc",
+            ]
+        );
+    }
+
+    #[test]
+    fn builder_entries() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record = Record::builder()
+            .entries(vec![Add('a'), Add('b'), Add('c')], 2)
+            .build_with("ab", move |signal| slot.borrow_mut().push(signal));
+        assert_eq!(
+            *signals.borrow(),
+            [
+                Signal::Current { old: 0, new: 2 },
+                Signal::Undo(true),
+                Signal::Redo(true)
+            ]
+        );
+        assert!(record.is_saved());
+        assert_eq!(record.as_receiver(), "ab");
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abc");
+        record.go_to(0).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "");
+        record.apply(Add('d')).unwrap();
+        assert_eq!(record.len(), 1);
+    }
+
+    #[test]
+    fn builder_entries_weight() {
+        let builder = |commands| {
+            Record::builder()
+                .limit_by(|add: &Add| if add.0 == 'b' { 2 } else { 1 }, 3)
+                .entries(commands, 2)
+        };
+        assert_eq!(
+            builder(vec![Add('a'), Add('b'), Add('c')])
+                .try_build("ab")
+                .unwrap_err(),
+            ConfigError::LimitExceeded { len: 3, limit: 2 }
+        );
+        let record: Record<String, _> = builder(vec![Add('a'), Add('b')]).build("ab");
+        assert_eq!(record.len(), 2);
+    }
+
+    #[test]
+    fn go_to_saved() {
+        let mut record = Record::default();
+        record.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        record.undo().unwrap().unwrap();
+        record.set_saved(true);
+        assert_eq!(record.saved(), Some(2));
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        record.go_to_saved().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        record.apply(Add('d')).unwrap();
+        assert_eq!(record.saved(), Some(2));
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        record.apply(Add('e')).unwrap();
+        assert_eq!(record.saved(), None);
+        assert!(record.go_to_saved().is_none());
+    }
+
+    #[test]
+    fn cascade_annul() {
+        struct Step(i32);
+
+        impl Command<i32> for Step {
+            type Error = ();
+
+            fn apply(&mut self, i: &mut i32) -> Result<(), ()> {
+                *i += self.0;
+                Ok(())
+            }
+
+            fn undo(&mut self, i: &mut i32) -> Result<(), ()> {
+                *i -= self.0;
+                Ok(())
+            }
+
+            fn merge(&mut self, step: Self) -> Merge<Self> {
+                if self.0 + step.0 == 0 {
+                    Merge::Annul
+                } else {
+                    Merge::No(step)
+                }
+            }
+        }
+
+        let mut record = Record::default();
+        record.cascade_annul(true);
+        record.apply(Step(3)).unwrap();
+        record.apply(Step(1)).unwrap();
+        record.set_saved(true);
+        record.apply(Step(-1)).unwrap();
+        record.set_saved(false);
+        record.apply(Step(2)).unwrap();
+        record.apply(Step(-2)).unwrap();
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.current(), 1);
+        assert_eq!(*record.as_receiver(), 3);
+        record.apply(Step(1)).unwrap();
+        record.set_saved(true);
+        record.apply(Step(-1)).unwrap();
+        record.apply(Step(2)).unwrap();
+        record.apply(Step(-2)).unwrap();
+        assert_eq!(record.len(), 3);
+        assert!(!record.is_saved());
+        record.undo().unwrap().unwrap();
+        assert!(record.is_saved());
+        assert_eq!(*record.as_receiver(), 4);
+    }
+
+    #[test]
+    fn cascade_annul_across_saved() {
+        struct Step(i32);
+
+        impl Command<i32> for Step {
+            type Error = ();
+
+            fn apply(&mut self, i: &mut i32) -> Result<(), ()> {
+                *i += self.0;
+                Ok(())
+            }
+
+            fn undo(&mut self, i: &mut i32) -> Result<(), ()> {
+                *i -= self.0;
+                Ok(())
+            }
+
+            fn merge(&mut self, step: Self) -> Merge<Self> {
+                if self.0 + step.0 == 0 {
+                    Merge::Annul
+                } else if self.0.signum() == step.0.signum() {
+                    self.0 += step.0;
+                    Merge::Yes
+                } else {
+                    Merge::No(step)
+                }
+            }
+        }
+
+        for &across in &[false, true] {
+            let mut record = Record::builder().merge_across_saved(across).default();
+            record.cascade_annul(true);
+            record.apply(Step(2)).unwrap();
+            record.set_saved(true);
+            record.apply(Step(-1)).unwrap();
+            record.apply(Step(-1)).unwrap();
+            record.apply(Step(5)).unwrap();
+            record.apply(Step(-5)).unwrap();
+            assert_eq!(*record.as_receiver(), 0);
+            if across {
+                assert_eq!(record.len(), 0);
+                assert_eq!(record.saved(), None);
+            } else {
+                assert_eq!(record.len(), 2);
+                assert_eq!(record.saved(), Some(1));
+                record.undo().unwrap().unwrap();
+                assert!(record.is_saved());
+                assert_eq!(*record.as_receiver(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn edit_commands() {
+        let mut record = Record::default();
+        record.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        record.undo().unwrap().unwrap();
+        record.set_saved(true);
+        record.redo().unwrap().unwrap();
+        record.edit_commands(|i, command| {
+            command.0 = command.0.to_ascii_uppercase();
+            i == 0
+        });
+        assert_eq!(record.saved(), Some(2));
+        assert_eq!(record.commands().map(|c| c.0).collect::<String>(), "ABC");
+        record.edit_commands(|i, _| i == 2);
+        assert_eq!(record.saved(), None);
+    }
+
+    #[test]
+    fn annulled() {
+        struct Step(i32);
+
+        impl Command<i32> for Step {
+            type Error = ();
+
+            fn apply(&mut self, i: &mut i32) -> Result<(), ()> {
+                *i += self.0;
+                Ok(())
+            }
+
+            fn undo(&mut self, i: &mut i32) -> Result<(), ()> {
+                *i -= self.0;
+                Ok(())
+            }
+
+            fn merge(&mut self, step: Self) -> Merge<Self> {
+                if self.0 + step.0 == 0 {
+                    Merge::Annul
+                } else {
+                    Merge::No(step)
+                }
+            }
+        }
+
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record =
+            Record::builder().default_with(move |signal| slot.borrow_mut().push(signal));
+        record.apply(Step(1)).unwrap();
+        let id = record.id(0).unwrap();
+        signals.borrow_mut().clear();
+        record.apply(Step(-1)).unwrap();
+        assert_eq!(
+            *signals.borrow(),
+            [
+                Signal::Current { old: 1, new: 0 },
+                Signal::Annulled { with: id },
+                Signal::Undo(false),
+                Signal::Saved(true)
+            ]
+        );
+    }
+
+    #[test]
+    fn expired() {
+        struct Push(char, Option<Rc<Cell<bool>>>);
+
+        impl Command<String> for Push {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.remove(s.rfind(self.0).unwrap());
+                Ok(())
+            }
+
+            fn undoable(&self, _: &String) -> bool {
+                self.1.as_ref().is_none_or(|sent| !sent.get())
+            }
+        }
+
+        let sent = Rc::new(Cell::new(false));
+        let mut record = Record::default();
+        record.apply(Push('a', None)).unwrap();
+        record.apply(Push('b', Some(Rc::clone(&sent)))).unwrap();
+        record.apply(Push('c', None)).unwrap();
+        assert_eq!(record.expired(), 0);
+        sent.set(true);
+        assert_eq!(record.expired(), 1);
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        // The expired command is removed and its changes are kept.
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "b");
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.expired(), 0);
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ba");
+    }
+
+    #[test]
+    fn expired_saved() {
+        struct Push(char, bool);
+
+        impl Command<String> for Push {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.remove(s.rfind(self.0).unwrap());
+                Ok(())
+            }
+
+            fn undoable(&self, _: &String) -> bool {
+                self.1
+            }
+        }
+
+        let mut record = Record::default();
+        record.apply(Push('a', true)).unwrap();
+        record.mark("a");
+        record.apply(Push('b', false)).unwrap();
+        record.mark("b");
+        record.apply(Push('c', true)).unwrap();
+        record.mark("c");
+        record.set_saved(true);
+        record.go_to(0).unwrap().unwrap();
+        // The positions after the expired command are moved back.
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.saved(), Some(2));
+        assert_eq!(record.mark_position("a"), Some(1));
+        assert_eq!(record.mark_position("b"), None);
+        assert_eq!(record.mark_position("c"), Some(2));
+        record.go_to(2).unwrap().unwrap();
+        assert!(record.is_saved());
+    }
+
+    #[test]
+    fn dead_saved() {
+        struct Push(char, Rc<Cell<bool>>);
+
+        impl Command<String> for Push {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.pop();
+                Ok(())
+            }
+
+            fn is_dead(&self) -> bool {
+                self.1.get()
+            }
+        }
+
+        let dead = Rc::new(Cell::new(false));
+        let mut record = Record::default();
+        record.apply(Push('a', Rc::new(Cell::new(false)))).unwrap();
+        record.mark("a");
+        record.apply(Push('b', Rc::clone(&dead))).unwrap();
+        record.mark("b");
+        record.apply(Push('c', Rc::new(Cell::new(false)))).unwrap();
+        record.mark("c");
+        record.set_saved(true);
+        record.go_to(1).unwrap().unwrap();
+        dead.set(true);
+        record.redo().unwrap().unwrap();
+        // The positions after the dead command are moved back.
+        assert_eq!(record.as_receiver(), "ac");
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.saved(), Some(2));
+        assert!(record.is_saved());
+        assert_eq!(record.mark_position("a"), Some(1));
+        assert_eq!(record.mark_position("b"), None);
+        assert_eq!(record.mark_position("c"), Some(2));
+    }
+
+    #[test]
+    fn prefetch() {
+        struct Lazy(char, Rc<RefCell<String>>);
+
+        impl Command<String> for Lazy {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.pop();
+                Ok(())
+            }
+
+            fn prefetch(&mut self) {
+                self.1.borrow_mut().push(self.0);
+            }
+        }
+
+        let loaded = Rc::new(RefCell::new(String::new()));
+        let mut record = Record::default();
+        for c in "abcde".chars() {
+            record.apply(Lazy(c, Rc::clone(&loaded))).unwrap();
+        }
+        record.go_to(3).unwrap().unwrap();
+        record.idle(2);
+        assert_eq!(*loaded.borrow(), "cbde");
+        loaded.borrow_mut().clear();
+        record.idle(10);
+        assert_eq!(*loaded.borrow(), "cbade");
+        loaded.borrow_mut().clear();
+        record.prefetch(..2);
+        assert_eq!(*loaded.borrow(), "ab");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn merge_within() {
+        use std::{thread, time::Duration};
+
+        struct Push(String);
+
+        impl Command<String> for Push {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push_str(&self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.truncate(s.len() - self.0.len());
+                Ok(())
+            }
+
+            fn merge(&mut self, Push(s): Self) -> Merge<Self> {
+                self.0.push_str(&s);
+                Merge::Yes
+            }
+        }
+
+        let mut record = Record::builder()
+            .merge_within(Duration::from_secs(3600))
+            .default();
+        record.apply(Push("a".into())).unwrap();
+        record.apply(Push("b".into())).unwrap();
+        assert_eq!(record.len(), 1);
+        record.undo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        record.apply(Push("c".into())).unwrap();
+        assert_eq!(record.len(), 2);
+        record.apply(Push("d".into())).unwrap();
+        assert_eq!(record.len(), 2);
+        let mut record = Record::builder().merge_within(Duration::ZERO).default();
+        record.apply(Push("a".into())).unwrap();
+        thread::sleep(Duration::from_millis(2));
+        record.apply(Push("b".into())).unwrap();
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.as_receiver(), "ab");
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", not(any(feature = "chrono", feature = "time"))))]
+    fn timestamps_opt_in() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        // The time is not recorded unless it is needed.
+        assert_eq!(record.entries().next().unwrap().timestamp(), UNIX_EPOCH);
+        let mut record = Record::builder()
+            .merge_within(Duration::from_secs(1))
+            .default();
+        record.apply(Add('a')).unwrap();
+        assert!(record.entries().next().unwrap().timestamp() > UNIX_EPOCH);
+    }
+
+    #[test]
+    fn undo_at() {
+        let mut record = Record::default();
+        record.extend(vec![Add('a'), Add('b'), Add('c')]).unwrap();
+        record.set_saved(true);
+        record.undo().unwrap().unwrap();
+        assert!(record.undo_at(2).is_none());
+        record.undo_at(0).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "b");
+        assert_eq!(record.current(), 1);
+        assert!(!record.is_saved());
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "bc");
+
+        struct Inc(usize, Rc<Cell<usize>>);
+
+        impl Command<Vec<i32>> for Inc {
+            type Error = ();
+
+            fn apply(&mut self, v: &mut Vec<i32>) -> Result<(), ()> {
+                v[self.0] += 1;
+                Ok(())
+            }
+
+            fn undo(&mut self, v: &mut Vec<i32>) -> Result<(), ()> {
+                self.1.set(self.1.get() + 1);
+                v[self.0] -= 1;
+                Ok(())
+            }
+
+            fn transpose(&self, _: &Self) -> bool {
+                true
+            }
         }
-    }
 
-    /// Sets the capacity for the record.
-    #[inline]
-    pub fn capacity(mut self, capacity: usize) -> RecordBuilder<R, C> {
-        self.capacity = capacity;
-        self
+        let undos = Rc::new(Cell::new(0));
+        let mut record = Record::new(vec![0, 0]);
+        for i in [0, 1, 1, 0] {
+            record.apply(Inc(i, Rc::clone(&undos))).unwrap();
+        }
+        record.undo_at(1).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), &[2, 1]);
+        assert_eq!(record.current(), 3);
+        assert_eq!(undos.get(), 1);
     }
 
-    /// Sets the `limit` of the record.
-    ///
-    /// # Panics
-    /// Panics if `limit` is `0`.
-    #[inline]
-    pub fn limit(mut self, limit: usize) -> RecordBuilder<R, C> {
-        self.limit = NonZeroUsize::new(limit).expect("limit can not be `0`");
-        self
-    }
+    #[test]
+    fn apply_with_label() {
+        struct Push(String);
 
-    /// Sets if the receiver is initially in a saved state.
-    /// By default the receiver is in a saved state.
-    #[inline]
-    pub fn saved(mut self, saved: bool) -> RecordBuilder<R, C> {
-        self.saved = saved;
-        self
-    }
+        impl Command<String> for Push {
+            type Error = ();
 
-    /// Builds the record.
-    #[inline]
-    pub fn build(self, receiver: impl Into<R>) -> Record<R, C> {
-        Record {
-            commands: VecDeque::with_capacity(self.capacity),
-            receiver: receiver.into(),
-            current: 0,
-            limit: self.limit,
-            saved: if self.saved { Some(0) } else { None },
-            slot: None,
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push_str(&self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.truncate(s.len() - self.0.len());
+                Ok(())
+            }
+
+            fn merge(&mut self, Push(s): Self) -> Merge<Self> {
+                self.0.push_str(&s);
+                Merge::Yes
+            }
         }
+
+        let mut record = Record::default();
+        record.apply(Push("a".into())).unwrap();
+        record.apply_with_label(Push("b".into()), "Type").unwrap();
+        record.apply_with_label(Push("c".into()), "Paste").unwrap();
+        assert_eq!(record.len(), 1);
+        let labels: Vec<_> = record.entries().map(|entry| entry.label()).collect();
+        assert_eq!(labels, [Some("Type")]);
+        record.set_saved(true);
+        record.apply_with_label(Push("d".into()), "Paste").unwrap();
+        let labels: Vec<_> = record.entries().map(|entry| entry.label()).collect();
+        assert_eq!(labels, [Some("Type"), Some("Paste")]);
     }
 
-    /// Builds the record with the slot.
-    #[inline]
-    pub fn build_with<F>(self, receiver: impl Into<R>, slot: F) -> Record<R, C, F> {
-        Record {
-            commands: VecDeque::with_capacity(self.capacity),
-            receiver: receiver.into(),
-            current: 0,
-            limit: self.limit,
-            saved: if self.saved { Some(0) } else { None },
-            slot: Some(slot),
+    #[test]
+    fn merge_across_saved() {
+        struct Push(String);
+
+        impl Command<String> for Push {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push_str(&self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.truncate(s.len() - self.0.len());
+                Ok(())
+            }
+
+            fn merge(&mut self, Push(s): Self) -> Merge<Self> {
+                self.0.push_str(&s);
+                Merge::Yes
+            }
         }
-    }
-}
 
-impl<R, C> Default for RecordBuilder<R, C> {
-    #[inline]
-    fn default() -> Self {
-        RecordBuilder::new()
+        let mut record = Record::builder().merge_across_saved(true).default();
+        record.apply(Push("a".into())).unwrap();
+        record.set_saved(true);
+        record.apply(Push("b".into())).unwrap();
+        assert_eq!(record.len(), 1);
+        assert!(!record.is_saved());
+        assert_eq!(record.saved(), None);
+        record.set_saved(true);
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "");
+        assert!(!record.is_saved());
+        record.redo().unwrap().unwrap();
+        assert!(record.is_saved());
     }
-}
 
-impl<R: Default, C> RecordBuilder<R, C> {
-    /// Creates the record with a default `receiver`.
-    #[inline]
-    pub fn default(self) -> Record<R, C> {
-        self.build(R::default())
+    #[test]
+    fn generation() {
+        let mut record = Record::default();
+        assert_eq!(record.generation(), 0);
+        record.extend(vec![Add('a'), Add('b')]).unwrap();
+        assert_eq!(record.generation(), 2);
+        record.undo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.generation(), 4);
+        record.go_to(0).unwrap().unwrap();
+        assert_eq!(record.generation(), 6);
+        record.set_saved(false);
+        assert_eq!(record.mutate().len(), 0);
+        assert_eq!(record.generation(), 6);
+        record.mutate().push('c');
+        assert_eq!(record.generation(), 7);
     }
 
-    /// Creates the record with a default `receiver`.
-    #[inline]
-    pub fn default_with<F>(self, slot: F) -> Record<R, C, F> {
-        self.build_with(R::default(), slot)
+    #[test]
+    #[cfg(feature = "checkpoint")]
+    fn fuzz() {
+        // A fixed-seed xorshift generator is used instead of proptest, which is not a
+        // dev-dependency of the crate, so every run checks the same operations.
+        let mut seed = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = move |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % n as u64) as usize
+        };
+        for _ in 0..100 {
+            let mut record = Record::default();
+            for _ in 0..100 {
+                match next(8) {
+                    0 | 1 => record.apply(Add((b'a' + next(3) as u8) as char)).unwrap(),
+                    2 => {
+                        record.undo();
+                    }
+                    3 => {
+                        record.redo();
+                    }
+                    4 => record.set_saved(next(2) == 0),
+                    5 => {
+                        record.set_limit(1 + next(5));
+                    }
+                    6 => {
+                        record.go_to_saved_offset(next(5) as isize - 2);
+                    }
+                    _ => {
+                        let mut checkpoint = record.checkpoint();
+                        checkpoint.apply(Add('x')).unwrap();
+                        checkpoint.undo();
+                        checkpoint.cancel().unwrap();
+                    }
+                }
+                assert!(record.current() <= record.len());
+                assert!(record.len() <= record.limit());
+                let applied: String = record
+                    .commands()
+                    .take(record.current())
+                    .map(|add| add.0)
+                    .collect();
+                assert!(record.as_receiver().ends_with(&applied));
+            }
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::{Command, Record};
+    #[test]
+    fn undo_n() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record =
+            Record::builder().default_with(move |signal| slot.borrow_mut().push(signal));
+        record.extend("abc".chars().map(Add)).unwrap();
+        record.defer_signals(true);
+        signals.borrow_mut().clear();
+        record.undo_n(2).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "a");
+        assert_eq!(
+            *signals.borrow(),
+            [Signal::Current { old: 3, new: 1 }, Signal::Redo(true)]
+        );
+        record.redo_n(5).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abc");
+        assert!(record.redo_n(1).is_none());
+        assert!(record.undo_n(0).is_none());
+    }
 
-    struct Add(char);
+    #[test]
+    #[cfg(all(feature = "std", feature = "tracing"))]
+    fn tracing() {
+        use tracing::{span, subscriber, Event, Metadata, Subscriber};
 
-    impl Command<String> for Add {
-        type Error = &'static str;
+        struct Messages(Arc<Mutex<Vec<String>>>);
 
-        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
-            s.push(self.0);
-            Ok(())
+        impl Subscriber for Messages {
+            fn enabled(&self, metadata: &Metadata) -> bool {
+                metadata.target() == "redo"
+            }
+            fn new_span(&self, _: &span::Attributes) -> span::Id {
+                span::Id::from_u64(1)
+            }
+            fn record(&self, _: &span::Id, _: &span::Record) {}
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, event: &Event) {
+                let mut message = String::new();
+                event.record(
+                    &mut |field: &tracing::field::Field, value: &dyn std::fmt::Debug| {
+                        if field.name() == "message" {
+                            message = format!("{:?}", value);
+                        }
+                    },
+                );
+                self.0.lock().unwrap().push(message);
+            }
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
         }
 
-        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
-            self.0 = s.pop().ok_or("`s` is empty")?;
-            Ok(())
-        }
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        subscriber::with_default(Messages(Arc::clone(&messages)), || {
+            let mut record = Record::default();
+            record.apply(Add('a')).unwrap();
+            record.undo().unwrap().unwrap();
+            record.redo().unwrap().unwrap();
+            record.go_to(0).unwrap().unwrap();
+        });
+        assert_eq!(
+            *messages.lock().unwrap(),
+            [
+                "applied command",
+                "undid command",
+                "redid command",
+                "going to command",
+                "undid command"
+            ]
+        );
     }
 
     #[test]
-    fn set_limit() {
+    fn validate() {
         let mut record = Record::default();
         record.apply(Add('a')).unwrap();
         record.apply(Add('b')).unwrap();
-        record.apply(Add('c')).unwrap();
-        record.apply(Add('d')).unwrap();
-        record.apply(Add('e')).unwrap();
+        assert_eq!(record.validate(), Ok(()));
+        record.saved = Some(3);
+        assert_eq!(
+            record.validate(),
+            Err(ConfigError::SavedOutOfBounds { saved: 3, len: 2 })
+        );
+        record.saved = None;
+        record.current = 3;
+        assert_eq!(
+            record.validate(),
+            Err(ConfigError::CurrentOutOfBounds { current: 3, len: 2 })
+        );
+    }
 
-        record.set_limit(3);
+    #[test]
+    fn undo_all() {
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record =
+            Record::builder().default_with(move |signal| slot.borrow_mut().push(signal));
+        record.extend("abc".chars().map(Add)).unwrap();
+        record.defer_signals(true);
+        signals.borrow_mut().clear();
+        record.undo_all().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "");
+        assert!(record.undo_all().is_none());
+        assert_eq!(signals.borrow()[0], Signal::Current { old: 3, new: 0 });
+        record.redo_n(1).unwrap().unwrap();
+        record.as_mut_receiver().clear();
+        record.redo_all().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "bc");
+        record.as_mut_receiver().clear();
+        let error = record.undo_all().unwrap().unwrap_err();
+        assert_eq!(
+            error.context().map(|c| (c.op(), c.index())),
+            Some((Op::Undo, 2))
+        );
         assert_eq!(record.current(), 3);
-        assert_eq!(record.limit(), 3);
-        assert_eq!(record.len(), 3);
-        assert!(record.can_undo());
-        assert!(!record.can_redo());
+        assert!(record.redo_all().is_none());
+    }
 
-        record.clear();
-        assert_eq!(record.set_limit(5), 5);
+    #[test]
+    fn entries() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.set_saved(true);
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        record.undo().unwrap().unwrap();
+        let entries: Vec<_> = record
+            .entries()
+            .map(|entry| (entry.command().0, entry.is_current(), entry.is_saved()))
+            .collect();
+        assert_eq!(
+            entries,
+            [('a', false, true), ('b', true, false), ('c', false, false)]
+        );
+    }
+
+    #[test]
+    fn events() {
+        let mut record = Record::default();
         record.apply(Add('a')).unwrap();
         record.apply(Add('b')).unwrap();
         record.apply(Add('c')).unwrap();
+        record.undo().unwrap().unwrap();
+        let events: Vec<_> = record
+            .events()
+            .map(|event| event.map(|add| Add(add.0)))
+            .collect();
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.sequence(), event.is_inverse()))
+                .collect::<Vec<_>>(),
+            [(0, false), (1, false), (2, true)]
+        );
+        let mut record: Record<String, Add> = Record::from_events("", events).unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        assert_eq!(record.current(), 2);
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abc");
         record.apply(Add('d')).unwrap();
+        assert_eq!(record.id(3), Some(3));
+        let events = vec![
+            Event::new(4, Add('a'), false),
+            Event::new(2, Add('b'), false),
+            Event::new(4, Add('c'), false),
+            Event::new(9, Add('d'), true),
+        ];
+        let mut record: Record<String, Add> = Record::from_events("", events).unwrap();
+        assert_eq!(record.as_receiver(), "abc");
         record.apply(Add('e')).unwrap();
+        let ids: Vec<_> = (0..record.len()).filter_map(|i| record.id(i)).collect();
+        assert_eq!(ids, [4, 5, 6, 10]);
+    }
 
-        record.undo().unwrap().unwrap();
-        record.undo().unwrap().unwrap();
-        record.undo().unwrap().unwrap();
+    #[test]
+    fn noop() {
+        struct Set(char);
 
-        record.set_limit(2);
-        assert_eq!(record.current(), 0);
-        assert_eq!(record.limit(), 3);
-        assert_eq!(record.len(), 3);
-        assert!(!record.can_undo());
-        assert!(record.can_redo());
+        impl Command<char> for Set {
+            type Error = ();
 
-        record.redo().unwrap().unwrap();
-        record.redo().unwrap().unwrap();
-        record.redo().unwrap().unwrap();
+            fn apply(&mut self, c: &mut char) -> Result<(), ()> {
+                mem::swap(&mut self.0, c);
+                Ok(())
+            }
 
-        record.clear();
-        assert_eq!(record.set_limit(5), 5);
+            fn undo(&mut self, c: &mut char) -> Result<(), ()> {
+                mem::swap(&mut self.0, c);
+                Ok(())
+            }
+
+            fn is_noop(&self, c: &char) -> bool {
+                self.0 == *c
+            }
+        }
+
+        let mut record = Record::new('a');
+        record.apply(Set('a')).unwrap();
+        assert!(record.is_empty());
+        record.apply(Set('b')).unwrap();
+        record.keep_noops(true);
+        record.apply(Set('b')).unwrap();
+        assert_eq!(record.len(), 2);
+        assert!(!record.is_noop(0));
+        assert!(record.is_noop(1));
+        record.go_to(0).unwrap().unwrap();
+        assert_eq!(*record.as_receiver(), 'a');
+    }
+
+    #[test]
+    fn soft_undo() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        assert!(record.soft_undo());
+        assert!(record.soft_undo());
+        assert_eq!(record.view(), 1);
+        assert_eq!(record.as_receiver(), "abc");
+        assert!(record.soft_redo());
+        record.materialize().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        assert_eq!(record.view(), 2);
+        assert!(record.soft_undo());
+        assert!(record.soft_undo());
+        assert!(!record.soft_undo());
+        assert_eq!(record.view(), 0);
+    }
+
+    #[test]
+    fn group() {
+        let mut record = Record::default();
         record.apply(Add('a')).unwrap();
+        record.begin_group();
         record.apply(Add('b')).unwrap();
+        record.begin_group();
         record.apply(Add('c')).unwrap();
+        record.end_group();
         record.apply(Add('d')).unwrap();
+        record.end_group();
+        assert!(!record.in_group());
         record.apply(Add('e')).unwrap();
-
-        record.undo().unwrap().unwrap();
-        record.undo().unwrap().unwrap();
-        record.undo().unwrap().unwrap();
         record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abcd");
         record.undo().unwrap().unwrap();
-
-        record.set_limit(2);
-        assert_eq!(record.current(), 0);
-        assert_eq!(record.limit(), 5);
-        assert_eq!(record.len(), 5);
-        assert!(!record.can_undo());
-        assert!(record.can_redo());
-
-        record.redo().unwrap().unwrap();
-        record.redo().unwrap().unwrap();
-        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "a");
         record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abcd");
+        record.go_to(2).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ab");
         record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abcd");
     }
 
     #[test]
-    fn go_to() {
+    fn group_starts_with_noop() {
+        struct Set(char, char);
+
+        impl Command<char> for Set {
+            type Error = ();
+
+            fn apply(&mut self, c: &mut char) -> Result<(), ()> {
+                self.1 = std::mem::replace(c, self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, c: &mut char) -> Result<(), ()> {
+                *c = self.1;
+                Ok(())
+            }
+
+            fn is_noop(&self, c: &char) -> bool {
+                self.0 == *c
+            }
+        }
+
+        let mut record = Record::new(' ');
+        record.apply(Set('a', ' ')).unwrap();
+        record.begin_group();
+        record.apply(Set('a', ' ')).unwrap();
+        record.apply(Set('b', ' ')).unwrap();
+        record.end_group();
+        record.undo().unwrap().unwrap();
+        assert_eq!(*record.as_receiver(), 'a');
+        record.undo().unwrap().unwrap();
+        assert_eq!(*record.as_receiver(), ' ');
+    }
+
+    #[test]
+    fn coarse() {
         let mut record = Record::default();
         record.apply(Add('a')).unwrap();
         record.apply(Add('b')).unwrap();
+        record.set_boundary();
         record.apply(Add('c')).unwrap();
         record.apply(Add('d')).unwrap();
+        record.set_boundary();
         record.apply(Add('e')).unwrap();
-
-        record.go_to(0).unwrap().unwrap();
-        assert_eq!(record.current(), 0);
-        assert_eq!(record.as_receiver(), "");
-        record.go_to(5).unwrap().unwrap();
-        assert_eq!(record.current(), 5);
-        assert_eq!(record.as_receiver(), "abcde");
-        record.go_to(1).unwrap().unwrap();
-        assert_eq!(record.current(), 1);
-        assert_eq!(record.as_receiver(), "a");
-        record.go_to(4).unwrap().unwrap();
-        assert_eq!(record.current(), 4);
+        assert!(record.is_boundary(2));
+        assert!(!record.is_boundary(3));
+        record.undo_coarse().unwrap().unwrap();
         assert_eq!(record.as_receiver(), "abcd");
-        record.go_to(2).unwrap().unwrap();
-        assert_eq!(record.current(), 2);
+        record.undo_coarse().unwrap().unwrap();
         assert_eq!(record.as_receiver(), "ab");
-        record.go_to(3).unwrap().unwrap();
-        assert_eq!(record.current(), 3);
-        assert_eq!(record.as_receiver(), "abc");
-        assert!(record.go_to(6).is_none());
-        assert_eq!(record.current(), 3);
+        record.redo().unwrap().unwrap();
+        record.undo_coarse().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        record.undo_coarse().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "");
+        assert!(record.undo_coarse().is_none());
+        record.redo_coarse().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        record.redo_coarse().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abcd");
+        record.redo_coarse().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abcde");
+        assert!(record.redo_coarse().is_none());
+    }
+
+    #[test]
+    fn resolve() {
+        struct Push(char);
+
+        impl Command<String> for Push {
+            type Error = ();
+
+            fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+                s.pop();
+                Ok(())
+            }
+
+            fn merge(&mut self, command: Self) -> Merge<Self> {
+                if command.0 == ' ' {
+                    Merge::No(command)
+                } else {
+                    Merge::Yes
+                }
+            }
+        }
+
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let slot = Rc::clone(&signals);
+        let mut record = Record::builder()
+            .saved(false)
+            .default_with(move |signal| slot.borrow_mut().push(signal));
+        record.apply(Push('a')).unwrap();
+        let a = record.id(0).unwrap();
+        record.apply(Push('b')).unwrap();
+        record.apply(Push(' ')).unwrap();
+        let space = record.id(1).unwrap();
+        assert_eq!(record.len(), 2);
+        assert!(signals.borrow().contains(&Signal::Merged { into: a }));
+        // The id of `b` was given out between `a` and ` `, and it now resolves to `a`.
+        assert_eq!(record.resolve(a + 1), Some(a));
+        assert_eq!(record.resolve(space), Some(space));
+        assert_eq!(record.index_of(space), Some(1));
+        record.undo().unwrap().unwrap();
+        record.apply(Push(' ')).unwrap();
+        assert_eq!(record.resolve(space), None);
     }
 
     #[test]
@@ -896,4 +5931,25 @@ mod tests {
         record.time_travel(&chrono::Utc::now()).unwrap().unwrap();
         assert_eq!(record.as_receiver(), "abc");
     }
+
+    #[test]
+    #[cfg(all(feature = "time", not(feature = "chrono")))]
+    fn time_travel_with_time() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        let a = time::OffsetDateTime::now_utc();
+        record.apply(Add('b')).unwrap();
+        record.apply(Add('c')).unwrap();
+        // The offset does not matter when the times are compared.
+        record
+            .time_travel(&a.to_offset(time::UtcOffset::from_hms(2, 0, 0).unwrap()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.as_receiver(), "a");
+        record
+            .time_travel(&time::OffsetDateTime::now_utc())
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.as_receiver(), "abc");
+    }
 }
@@ -0,0 +1,193 @@
+use alloc::{string::String, vec::Vec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A command in the log exported by [`Record::export_log`].
+///
+/// The log contains every command in the record in the order they were applied, together with
+/// everything that is needed to reconstruct the record with [`Record::replay`]: the id, the ids
+/// of the commands that were merged into it, the label, and if it has been undone. Commands that
+/// were annulled are not in the record, so they are not in the log either. The timestamps are left
+/// out, which makes the log of two runs with the same commands identical. The serialized form only
+/// consists of these fields and does not change between releases, so it can be attached to bug reports.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, Record};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+/// let mut record = Record::default();
+/// record.apply(Add('a')).unwrap();
+/// record.apply(Add('b')).unwrap();
+/// record.undo().unwrap().unwrap();
+/// let log: Vec<_> = record.export_log().into_iter().map(|c| c.map(|add| Add(add.0))).collect();
+/// assert!(log[1].is_undone());
+/// let record: Record<String, Add> = Record::replay("", log).unwrap();
+/// assert_eq!(record.as_receiver(), "a");
+/// assert_eq!(record.len(), 2);
+/// ```
+///
+/// [`Record::export_log`]: struct.Record.html#method.export_log
+/// [`Record::replay`]: struct.Record.html#method.replay
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct LoggedCommand<C> {
+    pub(crate) id: usize,
+    pub(crate) command: C,
+    pub(crate) undone: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) merged: Vec<usize>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) label: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) noop: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) boundary: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) joined: bool,
+}
+
+impl<C> LoggedCommand<C> {
+    /// Returns a new logged command that has not been merged with any other command.
+    #[inline]
+    pub fn new(id: usize, command: C, undone: bool) -> LoggedCommand<C> {
+        LoggedCommand {
+            id,
+            command,
+            undone,
+            merged: Vec::new(),
+            label: None,
+            noop: false,
+            boundary: false,
+            joined: false,
+        }
+    }
+
+    /// Returns the id of the command.
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns a reference to the command.
+    #[inline]
+    pub fn command(&self) -> &C {
+        &self.command
+    }
+
+    /// Returns `true` if the command has been undone.
+    #[inline]
+    pub fn is_undone(&self) -> bool {
+        self.undone
+    }
+
+    /// Returns the ids of the commands that has been merged into this one.
+    #[inline]
+    pub fn merged(&self) -> &[usize] {
+        &self.merged
+    }
+
+    /// Returns the label the command was applied with.
+    #[inline]
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Returns `true` if the command was a no-op when it was applied.
+    #[inline]
+    pub fn is_noop(&self) -> bool {
+        self.noop
+    }
+
+    /// Consumes the logged command, returning the command.
+    #[inline]
+    pub fn into_command(self) -> C {
+        self.command
+    }
+
+    /// Maps the command with `f`.
+    #[inline]
+    pub fn map<T>(self, f: impl FnOnce(C) -> T) -> LoggedCommand<T> {
+        LoggedCommand {
+            id: self.id,
+            command: f(self.command),
+            undone: self.undone,
+            merged: self.merged,
+            label: self.label,
+            noop: self.noop,
+            boundary: self.boundary,
+            joined: self.joined,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Merge, Record};
+
+    #[derive(Debug, PartialEq)]
+    struct Add(String);
+
+    impl Command<String> for Add {
+        type Error = ();
+
+        fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+            s.push_str(&self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+            s.truncate(s.len() - self.0.len());
+            Ok(())
+        }
+
+        fn merge(&mut self, Add(s): Self) -> Merge<Self> {
+            if s == " " {
+                Merge::No(Add(s))
+            } else {
+                self.0.push_str(&s);
+                Merge::Yes
+            }
+        }
+    }
+
+    #[test]
+    fn export_and_replay() {
+        let mut record = Record::default();
+        record.apply_with_label(Add("a".into()), "type").unwrap();
+        record.apply(Add("b".into())).unwrap();
+        record.apply(Add(" ".into())).unwrap();
+        record.apply(Add("c".into())).unwrap();
+        record.undo().unwrap().unwrap();
+        let log = record.export_log();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].id(), 0);
+        assert_eq!(log[0].merged(), &[1]);
+        assert_eq!(log[1].merged(), &[3]);
+        assert_eq!(log[0].label(), Some("type"));
+        assert!(!log[0].is_undone());
+        assert!(log[1].is_undone());
+        let log: Vec<_> = log
+            .into_iter()
+            .map(|c| c.map(|a| Add(a.0.clone())))
+            .collect();
+        let mut replayed: Record<String, Add> = Record::replay("", log).unwrap();
+        assert_eq!(replayed.as_receiver(), "ab");
+        assert_eq!(replayed.current(), 1);
+        assert_eq!(replayed.export_log(), record.export_log());
+        replayed.redo().unwrap().unwrap();
+        assert_eq!(replayed.as_receiver(), "ab c");
+        replayed.apply(Add(" ".into())).unwrap();
+        assert_eq!(replayed.export_log()[2].id(), 4);
+    }
+}
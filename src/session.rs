@@ -0,0 +1,375 @@
+use crate::{Command, Record};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, BufRead, Write},
+    time::{Duration, Instant},
+};
+
+/// An operation in a recorded [`Session`].
+///
+/// [`Session`]: struct.Session.html
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum SessionOp<C> {
+    /// The command was applied.
+    Apply(C),
+    /// The last command was undone.
+    Undo,
+    /// The next command was redone.
+    Redo,
+    /// The record went to the position.
+    GoTo(usize),
+}
+
+/// The ordered stream of operations done on a record, with the time each operation was done.
+///
+/// A session is recorded by a [`Recorder`] and can be replayed on a fresh receiver, which makes
+/// it possible to attach a reproducible session to a bug report. The session can be serialized
+/// with `serde`, or written to a compact line based format with [`write`] and read back with [`read`].
+///
+/// [`Recorder`]: struct.Recorder.html
+/// [`write`]: struct.Session.html#method.write
+/// [`read`]: struct.Session.html#method.read
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Session<C> {
+    entries: Vec<(Duration, SessionOp<C>)>,
+}
+
+impl<C> Session<C> {
+    /// Returns a new empty session.
+    #[inline]
+    pub fn new() -> Session<C> {
+        Session {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns the number of operations in the session.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the session is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the operations, and the time since the start of the session
+    /// they were done at.
+    #[inline]
+    pub fn entries(&self) -> impl Iterator<Item = (Duration, &SessionOp<C>)> {
+        self.entries.iter().map(|(elapsed, op)| (*elapsed, op))
+    }
+
+    /// Pushes the operation to the end of the session.
+    #[inline]
+    pub fn push(&mut self, elapsed: Duration, op: SessionOp<C>) {
+        self.entries.push((elapsed, op));
+    }
+
+    /// Replays the session on the `receiver` and returns the resulting record.
+    ///
+    /// The `receiver` must be in the state it was in when the session was started.
+    /// Undo, redo, and go to operations that can not be done are skipped.
+    ///
+    /// # Errors
+    /// If a command fails the error is returned together with the index of the operation.
+    #[inline]
+    pub fn replay<R>(self, receiver: impl Into<R>) -> Result<Record<R, C>, (usize, C::Error)>
+    where
+        C: Command<R>,
+    {
+        let mut record = Record::new(receiver);
+        for (i, (_, op)) in self.entries.into_iter().enumerate() {
+            let result = match op {
+                SessionOp::Apply(command) => record.apply(command),
+                SessionOp::Undo => record.undo().unwrap_or(Ok(())),
+                SessionOp::Redo => record.redo().unwrap_or(Ok(())),
+                SessionOp::GoTo(current) => record.go_to(current).unwrap_or(Ok(())),
+            };
+            result.map_err(|error| (i, error))?;
+        }
+        Ok(record)
+    }
+
+    /// Writes the session to the `writer`, one operation per line.
+    ///
+    /// Each line holds the time in milliseconds, the kind of the operation, and the command
+    /// encoded by `encode` for applied commands. The encoded command must not contain a newline.
+    ///
+    /// # Errors
+    /// If the writer fails the error is returned.
+    #[inline]
+    pub fn write(
+        &self,
+        mut writer: impl Write,
+        mut encode: impl FnMut(&C) -> String,
+    ) -> io::Result<()> {
+        for (elapsed, op) in &self.entries {
+            let millis = elapsed.as_millis();
+            match op {
+                SessionOp::Apply(command) => writeln!(writer, "{} a {}", millis, encode(command))?,
+                SessionOp::Undo => writeln!(writer, "{} u", millis)?,
+                SessionOp::Redo => writeln!(writer, "{} r", millis)?,
+                SessionOp::GoTo(current) => writeln!(writer, "{} g {}", millis, current)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a session written by [`write`] from the `reader`, using `decode` to decode the commands.
+    ///
+    /// # Errors
+    /// If the reader fails, a line is malformed, or `decode` returns `None`, an error is returned.
+    ///
+    /// [`write`]: struct.Session.html#method.write
+    #[inline]
+    pub fn read(
+        reader: impl BufRead,
+        mut decode: impl FnMut(&str) -> Option<C>,
+    ) -> io::Result<Session<C>> {
+        let mut session = Session::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, line.as_str());
+            let mut parts = line.splitn(3, ' ');
+            let millis: u64 = parts
+                .next()
+                .and_then(|millis| millis.parse().ok())
+                .ok_or_else(invalid)?;
+            let op = match (parts.next(), parts.next()) {
+                (Some("a"), Some(command)) => {
+                    SessionOp::Apply(decode(command).ok_or_else(invalid)?)
+                }
+                (Some("u"), None) => SessionOp::Undo,
+                (Some("r"), None) => SessionOp::Redo,
+                (Some("g"), Some(current)) => {
+                    SessionOp::GoTo(current.parse().map_err(|_| invalid())?)
+                }
+                _ => return Err(invalid()),
+            };
+            session.push(Duration::from_millis(millis), op);
+        }
+        Ok(session)
+    }
+}
+
+impl<C> Default for Session<C> {
+    #[inline]
+    fn default() -> Session<C> {
+        Session::new()
+    }
+}
+
+/// A record that records every operation into a [`Session`].
+///
+/// The command is cloned before it is applied, and the clone is recorded,
+/// which means that the session holds the commands as they were before they were applied.
+/// Operations that fail or can not be done are not recorded.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, Recorder, Session};
+/// # #[derive(Clone)]
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+/// let mut recorder = Recorder::new("");
+/// recorder.apply(Add('a')).unwrap();
+/// recorder.apply(Add('b')).unwrap();
+/// recorder.undo().unwrap().unwrap();
+/// let mut file = Vec::new();
+/// recorder.as_session().write(&mut file, |add| add.0.to_string()).unwrap();
+///
+/// let session = Session::read(&file[..], |s| s.chars().next().map(Add)).unwrap();
+/// let record = session.replay::<String>("").unwrap();
+/// assert_eq!(record.as_receiver(), "a");
+/// assert_eq!(record.len(), 2);
+/// ```
+///
+/// [`Session`]: struct.Session.html
+#[derive(Clone, Debug)]
+pub struct Recorder<R, C> {
+    record: Record<R, C>,
+    session: Session<C>,
+    start: Instant,
+}
+
+impl<R, C> Recorder<R, C> {
+    /// Returns a new recorder that starts recording a session on the `receiver`.
+    #[inline]
+    pub fn new(receiver: impl Into<R>) -> Recorder<R, C> {
+        Recorder {
+            record: Record::new(receiver),
+            session: Session::new(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Returns a reference to the record.
+    #[inline]
+    pub fn as_record(&self) -> &Record<R, C> {
+        &self.record
+    }
+
+    /// Returns a reference to the recorded session.
+    #[inline]
+    pub fn as_session(&self) -> &Session<C> {
+        &self.session
+    }
+
+    /// Consumes the recorder, returning the record and the recorded session.
+    #[inline]
+    pub fn into_parts(self) -> (Record<R, C>, Session<C>) {
+        (self.record, self.session)
+    }
+
+    #[inline]
+    fn push(&mut self, op: SessionOp<C>) {
+        self.session.push(self.start.elapsed(), op);
+    }
+}
+
+impl<R, C: Command<R>> Recorder<R, C> {
+    /// Pushes the command on top of the record, executes its [`apply`] method,
+    /// and records it in the session.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned and nothing is recorded.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    pub fn apply(&mut self, command: C) -> Result<(), C::Error>
+    where
+        C: Clone,
+    {
+        let copy = command.clone();
+        self.record.apply(command)?;
+        self.push(SessionOp::Apply(copy));
+        Ok(())
+    }
+
+    /// Calls the [`undo`] method for the active command and records it in the session.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned and nothing is recorded.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    #[inline]
+    pub fn undo(&mut self) -> Option<Result<(), C::Error>> {
+        let result = self.record.undo()?;
+        if result.is_ok() {
+            self.push(SessionOp::Undo);
+        }
+        Some(result)
+    }
+
+    /// Calls the [`redo`] method for the active command and records it in the session.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned and nothing is recorded.
+    ///
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn redo(&mut self) -> Option<Result<(), C::Error>> {
+        let result = self.record.redo()?;
+        if result.is_ok() {
+            self.push(SessionOp::Redo);
+        }
+        Some(result)
+    }
+
+    /// Repeatedly calls [`undo`] or [`redo`] until the command at `current` is reached,
+    /// and records it in the session.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned
+    /// and nothing is recorded.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn go_to(&mut self, current: usize) -> Option<Result<(), C::Error>> {
+        let result = self.record.go_to(current)?;
+        if result.is_ok() {
+            self.push(SessionOp::GoTo(current));
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Recorder, Session, SessionOp};
+    use std::time::Duration;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            if self.0 == '!' {
+                return Err("invalid");
+            }
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            self.0 = s.pop().ok_or("`s` is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let mut recorder = Recorder::new("");
+        recorder.apply(Add('a')).unwrap();
+        recorder.apply(Add('b')).unwrap();
+        recorder.apply(Add('c')).unwrap();
+        assert!(recorder.apply(Add('!')).is_err());
+        recorder.go_to(1).unwrap().unwrap();
+        recorder.redo().unwrap().unwrap();
+        assert!(recorder.redo().is_some());
+        recorder.undo().unwrap().unwrap();
+        let (record, session) = recorder.into_parts();
+        assert_eq!(session.len(), 7);
+        let mut file = Vec::new();
+        session.write(&mut file, |add| add.0.to_string()).unwrap();
+        let read = Session::read(&file[..], |s| s.chars().next().map(Add)).unwrap();
+        let ops: Vec<_> = read.entries().map(|(_, op)| op.clone()).collect();
+        let expected: Vec<_> = session.entries().map(|(_, op)| op.clone()).collect();
+        assert_eq!(ops, expected);
+        let replayed = read.replay::<String>("").unwrap();
+        assert_eq!(replayed.as_receiver(), record.as_receiver());
+        assert_eq!(replayed.current(), record.current());
+    }
+
+    #[test]
+    fn replay_error() {
+        let mut session = Session::new();
+        session.push(Duration::from_millis(0), SessionOp::Apply(Add('a')));
+        session.push(Duration::from_millis(5), SessionOp::Apply(Add('!')));
+        assert_eq!(session.replay::<String>("").unwrap_err(), (1, "invalid"));
+        let read = Session::<Add>::read(&b"12 x\n"[..], |_| None);
+        assert!(read.is_err());
+    }
+}
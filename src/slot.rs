@@ -0,0 +1,343 @@
+use crate::Signal;
+use alloc::{string::String, vec::Vec};
+use core::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    mem,
+};
+#[cfg(feature = "std")]
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+/// The part of the state that is reported through signals.
+#[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub(crate) struct State {
+    pub(crate) current: usize,
+    pub(crate) branch: usize,
+    pub(crate) can_undo: bool,
+    pub(crate) can_redo: bool,
+    pub(crate) is_saved: bool,
+}
+
+/// The order the signals caused by one step of an operation are emitted in.
+///
+/// The `Operation` signal always comes first if it is enabled.
+#[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum SignalOrder {
+    /// `Current`, `Annulled`, `Merged`, `Undo`, `Redo`, `Saved`, `Branch`, and `BranchEvicted`.
+    ///
+    /// This is the default.
+    #[default]
+    CurrentFirst,
+    /// `Undo`, `Redo`, `Saved`, `Current`, `Annulled`, `Merged`, `Branch`, and `BranchEvicted`.
+    ///
+    /// This lets the slot update what the user can do before it reacts to the new position.
+    CurrentLast,
+}
+
+/// An id that identifies a slot added with `subscribe`.
+///
+/// It is used to remove the slot again with `unsubscribe`.
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct SubscriptionId(usize);
+
+/// Handles the delivery of signals to the connected slot and the subscribed slots.
+///
+/// Signals can be emitted directly, or inside a transaction. Inside a transaction the signals
+/// about the state are not delivered, instead the state at the start of the transaction is
+/// compared with the state at its end, and only the changes are delivered in the given order.
+/// If deferred, only the outermost transaction delivers the changes, so the net changes of all
+/// the nested transactions are delivered once.
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub(crate) struct Slot<F> {
+    pub(crate) f: Option<F>,
+    subscribers: Vec<(SubscriptionId, F)>,
+    next_subscriber: usize,
+    senders: Senders,
+    depth: usize,
+    start: State,
+    merged: Option<usize>,
+    annulled: Option<usize>,
+    evicted: Vec<usize>,
+    touched: bool,
+    pub(crate) defer: bool,
+    pub(crate) order: SignalOrder,
+    pub(crate) catch: bool,
+    pub(crate) panic: Option<String>,
+    pub(crate) operations: bool,
+    operation: usize,
+    announced: bool,
+}
+
+impl<F> Slot<F> {
+    /// Adds a slot that receives the signals in addition to the connected slot.
+    #[inline]
+    pub(crate) fn subscribe(&mut self, f: F) -> SubscriptionId {
+        let id = SubscriptionId(self.next_subscriber);
+        self.next_subscriber += 1;
+        self.subscribers.push((id, f));
+        id
+    }
+
+    /// Returns a receiver that receives the signals in addition to the slots.
+    #[inline]
+    #[cfg(feature = "std")]
+    pub(crate) fn receiver(&mut self) -> Receiver<Signal> {
+        let (sender, receiver) = mpsc::channel();
+        self.senders.0.push(sender);
+        receiver
+    }
+
+    /// Removes the subscribed slot with the id.
+    #[inline]
+    pub(crate) fn unsubscribe(&mut self, id: SubscriptionId) -> Option<F> {
+        let index = self.subscribers.iter().position(|&(i, _)| i == id)?;
+        Some(self.subscribers.remove(index).1)
+    }
+
+    /// Starts a transaction.
+    #[inline]
+    pub(crate) fn begin(&mut self, state: State) {
+        if self.depth == 0 {
+            self.next_operation();
+            self.start = state;
+            self.merged = None;
+            self.annulled = None;
+            self.evicted.clear();
+            self.touched = false;
+        }
+        self.depth += 1;
+    }
+
+    /// Starts a new operation unless it is part of a transaction.
+    ///
+    /// The id is only used up if the previous operation emitted any signals.
+    #[inline]
+    pub(crate) fn next_operation(&mut self) {
+        if self.depth == 0 && self.announced {
+            self.operation += 1;
+            self.announced = false;
+        }
+    }
+}
+
+impl<F: FnMut(Signal)> Slot<F> {
+    /// Emits the signal, or records it if a transaction is in progress.
+    #[inline]
+    pub(crate) fn emit(&mut self, signal: Signal) {
+        if self.depth > 0 {
+            match signal {
+                // The position is the same, but the command at it has changed.
+                Signal::Current { old, new } if old == new => self.touched = true,
+                Signal::Merged { into } => self.merged = Some(into),
+                Signal::Annulled { with } => self.annulled = Some(with),
+                Signal::BranchEvicted(id) => self.evicted.push(id),
+                _ => (),
+            }
+        } else {
+            self.call(signal);
+        }
+    }
+
+    /// Calls the slot, preceded by the operation id if it has not been emitted yet.
+    #[inline]
+    fn call(&mut self, signal: Signal) {
+        if self.f.is_none() && self.subscribers.is_empty() && self.senders.is_empty() {
+            return;
+        }
+        if self.operations && !self.announced {
+            self.announced = true;
+            self.invoke(Signal::Operation(self.operation));
+        }
+        self.invoke(signal);
+    }
+
+    /// Calls the connected slot and then the subscribed slots in the order they were added,
+    /// catching any panic if enabled.
+    ///
+    /// Only the first panic is kept until it is taken.
+    #[inline]
+    fn invoke(&mut self, signal: Signal) {
+        let subscribers = self.subscribers.iter_mut().map(|(_, f)| f);
+        for f in self.f.iter_mut().chain(subscribers) {
+            #[cfg(feature = "std")]
+            if self.catch {
+                if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| f(signal))) {
+                    self.panic.get_or_insert_with(|| message(payload));
+                }
+                continue;
+            }
+            f(signal);
+        }
+        self.senders.send(signal);
+    }
+
+    /// Ends a transaction and emits the changes since the last emitted state,
+    /// unless the signals are deferred and it was not the outermost one.
+    #[inline]
+    pub(crate) fn end(&mut self, state: State) {
+        debug_assert!(self.depth > 0);
+        self.depth -= 1;
+        if self.depth > 0 && self.defer {
+            return;
+        }
+        let start = mem::replace(&mut self.start, state);
+        let merged = self.merged.take();
+        let annulled = self.annulled.take();
+        let touched = mem::take(&mut self.touched);
+        if self.order == SignalOrder::CurrentLast {
+            self.flags(start, state);
+        }
+        if start.current != state.current || touched || merged.is_some() || annulled.is_some() {
+            self.call(Signal::Current {
+                old: start.current,
+                new: state.current,
+            });
+        }
+        if let Some(with) = annulled {
+            self.call(Signal::Annulled { with });
+        }
+        if let Some(into) = merged {
+            self.call(Signal::Merged { into });
+        }
+        if self.order == SignalOrder::CurrentFirst {
+            self.flags(start, state);
+        }
+        if start.branch != state.branch {
+            self.call(Signal::Branch {
+                old: start.branch,
+                new: state.branch,
+            });
+        }
+        for id in mem::take(&mut self.evicted) {
+            self.call(Signal::BranchEvicted(id));
+        }
+    }
+
+    /// Emits the changes of what can be done from the `start` state to the `end` state.
+    #[inline]
+    fn flags(&mut self, start: State, end: State) {
+        if start.can_undo != end.can_undo {
+            self.call(Signal::Undo(end.can_undo));
+        }
+        if start.can_redo != end.can_redo {
+            self.call(Signal::Redo(end.can_redo));
+        }
+        if start.is_saved != end.is_saved {
+            self.call(Signal::Saved(end.is_saved));
+        }
+    }
+}
+
+impl<F> Default for Slot<F> {
+    #[inline]
+    fn default() -> Self {
+        Slot {
+            f: None,
+            subscribers: Vec::new(),
+            next_subscriber: 0,
+            senders: Senders::default(),
+            depth: 0,
+            start: State::default(),
+            merged: None,
+            annulled: None,
+            evicted: Vec::new(),
+            touched: false,
+            defer: false,
+            order: SignalOrder::CurrentFirst,
+            catch: false,
+            panic: None,
+            operations: false,
+            operation: 0,
+            announced: false,
+        }
+    }
+}
+
+impl<F> From<F> for Slot<F> {
+    #[inline]
+    fn from(f: F) -> Self {
+        Slot {
+            f: Some(f),
+            ..Slot::default()
+        }
+    }
+}
+
+/// The senders of the receivers returned by `receiver`.
+///
+/// The senders are not cloned, so a clone of a record does not send to the receivers of the original.
+/// Without the standard library there are no receivers, so there are never any senders.
+#[derive(Debug, Default)]
+struct Senders(#[cfg(feature = "std")] Vec<Sender<Signal>>);
+
+impl Senders {
+    /// Returns `true` if there are no senders.
+    #[inline]
+    fn is_empty(&self) -> bool {
+        #[cfg(feature = "std")]
+        return self.0.is_empty();
+        #[cfg(not(feature = "std"))]
+        return true;
+    }
+
+    /// Sends the signal to the receivers, and drops the senders whose receiver is gone.
+    #[inline]
+    fn send(&mut self, signal: Signal) {
+        #[cfg(feature = "std")]
+        self.0.retain(|sender| sender.send(signal).is_ok());
+        #[cfg(not(feature = "std"))]
+        let _ = signal;
+    }
+}
+
+impl Clone for Senders {
+    #[inline]
+    fn clone(&self) -> Self {
+        Senders::default()
+    }
+}
+
+impl PartialEq for Senders {
+    #[inline]
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Senders {}
+
+impl PartialOrd for Senders {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Senders {
+    #[inline]
+    fn cmp(&self, _: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl Hash for Senders {
+    #[inline]
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+/// Returns the message of the panic.
+#[inline]
+#[cfg(feature = "std")]
+fn message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => (*message).to_string(),
+            Err(_) => String::from("the slot panicked"),
+        },
+    }
+}
@@ -0,0 +1,160 @@
+use alloc::vec::Vec;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// An immutable copy of the state of a record or a record.
+///
+/// The snapshot owns a representation of each command, e.g. its string, together with the current
+/// and saved position. Since it does not borrow the record, it can be sent to another thread, like
+/// a renderer, which then does not need to lock the record each frame. A new snapshot can be sent
+/// whenever the slot receives a signal.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, Record};
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+/// let mut record = Record::default();
+/// record.apply(Add('a')).unwrap();
+/// record.apply(Add('b')).unwrap();
+/// let snapshot = record.snapshot_with(|add| add.0);
+/// assert_eq!(snapshot.commands(), ['a', 'b']);
+/// assert_eq!(snapshot.current(), 2);
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Snapshot<T> {
+    commands: Vec<T>,
+    current: usize,
+    saved: Option<usize>,
+    branch: usize,
+}
+
+impl<T> Snapshot<T> {
+    #[inline]
+    pub(crate) fn new(
+        commands: Vec<T>,
+        current: usize,
+        saved: Option<usize>,
+        branch: usize,
+    ) -> Snapshot<T> {
+        Snapshot {
+            commands,
+            current,
+            saved,
+            branch,
+        }
+    }
+
+    /// Returns the commands in the snapshot.
+    #[inline]
+    pub fn commands(&self) -> &[T] {
+        &self.commands
+    }
+
+    /// Returns the number of commands in the snapshot.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns `true` if the snapshot is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Returns the position of the current command.
+    #[inline]
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Returns the current branch.
+    #[inline]
+    pub fn branch(&self) -> usize {
+        self.branch
+    }
+
+    /// Returns `true` if the receiver is in a saved state.
+    #[inline]
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.current)
+    }
+
+    /// Returns `true` if the command before the current position can be undone.
+    #[inline]
+    pub fn can_undo(&self) -> bool {
+        self.current > 0
+    }
+
+    /// Returns `true` if the command after the current position can be redone.
+    #[inline]
+    pub fn can_redo(&self) -> bool {
+        self.current < self.len()
+    }
+
+    /// Returns the command that will be undone next.
+    #[inline]
+    pub fn undo_command(&self) -> Option<&T> {
+        self.current.checked_sub(1).map(|i| &self.commands[i])
+    }
+
+    /// Returns the command that will be redone next.
+    #[inline]
+    pub fn redo_command(&self) -> Option<&T> {
+        self.commands.get(self.current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Record, Snapshot};
+    use std::{sync::mpsc, thread};
+
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = ();
+
+        fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+            s.pop();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn send() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.undo().unwrap().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let renderer = thread::spawn(move || {
+            let snapshot: Snapshot<char> = receiver.recv().unwrap();
+            (
+                snapshot.commands().to_vec(),
+                snapshot.undo_command().copied(),
+            )
+        });
+        sender.send(record.snapshot_with(|add| add.0)).unwrap();
+        let (commands, undo) = renderer.join().unwrap();
+        assert_eq!(commands, ['a', 'b']);
+        assert_eq!(undo, Some('a'));
+    }
+}
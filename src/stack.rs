@@ -0,0 +1,228 @@
+use crate::{cursor::Cursor, Merge};
+use alloc::collections::VecDeque;
+use core::cmp::Ordering;
+
+/// The storage of the entries in a record, from the oldest to the newest.
+///
+/// The positions in the record are kept by the functions below, so the records only
+/// differ in how the entries are stored.
+pub(crate) trait Stack {
+    type Entry;
+
+    /// Returns the number of entries.
+    fn len(&self) -> usize;
+
+    /// Returns a mutable reference to the newest entry.
+    fn back_mut(&mut self) -> Option<&mut Self::Entry>;
+
+    /// Adds the entry after the newest one, which must fit.
+    fn push_back(&mut self, entry: Self::Entry);
+
+    /// Removes the newest entry.
+    fn pop_back(&mut self) -> Option<Self::Entry>;
+
+    /// Removes the oldest entry.
+    fn pop_front(&mut self) -> Option<Self::Entry>;
+
+    /// Removes the entry at `index` and moves the entries after it back.
+    fn remove(&mut self, index: usize) -> Option<Self::Entry>;
+}
+
+impl<E> Stack for VecDeque<E> {
+    type Entry = E;
+
+    #[inline]
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+
+    #[inline]
+    fn back_mut(&mut self) -> Option<&mut E> {
+        VecDeque::back_mut(self)
+    }
+
+    #[inline]
+    fn push_back(&mut self, entry: E) {
+        VecDeque::push_back(self, entry);
+    }
+
+    #[inline]
+    fn pop_back(&mut self) -> Option<E> {
+        VecDeque::pop_back(self)
+    }
+
+    #[inline]
+    fn pop_front(&mut self) -> Option<E> {
+        VecDeque::pop_front(self)
+    }
+
+    #[inline]
+    fn remove(&mut self, index: usize) -> Option<E> {
+        VecDeque::remove(self, index)
+    }
+}
+
+/// What happened to an entry that was pushed.
+pub(crate) enum Pushed<E> {
+    /// The entry was merged into the newest entry.
+    Merged,
+    /// The entry annulled the newest entry, which is returned.
+    Annulled(E),
+    /// The entry was pushed, and the oldest entry is returned if it was evicted to make room for it.
+    Added(Option<E>),
+}
+
+/// Pushes the entry on top of the stack, which must not have any entries after `current`,
+/// or merges it into the newest entry with `merge`.
+///
+/// The oldest entry is evicted if the stack already has `limit` entries.
+/// The saved position no longer exists if the saved entry was merged or annulled.
+#[inline]
+pub(crate) fn push<S: Stack>(
+    stack: &mut S,
+    current: &mut usize,
+    saved: &mut Option<usize>,
+    limit: usize,
+    entry: S::Entry,
+    merge: impl FnOnce(&mut S::Entry, S::Entry) -> Merge<S::Entry>,
+) -> Pushed<S::Entry> {
+    debug_assert_eq!(*current, stack.len());
+    let was_saved = *saved == Some(*current);
+    let merged = match stack.back_mut() {
+        Some(last) => merge(last, entry),
+        None => Merge::No(entry),
+    };
+    let pushed = match merged {
+        Merge::Yes => Pushed::Merged,
+        Merge::Annul => {
+            *current -= 1;
+            Pushed::Annulled(stack.pop_back().unwrap())
+        }
+        Merge::No(entry) => {
+            let mut evicted = None;
+            if limit == *current {
+                evict(stack, current, saved, 1, |entry| evicted = Some(entry));
+            }
+            stack.push_back(entry);
+            *current += 1;
+            return Pushed::Added(evicted);
+        }
+    };
+    if was_saved {
+        *saved = None;
+    }
+    pushed
+}
+
+/// Removes the `n` oldest entries, and calls `f` with each of them.
+#[inline]
+pub(crate) fn evict<S: Stack>(
+    stack: &mut S,
+    current: &mut usize,
+    saved: &mut Option<usize>,
+    n: usize,
+    mut f: impl FnMut(S::Entry),
+) {
+    let len = stack.len();
+    for _ in 0..n {
+        f(stack.pop_front().unwrap());
+    }
+    *current -= n;
+    *saved = saved
+        .and_then(|saved| Cursor::new(saved, len)?.evict(n))
+        .map(Cursor::get);
+}
+
+/// Removes the entry before `current`, which can no longer be undone, without undoing it.
+///
+/// The changes of the entry are kept, so the positions before it are gone.
+#[inline]
+pub(crate) fn expire<S: Stack>(
+    stack: &mut S,
+    current: &mut usize,
+    saved: &mut Option<usize>,
+) -> S::Entry {
+    *current -= 1;
+    let index = *current;
+    *saved = saved.and_then(|saved| saved.checked_sub(1).filter(|&saved| saved >= index));
+    stack.remove(index).unwrap()
+}
+
+/// Removes the dead entry at `current` without redoing it.
+///
+/// The entry is never redone, so the position after it is gone.
+#[inline]
+pub(crate) fn bury<S: Stack>(stack: &mut S, current: usize, saved: &mut Option<usize>) -> S::Entry {
+    let index = current + 1;
+    *saved = saved.and_then(|saved| match saved.cmp(&index) {
+        Ordering::Less => Some(saved),
+        Ordering::Equal => None,
+        Ordering::Greater => Some(saved - 1),
+    });
+    stack.remove(current).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bury, evict, expire, push, Pushed};
+    use crate::Merge;
+    use alloc::collections::VecDeque;
+
+    /// Merges `a` into `a`, and annuls `a` with `b`.
+    fn merge(last: &mut char, entry: char) -> Merge<char> {
+        match (*last, entry) {
+            ('a', 'a') => Merge::Yes,
+            ('a', 'b') => Merge::Annul,
+            _ => Merge::No(entry),
+        }
+    }
+
+    #[test]
+    fn positions() {
+        let mut stack = VecDeque::new();
+        let (mut current, mut saved) = (0, Some(0));
+        assert!(matches!(
+            push(&mut stack, &mut current, &mut saved, 2, 'c', merge),
+            Pushed::Added(None)
+        ));
+        assert!(matches!(
+            push(&mut stack, &mut current, &mut saved, 2, 'a', merge),
+            Pushed::Added(None)
+        ));
+        saved = Some(2);
+        // The saved entry is merged, so the saved position is gone.
+        assert!(matches!(
+            push(&mut stack, &mut current, &mut saved, 2, 'a', merge),
+            Pushed::Merged
+        ));
+        assert_eq!((current, saved), (2, None));
+        assert!(matches!(
+            push(&mut stack, &mut current, &mut saved, 2, 'b', merge),
+            Pushed::Annulled('a')
+        ));
+        assert_eq!(current, 1);
+        saved = Some(1);
+        assert!(matches!(
+            push(&mut stack, &mut current, &mut saved, 2, 'd', merge),
+            Pushed::Added(None)
+        ));
+        assert!(matches!(
+            push(&mut stack, &mut current, &mut saved, 2, 'e', merge),
+            Pushed::Added(Some('c'))
+        ));
+        assert_eq!((current, saved), (2, Some(0)));
+        evict(&mut stack, &mut current, &mut saved, 1, |entry| {
+            assert_eq!(entry, 'd')
+        });
+        assert_eq!((current, saved), (1, None));
+        saved = Some(1);
+        assert_eq!(expire(&mut stack, &mut current, &mut saved), 'e');
+        assert_eq!((current, saved), (0, Some(0)));
+        stack.extend(['f', 'g']);
+        saved = Some(2);
+        assert_eq!(bury(&mut stack, 0, &mut saved), 'f');
+        assert_eq!(saved, Some(1));
+        assert_eq!(bury(&mut stack, 0, &mut saved), 'g');
+        assert_eq!(saved, None);
+    }
+}
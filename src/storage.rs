@@ -0,0 +1,371 @@
+use crate::{Command, Record};
+use alloc::vec::Vec;
+use core::{convert::Infallible, error, fmt};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The error returned by the operations on a persistent record.
+type Failure<R, C, S> = StorageError<<C as Command<R>>::Error, <S as Storage<C>>::Error>;
+
+/// An entry in the log of a [`PersistentRecord`].
+///
+/// [`PersistentRecord`]: struct.PersistentRecord.html
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum LogEntry<C> {
+    /// The command was applied.
+    Apply(C),
+    /// The last command was undone.
+    Undo,
+    /// The next command was redone.
+    Redo,
+    /// The saved state was set or removed.
+    Saved(bool),
+}
+
+impl<C> LogEntry<C> {
+    /// Maps the command of the entry with `f`.
+    #[inline]
+    pub fn map<T>(self, f: impl FnOnce(C) -> T) -> LogEntry<T> {
+        match self {
+            LogEntry::Apply(command) => LogEntry::Apply(f(command)),
+            LogEntry::Undo => LogEntry::Undo,
+            LogEntry::Redo => LogEntry::Redo,
+            LogEntry::Saved(saved) => LogEntry::Saved(saved),
+        }
+    }
+}
+
+/// An append-only store for the log of a [`PersistentRecord`].
+///
+/// The store can be anything that is able to append entries and read them back in order,
+/// e.g. a file or an embedded database.
+///
+/// [`PersistentRecord`]: struct.PersistentRecord.html
+pub trait Storage<C> {
+    /// The error type.
+    type Error;
+
+    /// Appends the entry to the end of the log.
+    fn append(&mut self, entry: LogEntry<&C>) -> Result<(), Self::Error>;
+
+    /// Returns every entry in the log, in the order they were appended.
+    fn load(&mut self) -> Result<Vec<LogEntry<C>>, Self::Error>;
+}
+
+/// An in-memory log, mostly useful for testing.
+impl<C: Clone> Storage<C> for Vec<LogEntry<C>> {
+    type Error = Infallible;
+
+    #[inline]
+    fn append(&mut self, entry: LogEntry<&C>) -> Result<(), Infallible> {
+        self.push(entry.map(C::clone));
+        Ok(())
+    }
+
+    #[inline]
+    fn load(&mut self) -> Result<Vec<LogEntry<C>>, Infallible> {
+        Ok(self.clone())
+    }
+}
+
+/// A record that writes every operation to an append-only [`Storage`].
+///
+/// When the record is opened again with the same storage and the same initial receiver, the log
+/// is replayed to restore the commands, the current position, and the saved state.
+/// Since each operation is written as soon as it is done, the undo history survives a crash.
+/// If the storage fails, the operation is not done or is reverted, so the record and the log
+/// always agree.
+///
+/// The command is written to the storage before it is applied,
+/// which means that the log holds the commands as they were before they were applied.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, LogEntry, PersistentRecord};
+/// # #[derive(Clone)]
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+/// let mut record = PersistentRecord::open("", Vec::new()).unwrap();
+/// record.apply(Add('a')).unwrap();
+/// record.apply(Add('b')).unwrap();
+/// record.undo().unwrap().unwrap();
+/// let log: Vec<LogEntry<Add>> = record.into_storage();
+///
+/// let record: PersistentRecord<String, _, _> = PersistentRecord::open("", log).unwrap();
+/// assert_eq!(record.as_record().as_receiver(), "a");
+/// assert_eq!(record.as_record().len(), 2);
+/// ```
+///
+/// [`Storage`]: trait.Storage.html
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct PersistentRecord<R, C, S> {
+    record: Record<R, C>,
+    storage: S,
+}
+
+impl<R, C: Command<R>, S: Storage<C>> PersistentRecord<R, C, S> {
+    /// Opens the record by replaying the log in the `storage` on the `receiver`.
+    ///
+    /// The `receiver` must be in the state it was in when the log was started.
+    /// The commands that failed to apply are in the log, since they are written before they are
+    /// applied, and they are skipped again when they fail.
+    ///
+    /// # Errors
+    /// If the log can not be loaded, or a command fails to be undone or redone when it is replayed,
+    /// the error is returned.
+    #[inline]
+    pub fn open(
+        receiver: impl Into<R>,
+        mut storage: S,
+    ) -> Result<PersistentRecord<R, C, S>, Failure<R, C, S>> {
+        let mut record = Record::new(receiver);
+        for entry in storage.load().map_err(StorageError::Storage)? {
+            let result = match entry {
+                LogEntry::Apply(command) => {
+                    let _ = record.apply(command);
+                    Ok(())
+                }
+                LogEntry::Undo => record.undo().unwrap_or(Ok(())),
+                LogEntry::Redo => record.redo().unwrap_or(Ok(())),
+                LogEntry::Saved(saved) => {
+                    record.set_saved(saved);
+                    Ok(())
+                }
+            };
+            result.map_err(StorageError::Command)?;
+        }
+        Ok(PersistentRecord { record, storage })
+    }
+
+    /// Appends the command to the storage, pushes it on top of the record,
+    /// and executes its [`apply`] method.
+    ///
+    /// The command is written first, since the changes of an applied command can not always be
+    /// reverted, e.g. if it was merged with the previous command.
+    ///
+    /// # Errors
+    /// If the storage fails the error is returned and the command is not applied.
+    /// If an error occur when executing [`apply`] the error is returned,
+    /// and the command is skipped when the log is replayed.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    pub fn apply(&mut self, command: C) -> Result<(), Failure<R, C, S>>
+    where
+        C: Clone,
+    {
+        self.write(LogEntry::Apply(&command))?;
+        self.record.apply(command).map_err(StorageError::Command)
+    }
+
+    /// Calls the [`undo`] method for the active command and appends it to the storage.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned and nothing is written.
+    /// If the storage fails the error is returned, and the command is redone again.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    #[inline]
+    pub fn undo(&mut self) -> Option<Result<(), Failure<R, C, S>>> {
+        if let Err(error) = self.record.undo()? {
+            return Some(Err(StorageError::Command(error)));
+        }
+        let result = self.write(LogEntry::Undo);
+        if result.is_err() {
+            // The log is left as it was, so the record is reverted to match it.
+            let _ = self.record.redo();
+        }
+        Some(result)
+    }
+
+    /// Calls the [`redo`] method for the active command and appends it to the storage.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned and nothing is written.
+    /// If the storage fails the error is returned, and the command is undone again.
+    ///
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn redo(&mut self) -> Option<Result<(), Failure<R, C, S>>> {
+        if let Err(error) = self.record.redo()? {
+            return Some(Err(StorageError::Command(error)));
+        }
+        let result = self.write(LogEntry::Redo);
+        if result.is_err() {
+            // The log is left as it was, so the record is reverted to match it.
+            let _ = self.record.undo();
+        }
+        Some(result)
+    }
+
+    /// Appends the saved state to the storage, and marks the receiver as currently
+    /// being in a saved or unsaved state.
+    ///
+    /// # Errors
+    /// If the storage fails the error is returned and the saved state is not changed.
+    #[inline]
+    pub fn set_saved(&mut self, saved: bool) -> Result<(), Failure<R, C, S>> {
+        self.write(LogEntry::Saved(saved))?;
+        self.record.set_saved(saved);
+        Ok(())
+    }
+
+    #[inline]
+    fn write(&mut self, entry: LogEntry<&C>) -> Result<(), Failure<R, C, S>> {
+        self.storage.append(entry).map_err(StorageError::Storage)
+    }
+}
+
+impl<R, C, S> PersistentRecord<R, C, S> {
+    /// Returns a reference to the record.
+    #[inline]
+    pub fn as_record(&self) -> &Record<R, C> {
+        &self.record
+    }
+
+    /// Returns a reference to the storage.
+    #[inline]
+    pub fn as_storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Consumes the persistent record, returning the record.
+    #[inline]
+    pub fn into_record(self) -> Record<R, C> {
+        self.record
+    }
+
+    /// Consumes the persistent record, returning the storage.
+    #[inline]
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+}
+
+/// The error returned by a [`PersistentRecord`].
+///
+/// [`PersistentRecord`]: struct.PersistentRecord.html
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum StorageError<E, S> {
+    /// The command failed.
+    Command(E),
+    /// The storage failed.
+    Storage(S),
+}
+
+impl<E: fmt::Display, S: fmt::Display> fmt::Display for StorageError<E, S> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::Command(error) => error.fmt(f),
+            StorageError::Storage(error) => write!(f, "storage failed: {}", error),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display, S: fmt::Debug + fmt::Display> error::Error
+    for StorageError<E, S>
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, LogEntry, PersistentRecord, Storage, StorageError};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            self.0 = s.pop().ok_or("`s` is empty")?;
+            Ok(())
+        }
+    }
+
+    /// A storage that fails when it is full.
+    struct Bounded(Vec<LogEntry<Add>>, usize);
+
+    impl Storage<Add> for Bounded {
+        type Error = &'static str;
+
+        fn append(&mut self, entry: LogEntry<&Add>) -> Result<(), Self::Error> {
+            if self.0.len() == self.1 {
+                return Err("full");
+            }
+            self.0.push(entry.map(Add::clone));
+            Ok(())
+        }
+
+        fn load(&mut self) -> Result<Vec<LogEntry<Add>>, Self::Error> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn reopen() {
+        let mut record = PersistentRecord::open("", Vec::new()).unwrap();
+        record.apply(Add('a')).unwrap();
+        record.apply(Add('b')).unwrap();
+        record.set_saved(true).unwrap();
+        record.apply(Add('c')).unwrap();
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        let log = record.into_storage();
+        assert_eq!(log.len(), 7);
+        let mut record: PersistentRecord<String, _, _> = PersistentRecord::open("", log).unwrap();
+        assert_eq!(record.as_record().as_receiver(), "ab");
+        assert_eq!(record.as_record().len(), 3);
+        assert!(record.as_record().is_saved());
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_record().as_receiver(), "abc");
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_record().as_receiver(), "");
+    }
+
+    #[test]
+    fn storage_error() {
+        let mut record = PersistentRecord::open("", Bounded(Vec::new(), 1)).unwrap();
+        record.apply(Add('a')).unwrap();
+        // The record is left in the same state as the log.
+        assert_eq!(record.apply(Add('b')), Err(StorageError::Storage("full")));
+        assert_eq!(record.as_record().as_receiver(), "a");
+        assert_eq!(record.undo(), Some(Err(StorageError::Storage("full"))));
+        assert_eq!(record.as_record().as_receiver(), "a");
+        assert_eq!(record.set_saved(true), Err(StorageError::Storage("full")));
+        assert!(!record.as_record().is_saved());
+        let reopened: PersistentRecord<String, _, _> =
+            PersistentRecord::open("", Bounded(record.as_storage().0.clone(), 2)).unwrap();
+        assert_eq!(reopened.as_record().as_receiver(), "a");
+        assert_eq!(reopened.as_record().current(), 1);
+        assert!(!reopened.as_record().is_saved());
+        let mut record = reopened;
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.redo(), Some(Err(StorageError::Storage("full"))));
+        assert_eq!(record.as_record().as_receiver(), "");
+        let reopened: PersistentRecord<String, _, _> =
+            PersistentRecord::open("", record.as_storage().0.clone()).unwrap();
+        assert_eq!(reopened.as_record().as_receiver(), "");
+        assert_eq!(reopened.as_record().current(), 0);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{Command, Record, SendRecord, Signal};
+use std::{
+    fmt,
+    sync::{mpsc::Receiver, Arc, Mutex, MutexGuard},
+};
+
+/// A handle to a record that can be shared between threads.
+///
+/// The record is locked for the duration of each operation, and cloning the handle only
+/// clones the `Arc`, so every clone operates on the same record. The signals can be received
+/// on any thread by calling [`signals`].
+///
+/// # Panics
+/// Every method panics if the lock is poisoned, e.g. if a command panicked while it was applied.
+///
+/// # Examples
+/// ```
+/// # use redo::{Command, SharedRecord, Signal};
+/// # use std::thread;
+/// # struct Add(char);
+/// # impl Command<String> for Add {
+/// #     type Error = ();
+/// #     fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+/// #         s.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+/// let record = SharedRecord::new("");
+/// let signals = record.signals();
+/// let worker = record.clone();
+/// thread::spawn(move || worker.apply(Add('a')).unwrap())
+///     .join()
+///     .unwrap();
+/// assert_eq!(signals.try_recv(), Ok(Signal::Current { old: 0, new: 1 }));
+/// assert_eq!(record.lock().as_receiver(), "a");
+/// ```
+///
+/// [`signals`]: struct.SharedRecord.html#method.signals
+pub struct SharedRecord<R, C> {
+    record: Arc<Mutex<SendRecord<R, C>>>,
+}
+
+impl<R, C> SharedRecord<R, C> {
+    /// Returns a new shared record.
+    #[inline]
+    pub fn new(receiver: impl Into<R>) -> SharedRecord<R, C> {
+        SharedRecord::from(Record::new(receiver))
+    }
+
+    /// Locks the record and returns a guard that gives access to it.
+    ///
+    /// The other handles are blocked until the guard is dropped.
+    #[inline]
+    pub fn lock(&self) -> MutexGuard<'_, SendRecord<R, C>> {
+        self.record.lock().expect("the record is poisoned")
+    }
+
+    /// Returns a receiver that receives every signal emitted by the record from now on.
+    ///
+    /// The signals are sent to every receiver returned by this method.
+    #[inline]
+    pub fn signals(&self) -> Receiver<Signal> {
+        self.lock().signal_receiver()
+    }
+
+    /// Returns the number of commands in the record.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if the record is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Returns the position of the current command.
+    #[inline]
+    pub fn current(&self) -> usize {
+        self.lock().current()
+    }
+
+    /// Returns `true` if the record can undo.
+    #[inline]
+    pub fn can_undo(&self) -> bool {
+        self.lock().can_undo()
+    }
+
+    /// Returns `true` if the record can redo.
+    #[inline]
+    pub fn can_redo(&self) -> bool {
+        self.lock().can_redo()
+    }
+
+    /// Returns `true` if the receiver is in a saved state, `false` otherwise.
+    #[inline]
+    pub fn is_saved(&self) -> bool {
+        self.lock().is_saved()
+    }
+}
+
+impl<R, C: Command<R>> SharedRecord<R, C> {
+    /// Pushes the command on top of the record and executes its [`apply`] method.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned.
+    ///
+    /// [`apply`]: trait.Command.html#tymethod.apply
+    #[inline]
+    pub fn apply(&self, command: C) -> Result<(), C::Error> {
+        self.lock().apply(command)
+    }
+
+    /// Calls the [`undo`] method for the active command and sets
+    /// the previous one as the new active one.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    #[inline]
+    pub fn undo(&self) -> Option<Result<(), C::Error>> {
+        self.lock().undo()
+    }
+
+    /// Calls the [`redo`] method for the active command and sets
+    /// the next one as the new active one.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned.
+    ///
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn redo(&self) -> Option<Result<(), C::Error>> {
+        self.lock().redo()
+    }
+
+    /// Repeatedly calls [`undo`] or [`redo`] until the command at `current` is reached.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Command.html#tymethod.undo
+    /// [`redo`]: trait.Command.html#method.redo
+    #[inline]
+    pub fn go_to(&self, current: usize) -> Option<Result<(), C::Error>> {
+        self.lock().go_to(current)
+    }
+
+    /// Marks the receiver as currently being in a saved or unsaved state.
+    #[inline]
+    pub fn set_saved(&self, saved: bool) {
+        self.lock().set_saved(saved);
+    }
+}
+
+impl<R, C> Clone for SharedRecord<R, C> {
+    #[inline]
+    fn clone(&self) -> Self {
+        SharedRecord {
+            record: Arc::clone(&self.record),
+        }
+    }
+}
+
+impl<R: Default, C> Default for SharedRecord<R, C> {
+    #[inline]
+    fn default() -> SharedRecord<R, C> {
+        SharedRecord::new(R::default())
+    }
+}
+
+impl<R, C, F> From<Record<R, C, F>> for SharedRecord<R, C> {
+    /// Shares the record, the slots of the record are dropped.
+    #[inline]
+    fn from(record: Record<R, C, F>) -> Self {
+        let mut record: SendRecord<R, C> = record.connect_with(Box::new(|_| ()));
+        record.disconnect();
+        SharedRecord {
+            record: Arc::new(Mutex::new(record)),
+        }
+    }
+}
+
+impl<R, C> fmt::Debug for SharedRecord<R, C> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedRecord")
+            .field("handles", &Arc::strong_count(&self.record))
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, SharedRecord, Signal};
+    use std::thread;
+
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), Self::Error> {
+            self.0 = s.pop().ok_or("`s` is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn threads() {
+        let record = SharedRecord::default();
+        let signals = record.signals();
+        let workers: Vec<_> = "abc"
+            .chars()
+            .map(|c| {
+                let record = record.clone();
+                thread::spawn(move || record.apply(Add(c)).unwrap())
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(record.len(), 3);
+        record.undo().unwrap().unwrap();
+        record.go_to(0).unwrap().unwrap();
+        assert_eq!(record.lock().as_receiver(), "");
+        let current: Vec<_> = signals
+            .try_iter()
+            .filter_map(|signal| match signal {
+                Signal::Current { new, .. } => Some(new),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(current, vec![1, 2, 3, 2, 1, 0]);
+    }
+}
@@ -0,0 +1,94 @@
+//! Stable textual dumps of records and histories for golden-file tests.
+//!
+//! The format is line based and does not change between releases, unlike the [`Debug`] output:
+//!
+//! * `current <position>` is the current position.
+//! * `saved <position>` is the saved position, or `saved none` if there is no saved state.
+//!   In a history the branch is written before the position, as in `saved <branch> <position>`.
+//! * `command <position> <string>` is a command, where backslashes and line breaks in the
+//!   string are escaped as `\\`, `\n`, and `\r`.
+//! * `branch <id> <parent> <start>` starts a branch in a history, which is followed by its commands.
+//!   The current branch is written first with its own id as the parent, and the other branches
+//!   are written in order of their id.
+//!
+//! [`Debug`]: https://doc.rust-lang.org/std/fmt/trait.Debug.html
+
+use crate::Entry;
+use alloc::string::{String, ToString};
+use core::fmt::Write;
+
+/// Writes the commands in `entries`, starting at `start`.
+#[inline]
+pub(crate) fn commands<'a, C: ToString + 'a>(
+    dump: &mut String,
+    start: usize,
+    entries: impl IntoIterator<Item = &'a Entry<C>>,
+) {
+    for (i, entry) in entries.into_iter().enumerate() {
+        let mut string = String::new();
+        for c in entry.command.to_string().chars() {
+            match c {
+                '\\' => string.push_str("\\\\"),
+                '\n' => string.push_str("\\n"),
+                '\r' => string.push_str("\\r"),
+                c => string.push(c),
+            }
+        }
+        writeln!(dump, "command {} {}", start + i + 1, string).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, Record};
+    use std::fmt;
+
+    struct Add(char);
+
+    impl Command<String> for Add {
+        type Error = ();
+
+        fn apply(&mut self, s: &mut String) -> Result<(), ()> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<(), ()> {
+            s.pop();
+            Ok(())
+        }
+    }
+
+    impl fmt::Display for Add {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    #[test]
+    fn record() {
+        let mut record = Record::default();
+        record.apply(Add('a')).unwrap();
+        record.set_saved(true);
+        record.apply(Add('\n')).unwrap();
+        record.undo().unwrap().unwrap();
+        assert_eq!(
+            record.dump(),
+            "current 1\nsaved 1\ncommand 1 a\ncommand 2 \\n\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "history")]
+    fn history() {
+        let mut history = crate::History::default();
+        history.apply(Add('a')).unwrap();
+        history.apply(Add('b')).unwrap();
+        history.undo().unwrap().unwrap();
+        history.apply(Add('c')).unwrap();
+        assert_eq!(
+            history.dump(),
+            "current 2\nsaved 1 0\nbranch 1 1 0\ncommand 1 a\ncommand 2 c\nbranch 0 1 1\ncommand 2 b\n"
+        );
+    }
+}
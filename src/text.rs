@@ -0,0 +1,748 @@
+//! Ready-made commands for editing text.
+//!
+//! The commands work on any receiver that implements [Text], which is implemented for `String`.
+//! All positions are byte offsets and must lie on a char boundary.
+//! Consecutive edits are merged the same way a text editor coalesces typing:
+//!
+//! * An insert that starts where the previous insert ended is merged into it.
+//! * A backspace or forward delete next to the previous delete is merged into it.
+//! * A delete that only removes text from the previous insert shrinks it, and annuls it if all of it is removed.
+//! * A replace of the text inserted by the previous replace is merged into it,
+//!   and annuls it if the original text is restored.
+//!
+//! Use the [Edit] enum to store all the edit commands in the same record.
+//!
+//! # Examples
+//! ```
+//! # use redo::{Record, text::{Delete, Edit, Insert}};
+//! # fn main() -> Result<(), redo::text::Error> {
+//! let mut record = Record::<String, Edit>::default();
+//! record.apply(Insert::new(0, "a").into())?;
+//! record.apply(Insert::new(1, "b").into())?;
+//! record.apply(Insert::new(2, "c").into())?;
+//! record.apply(Delete::new(2..3).into())?;
+//! assert_eq!(record.as_receiver(), "ab");
+//! // All the edits were merged into a single insert.
+//! assert_eq!(record.len(), 1);
+//! record.undo().unwrap()?;
+//! assert_eq!(record.as_receiver(), "");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [Text]: trait.Text.html
+//! [Edit]: enum.Edit.html
+
+use crate::{Command, Merge};
+use alloc::string::String;
+use core::{error, fmt, ops::Range};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A receiver that can be edited by the text commands.
+pub trait Text {
+    /// Returns the length of the text in bytes.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if the text is empty.
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if `index` is on a char boundary.
+    fn is_char_boundary(&self, index: usize) -> bool;
+
+    /// Inserts `text` at the byte offset `at`.
+    fn insert_str(&mut self, at: usize, text: &str);
+
+    /// Removes the text in `start..end` and returns it.
+    fn remove_range(&mut self, start: usize, end: usize) -> String;
+}
+
+impl Text for String {
+    #[inline]
+    fn len(&self) -> usize {
+        str::len(self)
+    }
+
+    #[inline]
+    fn is_char_boundary(&self, index: usize) -> bool {
+        str::is_char_boundary(self, index)
+    }
+
+    #[inline]
+    fn insert_str(&mut self, at: usize, text: &str) {
+        String::insert_str(self, at, text);
+    }
+
+    #[inline]
+    fn remove_range(&mut self, start: usize, end: usize) -> String {
+        self.drain(start..end).collect()
+    }
+}
+
+/// The error returned when a text command is given an invalid position.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum Error {
+    /// The position is past the end of the text.
+    OutOfBounds(usize),
+    /// The position is not on a char boundary.
+    NotCharBoundary(usize),
+    /// The start of the range is past its end.
+    InvalidRange(usize, usize),
+}
+
+impl fmt::Display for Error {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::OutOfBounds(at) => write!(f, "position {} is out of bounds", at),
+            Error::NotCharBoundary(at) => write!(f, "position {} is not on a char boundary", at),
+            Error::InvalidRange(start, end) => write!(f, "range {}..{} is invalid", start, end),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Inserts text at a position.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Insert {
+    at: usize,
+    text: String,
+}
+
+impl Insert {
+    /// Returns a command that inserts `text` at the byte offset `at`.
+    #[inline]
+    pub fn new(at: usize, text: impl Into<String>) -> Insert {
+        Insert {
+            at,
+            text: text.into(),
+        }
+    }
+
+    /// Returns the position of the insert.
+    #[inline]
+    pub fn at(&self) -> usize {
+        self.at
+    }
+
+    /// Returns the inserted text.
+    #[inline]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    #[inline]
+    fn end(&self) -> usize {
+        self.at + self.text.len()
+    }
+}
+
+impl<T: Text> Command<T> for Insert {
+    type Error = Error;
+
+    #[inline]
+    fn apply(&mut self, receiver: &mut T) -> Result<(), Error> {
+        check(receiver, self.at)?;
+        receiver.insert_str(self.at, &self.text);
+        Ok(())
+    }
+
+    #[inline]
+    fn undo(&mut self, receiver: &mut T) -> Result<(), Error> {
+        check_range(receiver, self.at, self.end())?;
+        receiver.remove_range(self.at, self.end());
+        Ok(())
+    }
+
+    #[inline]
+    fn merge(&mut self, command: Self) -> Merge<Self> {
+        if command.at == self.end() {
+            self.text.push_str(&command.text);
+            Merge::Yes
+        } else {
+            Merge::No(command)
+        }
+    }
+}
+
+impl fmt::Display for Insert {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Insert `{}` at {}", self.text, self.at)
+    }
+}
+
+/// Deletes the text in a range.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Delete {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+impl Delete {
+    /// Returns a command that deletes the text in `range`.
+    #[inline]
+    pub fn new(range: Range<usize>) -> Delete {
+        Delete {
+            start: range.start,
+            end: range.end,
+            text: String::new(),
+        }
+    }
+
+    /// Returns the range of the delete.
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Returns the deleted text.
+    ///
+    /// The text is empty until the command has been applied.
+    #[inline]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl<T: Text> Command<T> for Delete {
+    type Error = Error;
+
+    #[inline]
+    fn apply(&mut self, receiver: &mut T) -> Result<(), Error> {
+        check_range(receiver, self.start, self.end)?;
+        self.text = receiver.remove_range(self.start, self.end);
+        Ok(())
+    }
+
+    #[inline]
+    fn undo(&mut self, receiver: &mut T) -> Result<(), Error> {
+        check(receiver, self.start)?;
+        receiver.insert_str(self.start, &self.text);
+        Ok(())
+    }
+
+    #[inline]
+    fn merge(&mut self, command: Self) -> Merge<Self> {
+        if command.end == self.start {
+            // Backspace: the new text is in front of the old text.
+            self.start = command.start;
+            self.text.insert_str(0, &command.text);
+            Merge::Yes
+        } else if command.start == self.start {
+            // Forward delete: the new text is behind the old text.
+            self.end += command.text.len();
+            self.text.push_str(&command.text);
+            Merge::Yes
+        } else {
+            Merge::No(command)
+        }
+    }
+}
+
+impl fmt::Display for Delete {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Delete {}..{}", self.start, self.end)
+    }
+}
+
+/// Replaces the text in a range with new text.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Replace {
+    start: usize,
+    end: usize,
+    text: String,
+    replaced: String,
+}
+
+impl Replace {
+    /// Returns a command that replaces the text in `range` with `text`.
+    #[inline]
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> Replace {
+        Replace {
+            start: range.start,
+            end: range.end,
+            text: text.into(),
+            replaced: String::new(),
+        }
+    }
+
+    /// Returns the range of the replaced text.
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Returns the new text.
+    #[inline]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the replaced text.
+    ///
+    /// The text is empty until the command has been applied.
+    #[inline]
+    pub fn replaced(&self) -> &str {
+        &self.replaced
+    }
+}
+
+impl<T: Text> Command<T> for Replace {
+    type Error = Error;
+
+    #[inline]
+    fn apply(&mut self, receiver: &mut T) -> Result<(), Error> {
+        check_range(receiver, self.start, self.end)?;
+        self.replaced = receiver.remove_range(self.start, self.end);
+        receiver.insert_str(self.start, &self.text);
+        Ok(())
+    }
+
+    #[inline]
+    fn undo(&mut self, receiver: &mut T) -> Result<(), Error> {
+        let end = self.start + self.text.len();
+        check_range(receiver, self.start, end)?;
+        receiver.remove_range(self.start, end);
+        receiver.insert_str(self.start, &self.replaced);
+        Ok(())
+    }
+
+    #[inline]
+    fn merge(&mut self, command: Self) -> Merge<Self> {
+        if command.start != self.start || command.end != self.start + self.text.len() {
+            Merge::No(command)
+        } else if command.text == self.replaced {
+            Merge::Annul
+        } else {
+            self.text = command.text;
+            Merge::Yes
+        }
+    }
+}
+
+impl fmt::Display for Replace {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Replace {}..{} with `{}`",
+            self.start, self.end, self.text
+        )
+    }
+}
+
+/// Any of the text commands.
+///
+/// In addition to the merging done by the individual commands,
+/// a delete that only removes text inserted by the previous insert is merged into the insert.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum Edit {
+    /// Inserts text.
+    Insert(Insert),
+    /// Deletes text.
+    Delete(Delete),
+    /// Replaces text.
+    Replace(Replace),
+}
+
+impl<T: Text> Command<T> for Edit {
+    type Error = Error;
+
+    #[inline]
+    fn apply(&mut self, receiver: &mut T) -> Result<(), Error> {
+        match self {
+            Edit::Insert(insert) => insert.apply(receiver),
+            Edit::Delete(delete) => delete.apply(receiver),
+            Edit::Replace(replace) => replace.apply(receiver),
+        }
+    }
+
+    #[inline]
+    fn undo(&mut self, receiver: &mut T) -> Result<(), Error> {
+        match self {
+            Edit::Insert(insert) => insert.undo(receiver),
+            Edit::Delete(delete) => delete.undo(receiver),
+            Edit::Replace(replace) => replace.undo(receiver),
+        }
+    }
+
+    #[inline]
+    fn merge(&mut self, command: Self) -> Merge<Self> {
+        match (self, command) {
+            (Edit::Insert(insert), Edit::Insert(command)) => {
+                Command::<T>::merge(insert, command).map(Edit::Insert)
+            }
+            (Edit::Delete(delete), Edit::Delete(command)) => {
+                Command::<T>::merge(delete, command).map(Edit::Delete)
+            }
+            (Edit::Replace(replace), Edit::Replace(command)) => {
+                Command::<T>::merge(replace, command).map(Edit::Replace)
+            }
+            (Edit::Insert(insert), Edit::Delete(command))
+                if insert.at <= command.start && command.end <= insert.end() =>
+            {
+                let start = command.start - insert.at;
+                let end = command.end - insert.at;
+                insert.text.replace_range(start..end, "");
+                if insert.text.is_empty() {
+                    Merge::Annul
+                } else {
+                    Merge::Yes
+                }
+            }
+            (_, command) => Merge::No(command),
+        }
+    }
+}
+
+impl From<Insert> for Edit {
+    #[inline]
+    fn from(insert: Insert) -> Self {
+        Edit::Insert(insert)
+    }
+}
+
+impl From<Delete> for Edit {
+    #[inline]
+    fn from(delete: Delete) -> Self {
+        Edit::Delete(delete)
+    }
+}
+
+impl From<Replace> for Edit {
+    #[inline]
+    fn from(replace: Replace) -> Self {
+        Edit::Replace(replace)
+    }
+}
+
+impl fmt::Display for Edit {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Edit::Insert(insert) => (insert as &dyn fmt::Display).fmt(f),
+            Edit::Delete(delete) => (delete as &dyn fmt::Display).fmt(f),
+            Edit::Replace(replace) => (replace as &dyn fmt::Display).fmt(f),
+        }
+    }
+}
+
+#[inline]
+fn check(text: &impl Text, at: usize) -> Result<(), Error> {
+    if at > text.len() {
+        Err(Error::OutOfBounds(at))
+    } else if !text.is_char_boundary(at) {
+        Err(Error::NotCharBoundary(at))
+    } else {
+        Ok(())
+    }
+}
+
+#[inline]
+fn check_range(text: &impl Text, start: usize, end: usize) -> Result<(), Error> {
+    if start > end {
+        return Err(Error::InvalidRange(start, end));
+    }
+    check(text, start)?;
+    check(text, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Delete, Edit, Error, Insert, Replace};
+    use crate::{Command, Merge, Record};
+
+    #[test]
+    fn insert() {
+        let mut record = Record::<String, Insert>::default();
+        record.apply(Insert::new(0, "a")).unwrap();
+        record.apply(Insert::new(1, "b")).unwrap();
+        record.apply(Insert::new(2, "c")).unwrap();
+        assert_eq!(record.as_receiver(), "abc");
+        assert_eq!(record.len(), 1);
+        // Not adjacent to the previous insert.
+        record.apply(Insert::new(0, "d")).unwrap();
+        assert_eq!(record.as_receiver(), "dabc");
+        assert_eq!(record.len(), 2);
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abc");
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "");
+        record.redo().unwrap().unwrap();
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "dabc");
+    }
+
+    #[test]
+    fn delete() {
+        let mut record = Record::<String, Delete>::new("abcdef");
+        record.set_saved(false);
+        // Backspace.
+        record.apply(Delete::new(3..4)).unwrap();
+        record.apply(Delete::new(2..3)).unwrap();
+        assert_eq!(record.as_receiver(), "abef");
+        assert_eq!(record.len(), 1);
+        // Forward delete.
+        record.apply(Delete::new(2..3)).unwrap();
+        record.apply(Delete::new(2..3)).unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+        assert_eq!(record.len(), 1);
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abcdef");
+        record.redo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "ab");
+    }
+
+    #[test]
+    fn replace() {
+        let mut record = Record::<String, Replace>::new("hello world");
+        record.set_saved(false);
+        record.apply(Replace::new(6..11, "there")).unwrap();
+        record.apply(Replace::new(6..11, "you")).unwrap();
+        assert_eq!(record.as_receiver(), "hello you");
+        assert_eq!(record.len(), 1);
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "hello world");
+        record.redo().unwrap().unwrap();
+        // Restoring the original text annuls the replace.
+        record.apply(Replace::new(6..9, "world")).unwrap();
+        assert_eq!(record.as_receiver(), "hello world");
+        assert!(record.is_empty());
+    }
+
+    #[test]
+    fn edit() {
+        let mut record = Record::<String, Edit>::default();
+        record.apply(Insert::new(0, "abc").into()).unwrap();
+        record.apply(Delete::new(1..2).into()).unwrap();
+        assert_eq!(record.as_receiver(), "ac");
+        assert_eq!(record.len(), 1);
+        record.apply(Delete::new(0..2).into()).unwrap();
+        assert_eq!(record.as_receiver(), "");
+        assert!(record.is_empty());
+        record.apply(Insert::new(0, "abc").into()).unwrap();
+        record.set_saved(true);
+        // Edits are not merged with the saved state.
+        record.apply(Delete::new(2..3).into()).unwrap();
+        assert_eq!(record.len(), 2);
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abc");
+    }
+
+    #[test]
+    fn errors() {
+        let mut record = Record::<String, Edit>::new("å");
+        assert_eq!(
+            record.apply(Insert::new(3, "a").into()),
+            Err(Error::OutOfBounds(3))
+        );
+        assert_eq!(
+            record.apply(Insert::new(1, "a").into()),
+            Err(Error::NotCharBoundary(1))
+        );
+        #[allow(clippy::reversed_empty_ranges)]
+        let range = 2..0;
+        assert_eq!(
+            record.apply(Delete::new(range).into()),
+            Err(Error::InvalidRange(2, 0))
+        );
+        assert_eq!(record.as_receiver(), "å");
+        assert!(record.is_empty());
+    }
+
+    #[test]
+    fn insert_boundaries() {
+        let mut record = Record::<String, Insert>::new("xy");
+        record.set_saved(false);
+        record.apply(Insert::new(1, "ab")).unwrap();
+        // Inside the previous insert.
+        record.apply(Insert::new(2, "c")).unwrap();
+        assert_eq!(record.len(), 2);
+        // At the start of the previous insert.
+        record.apply(Insert::new(2, "d")).unwrap();
+        assert_eq!(record.len(), 3);
+        // At the end of the previous insert, with a multibyte char.
+        record.apply(Insert::new(3, "é")).unwrap();
+        record.apply(Insert::new(5, "f")).unwrap();
+        assert_eq!(record.as_receiver(), "xadéfcby");
+        assert_eq!(record.len(), 3);
+        assert_eq!(record.commands().last().unwrap().text(), "déf");
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "xacby");
+    }
+
+    #[test]
+    fn delete_boundaries() {
+        let mut record = Record::<String, Delete>::new("abcdefgh");
+        record.set_saved(false);
+        record.apply(Delete::new(4..5)).unwrap();
+        // Neither before nor at the start of the previous delete.
+        record.apply(Delete::new(1..2)).unwrap();
+        assert_eq!(record.len(), 2);
+        record.apply(Delete::new(2..3)).unwrap();
+        assert_eq!(record.len(), 3);
+        // A backspace followed by a forward delete.
+        record.apply(Delete::new(1..2)).unwrap();
+        record.apply(Delete::new(1..3)).unwrap();
+        assert_eq!(record.as_receiver(), "ah");
+        assert_eq!(record.len(), 3);
+        let last = record.commands().last().unwrap();
+        assert_eq!(last.range(), 1..5);
+        assert_eq!(last.text(), "cdfg");
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "acdfgh");
+        record.undo().unwrap().unwrap();
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "abcdefgh");
+    }
+
+    #[test]
+    fn replace_boundaries() {
+        let mut replace = Replace::new(0..1, "ab");
+        Command::<String>::apply(&mut replace, &mut String::from("x")).unwrap();
+        // A different start.
+        let merged = Command::<String>::merge(&mut replace, Replace::new(1..2, "c"));
+        assert!(matches!(merged, Merge::No(_)));
+        // Only a part of the inserted text.
+        let merged = Command::<String>::merge(&mut replace, Replace::new(0..1, "c"));
+        assert!(matches!(merged, Merge::No(_)));
+        // More than the inserted text.
+        let merged = Command::<String>::merge(&mut replace, Replace::new(0..3, "c"));
+        assert!(matches!(merged, Merge::No(_)));
+        let merged = Command::<String>::merge(&mut replace, Replace::new(0..2, "c"));
+        assert!(matches!(merged, Merge::Yes));
+        assert_eq!((replace.range(), replace.text()), (0..1, "c"));
+        let merged = Command::<String>::merge(&mut replace, Replace::new(0..1, "x"));
+        assert!(matches!(merged, Merge::Annul));
+    }
+
+    #[test]
+    fn edit_composition() {
+        let mut record = Record::<String, Edit>::new("xy");
+        record.set_saved(false);
+        record.apply(Insert::new(1, "abc").into()).unwrap();
+        // Starts before the insert.
+        record.apply(Delete::new(0..2).into()).unwrap();
+        assert_eq!(record.len(), 2);
+        record.apply(Insert::new(0, "x").into()).unwrap();
+        // Ends after the insert.
+        record.apply(Delete::new(0..3).into()).unwrap();
+        assert_eq!(record.len(), 4);
+        assert_eq!(record.as_receiver(), "y");
+        // An insert after a delete, and a replace after an insert, are never merged.
+        record.apply(Insert::new(0, "a").into()).unwrap();
+        record.apply(Replace::new(0..1, "b").into()).unwrap();
+        assert_eq!(record.len(), 6);
+        record.apply(Replace::new(0..1, "c").into()).unwrap();
+        assert_eq!(record.len(), 6);
+        // A delete after a replace is never merged either.
+        record.apply(Delete::new(0..1).into()).unwrap();
+        assert_eq!(record.len(), 7);
+        // A delete of the whole insert annuls it.
+        record.apply(Insert::new(0, "de").into()).unwrap();
+        record.apply(Delete::new(0..2).into()).unwrap();
+        assert_eq!(record.len(), 7);
+        record.go_to(0).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "xy");
+    }
+
+    #[test]
+    fn undo_merged() {
+        let mut record = Record::<String, Edit>::new("é!");
+        record.set_saved(false);
+        record.apply(Insert::new(2, "ab").into()).unwrap();
+        record.apply(Insert::new(4, "ö").into()).unwrap();
+        record.apply(Delete::new(3..4).into()).unwrap();
+        assert_eq!(record.as_receiver(), "éaö!");
+        assert_eq!(record.len(), 1);
+        record.apply(Replace::new(0..2, "e").into()).unwrap();
+        record.apply(Replace::new(0..1, "E").into()).unwrap();
+        assert_eq!(record.as_receiver(), "Eaö!");
+        assert_eq!(record.len(), 2);
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "éaö!");
+        record.undo().unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "é!");
+        record.go_to(2).unwrap().unwrap();
+        assert_eq!(record.as_receiver(), "Eaö!");
+    }
+
+    #[test]
+    fn apply_errors() {
+        let mut record = Record::<String, Edit>::new("åb");
+        assert_eq!(
+            record.apply(Delete::new(1..3).into()),
+            Err(Error::NotCharBoundary(1))
+        );
+        assert_eq!(
+            record.apply(Delete::new(0..1).into()),
+            Err(Error::NotCharBoundary(1))
+        );
+        assert_eq!(
+            record.apply(Delete::new(2..4).into()),
+            Err(Error::OutOfBounds(4))
+        );
+        assert_eq!(
+            record.apply(Replace::new(1..2, "a").into()),
+            Err(Error::NotCharBoundary(1))
+        );
+        assert_eq!(
+            record.apply(Replace::new(4..5, "a").into()),
+            Err(Error::OutOfBounds(4))
+        );
+        #[allow(clippy::reversed_empty_ranges)]
+        let range = 3..2;
+        assert_eq!(
+            record.apply(Replace::new(range, "a").into()),
+            Err(Error::InvalidRange(3, 2))
+        );
+        assert_eq!(record.as_receiver(), "åb");
+        assert!(record.is_empty());
+    }
+
+    #[test]
+    fn undo_errors() {
+        let mut record = Record::<String, Edit>::new("abc");
+        record.apply(Insert::new(3, "d").into()).unwrap();
+        record.as_mut_receiver().truncate(3);
+        assert_eq!(record.undo(), Some(Err(Error::OutOfBounds(4))));
+        record.as_mut_receiver().push('d');
+
+        record.apply(Delete::new(2..4).into()).unwrap();
+        record.as_mut_receiver().clear();
+        assert_eq!(record.undo(), Some(Err(Error::OutOfBounds(2))));
+        record.as_mut_receiver().push_str("ab");
+
+        record.apply(Replace::new(0..1, "é").into()).unwrap();
+        record.as_mut_receiver().replace_range(0..2, "xy");
+        record.as_mut_receiver().insert(1, 'ö');
+        assert_eq!(record.undo(), Some(Err(Error::NotCharBoundary(2))));
+        assert_eq!(record.current(), 3);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Insert::new(1, "a").to_string(), "Insert `a` at 1");
+        assert_eq!(Edit::from(Delete::new(1..2)).to_string(), "Delete 1..2");
+        assert_eq!(
+            Edit::from(Replace::new(0..1, "b")).to_string(),
+            "Replace 0..1 with `b`"
+        );
+        assert_eq!(
+            Error::NotCharBoundary(1).to_string(),
+            "position 1 is not on a char boundary"
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use crate::ignored::Ignored;
+use alloc::sync::Arc;
+use core::fmt;
+
+type WeighFn<C> = dyn Fn(&C) -> usize + Send + Sync;
+
+/// The limit on the total weight of the commands in the record.
+///
+/// The weight function is shared when the limit is cloned, and it is ignored when comparing
+/// and hashing records.
+#[derive(Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub(crate) struct WeightLimit<C> {
+    weigh: Ignored<Option<Arc<WeighFn<C>>>>,
+    max: usize,
+}
+
+impl<C> WeightLimit<C> {
+    /// Returns a limit where the total weight of the commands can not exceed `max`.
+    #[inline]
+    pub(crate) fn new(weigh: impl Fn(&C) -> usize + Send + Sync + 'static, max: usize) -> Self {
+        WeightLimit {
+            weigh: Ignored(Some(Arc::new(weigh))),
+            max,
+        }
+    }
+
+    /// Returns the number of commands that must be removed from the start of `commands` to
+    /// keep the total weight within the limit. The last command is always kept.
+    #[inline]
+    pub(crate) fn excess<'a>(
+        &self,
+        commands: impl DoubleEndedIterator<Item = &'a C> + ExactSizeIterator,
+    ) -> usize
+    where
+        C: 'a,
+    {
+        let weigh = match &*self.weigh {
+            Some(weigh) => weigh,
+            None => return 0,
+        };
+        let len = commands.len();
+        let mut total = 0usize;
+        for (kept, command) in commands.rev().enumerate() {
+            total = total.saturating_add(weigh(command));
+            if kept > 0 && total > self.max {
+                return len - kept;
+            }
+        }
+        0
+    }
+}
+
+impl<C> Default for WeightLimit<C> {
+    #[inline]
+    fn default() -> Self {
+        WeightLimit {
+            weigh: Ignored(None),
+            max: usize::MAX,
+        }
+    }
+}
+
+impl<C> Clone for WeightLimit<C> {
+    #[inline]
+    fn clone(&self) -> Self {
+        WeightLimit {
+            weigh: self.weigh.clone(),
+            max: self.max,
+        }
+    }
+}
+
+impl<C> fmt::Debug for WeightLimit<C> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WeightLimit")
+            .field("weigh", &self.weigh.is_some())
+            .field("max", &self.max)
+            .finish()
+    }
+}